@@ -1,38 +1,69 @@
-use crate::models::{AniListResponse, MediaListEntry, User};
+use crate::exit::Failure;
+use crate::franchise;
+use crate::models::{
+    AiringSchedule, AniListResponse, CharacterEdge, EpisodeDetails, Media, MediaListEntry,
+    StaffMediaEdge, User,
+};
+use crate::net::{self, HostLimiter};
+use crate::provider::fake;
 use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::Deserialize;
 use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 const ANILIST_URL: &str = "https://graphql.anilist.co";
+const JIKAN_URL: &str = "https://api.jikan.moe/v4";
+const ANISKIP_URL: &str = "https://api.aniskip.com/v2";
+
+lazy_static! {
+    // Jikan allows 3 requests a second; scrolling an episode list shouldn't
+    // get anywhere near that.
+    static ref JIKAN_LIMITER: HostLimiter = HostLimiter::new(1, Duration::from_millis(400));
+}
 
 const SEARCH_QUERY: &str = r#"
-query ($search: String, $perPage: Int, $page: Int, $sort: [MediaSort], $id_in: [Int]) {
+query SearchMedia ($search: String, $perPage: Int, $page: Int, $sort: [MediaSort], $id_in: [Int], $idMal_in: [Int], $type: MediaType = ANIME, $onList: Boolean, $relations: Boolean = false, $slim: Boolean = false) {
   Page(perPage: $perPage, page: $page) {
     pageInfo { total, currentPage, hasNextPage }
-    media(search: $search, id_in: $id_in, sort: $sort, type: ANIME) {
+    media(search: $search, id_in: $id_in, idMal_in: $idMal_in, sort: $sort, type: $type, onList: $onList) {
       id
+      idMal
+      type
       title { romaji, english, native }
       coverImage { extraLarge large medium }
+      bannerImage
+      nextAiringEpisode { airingAt episode }
       episodes
+      chapters
+      duration
+      mediaListEntry { id status progress score(format: POINT_10_DECIMAL) private notes }
       averageScore
       popularity
       favourites
       status
       format
       genres
-      description
+      description @skip(if: $slim)
       studios { nodes { name } }
-      trailer { id, site }
+      trailer @skip(if: $slim) { id, site }
       startDate { year month day }
       endDate { year month day }
-      synonyms
+      synonyms @skip(if: $slim)
       tags { name }
+      streamingEpisodes @skip(if: $slim) { title }
+      relations @include(if: $relations) { edges { relationType node { id type } } }
     }
   }
 }
 "#;
 
 const VIEWER_QUERY: &str = r#"
-query {
+query Viewer {
   Viewer {
     id
     name
@@ -41,8 +72,21 @@ query {
 "#;
 
 const SAVE_PROGRESS_MUTATION: &str = r#"
-mutation ($mediaId: Int, $progress: Int, $status: MediaListStatus) {
-  SaveMediaListEntry(mediaId: $mediaId, progress: $progress, status: $status) {
+mutation SaveProgress ($mediaId: Int, $progress: Int, $status: MediaListStatus, $private: Boolean) {
+  SaveMediaListEntry(mediaId: $mediaId, progress: $progress, status: $status, private: $private) {
+    id
+    mediaId
+    status
+    progress
+    score
+    private
+  }
+}
+"#;
+
+const SAVE_NOTES_MUTATION: &str = r#"
+mutation SaveNotes ($mediaId: Int, $notes: String) {
+  SaveMediaListEntry(mediaId: $mediaId, notes: $notes) {
     id
     mediaId
     status
@@ -52,17 +96,407 @@ mutation ($mediaId: Int, $progress: Int, $status: MediaListStatus) {
 }
 "#;
 
+const LIST_IDS_QUERY: &str = r#"
+query ListIds ($userName: String, $status: MediaListStatus) {
+  MediaListCollection(userName: $userName, type: ANIME, status: $status, sort: [PRIORITY_DESC, ADDED_TIME]) {
+    lists { entries { mediaId } }
+  }
+}
+"#;
+
+const RELATIONS_QUERY: &str = r#"
+query Relations ($id: Int) {
+  Media(id: $id, type: ANIME) {
+    relations { edges { relationType node { id format episodes } } }
+  }
+}
+"#;
+
+const CHARACTERS_QUERY: &str = r#"
+query Characters ($id: Int, $language: StaffLanguage) {
+  Media(id: $id, type: ANIME) {
+    characters(sort: [ROLE, RELEVANCE, ID], perPage: 25) {
+      edges {
+        role
+        node { name { full } }
+        voiceActors(language: $language, sort: [RELEVANCE, ID]) { id name { full } }
+      }
+    }
+  }
+}
+"#;
+
+const VOICE_ROLES_QUERY: &str = r#"
+query VoiceRoles ($id: Int, $page: Int, $onList: Boolean) {
+  Staff(id: $id) {
+    characterMedia(page: $page, perPage: 50, sort: POPULARITY_DESC, onList: $onList) {
+      pageInfo { total, currentPage, hasNextPage }
+      edges {
+        characters { name { full } }
+        node {
+          type
+          id
+          idMal
+          title { romaji, english, native }
+          coverImage { extraLarge large medium }
+          bannerImage
+          episodes
+          duration
+          averageScore
+          genres
+          status
+          format
+          mediaListEntry { status progress }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Pages of a voice actor's roles read without a token, most popular first.
+/// With one, only shows on the viewer's list come back and all are read.
+const MAX_ROLE_PAGES: i32 = 4;
+
+const AIRED_QUERY: &str = r#"
+query Aired ($ids: [Int], $since: Int, $until: Int, $page: Int) {
+  Page(perPage: 50, page: $page) {
+    pageInfo { total, currentPage, hasNextPage }
+    airingSchedules(mediaId_in: $ids, airingAt_greater: $since, airingAt_lesser: $until, sort: TIME_DESC) {
+      mediaId
+      episode
+      airingAt
+    }
+  }
+}
+"#;
+
+/// Most pages of 50 `fetch_aired` reads, which covers a long time away.
+const MAX_AIRED_PAGES: i32 = 10;
+
+/// How far back `prequel_episode_counts` follows the prequel chain.
+const MAX_PREQUELS: usize = 15;
+
 const GET_PROGRESS_QUERY: &str = r#"
-query ($mediaId: Int, $userName: String) {
-  MediaList(mediaId: $mediaId, userName: $userName, type: ANIME) {
+query GetProgress ($mediaId: Int, $userName: String) {
+  MediaList(mediaId: $mediaId, userName: $userName) {
     progress
     status
   }
 }
 "#;
 
-pub async fn fetch_media(variables: Value) -> Result<AniListResponse> {
-    send_request(SEARCH_QUERY, variables, None).await
+/// With a token, each result also carries the viewer's own list entry.
+/// With `"slim": true` among the variables, the long per-show fields that
+/// only the details pane needs (description, trailer, synonyms, streaming
+/// episodes) are left out and the results are marked `slim`, for lists;
+/// `fetch_details` fills them in later.
+pub async fn fetch_media(variables: Value, token: Option<&str>) -> Result<AniListResponse> {
+    if fake::enabled() {
+        return Ok(fake::fetch_media(&variables));
+    }
+    let slim = variables.get("slim") == Some(&Value::Bool(true));
+    let mut response = send_request(SEARCH_QUERY, variables, token).await?;
+    if slim && let Some(page) = &mut response.data.page {
+        for media in &mut page.media {
+            media.slim = true;
+        }
+    }
+    Ok(response)
+}
+
+/// The show (or manga) with every field, for one fetched `slim`.
+pub async fn fetch_details(media_id: i32, token: Option<&str>) -> Result<Option<Media>> {
+    // A null type lifts the query's ANIME default, which would miss manga.
+    let variables = json!({ "id_in": [media_id], "type": null });
+    let response = fetch_media(variables, token).await?;
+    Ok(response.data.page.and_then(|p| p.media.into_iter().next()))
+}
+
+/// Every anime linked to `media_id` through `franchise::FOLLOWED`
+/// relations, fetched a generation at a time (50 shows a request), the show
+/// itself included.
+pub async fn fetch_franchise(media_id: i32, token: Option<&str>) -> Result<Vec<Media>> {
+    let mut shows: Vec<Media> = Vec::new();
+    let mut seen = HashSet::from([media_id]);
+    let mut frontier = vec![media_id];
+    while !frontier.is_empty() && shows.len() < franchise::MAX_ENTRIES {
+        let mut next = Vec::new();
+        for chunk in frontier.chunks(50) {
+            if shows.len() >= franchise::MAX_ENTRIES {
+                break;
+            }
+            let variables = json!({ "id_in": chunk, "perPage": 50, "relations": true });
+            let fetched = fetch_media(variables, token)
+                .await?
+                .data
+                .page
+                .map(|p| p.media)
+                .unwrap_or_default();
+            next.extend(
+                fetched
+                    .iter()
+                    .flat_map(franchise::links)
+                    .filter_map(|(_, id)| seen.insert(id).then_some(id)),
+            );
+            shows.extend(fetched);
+        }
+        frontier = next;
+    }
+    shows.truncate(franchise::MAX_ENTRIES);
+    Ok(shows)
+}
+
+/// Ids of the shows on the user's list with the given status, in the user's
+/// own priority order.
+pub async fn fetch_list_ids(token: &str, username: &str, status: &str) -> Result<Vec<i32>> {
+    if fake::enabled() {
+        return Ok(fake::library()
+            .into_iter()
+            .filter(|m| {
+                m.media_list_entry
+                    .as_ref()
+                    .and_then(|e| e.status.as_deref())
+                    == Some(status)
+            })
+            .map(|m| m.id)
+            .collect());
+    }
+    let variables = json!({ "userName": username, "status": status });
+    let response = send_request(LIST_IDS_QUERY, variables, Some(token)).await?;
+    Ok(response
+        .data
+        .media_list_collection
+        .map(|c| c.lists)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|l| l.entries)
+        .filter_map(|e| e.media_id)
+        .collect())
+}
+
+/// Every show on the user's anime lists, each with its list entry, for the
+/// organizer. Fetched 50 at a time, so large lists take a few requests.
+pub async fn fetch_library(token: &str, username: &str) -> Result<Vec<Media>> {
+    if fake::enabled() {
+        return Ok(fake::library());
+    }
+    let ids = send_request(LIST_IDS_QUERY, json!({ "userName": username }), Some(token))
+        .await?
+        .data
+        .media_list_collection
+        .map(|c| c.lists)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|l| l.entries)
+        .filter_map(|e| e.media_id)
+        .collect::<Vec<i32>>();
+
+    let mut media = Vec::new();
+    for chunk in ids.chunks(50) {
+        let response = fetch_media(json!({ "id_in": chunk, "perPage": 50 }), Some(token)).await?;
+        media.extend(response.data.page.map(|p| p.media).unwrap_or_default());
+    }
+    media.sort_by(|a, b| a.preferred_title().cmp(b.preferred_title()));
+    Ok(media)
+}
+
+/// Fetches the user's list with the given status (e.g. "PLANNING"), in the
+/// user's own priority order.
+pub async fn fetch_user_list(token: &str, username: &str, status: &str) -> Result<Vec<Media>> {
+    if fake::enabled() {
+        return Ok(fake::fetch_media(&json!({}))
+            .data
+            .page
+            .map(|p| p.media)
+            .unwrap_or_default());
+    }
+    let mut ids = fetch_list_ids(token, username, status).await?;
+    ids.truncate(50);
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let response = fetch_media(json!({ "id_in": ids, "perPage": 50 }), Some(token)).await?;
+    let mut media = response.data.page.map(|p| p.media).unwrap_or_default();
+    media.sort_by_key(|m| ids.iter().position(|&id| id == m.id));
+    Ok(media)
+}
+
+/// The show's characters, main cast first, with their voice actors in
+/// `language` (e.g. "JAPANESE", "ENGLISH").
+pub async fn fetch_characters(media_id: i32, language: &str) -> Result<Vec<CharacterEdge>> {
+    if fake::enabled() {
+        return Ok(Vec::new());
+    }
+    let variables = json!({ "id": media_id, "language": language });
+    let response = send_request(CHARACTERS_QUERY, variables, None).await?;
+    Ok(response
+        .data
+        .media
+        .and_then(|m| m.characters)
+        .map(|c| c.edges)
+        .unwrap_or_default())
+}
+
+/// The shows a voice actor has roles in. With a token, only those on the
+/// viewer's list, each carrying the viewer's list entry.
+pub async fn fetch_voice_roles(staff_id: i32, token: Option<&str>) -> Result<Vec<StaffMediaEdge>> {
+    if fake::enabled() {
+        return Ok(Vec::new());
+    }
+    let mut roles = Vec::new();
+    for page in 1.. {
+        let mut variables = json!({ "id": staff_id, "page": page });
+        if token.is_some() {
+            variables["onList"] = json!(true);
+        }
+        let response = send_request(VOICE_ROLES_QUERY, variables, token).await?;
+        let Some(connection) = response.data.staff.and_then(|s| s.character_media) else {
+            break;
+        };
+        roles.extend(connection.edges);
+        if !connection.page_info.has_next_page || (token.is_none() && page >= MAX_ROLE_PAGES) {
+            break;
+        }
+    }
+    Ok(roles)
+}
+
+/// Episodes of `media_ids` that aired between the two Unix timestamps,
+/// newest first.
+pub async fn fetch_aired(media_ids: &[i32], since: i64, until: i64) -> Result<Vec<AiringSchedule>> {
+    if fake::enabled() {
+        return Ok(fake::aired(media_ids, since, until));
+    }
+    let mut aired = Vec::new();
+    for page in 1..=MAX_AIRED_PAGES {
+        let variables = json!({ "ids": media_ids, "since": since, "until": until, "page": page });
+        let Some(page) = send_request(AIRED_QUERY, variables, None).await?.data.page else {
+            break;
+        };
+        aired.extend(page.airing_schedules);
+        if !page.page_info.has_next_page {
+            break;
+        }
+    }
+    Ok(aired)
+}
+
+/// Episode counts of the TV seasons and parts before `media_id`, nearest
+/// first, following PREQUEL relations. Stops at the first prequel with an
+/// unknown episode count.
+pub async fn prequel_episode_counts(media_id: i32) -> Result<Vec<i32>> {
+    if fake::enabled() {
+        return Ok(Vec::new());
+    }
+    let mut counts = Vec::new();
+    let mut current = media_id;
+    for _ in 0..MAX_PREQUELS {
+        let response = send_request(RELATIONS_QUERY, json!({ "id": current }), None).await?;
+        let prequel = response
+            .data
+            .media
+            .and_then(|m| m.relations)
+            .map(|r| r.edges)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|e| {
+                e.relation_type == "PREQUEL"
+                    && matches!(e.node.format.as_deref(), Some("TV") | Some("TV_SHORT"))
+            });
+        match prequel.and_then(|e| Some((e.node.id, e.node.episodes?))) {
+            Some((id, episodes)) => {
+                counts.push(episodes);
+                current = id;
+            }
+            None => break,
+        }
+    }
+    Ok(counts)
+}
+
+#[derive(Deserialize)]
+struct JikanResponse<T> {
+    data: T,
+}
+
+/// Title and synopsis of one episode from MyAnimeList. `None` when MAL has
+/// no entry for it (common for episodes that have just aired).
+pub async fn fetch_episode_details(mal_id: i32, episode: i32) -> Result<Option<EpisodeDetails>> {
+    if fake::enabled() {
+        return Ok(None);
+    }
+    let _permit = JIKAN_LIMITER.acquire().await;
+    let url = format!("{}/anime/{}/episodes/{}", JIKAN_URL, mal_id, episode);
+    let res = net::api_client()
+        .get(&url)
+        .header("User-Agent", "ani-l (github.com/komposer-aml/ani-l)")
+        .send()
+        .await
+        .context("Failed to send request")?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        anyhow::bail!("Jikan Error: {}", res.status());
+    }
+    let body: JikanResponse<EpisodeDetails> =
+        res.json().await.context("Failed to parse response")?;
+    Ok(Some(body.data))
+}
+
+#[derive(Deserialize)]
+struct SkipTimesResponse {
+    #[serde(default)]
+    results: Vec<SkipTime>,
+}
+
+/// An opening, ending or recap of one episode, from AniSkip.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SkipTime {
+    pub interval: SkipInterval,
+    /// "op", "ed", "mixed-op", "mixed-ed" or "recap".
+    #[serde(rename = "skipType")]
+    pub skip_type: String,
+    /// Length of the episode the times were measured on, in seconds.
+    #[serde(rename = "episodeLength")]
+    pub episode_length: f64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SkipInterval {
+    #[serde(rename = "startTime")]
+    pub start_time: f64,
+    #[serde(rename = "endTime")]
+    pub end_time: f64,
+}
+
+/// Community-submitted opening/ending times of an episode. Empty when
+/// nobody has submitted any.
+pub async fn fetch_skip_times(mal_id: i32, episode: &str) -> Result<Vec<SkipTime>> {
+    if fake::enabled() {
+        return Ok(Vec::new());
+    }
+    let url = format!(
+        "{}/skip-times/{}/{}?types=op&types=ed&types=mixed-op&types=mixed-ed&types=recap&episodeLength=0",
+        ANISKIP_URL, mal_id, episode
+    );
+    let res = net::api_client()
+        .get(&url)
+        .header("User-Agent", "ani-l (github.com/komposer-aml/ani-l)")
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .context("Failed to send request")?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(Vec::new());
+    }
+    if !res.status().is_success() {
+        anyhow::bail!("AniSkip Error: {}", res.status());
+    }
+    let body: SkipTimesResponse = res.json().await.context("Failed to parse response")?;
+    Ok(body.results)
 }
 
 pub async fn authenticate_user(token: &str) -> Result<User> {
@@ -73,28 +507,110 @@ pub async fn authenticate_user(token: &str) -> Result<User> {
         .context("No Viewer data found in response")
 }
 
+/// The list status that goes with `progress` out of `total` episodes (or
+/// chapters): COMPLETED once they're all seen, CURRENT until then.
+pub fn progress_status(progress: i32, total: Option<i32>) -> &'static str {
+    if total.is_some_and(|total| total > 0 && progress >= total) {
+        "COMPLETED"
+    } else {
+        "CURRENT"
+    }
+}
+
+/// Saves progress on the viewer's list entry, creating it if needed.
+/// `private` is only sent when set, so an existing entry keeps its own.
 pub async fn update_user_entry(
     token: &str,
     media_id: i32,
     progress: i32,
     status: &str,
+    private: Option<bool>,
 ) -> Result<MediaListEntry> {
-    let variables = json!({
+    if fake::enabled() {
+        return Ok(fake::save_progress(media_id, progress, status));
+    }
+    let mut variables = json!({
         "mediaId": media_id,
         "progress": progress,
         "status": status
     });
+    if let Some(private) = private {
+        variables["private"] = private.into();
+    }
     let response = send_request(SAVE_PROGRESS_MUTATION, variables, Some(token)).await?;
     response.data.saved_entry.context("Failed to save entry")
 }
 
+/// A change made to many of the viewer's list entries at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BulkEdit {
+    Pause,
+    Delete,
+    /// Score out of 10.
+    Score(u8),
+}
+
+/// List entries changed per request, each as an aliased mutation, so a
+/// cleanup of hundreds of shows doesn't run into the rate limit.
+pub const BULK_BATCH: usize = 10;
+
+/// One request applying `edit` to every `(media id, list entry id)` in
+/// `entries`.
+fn bulk_mutation(edit: BulkEdit, entries: &[(i32, i32)]) -> String {
+    let fields: Vec<String> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, &(media_id, entry_id))| match edit {
+            BulkEdit::Pause => format!(
+                "e{}: SaveMediaListEntry(mediaId: {}, status: PAUSED) {{ id }}",
+                i, media_id
+            ),
+            BulkEdit::Delete => format!(
+                "e{}: DeleteMediaListEntry(id: {}) {{ deleted }}",
+                i, entry_id
+            ),
+            BulkEdit::Score(score) => format!(
+                "e{}: SaveMediaListEntry(mediaId: {}, scoreRaw: {}) {{ id }}",
+                i,
+                media_id,
+                score as i32 * 10
+            ),
+        })
+        .collect();
+    format!("mutation BulkEdit {{ {} }}", fields.join(" "))
+}
+
+/// Applies `edit` to a batch of at most `BULK_BATCH` list entries, given as
+/// `(media id, list entry id)`.
+pub async fn bulk_edit(token: &str, edit: BulkEdit, entries: &[(i32, i32)]) -> Result<()> {
+    if fake::enabled() {
+        return Ok(());
+    }
+    send_request(&bulk_mutation(edit, entries), json!({}), Some(token)).await?;
+    Ok(())
+}
+
+/// Replaces the notes on the viewer's list entry for the show.
+pub async fn update_user_notes(token: &str, media_id: i32, notes: &str) -> Result<()> {
+    if fake::enabled() {
+        return Ok(());
+    }
+    let variables = json!({ "mediaId": media_id, "notes": notes });
+    let response = send_request(SAVE_NOTES_MUTATION, variables, Some(token)).await?;
+    response.data.saved_entry.context("Failed to save notes")?;
+    Ok(())
+}
+
 pub async fn get_user_progress(token: &str, media_id: i32, username: &str) -> Result<Option<i32>> {
+    if fake::enabled() {
+        return Ok(fake::get_progress(media_id));
+    }
     let variables = json!({
         "mediaId": media_id,
         "userName": username
     });
 
-    let client = reqwest::Client::new();
+    let client = net::api_client();
     let json_body = json!({ "query": GET_PROGRESS_QUERY, "variables": variables });
 
     let res = client
@@ -120,7 +636,10 @@ pub async fn get_user_progress(token: &str, media_id: i32, username: &str) -> Re
 }
 
 pub async fn check_for_updates() -> Result<Option<String>> {
-    let client = reqwest::Client::new();
+    if fake::enabled() {
+        return Ok(None);
+    }
+    let client = net::api_client();
     let url = "https://crates.io/api/v1/crates/ani-l";
 
     let resp = client
@@ -155,16 +674,101 @@ pub async fn check_for_updates() -> Result<Option<String>> {
     Ok(None)
 }
 
+/// Set by `--debug-api`: failing AniList requests are written out in full.
+static DEBUG_API: AtomicBool = AtomicBool::new(false);
+
+pub fn enable_debug() {
+    DEBUG_API.store(true, Ordering::Relaxed);
+}
+
+/// The name a query or mutation was given, e.g. "SearchMedia".
+fn operation_name(query: &str) -> &str {
+    query
+        .split(['(', '{'])
+        .next()
+        .and_then(|head| head.split_whitespace().nth(1))
+        .unwrap_or("anonymous")
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+    /// Per-argument messages on validation errors.
+    #[serde(default)]
+    validation: Option<serde_json::Map<String, Value>>,
+}
+
+/// The `errors` of a GraphQL response as readable lines.
+fn graphql_errors(body: &str) -> Vec<String> {
+    let Ok(parsed) = serde_json::from_str::<ErrorBody>(body) else {
+        return Vec::new();
+    };
+    parsed
+        .errors
+        .into_iter()
+        .map(|e| match e.validation {
+            Some(fields) if !fields.is_empty() => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, messages)| format!("{}: {}", field, messages))
+                    .collect();
+                format!("{} ({})", e.message, fields.join(", "))
+            }
+            _ => e.message,
+        })
+        .collect()
+}
+
+/// Writes a failed request to the cache directory for a bug report. The
+/// token only ever travels in a header, so it isn't in here.
+fn dump_failure(
+    operation: &str,
+    request_id: &str,
+    status: u16,
+    query: &str,
+    variables: &Value,
+    body: &str,
+) -> Option<PathBuf> {
+    let dir = ProjectDirs::from("com", "sleepy-foundry", "ani-l")?
+        .cache_dir()
+        .join("api-debug");
+    fs::create_dir_all(&dir).ok()?;
+    let path = dir.join(format!("{}-{}.json", operation, request_id));
+    let report = json!({
+        "operation": operation,
+        "request_id": request_id,
+        "status": status,
+        "query": query,
+        "variables": variables,
+        "response": body,
+    });
+    fs::write(&path, serde_json::to_string_pretty(&report).ok()?).ok()?;
+    Some(path)
+}
+
+/// Sends a GraphQL request. Failures name the operation and a request id
+/// (Cloudflare's ray id when AniList's edge sends one, which AniList
+/// support can look up), and with `--debug-api` the whole exchange is
+/// saved.
 async fn send_request(
     query: &str,
     variables: Value,
     token: Option<&str>,
 ) -> Result<AniListResponse> {
-    let client = reqwest::Client::new();
+    let operation = operation_name(query);
+    let request_id = format!("{:08x}", rand::random::<u32>());
+    let client = net::api_client();
     let mut req = client
         .post(ANILIST_URL)
         .header("Content-Type", "application/json")
-        .header("Accept", "application/json");
+        .header("Accept", "application/json")
+        .header("X-Request-Id", &request_id);
 
     if let Some(t) = token {
         req = req.header("Authorization", format!("Bearer {}", t));
@@ -175,11 +779,132 @@ async fn send_request(
         .json(&json_body)
         .send()
         .await
-        .context("Failed to send request")?;
+        .context(Failure::Provider)
+        .with_context(|| format!("Failed to send the AniList {} request", operation))?;
 
-    if !res.status().is_success() {
-        anyhow::bail!("API Error: {}", res.text().await?);
+    let status = res.status();
+    let request_id = res
+        .headers()
+        .get("cf-ray")
+        .and_then(|v| v.to_str().ok())
+        .map_or(request_id, str::to_string);
+    let body = res.text().await?;
+    let errors = graphql_errors(&body);
+    let parsed = if status.is_success() {
+        serde_json::from_str::<AniListResponse>(&body).map_err(anyhow::Error::from)
+    } else {
+        Err(anyhow::anyhow!("HTTP {}", status))
+    };
+
+    match parsed {
+        Ok(response) => {
+            // Partial data still gets used; the errors only go to the log.
+            if !errors.is_empty() {
+                log::warn!(
+                    "AniList {} (request {}) returned errors: {}",
+                    operation,
+                    request_id,
+                    errors.join("; ")
+                );
+            }
+            Ok(response)
+        }
+        Err(e) => {
+            let detail = if errors.is_empty() {
+                format!("{:#}", e)
+            } else {
+                errors.join("; ")
+            };
+            log::warn!(
+                "AniList {} (request {}) failed: {}",
+                operation,
+                request_id,
+                detail
+            );
+            let mut message = format!(
+                "AniList {} failed (request {}): {}",
+                operation, request_id, detail
+            );
+            if DEBUG_API.load(Ordering::Relaxed)
+                && let Some(path) = dump_failure(
+                    operation,
+                    &request_id,
+                    status.as_u16(),
+                    query,
+                    &variables,
+                    &body,
+                )
+            {
+                message.push_str(&format!("; details saved to {}", path.display()));
+            }
+            Err(anyhow::Error::new(failure_for(status, &errors)).context(message))
+        }
     }
+}
+
+/// The exit code a failed AniList request stands for: a revoked or expired
+/// token is an auth failure, a missing show no results, the rest AniList's
+/// (or the network's) fault.
+fn failure_for(status: reqwest::StatusCode, errors: &[String]) -> Failure {
+    let invalid_token = errors
+        .iter()
+        .any(|e| e.to_lowercase().contains("invalid token"));
+    match status.as_u16() {
+        401 => Failure::Auth,
+        _ if invalid_token => Failure::Auth,
+        404 => Failure::NoResults,
+        _ => Failure::Provider,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    res.json().await.context("Failed to parse response")
+    #[test]
+    fn test_failure_details() {
+        assert_eq!(operation_name(SEARCH_QUERY), "SearchMedia");
+        assert_eq!(operation_name(VIEWER_QUERY), "Viewer");
+        assert_eq!(operation_name("{ Viewer { id } }"), "anonymous");
+
+        let body = r#"{"errors":[{"message":"validation","status":400,
+            "validation":{"perPage":["The per page may not be greater than 50."]}}],"data":null}"#;
+        assert_eq!(
+            graphql_errors(body),
+            vec![r#"validation (perPage: ["The per page may not be greater than 50."])"#]
+        );
+        assert!(graphql_errors("<html>Bad gateway</html>").is_empty());
+
+        let bad_request = reqwest::StatusCode::BAD_REQUEST;
+        assert_eq!(
+            failure_for(bad_request, &["Invalid token".to_string()]),
+            Failure::Auth
+        );
+        assert_eq!(failure_for(bad_request, &[]), Failure::Provider);
+        assert_eq!(
+            failure_for(reqwest::StatusCode::UNAUTHORIZED, &[]),
+            Failure::Auth
+        );
+        assert_eq!(
+            failure_for(reqwest::StatusCode::NOT_FOUND, &[]),
+            Failure::NoResults
+        );
+    }
+
+    #[test]
+    fn test_bulk_mutation() {
+        assert_eq!(
+            bulk_mutation(BulkEdit::Pause, &[(1, 10), (2, 20)]),
+            "mutation BulkEdit { e0: SaveMediaListEntry(mediaId: 1, status: PAUSED) { id } \
+             e1: SaveMediaListEntry(mediaId: 2, status: PAUSED) { id } }"
+        );
+        assert_eq!(
+            bulk_mutation(BulkEdit::Delete, &[(1, 10)]),
+            "mutation BulkEdit { e0: DeleteMediaListEntry(id: 10) { deleted } }"
+        );
+        assert_eq!(
+            bulk_mutation(BulkEdit::Score(7), &[(1, 10)]),
+            "mutation BulkEdit { e0: SaveMediaListEntry(mediaId: 1, scoreRaw: 70) { id } }"
+        );
+    }
 }
@@ -0,0 +1,149 @@
+//! Command line rewriting before clap sees it: user aliases from
+//! `[aliases]` and what a bare `ani-l` runs (`general.default_command`).
+
+use crate::config::Config;
+use crate::util;
+use directories::ProjectDirs;
+use std::fs;
+use std::path::PathBuf;
+
+/// Flags dropped before saving, with whether they take a value: replaying
+/// `--include-auth` would write credentials into a backup unasked.
+const DROPPED_FLAGS: &[(&str, bool)] = &[("--include-auth", false)];
+
+/// Expands the first argument if it names an alias, or fills in the default
+/// command when there are no arguments. `args[0]` is the program name.
+pub fn expand(mut args: Vec<String>, config: &Config) -> Vec<String> {
+    if args.len() < 2 {
+        let default = match config.general.default_command.as_str() {
+            "tui" | "" => return args,
+            "continue" => "continue".to_string(),
+            "last" => match last_command() {
+                Some(last) => last,
+                None => return args,
+            },
+            other => other.to_string(),
+        };
+        args.extend(split(&default));
+        return expand_alias(args, config);
+    }
+    expand_alias(args, config)
+}
+
+fn expand_alias(args: Vec<String>, config: &Config) -> Vec<String> {
+    match args.get(1).and_then(|first| config.aliases.get(first)) {
+        Some(expansion) => {
+            let mut expanded = vec![args[0].clone()];
+            expanded.extend(split(expansion));
+            expanded.extend(args.into_iter().skip(2));
+            expanded
+        }
+        None => args,
+    }
+}
+
+/// Splits on whitespace, keeping "double quoted" parts together.
+fn split(line: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+fn last_command_path() -> Option<PathBuf> {
+    ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+        .map(|dirs| dirs.data_dir().join("last_command"))
+}
+
+fn last_command() -> Option<String> {
+    let line = fs::read_to_string(last_command_path()?).ok()?;
+    let line = line.trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+/// Saves the (expanded) arguments for `default_command = "last"`. Which
+/// commands are worth it is up to the caller, going by the parsed command:
+/// `auth` has the token on its command line and is never passed here.
+pub fn remember(args: &[String]) {
+    if args.len() < 2 {
+        return;
+    }
+    let Some(path) = last_command_path() else {
+        return;
+    };
+    let line = scrub(&args[1..])
+        .iter()
+        .map(|a| {
+            if a.contains(char::is_whitespace) {
+                format!("\"{}\"", a)
+            } else {
+                a.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = util::write_private(&path, line);
+}
+
+/// `args` without the flags in `DROPPED_FLAGS` or their values.
+fn scrub(args: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.split_once('=').map_or(arg.as_str(), |(flag, _)| flag);
+        match DROPPED_FLAGS.iter().find(|(name, _)| *name == flag) {
+            Some((_, true)) if !arg.contains('=') => {
+                iter.next();
+            }
+            Some(_) => {}
+            None => kept.push(arg.clone()),
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_expansion() {
+        let mut config = Config::default();
+        config
+            .aliases
+            .insert("d".to_string(), "download -q".to_string());
+
+        let args = ["ani-l", "d", "Frieren", "-e", "3"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            expand(args, &config),
+            ["ani-l", "download", "-q", "Frieren", "-e", "3"].map(String::from)
+        );
+        assert_eq!(
+            split(r#"download -q "One Piece""#),
+            ["download", "-q", "One Piece"]
+        );
+        assert_eq!(
+            scrub(&split("backup create --include-auth b.json")),
+            ["backup", "create", "b.json"]
+        );
+    }
+}
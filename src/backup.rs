@@ -0,0 +1,340 @@
+//! `ani-l backup`: the config, registry, history and show mappings in one
+//! JSON archive, for moving to another machine or keeping a copy before an
+//! update. The AniList token and the passwords and tokens in the config
+//! only go in when asked for.
+
+use crate::util;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Layout version of the archive itself.
+const FORMAT: u32 = 1;
+
+const AUTH: &str = "config/auth.toml";
+const CONFIG: &str = "config/config.toml";
+
+/// Provider settings that hold credentials: a Cookie or Authorization
+/// header counts as much as a token.
+const PROVIDER_SECRETS: &[&str] = &["token", "password", "headers"];
+
+/// Archived files by name; the prefix says which directory they live in.
+const FILES: &[&str] = &[
+    CONFIG,
+    AUTH,
+    "config/registry.json",
+    "data/history.json",
+    "data/show_choices.json",
+    "data/queue.json",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub format: u32,
+    pub created: DateTime<Utc>,
+    /// ani-l version that wrote the archive.
+    pub app_version: String,
+    /// File contents by archive name.
+    pub files: BTreeMap<String, String>,
+}
+
+/// Where the archived files live on this machine.
+pub struct Dirs {
+    pub config: PathBuf,
+    pub data: PathBuf,
+}
+
+impl Dirs {
+    pub fn new() -> Result<Self> {
+        let proj_dirs = ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+            .context("Could not determine config directory")?;
+        Ok(Self {
+            config: proj_dirs.config_dir().to_path_buf(),
+            data: proj_dirs.data_dir().to_path_buf(),
+        })
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        let (dir, file) = name.split_once('/')?;
+        match dir {
+            "config" => Some(self.config.join(file)),
+            "data" => Some(self.data.join(file)),
+            _ => None,
+        }
+    }
+}
+
+/// Reads every state file that exists into an archive.
+pub fn collect(dirs: &Dirs, include_auth: bool) -> Result<Backup> {
+    let mut files = BTreeMap::new();
+    for &name in FILES {
+        if name == AUTH && !include_auth {
+            continue;
+        }
+        let path = dirs.path(name).expect("archived names have a known prefix");
+        if path.exists() {
+            let mut content = fs::read_to_string(&path)
+                .with_context(|| format!("Could not read {}", path.display()))?;
+            if name == CONFIG && !include_auth {
+                content = strip_secrets(&content)?;
+            }
+            files.insert(name.to_string(), content);
+        }
+    }
+    Ok(Backup {
+        format: FORMAT,
+        created: Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        files,
+    })
+}
+
+/// The config without provider tokens, passwords and extra headers, the
+/// room password or the proxy's credentials. Left as written (comments and
+/// all) when there are none.
+fn strip_secrets(config: &str) -> Result<String> {
+    let mut value: toml::Table = toml::from_str(config).context("config.toml doesn't parse")?;
+    let mut stripped = false;
+    let mut remove = |table: Option<&mut toml::Value>, key: &str| {
+        if let Some(table) = table.and_then(toml::Value::as_table_mut) {
+            stripped |= table.remove(key).is_some();
+        }
+    };
+    if let Some(providers) = value
+        .get_mut("providers")
+        .and_then(toml::Value::as_table_mut)
+    {
+        for (_, provider) in providers.iter_mut() {
+            for key in PROVIDER_SECRETS {
+                remove(Some(&mut *provider), key);
+            }
+        }
+    }
+    remove(value.get_mut("remote"), "password");
+    if let Some(proxy) = value.get_mut("network").and_then(|n| n.get_mut("proxy"))
+        && let Some(mut url) = proxy.as_str().and_then(|p| reqwest::Url::parse(p).ok())
+        && (!url.username().is_empty() || url.password().is_some())
+    {
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+        *proxy = toml::Value::String(url.to_string());
+        stripped = true;
+    }
+    if !stripped {
+        return Ok(config.to_string());
+    }
+    Ok(toml::to_string_pretty(&value)?)
+}
+
+/// The archived config with the secrets `strip_secrets` takes out filled
+/// back in from the local one, so restoring a backup made without
+/// `--include-auth` doesn't sign this machine out of its providers. Secrets
+/// the archive does hold win.
+fn keep_local_secrets(archived: &str, local: &str) -> Result<String> {
+    let mut value: toml::Table =
+        toml::from_str(archived).context("The backup's config.toml doesn't parse")?;
+    let Ok(local) = toml::from_str::<toml::Table>(local) else {
+        return Ok(archived.to_string());
+    };
+    let mut kept = false;
+    let mut keep = |table: &mut toml::Table, key: &str, local: Option<&toml::Value>| {
+        if let Some(local) = local
+            && !table.contains_key(key)
+        {
+            table.insert(key.to_string(), local.clone());
+            kept = true;
+        }
+    };
+    if let Some(providers) = local.get("providers").and_then(toml::Value::as_table) {
+        for (name, provider) in providers {
+            let Some(provider) = provider.as_table() else {
+                continue;
+            };
+            if !PROVIDER_SECRETS
+                .iter()
+                .any(|key| provider.contains_key(*key))
+            {
+                continue;
+            }
+            let archived = value
+                .entry("providers")
+                .or_insert_with(|| toml::Table::new().into())
+                .as_table_mut()
+                .context("providers isn't a table")?
+                .entry(name)
+                .or_insert_with(|| toml::Table::new().into())
+                .as_table_mut()
+                .with_context(|| format!("providers.{} isn't a table", name))?;
+            for key in PROVIDER_SECRETS {
+                keep(archived, key, provider.get(*key));
+            }
+        }
+    }
+    if let Some(password) = local.get("remote").and_then(|r| r.get("password")) {
+        let remote = value
+            .entry("remote")
+            .or_insert_with(|| toml::Table::new().into())
+            .as_table_mut()
+            .context("remote isn't a table")?;
+        keep(remote, "password", Some(password));
+    }
+    // The proxy comes back with its credentials when it is the same proxy.
+    let local_proxy = local
+        .get("network")
+        .and_then(|n| n.get("proxy"))
+        .and_then(toml::Value::as_str);
+    if let Some(local_proxy) = local_proxy
+        && let Ok(mut bare) = reqwest::Url::parse(local_proxy)
+        && let Some(proxy) = value.get_mut("network").and_then(|n| n.get_mut("proxy"))
+    {
+        let _ = bare.set_username("");
+        let _ = bare.set_password(None);
+        if proxy.as_str() == Some(bare.as_str()) && bare.as_str() != local_proxy {
+            *proxy = toml::Value::String(local_proxy.to_string());
+            kept = true;
+        }
+    }
+    if !kept {
+        return Ok(archived.to_string());
+    }
+    Ok(toml::to_string_pretty(&value)?)
+}
+
+/// Writes the archive to `path`, returning the names it holds. Only the
+/// owner can read it when it holds credentials.
+pub fn create(path: &Path, include_auth: bool) -> Result<Vec<String>> {
+    let backup = collect(&Dirs::new()?, include_auth)?;
+    let json = serde_json::to_string_pretty(&backup)?;
+    if include_auth {
+        util::write_private(path, json)
+    } else {
+        fs::write(path, json)
+    }
+    .with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(backup.files.into_keys().collect())
+}
+
+pub fn read(path: &Path) -> Result<Backup> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let backup: Backup = serde_json::from_str(&content)
+        .with_context(|| format!("{} isn't an ani-l backup", path.display()))?;
+    if backup.format > FORMAT {
+        bail!(
+            "{} was made by a newer ani-l ({}); update ani-l to restore it",
+            path.display(),
+            backup.app_version
+        );
+    }
+    Ok(backup)
+}
+
+/// Writes the archived files back over the local ones. An archive with any
+/// name outside the known set is refused before anything is written, so it
+/// can't write anywhere else. The local config's secrets survive unless
+/// the archive holds its own. The registry migrates itself on the next
+/// load if the archive is older.
+pub fn restore(backup: &Backup, dirs: &Dirs, include_auth: bool) -> Result<Vec<String>> {
+    if let Some(name) = backup
+        .files
+        .keys()
+        .find(|name| !FILES.contains(&name.as_str()))
+    {
+        bail!("Unexpected file {} in the backup", name);
+    }
+    let mut restored = Vec::new();
+    for (name, content) in &backup.files {
+        if name == AUTH && !include_auth {
+            continue;
+        }
+        let path = dirs.path(name).expect("archived names have a known prefix");
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = match fs::read_to_string(&path) {
+            Ok(local) if name == CONFIG => keep_local_secrets(content, &local)?,
+            _ => content.clone(),
+        };
+        fs::write(&path, content).with_context(|| format!("Could not write {}", path.display()))?;
+        restored.push(name.clone());
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_round_trip() {
+        let root = std::env::temp_dir().join(format!("ani-l-backup-{}", std::process::id()));
+        let dirs = |name: &str| Dirs {
+            config: root.join(name).join("config"),
+            data: root.join(name).join("data"),
+        };
+        let from = dirs("from");
+        fs::create_dir_all(&from.config).unwrap();
+        fs::create_dir_all(&from.data).unwrap();
+        fs::write(from.config.join("registry.json"), "{}").unwrap();
+        fs::write(from.config.join("auth.toml"), "anilist_token = \"x\"").unwrap();
+        fs::write(from.data.join("history.json"), "[]").unwrap();
+        fs::write(
+            from.config.join("config.toml"),
+            "[network]\nproxy = \"socks5://me:pw@127.0.0.1:1080\"\n\n\
+             [providers.jellyfin]\nurl = \"http://nas\"\ntoken = \"t\"\npassword = \"p\"\n\
+             headers = { Authorization = \"Bearer z\" }\n",
+        )
+        .unwrap();
+
+        let backup = collect(&from, false).unwrap();
+        assert_eq!(
+            backup.files.keys().collect::<Vec<_>>(),
+            vec![
+                "config/config.toml",
+                "config/registry.json",
+                "data/history.json"
+            ]
+        );
+        let config = &backup.files["config/config.toml"];
+        assert!(config.contains("http://nas"));
+        assert!(config.contains("socks5://127.0.0.1:1080"));
+        assert!(!config.contains("pw") && !config.contains("\"t\"") && !config.contains("\"p\""));
+        assert!(!config.contains("Bearer"));
+        assert!(collect(&from, true).unwrap().files["config/config.toml"].contains("token"));
+
+        let to = dirs("to");
+        fs::create_dir_all(&to.config).unwrap();
+        let local = "[remote]\npassword = \"r\"\n\n\
+                     [providers.jellyfin]\ntoken = \"mine\"\nheaders = { Cookie = \"c\" }\n";
+        fs::write(to.config.join("config.toml"), local).unwrap();
+        restore(&backup, &to, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(to.data.join("history.json")).unwrap(),
+            "[]"
+        );
+        assert!(!to.config.join("auth.toml").exists());
+        let restored = fs::read_to_string(to.config.join("config.toml")).unwrap();
+        assert!(restored.contains("http://nas") && restored.contains("\"mine\""));
+        assert!(restored.contains("Cookie") && restored.contains("\"r\""));
+
+        let mut evil = collect(&from, true).unwrap();
+        evil.files
+            .insert("config/../../.bashrc".to_string(), String::new());
+        assert!(restore(&evil, &to, true).is_err());
+        // Nothing is written before an unknown name is found.
+        evil.files.clear();
+        evil.files.insert(CONFIG.to_string(), String::new());
+        evil.files.insert("data/zzz".to_string(), String::new());
+        assert!(restore(&evil, &to, true).is_err());
+        assert_eq!(
+            fs::read_to_string(to.config.join("config.toml")).unwrap(),
+            restored
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! What `ani-l watch` goes through in order: a range of one show's episodes
+//! (`--episode 1-5`) or a text file (or stdin) of "title<TAB>episode" lines
+//! (`--batch`), for marathons and scripted queues.
+
+use crate::playlist;
+use anyhow::{Context, Result, bail};
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchItem {
+    pub title: String,
+    pub episode: String,
+}
+
+/// Reads the batch from `path`, or from stdin when it is "-".
+pub fn read(path: &Path) -> Result<Vec<BatchItem>> {
+    let mut text = String::new();
+    if path == Path::new("-") {
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .context("Could not read the batch from stdin")?;
+    } else {
+        text = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+    }
+    parse(&text)
+}
+
+/// The items for `--query`: `spec` is one episode, or a range or list to
+/// binge in order ("1-5", "1,3,5").
+pub fn episodes(title: &str, spec: &str) -> Result<Vec<BatchItem>> {
+    let episodes = if spec.contains([',', '-']) {
+        playlist::parse_episodes(spec)?
+            .iter()
+            .map(u32::to_string)
+            .collect()
+    } else {
+        vec![spec.trim().to_string()]
+    };
+    Ok(episodes
+        .into_iter()
+        .map(|episode| BatchItem {
+            title: title.to_string(),
+            episode,
+        })
+        .collect())
+}
+
+/// One item per line; blank lines and `#` comments are skipped.
+pub fn parse(text: &str) -> Result<Vec<BatchItem>> {
+    let mut items = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let Some((title, episode)) = line.rsplit_once('\t') else {
+            bail!("Line {}: expected \"title<TAB>episode\"", i + 1);
+        };
+        let (title, episode) = (title.trim(), episode.trim());
+        if title.is_empty() || episode.is_empty() {
+            bail!("Line {}: expected \"title<TAB>episode\"", i + 1);
+        }
+        items.push(BatchItem {
+            title: title.to_string(),
+            episode: episode.to_string(),
+        });
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch() {
+        let items = parse("# weekend\nFrieren\t5\r\n\nOshi no Ko: Season 2\t12\n").unwrap();
+        assert_eq!(
+            items,
+            vec![
+                BatchItem {
+                    title: "Frieren".to_string(),
+                    episode: "5".to_string(),
+                },
+                BatchItem {
+                    title: "Oshi no Ko: Season 2".to_string(),
+                    episode: "12".to_string(),
+                },
+            ]
+        );
+        let err = parse("Frieren\t1\nFrieren 2\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 2"));
+
+        let binge: Vec<String> = episodes("Frieren", "1-3,5")
+            .unwrap()
+            .into_iter()
+            .map(|item| item.episode)
+            .collect();
+        assert_eq!(binge, ["1", "2", "3", "5"]);
+        assert_eq!(episodes("Frieren", "5.5").unwrap()[0].episode, "5.5");
+    }
+}
@@ -0,0 +1,207 @@
+//! Record/replay of provider HTTP traffic. `--record-cassette FILE` saves
+//! every provider response (with signed tokens, keys and passwords redacted)
+//! so a user can attach it to a bug report; `--replay-cassette FILE`
+//! answers requests from it instead of the network, to reproduce extraction
+//! bugs locally.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Query parameters (in URLs and in recorded bodies) and JSON keys whose
+/// values are session-specific or secret and get redacted.
+const SENSITIVE_PARAMS: &[&str] = &[
+    "token",
+    "sig",
+    "signature",
+    "key",
+    "hash",
+    "expires",
+    "auth",
+    "session",
+    "access_token",
+    "refresh_token",
+    "password",
+    "api_key",
+    "apikey",
+    "x-plex-token",
+    "authorization",
+];
+
+const REDACTED: &str = "REDACTED";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Interaction {
+    pub url: String,
+    pub status: u16,
+    pub body: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+enum Mode {
+    Record(PathBuf, Cassette),
+    Replay(Cassette),
+}
+
+lazy_static! {
+    static ref MODE: Mutex<Option<Mode>> = Mutex::new(None);
+}
+
+pub fn start_recording(path: PathBuf) {
+    *MODE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Mode::Record(path, Cassette::default()));
+}
+
+pub fn start_replay(path: PathBuf) -> Result<()> {
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Could not read cassette {}", path.display()))?;
+    let cassette = serde_json::from_str(&content).context("Invalid cassette file")?;
+    *MODE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Mode::Replay(cassette));
+    Ok(())
+}
+
+/// The recorded status and body for `url` when replaying, `None` otherwise.
+/// Fails when replaying and the request wasn't recorded. Callers check the
+/// status as they would a live response's, so recorded failures reproduce.
+pub fn replay(url: &str) -> Result<Option<(u16, String)>> {
+    let mode = MODE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(Mode::Replay(cassette)) = &*mode else {
+        return Ok(None);
+    };
+    let key = sanitize(url);
+    cassette
+        .interactions
+        .iter()
+        .find(|i| i.url == key)
+        .map(|i| Some((i.status, i.body.clone())))
+        .with_context(|| format!("No recorded response for {}", key))
+}
+
+/// Appends a response when recording. The file is rewritten every time so a
+/// crash still leaves a usable cassette.
+pub fn record(url: &str, status: u16, body: &str) {
+    let mut mode = MODE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(Mode::Record(path, cassette)) = &mut *mode else {
+        return;
+    };
+    cassette.interactions.push(Interaction {
+        url: sanitize(url),
+        status,
+        body: sanitize(body),
+    });
+    if let Ok(json) = serde_json::to_string_pretty(cassette)
+        && let Err(e) = fs::write(&*path, json)
+    {
+        log::warn!("Could not write cassette {}: {}", path.display(), e);
+    }
+}
+
+fn is_sensitive(name: &str) -> bool {
+    SENSITIVE_PARAMS.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// Replaces the values of sensitive `name=value` pairs and `"name": "value"`
+/// JSON members with "REDACTED".
+fn sanitize(text: &str) -> String {
+    redact_json(&redact_params(text))
+}
+
+fn redact_params(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(eq) = rest.find('=') {
+        let (before, after) = rest.split_at(eq);
+        out.push_str(before);
+        out.push('=');
+        let name_start = before
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .map_or(0, |i| i + 1);
+        let name = &before[name_start..];
+        let value = &after[1..];
+        let value_len = value
+            .find(['&', '"', '\'', ' ', '\n', '\\', '#'])
+            .unwrap_or(value.len());
+        if is_sensitive(name) && value_len > 0 {
+            out.push_str(REDACTED);
+        } else {
+            out.push_str(&value[..value_len]);
+        }
+        rest = &value[value_len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn redact_json(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(colon) = rest.find(':') {
+        let (before, after) = rest.split_at(colon + 1);
+        out.push_str(before);
+        rest = after;
+        let key = before[..colon]
+            .trim_end()
+            .strip_suffix('"')
+            .and_then(|b| b.rfind('"').map(|i| &b[i + 1..]));
+        let value = after.trim_start();
+        if let Some(key) = key
+            && is_sensitive(key)
+            && let Some(string) = value.strip_prefix('"')
+            && let Some(end) = closing_quote(string)
+        {
+            out.push_str(&after[..after.len() - value.len()]);
+            out.push('"');
+            out.push_str(REDACTED);
+            out.push('"');
+            rest = &string[end + 1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Byte index of the quote ending a JSON string that starts at `text`.
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_redacts_signed_params() {
+        assert_eq!(
+            sanitize("https://cdn.example/v.m3u8?token=abc123&expires=99&q=frieren"),
+            "https://cdn.example/v.m3u8?token=REDACTED&expires=REDACTED&q=frieren"
+        );
+        assert_eq!(
+            sanitize(r#"{"link":"a?sig=xyz"}"#),
+            r#"{"link":"a?sig=REDACTED"}"#
+        );
+        assert_eq!(
+            sanitize("http://nas/Videos/1/stream?api_key=k1&X-Plex-Token=p1&static=true"),
+            "http://nas/Videos/1/stream?api_key=REDACTED&X-Plex-Token=REDACTED&static=true"
+        );
+        assert_eq!(
+            sanitize(
+                r#"{"token": "t1", "access_token":"a\"1", "refresh_token": "r1", "password": "p1", "X-Plex-Token": "x1", "name": "Frieren", "n": 5}"#
+            ),
+            r#"{"token": "REDACTED", "access_token":"REDACTED", "refresh_token": "REDACTED", "password": "REDACTED", "X-Plex-Token": "REDACTED", "name": "Frieren", "n": 5}"#
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Picking the provider's show for a search when several look alike (a
+//! sequel, a recap special), and remembering what the viewer picked so each
+//! search is only asked about once.
+
+use crate::normalizer;
+use crate::provider::models::ShowEdge;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use strsim::normalized_levenshtein;
+
+/// Results scoring within this of the best one count as look-alikes.
+const AMBIGUOUS_MARGIN: f64 = 0.15;
+
+/// Most look-alikes offered to choose from.
+const MAX_CANDIDATES: usize = 8;
+
+pub enum ShowMatch<'a> {
+    Found(&'a ShowEdge),
+    /// Best first.
+    Ambiguous(Vec<&'a ShowEdge>),
+}
+
+/// Results with the similarity of their normalized name to `query`, best
+/// first.
+pub fn rank<'a>(
+    provider_key: &str,
+    results: &'a [ShowEdge],
+    query: &str,
+) -> Vec<(&'a ShowEdge, f64)> {
+    let query = query.to_lowercase();
+    let mut scored: Vec<(&ShowEdge, f64)> = results
+        .iter()
+        .map(|show| {
+            let name = normalizer::normalize(provider_key, &show.name).to_lowercase();
+            (show, normalized_levenshtein(&name, &query))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored
+}
+
+/// The show `query` means on this provider: the remembered choice when it
+/// is still among the results, the best match when it clearly stands out,
+/// or the look-alikes to ask about.
+pub fn match_show<'a>(
+    provider_key: &str,
+    results: &'a [ShowEdge],
+    query: &str,
+    remembered: Option<&str>,
+) -> Option<ShowMatch<'a>> {
+    if let Some(show) = remembered.and_then(|id| results.iter().find(|s| s.id == id)) {
+        return Some(ShowMatch::Found(show));
+    }
+    let ranked = rank(provider_key, results, query);
+    let best = ranked.first()?.1;
+    let close: Vec<&ShowEdge> = ranked
+        .iter()
+        .take_while(|(_, score)| best - score <= AMBIGUOUS_MARGIN)
+        .take(MAX_CANDIDATES)
+        .map(|(show, _)| *show)
+        .collect();
+    match close.as_slice() {
+        [only] => Some(ShowMatch::Found(only)),
+        _ => Some(ShowMatch::Ambiguous(close)),
+    }
+}
+
+pub struct ShowChoices {
+    file_path: PathBuf,
+    /// Show id per provider and lowercased query.
+    pub data: HashMap<String, String>,
+}
+
+impl ShowChoices {
+    pub fn new() -> Result<Self> {
+        let proj_dirs = ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+            .context("Could not determine data directory")?;
+        let data_dir = proj_dirs.data_dir();
+        fs::create_dir_all(data_dir)?;
+        let file_path = data_dir.join("show_choices.json");
+
+        let data = if file_path.exists() {
+            let content = fs::read_to_string(&file_path)?;
+            serde_json::from_str(&content).unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self { file_path, data })
+    }
+
+    fn key(provider_key: &str, query: &str) -> String {
+        format!("{}:{}", provider_key, query.trim().to_lowercase())
+    }
+
+    pub fn get(&self, provider_key: &str, query: &str) -> Option<&str> {
+        self.data
+            .get(&Self::key(provider_key, query))
+            .map(String::as_str)
+    }
+
+    pub fn remember(&mut self, provider_key: &str, query: &str, show_id: &str) -> Result<()> {
+        self.data
+            .insert(Self::key(provider_key, query), show_id.to_string());
+        let json_str = serde_json::to_string_pretty(&self.data)?;
+        fs::write(&self.file_path, json_str)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::models::AvailableEpisodes;
+
+    fn show(id: &str, name: &str) -> ShowEdge {
+        ShowEdge {
+            id: id.to_string(),
+            name: name.to_string(),
+            available_episodes: AvailableEpisodes {
+                sub: 12,
+                dub: 0,
+                raw: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_match_show() {
+        let results = vec![
+            show("a", "Sousou no Frieren"),
+            show("b", "Sousou no Frieren 2"),
+            show("c", "One Piece"),
+        ];
+        let Some(ShowMatch::Ambiguous(close)) =
+            match_show("allanime", &results, "Sousou no Frieren", None)
+        else {
+            panic!("expected look-alikes");
+        };
+        let ids: Vec<&str> = close.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        assert!(matches!(
+            match_show("allanime", &results, "Sousou no Frieren", Some("b")),
+            Some(ShowMatch::Found(s)) if s.id == "b"
+        ));
+        assert!(matches!(
+            match_show("allanime", &results, "One Piece", None),
+            Some(ShowMatch::Found(s)) if s.id == "c"
+        ));
+        assert!(match_show("allanime", &[], "One Piece", None).is_none());
+    }
+}
@@ -1,10 +1,17 @@
 use crate::api;
+use crate::exit::Failure;
+use crate::models::Media;
+use crate::output;
+use crate::player::chapters::SkipMode;
+use crate::player::launch::LaunchMode;
 use anyhow::{Context, Result};
 use crossterm::style::Stylize;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
@@ -15,15 +22,67 @@ const ANILIST_AUTH_URL: &str =
 pub struct Config {
     pub general: GeneralConfig,
     pub stream: StreamConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// Per-provider overrides, keyed by provider name (e.g. "allanime").
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderConfig>,
+    #[serde(default)]
+    pub download: DownloadConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub blocklist: BlocklistConfig,
+    #[serde(default)]
+    pub remote: RemoteConfig,
+    #[serde(default)]
+    pub anilist: AniListConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub picker: PickerConfig,
+    /// Command aliases, e.g. `d = "download -q"`.
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// TUI keyboard macros by F-key, e.g. `F5 = "/frieren<Enter><Enter>"`.
+    #[serde(default)]
+    pub macros: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn provider_config(&self, name: &str) -> ProviderConfig {
+        self.providers.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Reads the config file without creating it or running the setup
+    /// wizard. Defaults when it doesn't exist yet.
+    pub fn peek() -> Self {
+        ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+            .and_then(|dirs| fs::read_to_string(dirs.config_dir().join("config.toml")).ok())
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GeneralConfig {
     pub provider: String,
+    /// Used in order instead of `provider` while `ani-l doctor` last found
+    /// it unreachable.
+    #[serde(default)]
+    pub fallback_providers: Vec<String>,
     #[serde(default = "default_language")]
     pub language: String,
     #[serde(default = "default_check_updates")]
     pub check_updates: bool,
+    /// What a bare `ani-l` runs: "tui", "continue" (resume the last watched
+    /// show), "last" (repeat the previous command) or any command line.
+    #[serde(default = "default_command")]
+    pub default_command: String,
+}
+
+fn default_command() -> String {
+    "tui".to_string()
 }
 
 fn default_language() -> String {
@@ -37,9 +96,342 @@ fn default_check_updates() -> bool {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct StreamConfig {
     pub player: String,
+    /// Tried in order when `player` isn't installed.
+    #[serde(default)]
+    pub fallback_players: Vec<String>,
+    /// "auto", "direct", "host-spawn" (ani-l inside Flatpak) or "flatpak"
+    /// (player installed as a Flatpak).
+    #[serde(default)]
+    pub launch_mode: LaunchMode,
+    /// Overrides the player's Flatpak app id (default "io.mpv.Mpv").
+    #[serde(default)]
+    pub flatpak_app_id: Option<String>,
+    /// What to do without a display (e.g. over SSH): "terminal" plays inside
+    /// the terminal, "download" saves the episode instead.
+    #[serde(default = "default_headless_action")]
+    pub headless_action: String,
+    /// mpv `--vo` for terminal playback: "auto", "tct", "sixel" or "kitty".
+    #[serde(default = "default_terminal_vo")]
+    pub terminal_vo: String,
+    /// Offer a recap before resuming a show untouched for this many weeks
+    /// (0 turns it off).
+    #[serde(default = "default_recap_after_weeks")]
+    pub recap_after_weeks: u32,
+    /// Pause mpv when the system wakes from sleep.
+    #[serde(default = "default_pause_on_suspend")]
+    pub pause_on_suspend: bool,
+    /// Pause mpv when the session is locked (needs systemd-logind).
+    #[serde(default)]
+    pub pause_on_lock: bool,
+    /// Mark openings and endings as mpv chapters, using AniSkip's times.
+    #[serde(default = "default_chapters")]
+    pub chapters: bool,
+    /// Skip openings, endings and recaps in mpv: "off", "key" (offer it on
+    /// screen) or "auto". Anything but "off" looks up AniSkip's times even
+    /// with `chapters` off.
+    #[serde(default)]
+    pub skip: SkipMode,
+    /// Open the player fullscreen. Unset, mpv opens the way its last window
+    /// was left.
+    #[serde(default)]
+    pub fullscreen: Option<bool>,
+    /// mpv `--geometry`, e.g. "50%x50%+center" or "1280x720".
+    #[serde(default)]
+    pub geometry: Option<String>,
     pub quality: String,
     pub translation_type: String,
+    /// Subtitle track to keep when a source offers several, by language name
+    /// ("English") or code ("en"). Unset keeps them all.
+    #[serde(default)]
+    pub subtitle_language: Option<String>,
     pub episode_complete_at: u8,
+    /// Save progress the moment an episode passes `episode_complete_at`
+    /// rather than when the player closes, so a crash doesn't lose it (mpv
+    /// only).
+    #[serde(default)]
+    pub sync_during_playback: bool,
+    /// Start the next episode when one finishes (mpv only).
+    #[serde(default)]
+    pub autoplay_next: bool,
+    /// Command run for `player = "custom"`, with `{url}`, `{title}` and
+    /// `{headers}` placeholders.
+    #[serde(default)]
+    pub custom_command: Option<String>,
+}
+
+fn default_headless_action() -> String {
+    "terminal".to_string()
+}
+
+fn default_terminal_vo() -> String {
+    "auto".to_string()
+}
+
+fn default_recap_after_weeks() -> u32 {
+    4
+}
+
+fn default_pause_on_suspend() -> bool {
+    true
+}
+
+fn default_chapters() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetworkConfig {
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    #[serde(default = "default_min_request_interval_ms")]
+    pub min_request_interval_ms: u64,
+    /// Per-host overrides, keyed by host name (e.g. "api.allanime.day").
+    #[serde(default)]
+    pub hosts: HashMap<String, HostLimitConfig>,
+    /// DNS-over-HTTPS JSON endpoint used to resolve provider hosts,
+    /// e.g. "https://cloudflare-dns.com/dns-query". Unset uses the system resolver.
+    #[serde(default)]
+    pub dns_over_https: Option<String>,
+    /// Proxy for AniList and provider traffic, e.g. "http://127.0.0.1:8080" or
+    /// "socks5h://127.0.0.1:1080".
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Extra PEM root certificate(s) to trust, for proxies that intercept TLS.
+    #[serde(default)]
+    pub ca_certificate: Option<PathBuf>,
+    /// Gives up on a request after this many seconds. Unset waits as long as
+    /// the server keeps the connection open.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HostLimitConfig {
+    pub max_concurrent_requests: usize,
+    pub min_request_interval_ms: u64,
+}
+
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
+fn default_min_request_interval_ms() -> u64 {
+    250
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: default_max_concurrent_requests(),
+            min_request_interval_ms: default_min_request_interval_ms(),
+            hosts: HashMap::new(),
+            dns_over_https: None,
+            proxy: None,
+            ca_certificate: None,
+            timeout_secs: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProviderConfig {
+    /// Replaces the built-in browser User-Agent sent to the provider.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Replaces the built-in Referer sent to the provider API.
+    #[serde(default)]
+    pub referer: Option<String>,
+    /// Replaces the Referer the player sends with the stream, for when the
+    /// video host starts checking it.
+    #[serde(default)]
+    pub stream_referer: Option<String>,
+    /// Replaces the provider's built-in site address, for when it moves
+    /// domains.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Release groups to prefer, best first (torrent providers).
+    #[serde(default)]
+    pub release_groups: Vec<String>,
+    /// Extra headers added to every provider request.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// API key or access token (Jellyfin, Plex), or refresh token
+    /// (Crunchyroll).
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Sign-in used when no token is set (Jellyfin, Plex, Crunchyroll).
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Library names to search (Jellyfin, Plex). Defaults to the series
+    /// libraries with "anime" in their name, or all of them.
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    /// Replaces the built-in OAuth client id (Crunchyroll), for when the
+    /// service stops accepting it.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Channel handles to search, e.g. "@MuseAsia" (YouTube). Defaults to
+    /// Muse Asia and Ani-One.
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadConfig {
+    /// Where episodes are saved. Defaults to the app data directory.
+    #[serde(default)]
+    pub directory: Option<String>,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Also check finished files with ffprobe (if installed).
+    #[serde(default = "default_verify_with_ffprobe")]
+    pub verify_with_ffprobe: bool,
+}
+
+fn default_max_retries() -> u32 {
+    8
+}
+
+fn default_verify_with_ffprobe() -> bool {
+    true
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            max_retries: default_max_retries(),
+            verify_with_ffprobe: default_verify_with_ffprobe(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UiConfig {
+    /// Cover art protocol: "auto", "kitty", "sixel", "iterm2", "halfblocks" or
+    /// "ascii".
+    #[serde(default = "default_image_protocol")]
+    pub image_protocol: String,
+    /// Draw the show's banner, dimmed, behind the details text.
+    #[serde(default = "default_banner_background")]
+    pub banner_background: bool,
+    /// Year, format and genre chips next to titles in media lists.
+    #[serde(default = "default_list_chips")]
+    pub list_chips: bool,
+    #[serde(default)]
+    pub score_colors: ScoreColors,
+    /// Show synopses of unwatched episodes without pressing `s` first.
+    #[serde(default)]
+    pub episode_spoilers: bool,
+    /// Ask before quitting with syncs or downloads running and before
+    /// overwriting progress.
+    #[serde(default = "default_confirm_prompts")]
+    pub confirm_prompts: bool,
+    /// Ask for a 1–10 rating when a finished episode's player closes.
+    #[serde(default = "default_rate_episodes")]
+    pub rate_episodes: bool,
+}
+
+/// Colours for average score bands. Accepts colour names ("light-red") or
+/// hex ("#ffd700").
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ScoreColors {
+    /// Below 60.
+    pub low: String,
+    /// 60–74.
+    pub mid: String,
+    /// 75–84.
+    pub high: String,
+    /// 85 and above.
+    pub top: String,
+}
+
+impl Default for ScoreColors {
+    fn default() -> Self {
+        Self {
+            low: "red".to_string(),
+            mid: "yellow".to_string(),
+            high: "green".to_string(),
+            top: "#ffd700".to_string(),
+        }
+    }
+}
+
+fn default_image_protocol() -> String {
+    "auto".to_string()
+}
+
+fn default_banner_background() -> bool {
+    true
+}
+
+fn default_list_chips() -> bool {
+    true
+}
+
+fn default_confirm_prompts() -> bool {
+    true
+}
+
+fn default_rate_episodes() -> bool {
+    true
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            image_protocol: default_image_protocol(),
+            banner_background: default_banner_background(),
+            list_chips: default_list_chips(),
+            score_colors: ScoreColors::default(),
+            episode_spoilers: false,
+            confirm_prompts: default_confirm_prompts(),
+            rate_episodes: default_rate_episodes(),
+        }
+    }
+}
+
+/// Genres, tags and studios to keep out of media lists. Names are matched
+/// case-insensitively.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BlocklistConfig {
+    pub genres: Vec<String>,
+    pub tags: Vec<String>,
+    pub studios: Vec<String>,
+    pub mode: BlockMode,
+}
+
+/// What happens to blocked entries.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlockMode {
+    /// Leave them out of the list.
+    #[default]
+    Hide,
+    /// Keep them, greyed out.
+    Dim,
+}
+
+impl BlocklistConfig {
+    pub fn blocks(&self, media: &Media) -> bool {
+        let listed =
+            |list: &[String], name: &str| list.iter().any(|b| b.eq_ignore_ascii_case(name));
+        media.genres.iter().any(|g| listed(&self.genres, g))
+            || media
+                .tags
+                .iter()
+                .flatten()
+                .any(|t| listed(&self.tags, &t.name))
+            || media
+                .studios
+                .iter()
+                .flat_map(|s| &s.nodes)
+                .any(|s| listed(&self.studios, &s.name))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -48,20 +440,190 @@ pub struct AuthConfig {
     pub username: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AniListConfig {
+    /// Add shows to the list as private entries.
+    pub private_by_default: bool,
+}
+
+impl AniListConfig {
+    /// Privacy to save an entry with: shows new to the list follow
+    /// `private_by_default`, ones already on it keep their own.
+    pub fn private_for(&self, on_list: bool) -> Option<bool> {
+        (!on_list && self.private_by_default).then_some(true)
+    }
+}
+
+/// Who may reach what ani-l serves on the network (watch-together rooms,
+/// the DLNA server).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Address to listen on, e.g. "192.168.1.20" for one interface only.
+    pub bind: String,
+    /// Guests must give this to join a room. Unset, each room makes one up.
+    pub password: Option<String>,
+    /// Addresses or CIDR ranges ("192.168.1.0/24") allowed to connect.
+    /// Everyone when empty.
+    pub allow: Vec<String>,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0".to_string(),
+            password: None,
+            allow: Vec::new(),
+        }
+    }
+}
+
+impl RemoteConfig {
+    pub fn bind_addr(&self) -> Result<IpAddr> {
+        self.bind
+            .parse()
+            .with_context(|| format!("Invalid remote.bind address '{}'", self.bind))
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        self.allow.is_empty() || self.allow.iter().any(|range| in_range(ip, range))
+    }
+
+    /// Compares in constant time so the password can't be guessed byte by
+    /// byte from response times.
+    pub fn password_matches(&self, given: Option<&str>) -> bool {
+        let Some(expected) = &self.password else {
+            return true;
+        };
+        let given = given.unwrap_or_default().as_bytes();
+        let expected = expected.as_bytes();
+        given.len() == expected.len()
+            && given
+                .iter()
+                .zip(expected)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Shell commands run on playback events. `{title}`, `{ep}`, `{id}` and
+/// `{progress}` become references to `$ANI_L_TITLE` and friends, so they
+/// belong in double quotes, e.g.
+/// `on_episode_complete = "notify-send \"Finished {title} E{ep}\""`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// When an episode starts playing, including ones reached with Next.
+    pub on_episode_start: Option<String>,
+    /// When an episode is watched past `stream.episode_complete_at`.
+    pub on_episode_complete: Option<String>,
+    /// When the player closes, however far the episode got.
+    pub on_playback_end: Option<String>,
+}
+
+/// Who picks between search results and episodes on the command line.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PickerMode {
+    /// Numbered prompt on the terminal, only when results look alike.
+    #[default]
+    Builtin,
+    /// `picker.command`, for every choice with more than one option.
+    External,
+}
+
+/// An external picker such as fzf, rofi or dmenu: the options go to its
+/// stdin one per line and the line it prints back is the pick. `{prompt}`
+/// is filled in, e.g. `command = "rofi -dmenu -i -p '{prompt}'"`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PickerConfig {
+    pub mode: PickerMode,
+    pub command: String,
+}
+
+impl Default for PickerConfig {
+    fn default() -> Self {
+        Self {
+            mode: PickerMode::Builtin,
+            command: "fzf --prompt '{prompt}> '".to_string(),
+        }
+    }
+}
+
+/// Whether `ip` is `range`, a single address or CIDR block. IPv4 clients
+/// reaching an IPv6 socket are compared as IPv4.
+fn in_range(ip: IpAddr, range: &str) -> bool {
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    };
+    let (network, prefix) = match range.trim().split_once('/') {
+        Some((network, prefix)) => (network, prefix.parse::<u32>().ok()),
+        None => (range.trim(), None),
+    };
+    let Ok(network) = network.parse::<IpAddr>() else {
+        return false;
+    };
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let prefix = prefix.unwrap_or(32).min(32);
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let prefix = prefix.unwrap_or(128).min(128);
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             general: GeneralConfig {
                 provider: "allanime".to_string(),
+                fallback_providers: Vec::new(),
                 language: "en".to_string(),
                 check_updates: true,
+                default_command: default_command(),
             },
             stream: StreamConfig {
                 player: "mpv".to_string(),
+                fallback_players: Vec::new(),
+                launch_mode: LaunchMode::Auto,
+                flatpak_app_id: None,
+                headless_action: default_headless_action(),
+                terminal_vo: default_terminal_vo(),
+                recap_after_weeks: default_recap_after_weeks(),
+                pause_on_suspend: default_pause_on_suspend(),
+                pause_on_lock: false,
+                chapters: default_chapters(),
+                skip: SkipMode::default(),
+                fullscreen: None,
+                geometry: None,
                 quality: "1080".to_string(),
                 translation_type: "sub".to_string(),
+                subtitle_language: None,
                 episode_complete_at: 85,
+                sync_during_playback: false,
+                autoplay_next: false,
+                custom_command: None,
             },
+            network: NetworkConfig::default(),
+            providers: HashMap::new(),
+            download: DownloadConfig::default(),
+            ui: UiConfig::default(),
+            blocklist: BlocklistConfig::default(),
+            remote: RemoteConfig::default(),
+            anilist: AniListConfig::default(),
+            hooks: HooksConfig::default(),
+            picker: PickerConfig::default(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
         }
     }
 }
@@ -76,6 +638,66 @@ pub struct ConfigManager {
     pub config: Config,
     #[allow(dead_code)]
     pub auth: AuthConfig,
+    saved_config: Saved,
+    saved_auth: Saved,
+}
+
+/// A file's contents as loaded, and as they stood once this run's
+/// overrides (`--provider` and the like) were applied. Saving only writes
+/// what changed since then, so the overrides never reach the file.
+#[derive(Clone)]
+struct Saved {
+    disk: toml::Value,
+    baseline: toml::Value,
+}
+
+impl Saved {
+    fn of(value: &impl Serialize) -> Result<Self> {
+        let disk = toml::Value::try_from(value)?;
+        Ok(Self {
+            baseline: disk.clone(),
+            disk,
+        })
+    }
+
+    fn to_write(&self, current: &impl Serialize) -> Result<String> {
+        let current = toml::Value::try_from(current)?;
+        Ok(toml::to_string_pretty(&changes_only(
+            Some(&self.disk),
+            &self.baseline,
+            current,
+        ))?)
+    }
+}
+
+/// `current` with every value still equal to `baseline` put back to what's
+/// on `disk` (or left out, when it isn't there).
+fn changes_only(
+    disk: Option<&toml::Value>,
+    baseline: &toml::Value,
+    current: toml::Value,
+) -> toml::Value {
+    match (baseline, current) {
+        (toml::Value::Table(baseline), toml::Value::Table(current)) => {
+            let disk = disk.and_then(toml::Value::as_table);
+            toml::Value::Table(
+                current
+                    .into_iter()
+                    .filter_map(|(key, value)| {
+                        let on_disk = disk.and_then(|d| d.get(&key));
+                        let value = match baseline.get(&key) {
+                            Some(base) if on_disk.is_none() && *base == value => return None,
+                            Some(base) => changes_only(on_disk, base, value),
+                            None => value,
+                        };
+                        Some((key, value))
+                    })
+                    .collect(),
+            )
+        }
+        (baseline, current) if *baseline == current => disk.cloned().unwrap_or(current),
+        (_, current) => current,
+    }
 }
 
 impl ConfigManager {
@@ -90,7 +712,12 @@ impl ConfigManager {
         let auth_path = config_dir.join("auth.toml");
 
         if !config_path.exists() {
-            Self::run_setup_wizard(&config_path).await?;
+            if output::interactive() {
+                Self::run_setup_wizard(&config_path).await?;
+            } else {
+                // Scripted first run: nobody can answer the wizard.
+                fs::write(&config_path, toml::to_string_pretty(&Config::default())?)?;
+            }
         }
 
         let config = if config_path.exists() {
@@ -116,6 +743,8 @@ impl ConfigManager {
         let manager = Self {
             config_path,
             auth_path,
+            saved_config: Saved::of(&config)?,
+            saved_auth: Saved::of(&auth)?,
             config,
             auth,
         };
@@ -158,13 +787,27 @@ impl ConfigManager {
         Ok(Self {
             config_path,
             auth_path,
+            saved_config: Saved::of(&config)?,
+            saved_auth: Saved::of(&auth)?,
             config,
             auth,
         })
     }
 
+    /// Changes the config or auth for this run only; saving later keeps
+    /// these values out of the files.
+    pub fn apply_overrides(
+        &mut self,
+        apply: impl FnOnce(&mut Config, &mut AuthConfig),
+    ) -> Result<()> {
+        apply(&mut self.config, &mut self.auth);
+        self.saved_config.baseline = toml::Value::try_from(&self.config)?;
+        self.saved_auth.baseline = toml::Value::try_from(&self.auth)?;
+        Ok(())
+    }
+
     pub fn save_config(&self) -> Result<()> {
-        let toml_str = toml::to_string_pretty(&self.config)?;
+        let toml_str = self.saved_config.to_write(&self.config)?;
         fs::write(&self.config_path, toml_str)?;
         Ok(())
     }
@@ -208,17 +851,25 @@ impl ConfigManager {
             let config_dir = proj_dirs.config_dir();
             let auth_path = config_dir.join("auth.toml");
 
+            let auth = AuthConfig {
+                anilist_token: None,
+                username: None,
+            };
             let mut temp_manager = Self {
                 config_path: config_path.to_path_buf(),
                 auth_path,
+                saved_config: Saved::of(&config)?,
+                saved_auth: Saved::of(&auth)?,
                 config: config.clone(),
-                auth: AuthConfig {
-                    anilist_token: None,
-                    username: None,
-                },
+                auth,
             };
 
-            temp_manager.authenticate_interactive().await?;
+            if let Err(e) = temp_manager.authenticate_interactive().await {
+                say_err!(
+                    "{}",
+                    t!("setup.auth_failed", error = e.root_cause().to_string())
+                );
+            }
         }
 
         println!("{}", t!("setup.complete"));
@@ -228,6 +879,10 @@ impl ConfigManager {
     }
 
     pub async fn authenticate_interactive(&mut self) -> Result<()> {
+        if !output::interactive() {
+            return Err(anyhow::Error::new(Failure::Auth)
+                .context("Not a terminal; pass the token instead: ani-l auth <token>"));
+        }
         println!("{}", t!("setup.auth_browser"));
         println!("{}", t!("setup.auth_link", url = ANILIST_AUTH_URL));
         println!("{}", t!("setup.auth_tip_1"));
@@ -246,23 +901,23 @@ impl ConfigManager {
     }
 
     pub async fn verify_and_save_token(&mut self, token: &str) -> Result<()> {
-        println!("{}", t!("setup.verifying"));
+        say!("{}", t!("setup.verifying"));
         match api::authenticate_user(token).await {
             Ok(user) => {
-                println!("{}", t!("setup.logged_in", name = user.name));
+                say!("{}", t!("setup.logged_in", name = user.name));
                 self.auth.anilist_token = Some(token.to_string());
                 self.auth.username = Some(user.name);
                 self.save_auth()?;
             }
             Err(e) => {
-                eprintln!("{}", t!("setup.auth_failed", error = e.to_string()));
+                return Err(e.context(Failure::Auth));
             }
         }
         Ok(())
     }
 
     pub fn save_auth(&self) -> Result<()> {
-        let toml_str = toml::to_string_pretty(&self.auth)?;
+        let toml_str = self.saved_auth.to_write(&self.auth)?;
         fs::write(&self.auth_path, toml_str)?;
         Ok(())
     }
@@ -342,10 +997,73 @@ mod tests {
 
         assert_eq!(config.general.provider, "allanime");
         assert_eq!(config.general.language, "en");
-        assert_eq!(config.general.check_updates, true);
+        assert!(config.general.check_updates);
         assert_eq!(config.stream.player, "mpv");
         assert_eq!(config.stream.quality, "1080");
         assert_eq!(config.stream.translation_type, "sub");
         assert_eq!(config.stream.episode_complete_at, 85);
+        assert!(!config.stream.sync_during_playback);
+        assert_eq!(config.network.max_concurrent_requests, 4);
+        assert_eq!(config.network.min_request_interval_ms, 250);
+    }
+
+    #[test]
+    fn test_overrides_stay_out_of_saved_config() {
+        let mut config = Config::default();
+        config.stream.quality = "720".to_string();
+        let mut saved = Saved::of(&config).unwrap();
+
+        config.general.provider = "fake".to_string();
+        config.stream.subtitle_language = Some("pt".to_string());
+        saved.baseline = toml::Value::try_from(&config).unwrap();
+        config.stream.quality = "1080".to_string();
+
+        let written: Config = toml::from_str(&saved.to_write(&config).unwrap()).unwrap();
+        assert_eq!(written.general.provider, Config::default().general.provider);
+        assert_eq!(written.stream.subtitle_language, None);
+        assert_eq!(written.stream.quality, "1080");
+    }
+
+    #[test]
+    fn test_remote_access() {
+        let remote = RemoteConfig {
+            password: Some("hunter2".to_string()),
+            allow: vec!["192.168.1.0/24".to_string(), "10.0.0.5".to_string()],
+            ..Default::default()
+        };
+        assert!(remote.allows("192.168.1.77".parse().unwrap()));
+        assert!(remote.allows("::ffff:10.0.0.5".parse().unwrap()));
+        assert!(!remote.allows("192.168.2.1".parse().unwrap()));
+        assert!(!remote.allows("10.0.0.6".parse().unwrap()));
+        assert!(RemoteConfig::default().allows("8.8.8.8".parse().unwrap()));
+
+        assert!(remote.password_matches(Some("hunter2")));
+        assert!(!remote.password_matches(Some("hunter")));
+        assert!(!remote.password_matches(None));
+        assert!(RemoteConfig::default().password_matches(None));
+    }
+
+    #[test]
+    fn test_blocklist_matches_any_field() {
+        let blocklist: BlocklistConfig = toml::from_str(
+            r#"
+            genres = ["ecchi"]
+            studios = ["Studio Deen"]
+            mode = "dim"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(blocklist.mode, BlockMode::Dim);
+
+        let media = |json: &str| -> Media { serde_json::from_str(json).unwrap() };
+        let base = r#""id": 1, "title": {"romaji": "X"}, "genres": ["Action"]"#;
+        assert!(!blocklist.blocks(&media(&format!("{{{}}}", base))));
+        assert!(blocklist.blocks(&media(
+            r#"{"id": 2, "title": {"romaji": "Y"}, "genres": ["Ecchi"]}"#
+        )));
+        assert!(blocklist.blocks(&media(&format!(
+            r#"{{{}, "studios": {{"nodes": [{{"name": "studio deen"}}]}}}}"#,
+            base
+        ))));
     }
 }
@@ -0,0 +1,825 @@
+//! A small DLNA/UPnP media server for the downloaded library, so smart TVs
+//! on the LAN can browse and play finished downloads. It answers SSDP
+//! discovery, the ContentDirectory `Browse` action and ranged file requests;
+//! nothing else of UPnP is needed for playback.
+
+use crate::api;
+use crate::config::{Config, RemoteConfig};
+use crate::download::{DownloadEntry, DownloadManager, DownloadStatus};
+use crate::provider::html::decode_entities;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use log::debug;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+pub const DEFAULT_PORT: u16 = 8200;
+
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+/// How often the server re-announces itself; UPnP asks for well under the
+/// advertised max-age.
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_AGE_SECS: u64 = 1800;
+/// Requests whose line and headers run longer than this are dropped.
+const MAX_HEAD_BYTES: u64 = 16 * 1024;
+
+const DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+const CONNECTION_MANAGER: &str = "urn:schemas-upnp-org:service:ConnectionManager:1";
+
+struct Episode {
+    title: String,
+    path: PathBuf,
+    size: u64,
+    mime: &'static str,
+}
+
+struct Show {
+    title: String,
+    art: Option<String>,
+    episodes: Vec<Episode>,
+}
+
+struct Server {
+    name: String,
+    uuid: String,
+    base_url: String,
+    remote: RemoteConfig,
+    shows: Vec<Show>,
+}
+
+/// Serves finished downloads until interrupted.
+pub async fn serve(config: &Config, token: Option<&str>, name: &str, port: u16) -> Result<()> {
+    let manager = DownloadManager::new(config)?;
+    let mut shows = group_shows(&manager.manifest.entries);
+    if shows.is_empty() {
+        anyhow::bail!("No finished downloads to serve yet");
+    }
+    say!("🔍 Looking up {} shows on AniList...", shows.len());
+    for show in &mut shows {
+        add_metadata(show, token).await;
+    }
+
+    let bind = config.remote.bind_addr()?;
+    let ip = crate::room::reachable_ip(bind);
+    let listener = TcpListener::bind((bind, port))
+        .await
+        .with_context(|| format!("Could not listen on port {}", port))?;
+    let server = Arc::new(Server {
+        name: name.to_string(),
+        uuid: device_uuid(),
+        remote: config.remote.clone(),
+        base_url: format!("http://{}", SocketAddr::new(ip, port)),
+        shows,
+    });
+
+    let ssdp = ssdp_socket().context("Could not listen for SSDP on port 1900")?;
+    ssdp.join_multicast_v4(SSDP_ADDR, Ipv4Addr::UNSPECIFIED)?;
+
+    say!(
+        "📺 Serving {} shows as \"{}\" at {}",
+        server.shows.len(),
+        server.name,
+        server.base_url
+    );
+    say!("   Press Ctrl+C to stop.");
+
+    tokio::select! {
+        result = accept(listener, server.clone()) => result?,
+        result = discovery(&ssdp, &server) => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    for message in notifications(&server, "ssdp:byebye") {
+        let _ = ssdp
+            .send_to(message.as_bytes(), (SSDP_ADDR, SSDP_PORT))
+            .await;
+    }
+    say!("👋 Media server stopped.");
+    Ok(())
+}
+
+async fn accept(listener: TcpListener, server: Arc<Server>) -> Result<()> {
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        if !server.remote.allows(addr.ip()) {
+            debug!("Refused DLNA client {} (not in remote.allow)", addr);
+            continue;
+        }
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_http(stream, &server).await {
+                debug!("DLNA request failed: {:#}", e);
+            }
+        });
+    }
+}
+
+/// Finished downloads grouped by show, episodes in order.
+fn group_shows(entries: &[DownloadEntry]) -> Vec<Show> {
+    let mut by_show: BTreeMap<&str, Vec<&DownloadEntry>> = BTreeMap::new();
+    for entry in entries
+        .iter()
+        .filter(|e| e.status == DownloadStatus::Completed && e.output.exists())
+    {
+        by_show.entry(&entry.show_id).or_default().push(entry);
+    }
+    by_show
+        .into_values()
+        .map(|mut entries| {
+            entries.sort_by_key(|e| e.episode.parse::<u32>().unwrap_or(u32::MAX));
+            Show {
+                title: entries[0].title.clone(),
+                art: None,
+                episodes: entries
+                    .into_iter()
+                    .map(|e| Episode {
+                        title: format!("Episode {}", e.episode),
+                        size: fs::metadata(&e.output).map(|m| m.len()).unwrap_or(0),
+                        mime: mime_type(&e.output),
+                        path: e.output.clone(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Swaps the provider's show name for AniList's title and cover. The
+/// provider name stays when AniList has nothing.
+async fn add_metadata(show: &mut Show, token: Option<&str>) {
+    let variables = serde_json::json!({ "search": show.title, "perPage": 1 });
+    let Ok(res) = api::fetch_media(variables, token).await else {
+        return;
+    };
+    if let Some(media) = res.data.page.and_then(|p| p.media.into_iter().next()) {
+        show.title = media.preferred_title().to_string();
+        show.art = media
+            .cover_image
+            .and_then(|c| c.extra_large.or(c.large).or(c.medium));
+    }
+}
+
+fn mime_type(path: &std::path::Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("ts") => "video/mp2t",
+        Some("mkv") => "video/x-matroska",
+        _ => "video/mp4",
+    }
+}
+
+/// A random id, kept across runs so TVs don't list the server twice.
+fn device_uuid() -> String {
+    let path = ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+        .map(|dirs| dirs.data_dir().join("dlna_uuid"));
+    if let Some(uuid) = path
+        .as_ref()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+    {
+        return uuid;
+    }
+    let bytes: [u8; 16] = rand::random();
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let uuid = format!(
+        "{}-{}-4{}-a{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[13..16],
+        &hex[17..20],
+        &hex[20..]
+    );
+    if let Some(path) = path {
+        let _ = fs::write(path, &uuid);
+    }
+    uuid
+}
+
+/// Binds the SSDP port shared, so other media servers on this machine can
+/// keep listening on it too.
+fn ssdp_socket() -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, SSDP_PORT)).into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Answers M-SEARCH requests and announces the server periodically.
+async fn discovery(ssdp: &UdpSocket, server: &Server) -> Result<()> {
+    let mut buf = [0u8; 2048];
+    let mut announce = tokio::time::interval(NOTIFY_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = announce.tick() => {
+                for message in notifications(server, "ssdp:alive") {
+                    ssdp.send_to(message.as_bytes(), (SSDP_ADDR, SSDP_PORT)).await?;
+                }
+            }
+            received = ssdp.recv_from(&mut buf) => {
+                let (len, from) = received?;
+                if !server.remote.allows(from.ip()) {
+                    continue;
+                }
+                let request = String::from_utf8_lossy(&buf[..len]);
+                if !request.starts_with("M-SEARCH") {
+                    continue;
+                }
+                let Some(target) = header(&request, "ST") else {
+                    continue;
+                };
+                for st in search_targets(server, target) {
+                    let reply = format!(
+                        "HTTP/1.1 200 OK\r\n\
+                         CACHE-CONTROL: max-age={}\r\n\
+                         EXT:\r\n\
+                         LOCATION: {}/description.xml\r\n\
+                         SERVER: ani-l/{} UPnP/1.0 DLNADOC/1.50\r\n\
+                         ST: {}\r\n\
+                         USN: {}\r\n\r\n",
+                        MAX_AGE_SECS,
+                        server.base_url,
+                        env!("CARGO_PKG_VERSION"),
+                        st,
+                        usn(server, &st)
+                    );
+                    let _ = ssdp.send_to(reply.as_bytes(), from).await;
+                }
+            }
+        }
+    }
+}
+
+/// Notification types the server advertises.
+fn advertised(server: &Server) -> Vec<String> {
+    vec![
+        "upnp:rootdevice".to_string(),
+        format!("uuid:{}", server.uuid),
+        DEVICE_TYPE.to_string(),
+        CONTENT_DIRECTORY.to_string(),
+        CONNECTION_MANAGER.to_string(),
+    ]
+}
+
+fn search_targets(server: &Server, target: &str) -> Vec<String> {
+    if target == "ssdp:all" {
+        return advertised(server);
+    }
+    advertised(server)
+        .into_iter()
+        .filter(|nt| nt == target)
+        .collect()
+}
+
+fn usn(server: &Server, nt: &str) -> String {
+    if nt.starts_with("uuid:") {
+        nt.to_string()
+    } else {
+        format!("uuid:{}::{}", server.uuid, nt)
+    }
+}
+
+fn notifications(server: &Server, nts: &str) -> Vec<String> {
+    advertised(server)
+        .into_iter()
+        .map(|nt| {
+            format!(
+                "NOTIFY * HTTP/1.1\r\n\
+                 HOST: {}:{}\r\n\
+                 CACHE-CONTROL: max-age={}\r\n\
+                 LOCATION: {}/description.xml\r\n\
+                 NT: {}\r\n\
+                 NTS: {}\r\n\
+                 SERVER: ani-l/{} UPnP/1.0 DLNADOC/1.50\r\n\
+                 USN: {}\r\n\r\n",
+                SSDP_ADDR,
+                SSDP_PORT,
+                MAX_AGE_SECS,
+                server.base_url,
+                nt,
+                nts,
+                env!("CARGO_PKG_VERSION"),
+                usn(server, &nt)
+            )
+        })
+        .collect()
+}
+
+/// Value of a `Name: value` header line, matched case-insensitively.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+/// Contents of the first `tag` element in `xml`, whatever its namespace
+/// prefix and attributes; empty for `<tag/>`.
+fn tag<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let mut from = 0;
+    while let Some(open) = xml[from..].find('<') {
+        let start = from + open + 1;
+        let head_end = start + xml[start..].find('>')?;
+        let head = &xml[start..head_end];
+        let name = head.split_whitespace().next().unwrap_or_default();
+        let local = name.rsplit(':').next().unwrap_or(name);
+        if !name.starts_with('/') && local.trim_end_matches('/') == tag {
+            if head.ends_with('/') {
+                return Some("");
+            }
+            let body = head_end + 1;
+            let end = xml[body..].find(&format!("</{}>", name))? + body;
+            return Some(&xml[body..end]);
+        }
+        from = head_end + 1;
+    }
+    None
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+async fn handle_http(stream: TcpStream, server: &Server) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    let mut limited = (&mut reader).take(MAX_HEAD_BYTES);
+    loop {
+        let mut line = String::new();
+        if limited.read_line(&mut line).await? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        head.push_str(&line);
+    }
+    if limited.limit() == 0 {
+        return Ok(());
+    }
+    let mut parts = head.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Ok(());
+    };
+    let length: usize = header(&head, "Content-Length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; length.min(64 * 1024)];
+    reader.read_exact(&mut body).await?;
+    let body = String::from_utf8_lossy(&body);
+    let mut stream = reader.into_inner();
+
+    match (method, path) {
+        ("GET", "/description.xml") => {
+            respond(&mut stream, "200 OK", "text/xml", &description(server)).await
+        }
+        ("GET", "/ContentDirectory.xml") => {
+            respond(&mut stream, "200 OK", "text/xml", CONTENT_DIRECTORY_SCPD).await
+        }
+        ("GET", "/ConnectionManager.xml") => {
+            respond(&mut stream, "200 OK", "text/xml", CONNECTION_MANAGER_SCPD).await
+        }
+        ("POST", "/ContentDirectory/control") => {
+            let action = header(&head, "SOAPAction").unwrap_or_default();
+            let reply = content_directory(server, action, &body);
+            respond(&mut stream, "200 OK", "text/xml; charset=\"utf-8\"", &reply).await
+        }
+        ("POST", "/ConnectionManager/control") => {
+            let reply = soap_response(
+                CONNECTION_MANAGER,
+                "GetProtocolInfo",
+                "<Source>http-get:*:video/mp4:*,http-get:*:video/mp2t:*,http-get:*:video/x-matroska:*</Source><Sink></Sink>",
+            );
+            respond(&mut stream, "200 OK", "text/xml; charset=\"utf-8\"", &reply).await
+        }
+        ("SUBSCRIBE", _) => {
+            // Events aren't sent, but some TVs won't browse without a
+            // subscription.
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\nSID: uuid:{}-events\r\nTIMEOUT: Second-{}\r\nContent-Length: 0\r\n\r\n",
+                server.uuid, MAX_AGE_SECS
+            );
+            stream.write_all(reply.as_bytes()).await?;
+            Ok(())
+        }
+        ("GET" | "HEAD", path) if path.starts_with("/media/") => {
+            let episode = path
+                .trim_start_matches("/media/")
+                .split_once('/')
+                .and_then(|(s, e)| Some((s.parse::<usize>().ok()?, e.parse::<usize>().ok()?)))
+                .and_then(|(s, e)| server.shows.get(s)?.episodes.get(e));
+            match episode {
+                Some(episode) => {
+                    let range = header(&head, "Range").and_then(|r| parse_range(r, episode.size));
+                    send_file(&mut stream, episode, range, method == "HEAD").await
+                }
+                None => respond(&mut stream, "404 Not Found", "text/plain", "Not found").await,
+            }
+        }
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "Not found").await,
+    }
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let reply = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(reply.as_bytes()).await?;
+    Ok(())
+}
+
+/// What a `Range` header asks of a file.
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// First and last byte, clamped to the file.
+    Bytes(u64, u64),
+    /// Starts past the end of the file.
+    Unsatisfiable,
+}
+
+/// The range a `Range: bytes=a-b` request asks for; suffix ranges
+/// (`bytes=-500`) are supported too. `None` for a header that doesn't parse,
+/// which is ignored.
+fn parse_range(range: &str, size: u64) -> Option<ByteRange> {
+    let spec = range.trim().strip_prefix("bytes=")?.split(',').next()?;
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<u64>().ok()? {
+            0 => return Some(ByteRange::Unsatisfiable),
+            suffix => (size.saturating_sub(suffix), u64::MAX),
+        },
+        (start, "") => (start.parse().ok()?, u64::MAX),
+        (start, end) => (start.parse().ok()?, end.parse().ok()?),
+    };
+    if end < start {
+        return None;
+    }
+    if start >= size {
+        return Some(ByteRange::Unsatisfiable);
+    }
+    Some(ByteRange::Bytes(start, end.min(size - 1)))
+}
+
+async fn send_file(
+    stream: &mut TcpStream,
+    episode: &Episode,
+    range: Option<ByteRange>,
+    head_only: bool,
+) -> Result<()> {
+    let (status, start, end) = match range {
+        Some(ByteRange::Bytes(start, end)) => ("206 Partial Content", start, end),
+        Some(ByteRange::Unsatisfiable) => {
+            let reply = format!(
+                "HTTP/1.1 416 Range Not Satisfiable\r\n\
+                 Content-Range: bytes */{}\r\n\
+                 Content-Length: 0\r\n\
+                 Connection: close\r\n\r\n",
+                episode.size
+            );
+            stream.write_all(reply.as_bytes()).await?;
+            return Ok(());
+        }
+        None => ("200 OK", 0, episode.size.saturating_sub(1)),
+    };
+    let length = if episode.size == 0 {
+        0
+    } else {
+        end - start + 1
+    };
+    let mut reply = format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: {}\r\n\
+         Content-Length: {}\r\n\
+         Accept-Ranges: bytes\r\n\
+         transferMode.dlna.org: Streaming\r\n\
+         contentFeatures.dlna.org: DLNA.ORG_OP=01;DLNA.ORG_CI=0\r\n\
+         Connection: close\r\n",
+        status, episode.mime, length
+    );
+    if range.is_some() {
+        reply.push_str(&format!(
+            "Content-Range: bytes {}-{}/{}\r\n",
+            start, end, episode.size
+        ));
+    }
+    reply.push_str("\r\n");
+    stream.write_all(reply.as_bytes()).await?;
+    if head_only {
+        return Ok(());
+    }
+
+    let mut file = tokio::fs::File::open(&episode.path).await?;
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    // TVs drop the connection whenever they seek; that isn't an error.
+    let _ = tokio::io::copy(&mut file.take(length), stream).await;
+    Ok(())
+}
+
+fn description(server: &Server) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0" xmlns:dlna="urn:schemas-dlna-org:device-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <device>
+    <deviceType>{}</deviceType>
+    <dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>
+    <friendlyName>{}</friendlyName>
+    <manufacturer>ani-l</manufacturer>
+    <modelName>ani-l</modelName>
+    <modelNumber>{}</modelNumber>
+    <UDN>uuid:{}</UDN>
+    <serviceList>
+      <service>
+        <serviceType>{}</serviceType>
+        <serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId>
+        <SCPDURL>/ContentDirectory.xml</SCPDURL>
+        <controlURL>/ContentDirectory/control</controlURL>
+        <eventSubURL>/ContentDirectory/events</eventSubURL>
+      </service>
+      <service>
+        <serviceType>{}</serviceType>
+        <serviceId>urn:upnp-org:serviceId:ConnectionManager</serviceId>
+        <SCPDURL>/ConnectionManager.xml</SCPDURL>
+        <controlURL>/ConnectionManager/control</controlURL>
+        <eventSubURL>/ConnectionManager/events</eventSubURL>
+      </service>
+    </serviceList>
+  </device>
+</root>"#,
+        DEVICE_TYPE,
+        escape(&server.name),
+        env!("CARGO_PKG_VERSION"),
+        server.uuid,
+        CONTENT_DIRECTORY,
+        CONNECTION_MANAGER
+    )
+}
+
+fn soap_response(service: &str, action: &str, arguments: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><u:{action}Response xmlns:u="{service}">{arguments}</u:{action}Response></s:Body></s:Envelope>"#
+    )
+}
+
+fn content_directory(server: &Server, soap_action: &str, body: &str) -> String {
+    let action = soap_action
+        .trim_matches('"')
+        .rsplit('#')
+        .next()
+        .unwrap_or_default();
+    let arguments = match action {
+        "Browse" => {
+            let object_id = tag(body, "ObjectID")
+                .map(decode_entities)
+                .unwrap_or_default();
+            let metadata = tag(body, "BrowseFlag") == Some("BrowseMetadata");
+            let start: usize = tag(body, "StartingIndex")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+            let count: usize = tag(body, "RequestedCount")
+                .and_then(|s| s.parse().ok())
+                .filter(|&c| c > 0)
+                .unwrap_or(usize::MAX);
+            let (objects, total) = browse(server, &object_id, metadata, start, count);
+            format!(
+                "<Result>{}</Result><NumberReturned>{}</NumberReturned><TotalMatches>{}</TotalMatches><UpdateID>1</UpdateID>",
+                escape(&didl(&objects)),
+                objects.len(),
+                total
+            )
+        }
+        "GetSystemUpdateID" => "<Id>1</Id>".to_string(),
+        "GetSearchCapabilities" => "<SearchCaps></SearchCaps>".to_string(),
+        "GetSortCapabilities" => "<SortCaps></SortCaps>".to_string(),
+        _ => String::new(),
+    };
+    soap_response(CONTENT_DIRECTORY, action, &arguments)
+}
+
+/// The library as a tree: "0" holds shows "s<i>", which hold episodes
+/// "s<i>e<j>". Returns the DIDL-Lite objects and how many there are in all.
+fn browse(
+    server: &Server,
+    object_id: &str,
+    metadata: bool,
+    start: usize,
+    count: usize,
+) -> (Vec<String>, usize) {
+    let root = || {
+        format!(
+            r#"<container id="0" parentID="-1" restricted="1" childCount="{}"><dc:title>{}</dc:title><upnp:class>object.container</upnp:class></container>"#,
+            server.shows.len(),
+            escape(&server.name)
+        )
+    };
+    let show_object = |i: usize, show: &Show| {
+        let art = show
+            .art
+            .as_ref()
+            .map(|url| format!("<upnp:albumArtURI>{}</upnp:albumArtURI>", escape(url)))
+            .unwrap_or_default();
+        format!(
+            r#"<container id="s{}" parentID="0" restricted="1" childCount="{}"><dc:title>{}</dc:title><upnp:class>object.container.storageFolder</upnp:class>{}</container>"#,
+            i,
+            show.episodes.len(),
+            escape(&show.title),
+            art
+        )
+    };
+    let episode_object = |i: usize, j: usize, episode: &Episode| {
+        format!(
+            r#"<item id="s{i}e{j}" parentID="s{i}" restricted="1"><dc:title>{}</dc:title><upnp:class>object.item.videoItem</upnp:class><res protocolInfo="http-get:*:{}:DLNA.ORG_OP=01;DLNA.ORG_CI=0" size="{}">{}/media/{i}/{j}</res></item>"#,
+            escape(&episode.title),
+            episode.mime,
+            episode.size,
+            server.base_url
+        )
+    };
+
+    let (show, episode) = parse_object_id(object_id);
+    let all: Vec<String> = match (show.and_then(|s| Some((s, server.shows.get(s)?))), episode) {
+        (None, _) if object_id == "0" => {
+            if metadata {
+                vec![root()]
+            } else {
+                server
+                    .shows
+                    .iter()
+                    .enumerate()
+                    .map(|(i, show)| show_object(i, show))
+                    .collect()
+            }
+        }
+        (Some((i, show)), None) => {
+            if metadata {
+                vec![show_object(i, show)]
+            } else {
+                show.episodes
+                    .iter()
+                    .enumerate()
+                    .map(|(j, episode)| episode_object(i, j, episode))
+                    .collect()
+            }
+        }
+        (Some((i, show)), Some(j)) => show
+            .episodes
+            .get(j)
+            .map(|episode| vec![episode_object(i, j, episode)])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let total = all.len();
+    (all.into_iter().skip(start).take(count).collect(), total)
+}
+
+/// Show and episode index of "s<i>" or "s<i>e<j>".
+fn parse_object_id(id: &str) -> (Option<usize>, Option<usize>) {
+    let Some(rest) = id.strip_prefix('s') else {
+        return (None, None);
+    };
+    match rest.split_once('e') {
+        Some((show, episode)) => (show.parse().ok(), episode.parse().ok()),
+        None => (rest.parse().ok(), None),
+    }
+}
+
+fn didl(objects: &[String]) -> String {
+    format!(
+        r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">{}</DIDL-Lite>"#,
+        objects.concat()
+    )
+}
+
+const CONTENT_DIRECTORY_SCPD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <actionList>
+    <action><name>Browse</name><argumentList>
+      <argument><name>ObjectID</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_ObjectID</relatedStateVariable></argument>
+      <argument><name>BrowseFlag</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_BrowseFlag</relatedStateVariable></argument>
+      <argument><name>Filter</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Filter</relatedStateVariable></argument>
+      <argument><name>StartingIndex</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Index</relatedStateVariable></argument>
+      <argument><name>RequestedCount</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+      <argument><name>SortCriteria</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_SortCriteria</relatedStateVariable></argument>
+      <argument><name>Result</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Result</relatedStateVariable></argument>
+      <argument><name>NumberReturned</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+      <argument><name>TotalMatches</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+      <argument><name>UpdateID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_UpdateID</relatedStateVariable></argument>
+    </argumentList></action>
+    <action><name>GetSystemUpdateID</name><argumentList>
+      <argument><name>Id</name><direction>out</direction><relatedStateVariable>SystemUpdateID</relatedStateVariable></argument>
+    </argumentList></action>
+    <action><name>GetSearchCapabilities</name><argumentList>
+      <argument><name>SearchCaps</name><direction>out</direction><relatedStateVariable>SearchCapabilities</relatedStateVariable></argument>
+    </argumentList></action>
+    <action><name>GetSortCapabilities</name><argumentList>
+      <argument><name>SortCaps</name><direction>out</direction><relatedStateVariable>SortCapabilities</relatedStateVariable></argument>
+    </argumentList></action>
+  </actionList>
+  <serviceStateTable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_ObjectID</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_BrowseFlag</name><dataType>string</dataType>
+      <allowedValueList><allowedValue>BrowseMetadata</allowedValue><allowedValue>BrowseDirectChildren</allowedValue></allowedValueList></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Filter</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Index</name><dataType>ui4</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Count</name><dataType>ui4</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_SortCriteria</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Result</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_UpdateID</name><dataType>ui4</dataType></stateVariable>
+    <stateVariable sendEvents="yes"><name>SystemUpdateID</name><dataType>ui4</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>SearchCapabilities</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>SortCapabilities</name><dataType>string</dataType></stateVariable>
+  </serviceStateTable>
+</scpd>"#;
+
+const CONNECTION_MANAGER_SCPD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <actionList>
+    <action><name>GetProtocolInfo</name><argumentList>
+      <argument><name>Source</name><direction>out</direction><relatedStateVariable>SourceProtocolInfo</relatedStateVariable></argument>
+      <argument><name>Sink</name><direction>out</direction><relatedStateVariable>SinkProtocolInfo</relatedStateVariable></argument>
+    </argumentList></action>
+  </actionList>
+  <serviceStateTable>
+    <stateVariable sendEvents="yes"><name>SourceProtocolInfo</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="yes"><name>SinkProtocolInfo</name><dataType>string</dataType></stateVariable>
+  </serviceStateTable>
+</scpd>"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browse_and_ranges() {
+        let server = Server {
+            name: "ani-l".to_string(),
+            uuid: "u".to_string(),
+            base_url: "http://10.0.0.2:8200".to_string(),
+            remote: RemoteConfig::default(),
+            shows: vec![Show {
+                title: "Frieren & Co".to_string(),
+                art: None,
+                episodes: vec![Episode {
+                    title: "Episode 1".to_string(),
+                    path: PathBuf::from("/tmp/ep1.mp4"),
+                    size: 1000,
+                    mime: "video/mp4",
+                }],
+            }],
+        };
+        let (shows, total) = browse(&server, "0", false, 0, usize::MAX);
+        assert_eq!(total, 1);
+        assert!(shows[0].contains(r#"id="s0""#) && shows[0].contains("Frieren &amp; Co"));
+        let (episodes, _) = browse(&server, "s0", false, 0, usize::MAX);
+        assert!(episodes[0].contains(">http://10.0.0.2:8200/media/0/0</res>"));
+        assert!(browse(&server, "s3", false, 0, 10).0.is_empty());
+
+        assert_eq!(
+            parse_range("bytes=100-", 1000),
+            Some(ByteRange::Bytes(100, 999))
+        );
+        assert_eq!(
+            parse_range("bytes=0-1999", 1000),
+            Some(ByteRange::Bytes(0, 999))
+        );
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            Some(ByteRange::Bytes(900, 999))
+        );
+        assert_eq!(parse_range("bytes=900-100", 1000), None);
+        assert_eq!(
+            parse_range("bytes=999999999-", 1000),
+            Some(ByteRange::Unsatisfiable)
+        );
+
+        let body = r#"<s:Body><u:Browse xmlns:u="x"><ObjectID>s0</ObjectID><Filter/><u:BrowseFlag dt="string">BrowseMetadata</u:BrowseFlag></u:Browse></s:Body>"#;
+        assert_eq!(tag(body, "ObjectID"), Some("s0"));
+        assert_eq!(tag(body, "Filter"), Some(""));
+        assert_eq!(tag(body, "BrowseFlag"), Some("BrowseMetadata"));
+        assert_eq!(tag(body, "StartingIndex"), None);
+    }
+}
@@ -0,0 +1,616 @@
+use crate::config::Config;
+use crate::player::traits::PlayOptions;
+use crate::{net, output};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use log::{debug, warn};
+use reqwest::{Client, StatusCode, Url, header};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// Persist progress to the manifest at most every this many bytes.
+const CHECKPOINT_BYTES: u64 = 4 * 1024 * 1024;
+/// A stalled read (e.g. after the laptop wakes up on another network) is
+/// treated as a dropped connection after this long.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum DownloadStatus {
+    Pending,
+    Completed,
+    Failed,
+    /// Finished, but failed the integrity check. Re-downloaded from scratch
+    /// on the next run.
+    Suspect,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadEntry {
+    pub id: String,
+    pub title: String,
+    pub show_id: String,
+    pub episode: String,
+    pub translation_type: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub output: PathBuf,
+    pub bytes_downloaded: u64,
+    pub total_bytes: Option<u64>,
+    /// Index of the next HLS segment to fetch. Unused for direct files.
+    pub next_segment: usize,
+    pub total_segments: Option<usize>,
+    /// Sum of the HLS segment durations, used to sanity-check the result.
+    #[serde(default)]
+    pub expected_duration: Option<f64>,
+    pub status: DownloadStatus,
+    pub last_error: Option<String>,
+    pub last_updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct DownloadManifest {
+    pub entries: Vec<DownloadEntry>,
+}
+
+pub struct DownloadManager {
+    dir: PathBuf,
+    manifest_path: PathBuf,
+    pub manifest: DownloadManifest,
+    client: Client,
+    max_retries: u32,
+    verify_with_ffprobe: bool,
+}
+
+impl DownloadManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        let network = &config.network;
+        let config = &config.download;
+        let dir = match &config.directory {
+            Some(dir) => PathBuf::from(dir),
+            None => ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+                .context("Could not determine data directory")?
+                .data_dir()
+                .join("downloads"),
+        };
+        fs::create_dir_all(&dir)?;
+
+        let manifest_path = dir.join("manifest.json");
+        let manifest = if manifest_path.exists() {
+            let content = fs::read_to_string(&manifest_path)?;
+            serde_json::from_str(&content).unwrap_or_default()
+        } else {
+            DownloadManifest::default()
+        };
+
+        Ok(Self {
+            dir,
+            manifest_path,
+            manifest,
+            client: net::download_client_builder(network)?
+                .read_timeout(READ_TIMEOUT)
+                .connect_timeout(READ_TIMEOUT)
+                .build()?,
+            max_retries: config.max_retries,
+            verify_with_ffprobe: config.verify_with_ffprobe,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let json_str = serde_json::to_string_pretty(&self.manifest)?;
+        fs::write(&self.manifest_path, json_str)?;
+        Ok(())
+    }
+
+    /// Adds an episode to the manifest, or refreshes the stream URL of an
+    /// existing unfinished entry while keeping its resume offsets.
+    pub fn enqueue(
+        &mut self,
+        title: &str,
+        show_id: &str,
+        episode: &str,
+        translation_type: &str,
+        options: &PlayOptions,
+    ) -> Result<String> {
+        let id = format!("{}-{}-{}", show_id, episode, translation_type);
+        let headers = options.headers.clone().unwrap_or_default();
+
+        if let Some(entry) = self.manifest.entries.iter_mut().find(|e| e.id == id) {
+            if is_hls(&entry.url) != is_hls(&options.url) {
+                // A different stream kind can't continue the old partial file.
+                let _ = fs::remove_file(&entry.output);
+                entry.bytes_downloaded = 0;
+                entry.next_segment = 0;
+                entry.total_segments = None;
+            }
+            entry.url = options.url.clone();
+            entry.headers = headers;
+            if entry.status == DownloadStatus::Failed {
+                entry.status = DownloadStatus::Pending;
+            }
+        } else {
+            let extension = if is_hls(&options.url) { "ts" } else { "mp4" };
+            let file_name = format!(
+                "{} - Episode {}.{}",
+                sanitize_file_name(title),
+                episode,
+                extension
+            );
+            self.manifest.entries.push(DownloadEntry {
+                id: id.clone(),
+                title: title.to_string(),
+                show_id: show_id.to_string(),
+                episode: episode.to_string(),
+                translation_type: translation_type.to_string(),
+                url: options.url.clone(),
+                headers,
+                output: self.dir.join(file_name),
+                bytes_downloaded: 0,
+                total_bytes: None,
+                next_segment: 0,
+                total_segments: None,
+                expected_duration: None,
+                status: DownloadStatus::Pending,
+                last_error: None,
+                last_updated: Utc::now(),
+            });
+        }
+
+        self.save()?;
+        Ok(id)
+    }
+
+    pub fn pending_ids(&self) -> Vec<String> {
+        self.manifest
+            .entries
+            .iter()
+            .filter(|e| e.status != DownloadStatus::Completed)
+            .map(|e| e.id.clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&DownloadEntry> {
+        self.manifest.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Downloads an entry, retrying with exponential backoff after connection
+    /// drops. Progress is checkpointed so an interrupted run resumes where it
+    /// stopped.
+    pub async fn run(&mut self, id: &str) -> Result<()> {
+        let idx = self
+            .manifest
+            .entries
+            .iter()
+            .position(|e| e.id == id)
+            .context("Unknown download")?;
+
+        if self.manifest.entries[idx].status == DownloadStatus::Suspect {
+            let entry = &mut self.manifest.entries[idx];
+            let _ = fs::remove_file(&entry.output);
+            entry.bytes_downloaded = 0;
+            entry.next_segment = 0;
+            entry.status = DownloadStatus::Pending;
+        }
+
+        let mut attempt = 0;
+        loop {
+            match self.attempt(idx).await {
+                Ok(()) => {
+                    println!();
+                    let problem = self.verify(&self.manifest.entries[idx]);
+                    let entry = &mut self.manifest.entries[idx];
+                    entry.last_updated = Utc::now();
+                    if let Some(problem) = problem {
+                        warn!("{} failed integrity check: {}", entry.id, problem);
+                        entry.status = DownloadStatus::Suspect;
+                        entry.last_error = Some(problem.clone());
+                        self.save()?;
+                        anyhow::bail!("Integrity check failed: {}", problem);
+                    }
+                    entry.status = DownloadStatus::Completed;
+                    entry.last_error = None;
+                    self.save()?;
+                    return Ok(());
+                }
+                Err(e) if is_expired(&e) => {
+                    // The stream URL is dead; retrying it won't help, the
+                    // caller has to resolve a fresh one.
+                    self.mark_failed(idx, &e)?;
+                    return Err(e);
+                }
+                Err(e) if attempt < self.max_retries => {
+                    attempt += 1;
+                    let delay = Duration::from_secs(2u64.pow(attempt.min(6)));
+                    warn!("Download interrupted ({}), retrying in {:?}", e, delay);
+                    say!(
+                        "\n⚠️  Connection lost, retrying in {}s ({}/{})...",
+                        delay.as_secs(),
+                        attempt,
+                        self.max_retries
+                    );
+                    self.save()?;
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    self.mark_failed(idx, &e)?;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    fn mark_failed(&mut self, idx: usize, err: &anyhow::Error) -> Result<()> {
+        let entry = &mut self.manifest.entries[idx];
+        entry.status = DownloadStatus::Failed;
+        entry.last_error = Some(err.to_string());
+        entry.last_updated = Utc::now();
+        self.save()
+    }
+
+    /// Checks a finished download against what the server promised. Returns a
+    /// description of the problem when the file looks incomplete or corrupt.
+    fn verify(&self, entry: &DownloadEntry) -> Option<String> {
+        let size = fs::metadata(&entry.output).map(|m| m.len()).unwrap_or(0);
+        if size == 0 {
+            return Some("file is empty".to_string());
+        }
+
+        if let Some(total) = entry.total_bytes
+            && !is_hls(&entry.url)
+            && size != total
+        {
+            return Some(format!("expected {} bytes, found {}", total, size));
+        }
+
+        if let Some(total) = entry.total_segments
+            && entry.next_segment != total
+        {
+            return Some(format!(
+                "expected {} segments, found {}",
+                total, entry.next_segment
+            ));
+        }
+
+        if self.verify_with_ffprobe {
+            match probe_duration(&entry.output) {
+                Some(Ok(duration)) => {
+                    if let Some(expected) = entry.expected_duration {
+                        let tolerance = (expected * 0.05).max(10.0);
+                        if (duration - expected).abs() > tolerance {
+                            return Some(format!(
+                                "duration is {:.0}s, expected {:.0}s",
+                                duration, expected
+                            ));
+                        }
+                    }
+                }
+                Some(Err(e)) => return Some(format!("ffprobe could not read the file: {}", e)),
+                None => debug!("ffprobe not available, skipping duration check"),
+            }
+        }
+
+        None
+    }
+
+    async fn attempt(&mut self, idx: usize) -> Result<()> {
+        if is_hls(&self.manifest.entries[idx].url) {
+            self.attempt_hls(idx).await
+        } else {
+            self.attempt_direct(idx).await
+        }
+    }
+
+    async fn attempt_direct(&mut self, idx: usize) -> Result<()> {
+        let entry = self.manifest.entries[idx].clone();
+        let offset = fs::metadata(&entry.output).map(|m| m.len()).unwrap_or(0);
+
+        let mut req = self.client.get(&entry.url);
+        for (k, v) in &entry.headers {
+            req = req.header(k, v);
+        }
+        if offset > 0 {
+            req = req.header(header::RANGE, format!("bytes={}-", offset));
+        }
+
+        let mut resp = req.send().await?.error_for_status()?;
+        let resumed = resp.status() == StatusCode::PARTIAL_CONTENT;
+        let start = if resumed { offset } else { 0 };
+        debug!(
+            "Downloading {} from byte {} (resumed: {})",
+            entry.id, start, resumed
+        );
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(&entry.output)?;
+
+        let total = resp.content_length().map(|len| len + start);
+        let mut written = start;
+        let mut last_checkpoint = start;
+        self.manifest.entries[idx].total_bytes = total;
+
+        while let Some(chunk) = resp.chunk().await? {
+            file.write_all(&chunk)?;
+            written += chunk.len() as u64;
+
+            if written - last_checkpoint >= CHECKPOINT_BYTES {
+                file.flush()?;
+                last_checkpoint = written;
+                self.checkpoint(idx, written, None)?;
+                print_progress(&entry.title, &entry.episode, written, total);
+            }
+        }
+        file.flush()?;
+        self.checkpoint(idx, written, None)?;
+        print_progress(&entry.title, &entry.episode, written, total);
+
+        if let Some(total) = total
+            && written < total
+        {
+            anyhow::bail!("Connection closed after {} of {} bytes", written, total);
+        }
+        Ok(())
+    }
+
+    async fn attempt_hls(&mut self, idx: usize) -> Result<()> {
+        let entry = self.manifest.entries[idx].clone();
+        let (segments, duration) = self.fetch_segment_list(&entry).await?;
+        self.manifest.entries[idx].total_segments = Some(segments.len());
+        self.manifest.entries[idx].expected_duration = Some(duration);
+
+        let mut start = entry.next_segment;
+        if start == 0 || !entry.output.exists() {
+            start = 0;
+            fs::write(&entry.output, [])?;
+        }
+
+        let mut file = OpenOptions::new().append(true).open(&entry.output)?;
+        // A segment written after the last checkpoint is fetched again, so
+        // drop it rather than have it twice.
+        let mut written = if start == 0 {
+            0
+        } else {
+            entry.bytes_downloaded
+        };
+        file.set_len(written)?;
+
+        for (i, segment) in segments.iter().enumerate().skip(start) {
+            let mut req = self.client.get(segment.as_str());
+            for (k, v) in &entry.headers {
+                req = req.header(k, v);
+            }
+            let bytes = req.send().await?.error_for_status()?.bytes().await?;
+            file.write_all(&bytes)?;
+            file.flush()?;
+            written += bytes.len() as u64;
+
+            self.checkpoint(idx, written, Some(i + 1))?;
+            print_progress(
+                &entry.title,
+                &entry.episode,
+                (i + 1) as u64,
+                Some(segments.len() as u64),
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the media segment URLs and their total duration in seconds.
+    async fn fetch_segment_list(&self, entry: &DownloadEntry) -> Result<(Vec<Url>, f64)> {
+        let mut url = Url::parse(&entry.url)?;
+        loop {
+            let mut req = self.client.get(url.as_str());
+            for (k, v) in &entry.headers {
+                req = req.header(k, v);
+            }
+            let body = req.send().await?.error_for_status()?.text().await?;
+
+            if body.contains("#EXT-X-KEY") && !body.contains("METHOD=NONE") {
+                anyhow::bail!("Encrypted HLS streams can't be downloaded yet");
+            }
+
+            let uris: Vec<Url> = body
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .filter_map(|l| url.join(l).ok())
+                .collect();
+
+            if body.contains("#EXT-X-STREAM-INF") {
+                // Master playlist: the last variant is usually the highest quality.
+                url = uris.last().cloned().context("Empty HLS master playlist")?;
+                continue;
+            }
+            let duration = body
+                .lines()
+                .filter_map(|l| l.strip_prefix("#EXTINF:"))
+                .filter_map(|l| l.split(',').next()?.trim().parse::<f64>().ok())
+                .sum();
+            return Ok((uris, duration));
+        }
+    }
+
+    fn checkpoint(&mut self, idx: usize, written: u64, next_segment: Option<usize>) -> Result<()> {
+        let entry = &mut self.manifest.entries[idx];
+        entry.bytes_downloaded = written;
+        if let Some(next) = next_segment {
+            entry.next_segment = next;
+        }
+        entry.last_updated = Utc::now();
+        self.save()
+    }
+}
+
+fn print_progress(title: &str, episode: &str, done: u64, total: Option<u64>) {
+    // Carriage-return updates only make sense on a terminal.
+    if output::plain() {
+        return;
+    }
+    match total {
+        Some(total) if total > 0 => print!(
+            "\r⬇️  {} - Episode {}: {:.1}%",
+            title,
+            episode,
+            done as f64 / total as f64 * 100.0
+        ),
+        _ => print!(
+            "\r⬇️  {} - Episode {}: {:.1} MB",
+            title,
+            episode,
+            done as f64 / 1_048_576.0
+        ),
+    }
+    let _ = io::stdout().flush();
+}
+
+/// Reads the container duration with ffprobe. `None` when ffprobe isn't
+/// installed.
+fn probe_duration(path: &Path) -> Option<Result<f64>> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return Some(Err(anyhow::anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<f64>()
+            .context("Unexpected ffprobe output"),
+    )
+}
+
+fn is_hls(url: &str) -> bool {
+    url.split('?').next().unwrap_or(url).ends_with(".m3u8")
+}
+
+/// True when the server rejected the stream URL itself, as opposed to a
+/// transient network failure.
+pub fn is_expired(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|s| {
+            matches!(
+                s,
+                StatusCode::FORBIDDEN | StatusCode::NOT_FOUND | StatusCode::GONE
+            )
+        })
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DownloadConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves `files` by path over plain HTTP on a local port.
+    async fn serve(files: &'static [(&'static str, &'static str)]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let path = request.split(' ').nth(1).unwrap_or("/");
+                let response = match files.iter().find(|(p, _)| *p == path) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    ),
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_hls_resume_drops_unsaved_segment() {
+        let host = serve(&[
+            (
+                "/ep.m3u8",
+                "#EXTM3U\n#EXTINF:10,\n/s0.ts\n#EXTINF:10,\n/s1.ts\n#EXTINF:10,\n/s2.ts\n",
+            ),
+            ("/s0.ts", "aaa"),
+            ("/s1.ts", "bbb"),
+            ("/s2.ts", "ccc"),
+        ])
+        .await;
+        let dir = std::env::temp_dir().join(format!("ani-l-download-{}", std::process::id()));
+        let config = Config {
+            download: DownloadConfig {
+                directory: Some(dir.to_string_lossy().into_owned()),
+                max_retries: 0,
+                verify_with_ffprobe: false,
+            },
+            ..Default::default()
+        };
+        let mut manager = DownloadManager::new(&config).unwrap();
+        let options = PlayOptions {
+            url: format!("{}/ep.m3u8", host),
+            ..Default::default()
+        };
+        let id = manager
+            .enqueue("Frieren", "x", "1", "sub", &options)
+            .unwrap();
+
+        // The run was killed after writing s1 but before checkpointing it.
+        let output = manager.get(&id).unwrap().output.clone();
+        fs::write(&output, "aaabbb").unwrap();
+        let entry = &mut manager.manifest.entries[0];
+        entry.next_segment = 1;
+        entry.bytes_downloaded = 3;
+
+        manager.run(&id).await.unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "aaabbbccc");
+        let entry = manager.get(&id).unwrap();
+        assert_eq!(entry.status, DownloadStatus::Completed);
+        assert_eq!(entry.bytes_downloaded, 9);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,70 @@
+//! Exit codes scripts can branch on:
+//!
+//! | code | meaning        |
+//! |------|----------------|
+//! | 0    | success        |
+//! | 1    | other error    |
+//! | 2    | no results     |
+//! | 3    | provider error |
+//! | 4    | auth error     |
+//! | 5    | player error   |
+
+use std::fmt;
+use std::process::ExitCode;
+
+/// Attached to errors (as the error itself or with `.context(...)`) to pick
+/// the process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    NoResults,
+    Provider,
+    Auth,
+    Player,
+}
+
+impl Failure {
+    pub fn code(self) -> u8 {
+        match self {
+            Failure::NoResults => 2,
+            Failure::Provider => 3,
+            Failure::Auth => 4,
+            Failure::Player => 5,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Failure::NoResults => "No results found",
+            Failure::Provider => "Provider error",
+            Failure::Auth => "Authentication failed",
+            Failure::Player => "Player error",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+pub fn code_for(error: &anyhow::Error) -> ExitCode {
+    ExitCode::from(error.downcast_ref::<Failure>().map_or(1, |f| f.code()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_failure_found_through_context() {
+        let err = anyhow::Error::new(Failure::Player).context("mpv is not installed");
+        assert_eq!(err.downcast_ref::<Failure>(), Some(&Failure::Player));
+
+        let err: anyhow::Error = Err::<(), _>(anyhow::anyhow!("timed out"))
+            .context(Failure::Provider)
+            .context("while searching")
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<Failure>(), Some(&Failure::Provider));
+    }
+}
@@ -0,0 +1,75 @@
+//! An RSS feed of newly aired episodes of the shows being watched, for feed
+//! readers and download automation that polls a file or URL.
+
+use chrono::{DateTime, Utc};
+
+pub struct FeedItem {
+    pub media_id: i32,
+    pub title: String,
+    pub episode: i32,
+    pub aired: DateTime<Utc>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// An RSS 2.0 document. Item titles read "Show - Episode N", the shape
+/// torrent RSS filters usually expect, and each guid is stable across runs
+/// so readers don't report an episode twice.
+pub fn render(items: &[FeedItem], built: DateTime<Utc>) -> String {
+    let mut rss = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\">\n\
+         <channel>\n\
+         <title>ani-l: new episodes</title>\n\
+         <link>https://anilist.co/</link>\n\
+         <description>Newly aired episodes of shows on your AniList watching list</description>\n",
+    );
+    rss.push_str(&format!(
+        "<lastBuildDate>{}</lastBuildDate>\n",
+        built.to_rfc2822()
+    ));
+    for item in items {
+        let title = escape(&format!("{} - Episode {}", item.title, item.episode));
+        rss.push_str(&format!(
+            "<item>\n\
+             <title>{}</title>\n\
+             <link>https://anilist.co/anime/{}</link>\n\
+             <guid isPermaLink=\"false\">ani-l-{}-{}</guid>\n\
+             <pubDate>{}</pubDate>\n\
+             </item>\n",
+            title,
+            item.media_id,
+            item.media_id,
+            item.episode,
+            item.aired.to_rfc2822()
+        ));
+    }
+    rss.push_str("</channel>\n</rss>\n");
+    rss
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let aired = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let rss = render(
+            &[FeedItem {
+                media_id: 154587,
+                title: "Frieren <Beyond> & Co".to_string(),
+                episode: 5,
+                aired,
+            }],
+            aired,
+        );
+        assert!(rss.contains("<title>Frieren &lt;Beyond&gt; &amp; Co - Episode 5</title>"));
+        assert!(rss.contains("<guid isPermaLink=\"false\">ani-l-154587-5</guid>"));
+        assert!(rss.contains("<pubDate>Tue, 14 Nov 2023 22:13:20 +0000</pubDate>"));
+    }
+}
@@ -0,0 +1,160 @@
+//! A franchise's watch order: every anime reachable from a show through
+//! AniList relations, the main line of prequels and sequels in release order
+//! with side stories and spin-offs under the entry they branch off.
+
+use crate::models::Media;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Relations followed when gathering a franchise. Adaptations, characters
+/// and alternative versions lead out into unrelated franchises.
+pub const FOLLOWED: &[&str] = &["PREQUEL", "SEQUEL", "PARENT", "SIDE_STORY", "SPIN_OFF"];
+
+/// Relations that keep to the main line.
+const MAIN_LINE: &[&str] = &["PREQUEL", "SEQUEL"];
+
+/// Franchises with more entries than this (Gundam, Lupin) are cut off.
+pub const MAX_ENTRIES: usize = 60;
+
+#[derive(Debug, Clone)]
+pub struct FranchiseEntry {
+    pub media: Media,
+    /// How it relates to the entry above it ("SIDE_STORY", ...); `None` on
+    /// the main line.
+    pub relation: Option<String>,
+    pub depth: usize,
+}
+
+/// Ids of the followed relations of `media`, with the relation type.
+pub fn links(media: &Media) -> impl Iterator<Item = (&str, i32)> {
+    media
+        .relations
+        .iter()
+        .flat_map(|r| &r.edges)
+        .filter(|e| {
+            e.node.kind.as_deref() == Some("ANIME") && FOLLOWED.contains(&e.relation_type.as_str())
+        })
+        .map(|e| (e.relation_type.as_str(), e.node.id))
+}
+
+/// Unannounced and undated entries sort last.
+fn release_key(media: &Media) -> (i32, i32, i32) {
+    let date = media.start_date.as_ref();
+    (
+        date.and_then(|d| d.year).unwrap_or(i32::MAX),
+        date.and_then(|d| d.month).unwrap_or(13),
+        date.and_then(|d| d.day).unwrap_or(32),
+    )
+}
+
+/// Orders `shows` (as fetched by `api::fetch_franchise`) into a tree, depth
+/// first: the main line through `origin` by release date, each entry
+/// followed by what branches off it. Shows not linked to `origin` are left
+/// out.
+pub fn timeline(shows: Vec<Media>, origin: i32) -> Vec<FranchiseEntry> {
+    let mut by_id: HashMap<i32, Media> = shows.into_iter().map(|m| (m.id, m)).collect();
+    if !by_id.contains_key(&origin) {
+        return Vec::new();
+    }
+
+    let mut main = vec![origin];
+    let mut i = 0;
+    while i < main.len() {
+        for (relation, id) in links(&by_id[&main[i]]) {
+            if MAIN_LINE.contains(&relation) && by_id.contains_key(&id) && !main.contains(&id) {
+                main.push(id);
+            }
+        }
+        i += 1;
+    }
+    main.sort_by_key(|id| release_key(&by_id[id]));
+
+    // Each remaining show hangs under the first entry found linking to it,
+    // searching outwards from the main line.
+    let mut children: HashMap<i32, Vec<(i32, String)>> = HashMap::new();
+    let mut placed: HashSet<i32> = main.iter().copied().collect();
+    let mut queue: VecDeque<i32> = main.iter().copied().collect();
+    while let Some(id) = queue.pop_front() {
+        for (relation, next) in links(&by_id[&id]) {
+            if by_id.contains_key(&next) && placed.insert(next) {
+                children
+                    .entry(id)
+                    .or_default()
+                    .push((next, relation.to_string()));
+                queue.push_back(next);
+            }
+        }
+    }
+    for kids in children.values_mut() {
+        kids.sort_by_key(|(id, _)| release_key(&by_id[id]));
+    }
+
+    let mut entries = Vec::new();
+    let mut stack: Vec<(i32, Option<String>, usize)> =
+        main.into_iter().rev().map(|id| (id, None, 0)).collect();
+    while let Some((id, relation, depth)) = stack.pop() {
+        for (kid, kid_relation) in children.remove(&id).unwrap_or_default().into_iter().rev() {
+            stack.push((kid, Some(kid_relation), depth + 1));
+        }
+        if let Some(media) = by_id.remove(&id) {
+            entries.push(FranchiseEntry {
+                media,
+                relation,
+                depth,
+            });
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(id: i32, year: i32, relations: &[(&str, i32)]) -> Media {
+        let edges: Vec<_> = relations
+            .iter()
+            .map(|(relation, id)| {
+                serde_json::json!({ "relationType": relation, "node": { "id": id, "type": "ANIME" } })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "id": id, "title": {}, "genres": [],
+            "startDate": { "year": year },
+            "relations": { "edges": edges }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_timeline() {
+        let shows = vec![
+            show(3, 2019, &[("PREQUEL", 1), ("SIDE_STORY", 4)]),
+            show(
+                1,
+                2013,
+                &[("SEQUEL", 3), ("SIDE_STORY", 2), ("SIDE_STORY", 5)],
+            ),
+            show(2, 2014, &[("PARENT", 1)]),
+            show(4, 2020, &[("PARENT", 3), ("SEQUEL", 6)]),
+            show(5, 2013, &[("PARENT", 1)]),
+            show(6, 2021, &[("PREQUEL", 4)]),
+            show(7, 2022, &[]),
+        ];
+        let entries = timeline(shows, 3);
+        let order: Vec<(i32, Option<&str>, usize)> = entries
+            .iter()
+            .map(|e| (e.media.id, e.relation.as_deref(), e.depth))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (1, None, 0),
+                (5, Some("SIDE_STORY"), 1),
+                (2, Some("SIDE_STORY"), 1),
+                (3, None, 0),
+                (4, Some("SIDE_STORY"), 1),
+                (6, Some("SEQUEL"), 2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Provider health: `ani-l doctor` probes each configured provider (search
+//! latency, whether a stream can be extracted) and remembers the results, so
+//! a provider found unreachable is passed over for `general.fallback_providers`
+//! until it answers again.
+
+use crate::config::{Config, GeneralConfig};
+use crate::provider::AnyProvider;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// A long-running show every provider carries.
+const PROBE_QUERY: &str = "One Piece";
+const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(20);
+
+/// Results older than this no longer say anything about the provider.
+const STALE_AFTER_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub reachable: bool,
+    pub search_ms: Option<u64>,
+    pub results: usize,
+    /// Whether a stream came out of the first episode's sources. `None`
+    /// when not tried (no results, or a torrent provider).
+    pub extractor: Option<bool>,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl ProviderHealth {
+    fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        now - self.checked_at < Duration::hours(STALE_AFTER_HOURS)
+    }
+}
+
+pub struct HealthManager {
+    file_path: PathBuf,
+    /// Last probe per provider key.
+    pub data: HashMap<String, ProviderHealth>,
+}
+
+impl HealthManager {
+    pub fn new() -> Result<Self> {
+        let proj_dirs = ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+            .context("Could not determine data directory")?;
+        let data_dir = proj_dirs.data_dir();
+        fs::create_dir_all(data_dir)?;
+        let file_path = data_dir.join("provider_health.json");
+
+        let data = if file_path.exists() {
+            let content = fs::read_to_string(&file_path)?;
+            serde_json::from_str(&content).unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self { file_path, data })
+    }
+
+    pub fn record(&mut self, provider: &str, health: ProviderHealth) -> Result<()> {
+        self.data.insert(provider.to_string(), health);
+        let json_str = serde_json::to_string_pretty(&self.data)?;
+        fs::write(&self.file_path, json_str)?;
+        Ok(())
+    }
+
+    /// The last probe of `provider`, unless it's too old to go by.
+    pub fn fresh(&self, provider: &str) -> Option<&ProviderHealth> {
+        self.data.get(provider).filter(|h| h.is_fresh(Utc::now()))
+    }
+
+    /// Unreachable at a recent check. Providers never probed count as up.
+    pub fn is_down(&self, provider: &str) -> bool {
+        self.fresh(provider).is_some_and(|h| !h.reachable)
+    }
+}
+
+/// Every provider the config mentions, the chosen one first.
+pub fn configured_providers(config: &Config) -> Vec<String> {
+    let mut keys = vec![config.general.provider.clone()];
+    let mut others: Vec<&String> = config.providers.keys().collect();
+    others.sort();
+    for key in config.general.fallback_providers.iter().chain(others) {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+    keys
+}
+
+/// The provider to use instead of `general.provider` when it was found
+/// down: the first fallback not also down. `None` to keep the chosen one.
+pub fn replacement(general: &GeneralConfig, is_down: impl Fn(&str) -> bool) -> Option<String> {
+    if !is_down(&general.provider) {
+        return None;
+    }
+    general
+        .fallback_providers
+        .iter()
+        .find(|p| !is_down(p))
+        .cloned()
+}
+
+/// Searches the provider and tries to extract the first episode's stream.
+pub async fn probe(config: &Config, provider_key: &str) -> ProviderHealth {
+    let mut config = config.clone();
+    config.general.provider = provider_key.to_string();
+    let mut health = ProviderHealth {
+        reachable: false,
+        search_ms: None,
+        results: 0,
+        extractor: None,
+        error: None,
+        checked_at: Utc::now(),
+    };
+    let provider = match AnyProvider::new(config.stream.translation_type.clone(), &config) {
+        Ok(provider) => provider,
+        Err(e) => {
+            health.error = Some(format!("{:#}", e));
+            return health;
+        }
+    };
+
+    let started = Instant::now();
+    let results = match tokio::time::timeout(PROBE_TIMEOUT, provider.search(PROBE_QUERY)).await {
+        Ok(Ok(results)) => results,
+        Ok(Err(e)) => {
+            health.error = Some(e.root_cause().to_string());
+            return health;
+        }
+        Err(_) => {
+            health.error = Some("Search timed out".to_string());
+            return health;
+        }
+    };
+    health.reachable = true;
+    health.search_ms = Some(started.elapsed().as_millis() as u64);
+    health.results = results.len();
+
+    // Extracting a torrent starts downloading it.
+    #[cfg(feature = "torrent")]
+    if matches!(provider, AnyProvider::Nyaa(_)) {
+        return health;
+    }
+    let Some(show) = results.first() else {
+        return health;
+    };
+    let extracted = tokio::time::timeout(PROBE_TIMEOUT, async {
+        let sources = provider.get_episode_sources(&show.id, "1").await?;
+        anyhow::Ok(provider.extract_first(&sources).await.is_some())
+    })
+    .await;
+    health.extractor = Some(matches!(extracted, Ok(Ok(true))));
+    match extracted {
+        Ok(Err(e)) => health.error = Some(e.root_cause().to_string()),
+        Err(_) => health.error = Some("Extraction timed out".to_string()),
+        _ => {}
+    }
+    health
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replacement() {
+        let mut general = Config::default().general;
+        general.fallback_providers = vec!["hianime".to_string(), "animepahe".to_string()];
+
+        assert_eq!(replacement(&general, |_| false), None);
+        assert_eq!(
+            replacement(&general, |p| p == "allanime"),
+            Some("hianime".to_string())
+        );
+        assert_eq!(
+            replacement(&general, |p| p != "animepahe"),
+            Some("animepahe".to_string())
+        );
+        assert_eq!(replacement(&general, |_| true), None);
+
+        let health = ProviderHealth {
+            reachable: false,
+            search_ms: None,
+            results: 0,
+            extractor: None,
+            error: None,
+            checked_at: Utc::now() - Duration::hours(STALE_AFTER_HOURS + 1),
+        };
+        assert!(!health.is_fresh(Utc::now()));
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// How far back the average daily watch time looks.
+const AVERAGE_WINDOW_DAYS: i64 = 30;
+
+/// One finished playback session.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WatchSession {
+    pub media_id: i32,
+    pub title: String,
+    pub episode: i32,
+    pub minutes: f64,
+    pub watched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct WatchHistory {
+    pub sessions: Vec<WatchSession>,
+}
+
+impl WatchHistory {
+    pub fn last_session(&self) -> Option<&WatchSession> {
+        self.sessions.iter().max_by_key(|s| s.watched_at)
+    }
+
+    /// The latest session of `media_id` when it is more than `weeks` old.
+    pub fn stale_session(
+        &self,
+        media_id: i32,
+        weeks: u32,
+        now: DateTime<Utc>,
+    ) -> Option<&WatchSession> {
+        let last = self
+            .sessions
+            .iter()
+            .filter(|s| s.media_id == media_id)
+            .max_by_key(|s| s.watched_at)?;
+        (weeks > 0 && now - last.watched_at > Duration::weeks(weeks as i64)).then_some(last)
+    }
+
+    /// Average minutes watched per day over the last 30 days, counted from
+    /// the first session when the history is younger than that.
+    pub fn average_daily_minutes(&self, now: DateTime<Utc>) -> Option<f64> {
+        let since = now - Duration::days(AVERAGE_WINDOW_DAYS);
+        let recent: Vec<&WatchSession> = self
+            .sessions
+            .iter()
+            .filter(|s| s.watched_at >= since)
+            .collect();
+        let first = recent.iter().map(|s| s.watched_at).min()?;
+        let days = (now - first).num_days().clamp(1, AVERAGE_WINDOW_DAYS) as f64;
+        let total: f64 = recent.iter().map(|s| s.minutes).sum();
+        (total > 0.0).then(|| total / days)
+    }
+}
+
+/// Expected finish date of each show when watched one after another at
+/// `daily_minutes` a day.
+pub fn estimate_finish_dates(
+    remaining_minutes: &[i32],
+    daily_minutes: f64,
+    today: NaiveDate,
+) -> Vec<NaiveDate> {
+    let mut cumulative = 0.0;
+    remaining_minutes
+        .iter()
+        .map(|&minutes| {
+            cumulative += minutes.max(0) as f64;
+            today + Duration::days((cumulative / daily_minutes).ceil() as i64)
+        })
+        .collect()
+}
+
+pub struct HistoryManager {
+    file_path: PathBuf,
+    pub data: WatchHistory,
+}
+
+impl HistoryManager {
+    pub fn new() -> Result<Self> {
+        let proj_dirs = ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+            .context("Could not determine data directory")?;
+        let data_dir = proj_dirs.data_dir();
+        fs::create_dir_all(data_dir)?;
+        let file_path = data_dir.join("history.json");
+
+        let data = if file_path.exists() {
+            let content = fs::read_to_string(&file_path)?;
+            serde_json::from_str(&content).unwrap_or_default()
+        } else {
+            WatchHistory::default()
+        };
+
+        Ok(Self { file_path, data })
+    }
+
+    pub fn record(&mut self, session: WatchSession) -> Result<()> {
+        self.data.sessions.push(session);
+        let json_str = serde_json::to_string_pretty(&self.data)?;
+        fs::write(&self.file_path, json_str)?;
+        Ok(())
+    }
+
+    pub fn today() -> NaiveDate {
+        Local::now().date_naive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_finish_dates() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let dates = estimate_finish_dates(&[240, 300, 0], 60.0, today);
+
+        assert_eq!(dates[0], NaiveDate::from_ymd_opt(2025, 1, 5).unwrap());
+        assert_eq!(dates[1], NaiveDate::from_ymd_opt(2025, 1, 10).unwrap());
+        assert_eq!(dates[2], dates[1]);
+    }
+
+    #[test]
+    fn test_stale_session() {
+        let now = Utc::now();
+        let session = |media_id, days_ago| WatchSession {
+            media_id,
+            title: String::new(),
+            episode: 3,
+            minutes: 24.0,
+            watched_at: now - Duration::days(days_ago),
+        };
+        let history = WatchHistory {
+            sessions: vec![session(1, 60), session(2, 60), session(2, 3)],
+        };
+
+        assert_eq!(history.stale_session(1, 4, now).map(|s| s.episode), Some(3));
+        assert!(history.stale_session(2, 4, now).is_none());
+        assert!(history.stale_session(1, 0, now).is_none());
+        assert!(history.stale_session(3, 4, now).is_none());
+    }
+}
@@ -0,0 +1,162 @@
+//! User shell commands run on playback events (`[hooks]` in the config).
+//! Hooks run in the background with their output discarded, so a slow or
+//! noisy command never holds up or garbles the TUI.
+
+use crate::config::HooksConfig;
+use log::{debug, warn};
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[derive(Debug, Clone, Copy)]
+pub enum HookEvent {
+    EpisodeStart,
+    EpisodeComplete,
+    PlaybackEnd,
+}
+
+/// What a hook command gets to know about the episode.
+pub struct HookContext<'a> {
+    pub title: &'a str,
+    pub episode: &'a str,
+    pub media_id: i32,
+    /// Percent watched; 0 when the episode is only starting.
+    pub progress: f64,
+}
+
+impl HookContext<'_> {
+    fn variables(&self) -> [(&'static str, String); 4] {
+        [
+            ("title", self.title.to_string()),
+            ("ep", self.episode.to_string()),
+            ("id", self.media_id.to_string()),
+            ("progress", format!("{:.0}", self.progress)),
+        ]
+    }
+}
+
+fn command_for(hooks: &HooksConfig, event: HookEvent) -> Option<&str> {
+    match event {
+        HookEvent::EpisodeStart => hooks.on_episode_start.as_deref(),
+        HookEvent::EpisodeComplete => hooks.on_episode_complete.as_deref(),
+        HookEvent::PlaybackEnd => hooks.on_playback_end.as_deref(),
+    }
+}
+
+fn env_name(name: &str) -> String {
+    format!("ANI_L_{}", name.to_uppercase())
+}
+
+/// Turns `{name}` placeholders into references to the `ANI_L_*` environment
+/// variables, so titles reach the command exactly as they are but are never
+/// parsed by the shell. Inside double quotes they stay one argument; inside
+/// single quotes, where `sh` expands nothing, the quote is closed around a
+/// double-quoted reference and reopened.
+fn fill(template: &str, variables: &[(&'static str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut quote = None;
+    let mut chars = template.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let placeholder = variables.iter().map(|(name, _)| *name).find(|name| {
+            template[i + 1..].starts_with(name) && template[i + 1 + name.len()..].starts_with('}')
+        });
+        if let (Some(name), '{') = (placeholder, c) {
+            let reference = if cfg!(windows) {
+                // Delayed expansion (`cmd /V:ON`) happens after parsing.
+                format!("!{}!", env_name(name))
+            } else if quote == Some('\'') {
+                format!("'\"${{{}}}\"'", env_name(name))
+            } else {
+                format!("${{{}}}", env_name(name))
+            };
+            out.push_str(&reference);
+            chars.nth(name.len());
+            continue;
+        }
+        out.push(c);
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            // An escaped character, outside single quotes, is never a quote.
+            (q, '\\') if q != Some('\'') => {
+                if let Some((_, escaped)) = chars.next() {
+                    out.push(escaped);
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Starts the configured hook for `event`, if any, without waiting for it.
+pub fn run(hooks: &HooksConfig, event: HookEvent, context: &HookContext) {
+    let Some(template) = command_for(hooks, event) else {
+        return;
+    };
+    let variables = context.variables();
+    let command = fill(template, &variables);
+    debug!("Running {:?} hook: {}", event, command);
+
+    let mut child = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/V:ON").arg("/C").arg(&command);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(&command);
+        c
+    };
+    for (name, value) in &variables {
+        child.env(env_name(name), value);
+    }
+    child
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    match child.spawn() {
+        Ok(mut child) => {
+            tokio::spawn(async move {
+                if let Ok(status) = child.wait().await
+                    && !status.success()
+                {
+                    warn!("{:?} hook exited with {}", event, status);
+                }
+            });
+        }
+        Err(e) => warn!("Failed to run {:?} hook: {}", event, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fill() {
+        let context = HookContext {
+            title: "Frieren's \"Journey\"; $(rm -rf ~) | & <x>",
+            episode: "5",
+            media_id: 154587,
+            progress: 91.6,
+        };
+        let command = fill(
+            "printf '%s|' \"Finished {title} E{ep}\" {id} {progress}% {other} 'E{ep} \\'",
+            &context.variables(),
+        );
+        assert_eq!(
+            command,
+            "printf '%s|' \"Finished ${ANI_L_TITLE} E${ANI_L_EP}\" ${ANI_L_ID} ${ANI_L_PROGRESS}% {other} 'E'\"${ANI_L_EP}\"' \\'"
+        );
+        let mut sh = std::process::Command::new("sh");
+        sh.arg("-c").arg(&command);
+        for (name, value) in context.variables() {
+            sh.env(env_name(name), value);
+        }
+        let output = sh.output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "Finished Frieren's \"Journey\"; $(rm -rf ~) | & <x> E5|154587|92%|{other}|E5 \\|"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use crate::models::{Media, Page, PageInfo};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// The first page of a ranked list as last fetched.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CachedList {
+    pub fetched: DateTime<Utc>,
+    pub media: Vec<Media>,
+    pub page_info: PageInfo,
+}
+
+/// Trending and Popular as last seen, by list, so opening them shows
+/// something straight away while a fresh copy loads.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ListCache {
+    pub lists: HashMap<String, CachedList>,
+}
+
+pub struct ListCacheManager {
+    file_path: PathBuf,
+    pub data: ListCache,
+}
+
+impl ListCacheManager {
+    pub fn new() -> Result<Self> {
+        let proj_dirs = ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+            .context("Could not determine cache directory")?;
+        let cache_dir = proj_dirs.cache_dir();
+        fs::create_dir_all(cache_dir)?;
+        let file_path = cache_dir.join("lists.json");
+
+        let data = if file_path.exists() {
+            let content = fs::read_to_string(&file_path)?;
+            serde_json::from_str(&content).unwrap_or_default()
+        } else {
+            ListCache::default()
+        };
+
+        Ok(Self { file_path, data })
+    }
+
+    pub fn store(&mut self, key: &str, page: &Page) -> Result<()> {
+        self.data.lists.insert(
+            key.to_string(),
+            CachedList {
+                fetched: Utc::now(),
+                media: page.media.clone(),
+                page_info: page.page_info.clone(),
+            },
+        );
+        let json_str = serde_json::to_string(&self.data)?;
+        fs::write(&self.file_path, json_str)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_media_round_trip() {
+        let mut media: Media = serde_json::from_value(serde_json::json!({
+            "id": 1, "title": { "romaji": "Frieren" }, "genres": [],
+            "mediaListEntry": { "status": "CURRENT", "progress": 3 }
+        }))
+        .unwrap();
+        media.slim = true;
+        let cached: Media = serde_json::from_str(&serde_json::to_string(&media).unwrap()).unwrap();
+        assert!(cached.slim);
+        assert_eq!(cached.preferred_title(), "Frieren");
+        assert_eq!(cached.media_list_entry.and_then(|e| e.progress), Some(3));
+    }
+}
@@ -1,42 +1,145 @@
+#[macro_use]
+mod output;
+
 mod api;
+mod args;
+mod backup;
+mod batch;
+mod cassette;
+mod choices;
 mod config;
+#[cfg(feature = "dlna")]
+mod dlna;
+#[cfg(feature = "downloads")]
+mod download;
+mod exit;
+mod feed;
+mod franchise;
+mod health;
+mod history;
+mod hooks;
+mod list_cache;
 mod models;
+mod net;
 mod normalizer;
+mod numbering;
+mod picker;
 mod player;
+mod playlist;
 mod provider;
+mod queue;
+mod random;
 mod registry;
+mod room;
+mod status;
+mod suggest;
+mod timefmt;
 mod tui;
+mod util;
+mod voices;
+mod watch_order;
 
 #[macro_use]
 extern crate rust_i18n;
+#[macro_use]
+extern crate lazy_static;
 
 i18n!("locales");
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+#[cfg(feature = "images")]
+use image::DynamicImage;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
 use ratatui::{Terminal, backend::CrosstermBackend};
-use std::io;
+#[cfg(feature = "images")]
+use ratatui_image::picker::Picker;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::Arc;
-use std::time::Duration;
-use strsim::normalized_levenshtein;
-use tokio::sync::Notify;
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, mpsc};
 
-use crate::config::ConfigManager;
-use crate::player::traits::{EpisodeAction, EpisodeNavigator, PlayOptions, Player};
-use crate::provider::allanime::AllAnimeProvider;
-use crate::registry::RegistryManager;
-use crate::tui::app::{Action, App, Focus, ListMode};
+use crate::api::BulkEdit;
+use crate::batch::BatchItem;
+use crate::choices::{ShowChoices, ShowMatch};
+use crate::config::{Config, ConfigManager, PickerConfig, PickerMode};
+#[cfg(feature = "downloads")]
+use crate::download::DownloadManager;
+use crate::exit::Failure;
+use crate::health::HealthManager;
+use crate::history::{HistoryManager, WatchSession};
+use crate::hooks::{HookContext, HookEvent};
+use crate::list_cache::ListCacheManager;
+use crate::player::chapters::{self, Chapter, SkipMode};
+use crate::player::traits::{
+    CompletionReport, EpisodeAction, EpisodeNavigator, PlayOptions, Player, window_title,
+};
+use crate::provider::AnyProvider;
+use crate::provider::manga::MangaProvider;
+use crate::provider::models::{AvailableEpisodes, ShowEdge};
+use crate::queue::QueueManager;
+use crate::registry::{ProviderPin, RegistryManager, Resolution};
+use crate::tui::app::{
+    Action, App, Confirm, DETAIL_DELAY, Focus, ListMode, MangaSession, MediaPager, NoteEditor,
+    RECAP_EPISODES, RatingPrompt, ShowChoice,
+};
+use crate::tui::count::Feed;
+#[cfg(feature = "images")]
+use crate::tui::images::{self, AsciiCover};
+use crate::tui::macros::Intercept;
 
 #[derive(Parser)]
-#[command(name = "ani-l")]
+#[command(name = "ani-l", version)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Override `general.provider` ("fake" runs fully offline on sample data)
+    #[arg(long, global = true)]
+    provider: Option<String>,
+    /// Save provider traffic to FILE (tokens redacted) for bug reports
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        conflicts_with = "replay_cassette"
+    )]
+    record_cassette: Option<PathBuf>,
+    /// Answer provider requests from a recorded FILE instead of the network
+    #[arg(long, global = true, value_name = "FILE")]
+    replay_cassette: Option<PathBuf>,
+    /// Don't record watch history or update AniList this session
+    #[arg(long, global = true)]
+    private: bool,
+    /// Play audio only, without a video window, and leave AniList progress
+    /// alone (for shows already seen)
+    #[arg(long, global = true)]
+    audio_only: bool,
+    /// Seed the Random menu so a session's picks can be reproduced
+    #[arg(long, global = true, value_name = "N")]
+    seed: Option<u64>,
+    /// Save failing AniList requests (query, variables and response; never
+    /// the token) to the cache directory for bug reports
+    #[arg(long, global = true)]
+    debug_api: bool,
+    /// Override `stream.subtitle_language`, e.g. "en" or "Spanish"
+    #[arg(long, global = true, value_name = "LANG")]
+    sub_lang: Option<String>,
+    /// Override `picker.mode`: "external" hands choices to `picker.command`
+    /// (fzf, rofi, dmenu, ...)
+    #[arg(long, global = true, value_name = "MODE")]
+    picker: Option<PickerMode>,
 }
 
 #[derive(Subcommand)]
@@ -48,47 +151,403 @@ enum Commands {
         #[arg(long, short)]
         logout: bool,
     },
+    #[cfg(feature = "downloads")]
+    Download {
+        #[arg(long, short)]
+        query: Option<String>,
+        #[arg(long, short)]
+        episode: Option<String>,
+        /// Resume every unfinished download from the manifest
+        #[arg(long)]
+        resume_all: bool,
+    },
+    /// Open the TUI and resume the most recently watched show
+    Continue,
+    /// Watch an episode together with friends on other machines
+    Room {
+        #[command(subcommand)]
+        action: RoomAction,
+    },
+    /// Resolve episodes to an M3U playlist for other players
+    Playlist {
+        #[arg(long, short)]
+        query: String,
+        /// Episodes, e.g. "1-12" or "1,3,5"
+        #[arg(long, short, default_value = "1")]
+        episodes: String,
+        /// Write to FILE instead of stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Share downloaded episodes with TVs on the local network over DLNA
+    #[cfg(feature = "dlna")]
+    Serve {
+        /// HTTP port the TV streams from
+        #[arg(long, default_value_t = dlna::DEFAULT_PORT)]
+        port: u16,
+        /// Name the server shows up as on the TV
+        #[arg(long, default_value = "ani-l")]
+        name: String,
+    },
+    /// Write an RSS feed of newly aired episodes of shows you're watching
+    Feed {
+        /// Write to FILE instead of stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// How many days back to include
+        #[arg(long, default_value_t = 14)]
+        days: u32,
+    },
+    /// Check each configured provider's search and stream extraction
+    Doctor,
+    /// Play a show from a specific provider instead of `general.provider`
+    Pin {
+        /// AniList id or title
+        query: String,
+        /// Provider to use for this show, e.g. "hianime"
+        #[arg(long, short, value_name = "PROVIDER")]
+        to: Option<String>,
+        /// The provider's own id for the show, when its search misses it
+        #[arg(long, requires = "to")]
+        show_id: Option<String>,
+        /// Go back to `general.provider`
+        #[arg(long, conflicts_with = "to")]
+        clear: bool,
+    },
+    /// Push progress watched in ani-l to AniList and pull what AniList is ahead on
+    Sync {
+        /// Print what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Also list shows that are already in sync or skipped
+        #[arg(long, short)]
+        verbose: bool,
+    },
+    /// Merge registry entries recorded more than once for the same show
+    Dedupe {
+        /// Merge without asking
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Save or restore the config, registry, history and show mappings
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+    /// Play episodes one after another, or download them
+    Watch {
+        #[arg(long, short, required_unless_present = "batch")]
+        query: Option<String>,
+        /// Episode, or a range or list to binge in order, e.g. "1-5" or "1,3,5"
+        /// (the first, or picked with --picker external)
+        #[arg(long, short, conflicts_with = "batch")]
+        episode: Option<String>,
+        /// Read "title<TAB>episode" lines from FILE ("-" for stdin)
+        #[arg(long, value_name = "FILE", conflicts_with = "query")]
+        batch: Option<PathBuf>,
+        /// Download each episode instead of playing it
+        #[arg(long)]
+        download: bool,
+    },
+    /// Print what is currently playing, for status bar modules
+    Status {
+        /// Placeholders: {title} {ep} {percent} {position} {duration} {state}
+        #[arg(long, short, default_value = "{title} E{ep} {percent}%")]
+        format: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupAction {
+    /// Write every state file into FILE
+    Create {
+        file: PathBuf,
+        /// Include the AniList token and config passwords; keep the file private
+        #[arg(long)]
+        include_auth: bool,
+    },
+    /// Replace the local state with FILE's
+    Restore {
+        file: PathBuf,
+        /// Also restore the AniList token, if the backup has it
+        #[arg(long)]
+        include_auth: bool,
+        /// Restore without asking
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RoomAction {
+    /// Play an episode and print a code guests can join with
+    Host {
+        #[arg(long, short)]
+        query: String,
+        /// The first episode, or picked with --picker external
+        #[arg(long, short)]
+        episode: Option<String>,
+        /// TCP port guests connect to
+        #[arg(long, default_value_t = room::DEFAULT_PORT)]
+        port: u16,
+        /// Address guests reach this machine at, e.g. a public IP or hostname
+        /// (the code holds the LAN address otherwise)
+        #[arg(long)]
+        address: Option<String>,
+    },
+    /// Join a room and follow the host's playback
+    ///
+    /// The room password is read from $ANI_L_ROOM_PASSWORD, or asked for.
+    Join { code: String },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    // Parse first so `--help`/`--version` and argument errors return before
+    // any config, registry or terminal work happens.
+    let argv = args::expand(std::env::args().collect(), &Config::peek());
+    let cli = Cli::parse_from(&argv);
+    // Status bar polls aren't worth replaying, and `auth` has the AniList
+    // token on its command line, wherever the global flags went.
+    if !matches!(
+        cli.command,
+        None | Some(Commands::Status { .. } | Commands::Auth { .. })
+    ) {
+        args::remember(&argv);
+    }
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("off")).init();
+    output::init();
 
-    let mut config_manager = ConfigManager::init_interactive().await?;
-    let _registry_manager = RegistryManager::new()?;
-    rust_i18n::set_locale(&config_manager.config.general.language);
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            say_err!("❌ {:#}", e);
+            exit::code_for(&e)
+        }
+    }
+}
 
-    let cli = Cli::parse();
-    match cli.command.unwrap_or(Commands::Tui) {
-        Commands::Tui => run_tui(config_manager).await?,
+async fn run(mut cli: Cli) -> Result<()> {
+    if cli.debug_api {
+        api::enable_debug();
+    }
+    if let Some(path) = &cli.record_cassette {
+        cassette::start_recording(path.clone());
+    }
+    if let Some(path) = &cli.replay_cassette {
+        cassette::start_replay(path.clone())?;
+    }
+
+    match cli.command.take().unwrap_or(Commands::Tui) {
+        Commands::Tui => {
+            let config_manager = load_config(&cli).await?;
+            let _registry_manager = RegistryManager::new()?;
+            run_tui(config_manager, None, cli.private, cli.audio_only, cli.seed).await?
+        }
+        Commands::Continue => {
+            let config_manager = load_config(&cli).await?;
+            let last = HistoryManager::new()?
+                .data
+                .last_session()
+                .cloned()
+                .ok_or(Failure::NoResults)
+                .context("Nothing watched yet")?;
+            run_tui(
+                config_manager,
+                Some(last),
+                cli.private,
+                cli.audio_only,
+                cli.seed,
+            )
+            .await?
+        }
         Commands::Auth {
             token_input,
             logout,
         } => {
+            let mut config_manager = load_config(&cli).await?;
             if logout {
                 config_manager.auth.anilist_token = None;
                 config_manager.auth.username = None;
                 config_manager.save_auth()?;
-                println!("✅ Logged out successfully.");
+                say!("✅ Logged out successfully.");
             } else if let Some(input) = token_input {
                 config_manager.verify_and_save_token(&input).await?;
             } else {
                 config_manager.authenticate_interactive().await?;
             }
         }
+        #[cfg(feature = "downloads")]
+        Commands::Download {
+            query,
+            episode,
+            resume_all,
+        } => {
+            let config_manager = load_config(&cli).await?;
+            run_download(&config_manager.config, query, episode, resume_all).await?
+        }
+        Commands::Room { action } => {
+            let config_manager = load_config(&cli).await?;
+            match action {
+                RoomAction::Host {
+                    query,
+                    episode,
+                    port,
+                    address,
+                } => {
+                    let config = &config_manager.config;
+                    room::host(config, &query, episode, port, address.as_deref()).await?
+                }
+                RoomAction::Join { code } => room::join(&config_manager.config, &code).await?,
+            }
+        }
+        Commands::Playlist {
+            query,
+            episodes,
+            output,
+        } => {
+            let config_manager = load_config(&cli).await?;
+            run_playlist(&config_manager.config, &query, &episodes, output).await?
+        }
+        #[cfg(feature = "dlna")]
+        Commands::Serve { port, name } => {
+            let config_manager = load_config(&cli).await?;
+            dlna::serve(
+                &config_manager.config,
+                config_manager.auth.anilist_token.as_deref(),
+                &name,
+                port,
+            )
+            .await?
+        }
+        Commands::Feed { output, days } => {
+            let config_manager = load_config(&cli).await?;
+            run_feed(&config_manager, days, output).await?
+        }
+        Commands::Doctor => {
+            // Probes the chosen provider even when it was last down.
+            let config_manager = load_config_with_fallbacks(&cli, false).await?;
+            run_doctor(&config_manager.config).await?
+        }
+        Commands::Pin {
+            query,
+            to,
+            show_id,
+            clear,
+        } => {
+            let config_manager = load_config(&cli).await?;
+            run_pin(&config_manager, &query, to, show_id, clear).await?
+        }
+        Commands::Sync { dry_run, verbose } => {
+            let config_manager = load_config(&cli).await?;
+            run_sync(&config_manager, dry_run, verbose).await?
+        }
+        Commands::Watch {
+            query,
+            episode,
+            batch,
+            download,
+        } => {
+            let pick = batch.is_none() && episode.is_none();
+            let items = match (batch, query) {
+                (Some(path), _) => batch::read(&path)?,
+                (None, Some(title)) => batch::episodes(&title, episode.as_deref().unwrap_or("1"))?,
+                (None, None) => unreachable!("clap requires --query without --batch"),
+            };
+            let config_manager = load_config(&cli).await?;
+            run_watch(
+                &config_manager,
+                items,
+                pick,
+                download,
+                cli.private,
+                cli.audio_only,
+            )
+            .await?
+        }
+        Commands::Dedupe { yes } => run_dedupe(yes)?,
+        Commands::Backup { action } => run_backup(action)?,
+        Commands::Status { format } => {
+            let now_playing = status::read().ok_or(Failure::NoResults)?;
+            println!("{}", now_playing.render(&format));
+        }
     }
 
     Ok(())
 }
 
-async fn run_tui(config_manager: ConfigManager) -> Result<()> {
+async fn load_config(cli: &Cli) -> Result<ConfigManager> {
+    load_config_with_fallbacks(cli, true).await
+}
+
+/// `use_fallbacks` swaps in a fallback provider when the chosen one was
+/// last found unreachable.
+async fn load_config_with_fallbacks(cli: &Cli, use_fallbacks: bool) -> Result<ConfigManager> {
+    let mut config_manager = ConfigManager::init_interactive().await?;
+    rust_i18n::set_locale(&config_manager.config.general.language);
+    net::init(&config_manager.config.network)?;
+    // For this run only; saving from the options menu leaves them out.
+    config_manager.apply_overrides(|config, auth| {
+        if let Some(language) = &cli.sub_lang {
+            config.stream.subtitle_language = Some(language.clone());
+        }
+        if let Some(mode) = cli.picker {
+            config.picker.mode = mode;
+        }
+        if let Some(provider) = &cli.provider {
+            config.general.provider = provider.to_string();
+        } else if use_fallbacks
+            && let Ok(health) = HealthManager::new()
+            && let Some(replacement) = health::replacement(&config.general, |p| health.is_down(p))
+        {
+            say_err!(
+                "⚠️  {} was unreachable at the last `ani-l doctor` check; using {}",
+                config.general.provider,
+                replacement
+            );
+            config.general.provider = replacement;
+        }
+        if config.general.provider == "fake" {
+            provider::fake::enable();
+            // Progress sync runs against the in-memory fake list.
+            auth.anilist_token = Some("fake".to_string());
+            auth.username = Some("tester".to_string());
+        }
+    })?;
+    Ok(config_manager)
+}
+
+async fn run_tui(
+    config_manager: ConfigManager,
+    resume: Option<WatchSession>,
+    private: bool,
+    audio_only: bool,
+    seed: Option<u64>,
+) -> Result<()> {
     enable_raw_mode()?;
     execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Some(Terminal::new(backend)?);
 
     let mut app = App::new(config_manager);
-    app.init_image_picker();
+    // Query the terminal before the event loop starts reading input, so
+    // the reply can't be mistaken for (or swallow) key presses.
+    #[cfg(feature = "images")]
+    app.ensure_image_picker();
+    app.private = private;
+    app.audio_only = audio_only;
+    app.random = random::session_rng(seed);
+    if let Ok(registry) = RegistryManager::new() {
+        let duplicates = registry::find_duplicates(&registry.data).len();
+        if duplicates > 0 {
+            app.confirm = Some(Confirm {
+                message: t!("confirm.merge_duplicates", count = duplicates).to_string(),
+                on_yes: Action::MergeDuplicates,
+            });
+        }
+        app.episode_ratings = registry.data.ratings;
+        app.registry_entries = registry.data.entries;
+    }
 
     if app.config_manager.config.general.check_updates {
         let tx = app.action_tx.clone();
@@ -99,21 +558,163 @@ async fn run_tui(config_manager: ConfigManager) -> Result<()> {
         });
     }
 
+    if let Some(session) = resume {
+        let tx = app.action_tx.clone();
+        let token = app.config_manager.auth.anilist_token.clone();
+        let complete_at = app.config_manager.config.stream.episode_complete_at as f64;
+        tokio::spawn(async move {
+            let fetched = api::fetch_media(
+                serde_json::json!({ "id_in": [session.media_id] }),
+                token.as_deref(),
+            )
+            .await;
+            match fetched.map(|res| res.data.page.map(|p| p.media).unwrap_or_default()) {
+                Ok(media) if !media.is_empty() => {
+                    // Sessions keep minutes watched, recorded against the
+                    // same 24 minute fallback.
+                    let minutes = media[0].duration.unwrap_or(24) as f64;
+                    let finished = session.minutes * 100.0 / minutes >= complete_at;
+                    let next = if finished {
+                        session.episode + 1
+                    } else {
+                        session.episode
+                    };
+                    let _ = tx.send(Action::ResumeMedia(
+                        Arc::new(media[0].clone()),
+                        next.to_string(),
+                    ));
+                }
+                Ok(_) => {
+                    let _ = tx.send(Action::SearchError(t!("logs.no_results").to_string()));
+                }
+                Err(e) => {
+                    let _ = tx.send(Action::SearchError(e.to_string()));
+                }
+            }
+        });
+    }
+
     let mut suspended = false;
 
     loop {
         if !suspended {
             if let Some(term) = &mut terminal {
+                let started = Instant::now();
                 term.draw(|f| tui::ui::draw(f, &mut app))?;
+                app.perf.frame_drawn(started.elapsed());
 
                 let mut input_event = None;
-                if crossterm::event::poll(Duration::from_millis(16))? {
-                    input_event = Some(crossterm::event::read()?);
+                // Let each replayed step's searches and loads finish first.
+                if let Some(key) = (!app.is_loading)
+                    .then(|| app.macros.next_replayed())
+                    .flatten()
+                {
+                    input_event = Some(Event::Key(key));
+                } else if crossterm::event::poll(Duration::from_millis(16))? {
+                    app.perf.input_received();
+                    input_event = match crossterm::event::read()? {
+                        // Hidden performance overlay; taken before macros so
+                        // it works whatever is bound.
+                        Event::Key(key)
+                            if key.kind == event::KeyEventKind::Press
+                                && key.code == KeyCode::F(12) =>
+                        {
+                            app.perf.toggle();
+                            None
+                        }
+                        Event::Key(key) if key.kind == event::KeyEventKind::Press => {
+                            macro_input(&mut app, key).map(Event::Key)
+                        }
+                        event => Some(event),
+                    };
                 }
 
                 if let Some(Event::Key(key)) = input_event {
                     if key.kind == event::KeyEventKind::Press {
-                        if app.show_update_modal {
+                        if let Some(confirm) = app.confirm.take() {
+                            match key.code {
+                                KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                                    app.action_tx.send(confirm.on_yes)?
+                                }
+                                KeyCode::Char('n')
+                                | KeyCode::Char('N')
+                                | KeyCode::Esc
+                                | KeyCode::Char('q') => {}
+                                _ => app.confirm = Some(confirm),
+                            }
+                        } else if let Some(mut editor) = app.note_editor.take() {
+                            match key.code {
+                                KeyCode::Enter => {
+                                    let note = Some(editor.note.trim().to_string());
+                                    let tags = editor.tag_list();
+                                    app.action_tx.send(Action::SaveNote(
+                                        editor.media,
+                                        note,
+                                        tags,
+                                    ))?
+                                }
+                                KeyCode::Esc => {}
+                                KeyCode::Tab | KeyCode::BackTab => {
+                                    editor.editing_tags = !editor.editing_tags;
+                                    app.note_editor = Some(editor);
+                                }
+                                KeyCode::Backspace => {
+                                    editor.field().pop();
+                                    app.note_editor = Some(editor);
+                                }
+                                KeyCode::Char(c) => {
+                                    editor.field().push(c);
+                                    app.note_editor = Some(editor);
+                                }
+                                _ => app.note_editor = Some(editor),
+                            }
+                        } else if app.score_prompt {
+                            match key.code {
+                                KeyCode::Char(c @ '0'..='9') => {
+                                    app.score_prompt = false;
+                                    let score = c.to_digit(10).filter(|&d| d > 0).unwrap_or(10);
+                                    app.action_tx
+                                        .send(Action::BulkEdit(BulkEdit::Score(score as u8)))?
+                                }
+                                KeyCode::Esc | KeyCode::Char('q') => app.score_prompt = false,
+                                _ => {}
+                            }
+                        } else if let Some(prompt) = app.rating_prompt.take() {
+                            match key.code {
+                                KeyCode::Char(c @ '0'..='9') => {
+                                    // 0 stands for 10.
+                                    let rating = c.to_digit(10).filter(|&d| d > 0).unwrap_or(10);
+                                    app.action_tx.send(Action::RateEpisode(
+                                        prompt.media_id,
+                                        prompt.episode,
+                                        rating as u8,
+                                    ))?
+                                }
+                                KeyCode::Esc | KeyCode::Char('q') => {}
+                                _ => app.rating_prompt = Some(prompt),
+                            }
+                        } else if let Some(choice) = &mut app.show_choice {
+                            match key.code {
+                                KeyCode::Down | KeyCode::Char('j') => {
+                                    choice.selected = (choice.selected + 1) % choice.names.len();
+                                }
+                                KeyCode::Up | KeyCode::Char('k') => {
+                                    choice.selected = choice
+                                        .selected
+                                        .checked_sub(1)
+                                        .unwrap_or(choice.names.len() - 1);
+                                }
+                                KeyCode::Enter => {
+                                    let _ = choice.reply.send(Some(choice.selected));
+                                    app.show_choice = None;
+                                }
+                                KeyCode::Esc | KeyCode::Char('q') => {
+                                    let _ = choice.reply.send(None);
+                                    app.show_choice = None;
+                                }
+                                _ => {}
+                            }
+                        } else if app.show_update_modal {
                             match key.code {
                                 KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
                                     app.show_update_modal = false;
@@ -126,26 +727,74 @@ async fn run_tui(config_manager: ConfigManager) -> Result<()> {
                                     KeyCode::Char('/') => {
                                         app.action_tx.send(Action::ToggleFocus)?
                                     }
-                                    KeyCode::Enter => {
-                                        if !app.search_query.is_empty() {
-                                            app.action_tx.send(Action::SearchStarted)?;
-                                            let query = app.search_query.clone();
-                                            let tx = app.action_tx.clone();
-                                            tokio::spawn(async move {
-                                                match api::fetch_media(serde_json::json!({
-                                                    "search": query, "perPage": 20, "sort": "POPULARITY_DESC"
-                                                })).await {
-                                                    Ok(res) => {
-                                                        if let Some(page) = res.data.page {
-                                                            let _ = tx.send(Action::SearchCompleted(page.media, None));
+                                    KeyCode::Enter if app.search_query.starts_with('#') => {
+                                        let query = app.search_query[1..].to_string();
+                                        load_annotated(&mut app, query)?
+                                    }
+                                    KeyCode::Enter
+                                        if !app.search_query.is_empty()
+                                            && app.list_mode.is_library() =>
+                                    {
+                                        let query = app.search_query.clone();
+                                        search_library(&mut app, query)?
+                                    }
+                                    KeyCode::Enter if !app.search_query.is_empty() => {
+                                        app.action_tx.send(Action::SearchStarted)?;
+                                        let query = app.search_query.clone();
+                                        let tx = app.action_tx.clone();
+                                        let token = app.config_manager.auth.anilist_token.clone();
+                                        let kind = if app.manga { "MANGA" } else { "ANIME" };
+                                        tokio::spawn(async move {
+                                            let variables = serde_json::json!({
+                                                "search": query, "perPage": 20, "sort": "POPULARITY_DESC",
+                                                "type": kind, "slim": true
+                                            });
+                                            match api::fetch_media(
+                                                variables.clone(),
+                                                token.as_deref(),
+                                            )
+                                            .await
+                                            {
+                                                Ok(res) => {
+                                                    let page = res
+                                                        .data
+                                                        .page
+                                                        .filter(|p| !p.media.is_empty());
+                                                    if let Some(page) = page {
+                                                        let pager = MediaPager::after(
+                                                            variables,
+                                                            &page.page_info,
+                                                        );
+                                                        let _ = tx.send(Action::SearchCompleted(
+                                                            page.media, None, pager,
+                                                        ));
+                                                    } else {
+                                                        match suggest::did_you_mean(&query).await {
+                                                            Ok(suggestions)
+                                                                if !suggestions.is_empty() =>
+                                                            {
+                                                                let _ = tx.send(Action::SearchCompleted(
+                                                                    suggestions,
+                                                                    Some(t!("titles.did_you_mean").to_string()),
+                                                                    None,
+                                                                ));
+                                                            }
+                                                            _ => {
+                                                                let _ =
+                                                                    tx.send(Action::SearchError(
+                                                                        t!("logs.no_results")
+                                                                            .to_string(),
+                                                                    ));
+                                                            }
                                                         }
                                                     }
-                                                    Err(e) => {
-                                                        let _ = tx.send(Action::SearchError(e.to_string()));
-                                                    }
                                                 }
-                                            });
-                                        }
+                                                Err(e) => {
+                                                    let _ =
+                                                        tx.send(Action::SearchError(e.to_string()));
+                                                }
+                                            }
+                                        });
                                     }
                                     KeyCode::Char(c) => {
                                         app.search_query.push(c);
@@ -158,27 +807,123 @@ async fn run_tui(config_manager: ConfigManager) -> Result<()> {
                                     }
                                     _ => {}
                                 },
-                                Focus::List => match key.code {
-                                    KeyCode::Char('q') => app.action_tx.send(Action::Quit)?,
-                                    KeyCode::Char('/') => {
-                                        app.action_tx.send(Action::ToggleFocus)?
-                                    }
-                                    KeyCode::Down | KeyCode::Char('j') => {
-                                        app.action_tx.send(Action::NavigateDown)?
-                                    }
-                                    KeyCode::Up | KeyCode::Char('k') => {
-                                        app.action_tx.send(Action::NavigateUp)?
-                                    }
-                                    KeyCode::PageDown | KeyCode::Char('J') => {
-                                        app.action_tx.send(Action::NavigatePageDown)?
-                                    }
-                                    KeyCode::PageUp | KeyCode::Char('K') => {
-                                        app.action_tx.send(Action::NavigatePageUp)?
-                                    }
-                                    KeyCode::Enter => app.action_tx.send(Action::Select)?,
-                                    KeyCode::Esc => app.action_tx.send(Action::GoBack)?,
-                                    KeyCode::Backspace => app.action_tx.send(Action::GoBack)?,
-                                    _ => {}
+                                Focus::List => match app.count.feed(key.code) {
+                                    Feed::Pending => {}
+                                    Feed::Navigate(action) => app.action_tx.send(action)?,
+                                    Feed::Pass => match key.code {
+                                        KeyCode::Char('q') => app.action_tx.send(Action::Quit)?,
+                                        KeyCode::Char('/') => {
+                                            app.action_tx.send(Action::ToggleFocus)?
+                                        }
+                                        KeyCode::Char(c @ ('l' | 'r' | 'm'))
+                                            if app.list_mode == ListMode::Conflicts =>
+                                        {
+                                            let resolution = match c {
+                                                'l' => Resolution::KeepLocal,
+                                                'r' => Resolution::KeepRemote,
+                                                _ => Resolution::Max,
+                                            };
+                                            confirm_conflict(&mut app, resolution)
+                                        }
+                                        KeyCode::Char('x') if app.list_mode == ListMode::Queue => {
+                                            remove_from_queue(&mut app)
+                                        }
+                                        KeyCode::Char(' ')
+                                            if app.list_mode == ListMode::Organizer =>
+                                        {
+                                            if let Some(media) =
+                                                app.media_list.get(app.get_selected_index())
+                                                && !app.organizer_selected.remove(&media.id)
+                                            {
+                                                app.organizer_selected.insert(media.id);
+                                            }
+                                            app.next();
+                                        }
+                                        KeyCode::Char('A')
+                                            if app.list_mode == ListMode::Organizer =>
+                                        {
+                                            if app.organizer_selected.len() == app.media_list.len()
+                                            {
+                                                app.organizer_selected.clear();
+                                            } else {
+                                                app.organizer_selected =
+                                                    app.media_list.iter().map(|m| m.id).collect();
+                                            }
+                                        }
+                                        KeyCode::Char(c @ ('p' | 'd'))
+                                            if app.list_mode == ListMode::Organizer =>
+                                        {
+                                            let count = app.organizer_targets().len();
+                                            let (message, edit) = if c == 'p' {
+                                                (
+                                                    t!("confirm.bulk_pause", count = count),
+                                                    BulkEdit::Pause,
+                                                )
+                                            } else {
+                                                (
+                                                    t!("confirm.bulk_delete", count = count),
+                                                    BulkEdit::Delete,
+                                                )
+                                            };
+                                            if count > 0 {
+                                                app.confirm_then(
+                                                    message.to_string(),
+                                                    Action::BulkEdit(edit),
+                                                );
+                                            }
+                                        }
+                                        KeyCode::Char('s')
+                                            if app.list_mode == ListMode::Organizer =>
+                                        {
+                                            app.score_prompt = !app.organizer_targets().is_empty();
+                                        }
+                                        KeyCode::Char(' ')
+                                            if app.list_mode == ListMode::EpisodeSelect =>
+                                        {
+                                            let episode = app.get_selected_index() as i32 + 1;
+                                            if !app.episodes_selected.remove(&episode) {
+                                                app.episodes_selected.insert(episode);
+                                            }
+                                            app.next();
+                                        }
+                                        KeyCode::Char('s')
+                                            if app.list_mode == ListMode::EpisodeSelect =>
+                                        {
+                                            app.action_tx.send(Action::ToggleSpoilers)?
+                                        }
+                                        KeyCode::Char('R')
+                                            if app.list_mode == ListMode::EpisodeSelect =>
+                                        {
+                                            if let Some(media) = &app.active_media {
+                                                app.rating_prompt = Some(RatingPrompt {
+                                                    media_id: media.id,
+                                                    title: media.preferred_title().to_string(),
+                                                    episode: app.get_selected_index() as i32 + 1,
+                                                });
+                                            }
+                                        }
+                                        KeyCode::Char('t')
+                                            if matches!(
+                                                app.list_mode,
+                                                ListMode::AnimeActions | ListMode::EpisodeSelect
+                                            ) =>
+                                        {
+                                            app.action_tx.send(Action::ToggleTranslation)?
+                                        }
+                                        KeyCode::Char('p') => {
+                                            app.action_tx.send(Action::TogglePrivate)?
+                                        }
+                                        KeyCode::Char('a') => {
+                                            app.action_tx.send(Action::ToggleAudioOnly)?
+                                        }
+                                        KeyCode::Char('r') if app.pending_retry.is_some() => {
+                                            app.action_tx.send(Action::RetryStream)?
+                                        }
+                                        KeyCode::Enter => app.action_tx.send(Action::Select)?,
+                                        KeyCode::Esc => app.action_tx.send(Action::GoBack)?,
+                                        KeyCode::Backspace => app.action_tx.send(Action::GoBack)?,
+                                        _ => {}
+                                    },
                                 },
                             }
                         }
@@ -193,39 +938,89 @@ async fn run_tui(config_manager: ConfigManager) -> Result<()> {
 
         while let Ok(action) = app.action_rx.try_recv() {
             match action {
-                Action::Tick => app.on_tick(),
-                Action::Quit => app.running = false,
+                Action::Tick => {
+                    app.on_tick();
+                    load_pending_details(&mut app);
+                }
+                Action::DetailsLoaded(media) => {
+                    let media = Arc::new(*media);
+                    for row in app.media_list.iter_mut().filter(|m| m.id == media.id) {
+                        *row = media.clone();
+                    }
+                    if app.active_media.as_ref().is_some_and(|m| m.id == media.id) {
+                        app.active_media = Some(media);
+                    }
+                }
+                Action::Quit => {
+                    let pending = app.pending_work.count();
+                    if pending > 0 {
+                        app.confirm_then(
+                            t!("confirm.quit_pending", count = pending).to_string(),
+                            Action::QuitNow,
+                        );
+                    } else {
+                        app.running = false;
+                    }
+                }
+                Action::QuitNow => app.running = false,
                 Action::ToggleFocus => {
                     app.focus = match app.focus {
                         Focus::List => Focus::SearchBar,
                         Focus::SearchBar => Focus::List,
                     };
                 }
-                Action::NavigateDown => {
+                // A single step wraps around the list; counted jumps stop at
+                // its ends.
+                Action::NavigateDown(1) => {
                     app.next();
                     update_preview(&mut app);
+                    load_next_page(&mut app);
+                }
+                Action::NavigateDown(n) => {
+                    app.jump_forward(n);
+                    update_preview(&mut app);
+                    load_next_page(&mut app);
                 }
-                Action::NavigateUp => {
+                Action::NavigateUp(1) => {
                     app.previous();
                     update_preview(&mut app);
                 }
-                Action::NavigatePageDown => {
-                    app.jump_forward(10);
+                Action::NavigateUp(n) => {
+                    app.jump_backward(n);
+                    update_preview(&mut app);
+                }
+                Action::NavigatePageDown(n) => {
+                    app.jump_forward(n.saturating_mul(10));
+                    update_preview(&mut app);
+                    load_next_page(&mut app);
+                }
+                Action::NavigatePageUp(n) => {
+                    app.jump_backward(n.saturating_mul(10));
                     update_preview(&mut app);
                 }
-                Action::NavigatePageUp => {
-                    app.jump_backward(10);
+                Action::NavigateTo(row) => {
+                    app.jump_to(row);
                     update_preview(&mut app);
+                    load_next_page(&mut app);
+                }
+                Action::NavigateToEnd => {
+                    app.jump_to(usize::MAX);
+                    update_preview(&mut app);
+                    load_next_page(&mut app);
                 }
                 Action::GoBack => app.go_back(),
                 Action::SearchStarted => {
                     app.is_loading = true;
                     app.status_message = Some(t!("status.searching").to_string());
                 }
-                Action::SearchCompleted(media, title_opt) => {
+                Action::SearchCompleted(media, title_opt, pager) => {
                     app.is_loading = false;
                     app.status_message = None;
-                    app.media_list = media;
+                    #[cfg(feature = "images")]
+                    app.image_prefetcher.cancel();
+                    app.media_list.clear();
+                    app.append_media(media);
+                    app.pager = pager;
                     if let Some(title) = title_opt {
                         app.go_to_mode(ListMode::AnimeList(title), true);
                     } else {
@@ -233,8 +1028,241 @@ async fn run_tui(config_manager: ConfigManager) -> Result<()> {
                     }
                     app.focus = Focus::List;
                     app.active_media = None;
+                    app.clear_cover();
+                    update_preview(&mut app);
+                }
+                Action::ListRefreshed(title, media, pager) => {
+                    // Dropped when the cached list was already left.
+                    if app.list_mode == ListMode::AnimeList(title) {
+                        app.refresh_media(media);
+                        // Pages scrolled in meanwhile keep the pager where it is.
+                        if app.pager.as_ref().is_none_or(|p| p.page <= 1 && !p.loading) {
+                            app.pager = pager;
+                        }
+                        update_preview(&mut app);
+                    }
+                }
+                Action::PageLoaded(media, info) => {
+                    // A list that has since been replaced isn't waiting on a page.
+                    if let Some(pager) = app.pager.as_mut().filter(|p| p.loading) {
+                        pager.loading = false;
+                        match info {
+                            Some(info) if info.has_next_page => pager.page = info.current_page,
+                            Some(_) => app.pager = None,
+                            // Failed; scrolling again retries.
+                            None => {}
+                        }
+                        app.append_media(media);
+                    }
+                }
+                Action::BacklogLoaded(media) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    #[cfg(feature = "images")]
+                    app.image_prefetcher.cancel();
+                    let remaining: Vec<i32> = media
+                        .iter()
+                        .map(|m| m.minutes_remaining().unwrap_or(0))
+                        .collect();
+                    app.backlog_finish = HistoryManager::new()
+                        .ok()
+                        .and_then(|h| h.data.average_daily_minutes(chrono::Utc::now()))
+                        .map(|daily| {
+                            history::estimate_finish_dates(
+                                &remaining,
+                                daily,
+                                HistoryManager::today(),
+                            )
+                        })
+                        .unwrap_or_default();
+                    app.media_list = media.into_iter().map(Arc::new).collect();
+                    app.pager = None;
+                    app.go_to_mode(ListMode::Backlog, true);
+                    app.focus = Focus::List;
+                    app.active_media = None;
+                    app.clear_cover();
+                    update_preview(&mut app);
+                }
+                Action::LibraryLoaded(media) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    if media.is_empty() {
+                        app.status_message = Some(t!("status.library_empty").to_string());
+                    } else {
+                        app.media_list = media.into_iter().map(Arc::new).collect();
+                        app.pager = None;
+                        app.organizer_selected.clear();
+                        app.go_to_mode(ListMode::Organizer, true);
+                        app.focus = Focus::List;
+                        app.active_media = None;
+                        app.clear_cover();
+                    }
+                }
+                Action::BulkEdit(edit) => start_bulk_edit(&mut app, edit),
+                Action::BulkProgress(done, total) => {
+                    app.status_message =
+                        Some(t!("status.bulk_progress", done = done, total = total).to_string());
+                }
+                Action::BulkFinished(edit, done, error) => {
+                    app.is_loading = false;
+                    apply_bulk_edit(&mut app, edit, &done);
+                    app.status_message = Some(match error {
+                        Some(e) => {
+                            t!("status.bulk_failed", count = done.len(), err = e).to_string()
+                        }
+                        None => t!("status.bulk_done", count = done.len()).to_string(),
+                    });
+                }
+                Action::ConflictsLoaded(conflicts) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    if conflicts.is_empty() {
+                        app.status_message = Some(t!("status.no_conflicts").to_string());
+                    } else {
+                        app.conflicts = conflicts;
+                        app.go_to_mode(ListMode::Conflicts, true);
+                        app.focus = Focus::List;
+                    }
+                }
+                Action::CharactersLoaded(characters) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    if characters.is_empty() {
+                        app.status_message = Some(t!("status.no_characters").to_string());
+                    } else {
+                        app.characters = characters;
+                        app.go_to_mode(ListMode::Characters, true);
+                        app.focus = Focus::List;
+                    }
+                }
+                Action::VoiceRolesLoaded(name, roles) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    if roles.is_empty() {
+                        app.status_message =
+                            Some(t!("status.no_voice_roles", name = name).to_string());
+                    } else {
+                        app.voice_roles = roles;
+                        app.go_to_mode(ListMode::VoiceRoles(name), true);
+                        app.focus = Focus::List;
+                    }
+                }
+                Action::FranchiseLoaded(entries) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    let mal_ids: Vec<i32> = entries.iter().filter_map(|e| e.media.id_mal).collect();
+                    let orders = watch_order::for_franchise(&mal_ids);
+                    // The show on its own isn't much of a franchise.
+                    if entries.len() < 2 && orders.is_empty() {
+                        app.status_message = Some(t!("status.no_franchise").to_string());
+                    } else {
+                        app.franchise = entries;
+                        app.watch_orders = orders;
+                        app.go_to_mode(ListMode::Franchise, true);
+                        app.focus = Focus::List;
+                    }
+                }
+                Action::WatchOrderLoaded(shows) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    queue_shows(&mut app, &shows)?;
+                }
+                Action::ChaptersLoaded(media, manga_id, chapters) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    if chapters.is_empty() {
+                        app.status_message = Some(t!("status.no_chapters").to_string());
+                    } else {
+                        let progress = media
+                            .media_list_entry
+                            .as_ref()
+                            .and_then(|e| e.progress)
+                            .or_else(|| {
+                                RegistryManager::new()
+                                    .ok()?
+                                    .get_entry(media.id)
+                                    .map(|e| e.progress)
+                            })
+                            .unwrap_or(0);
+                        let reading = MangaSession {
+                            media: media.clone(),
+                            manga_id,
+                            chapters,
+                            chapter: 0,
+                            pages: Vec::new(),
+                            progress,
+                        };
+                        // Pick up after the last chapter read.
+                        let next = (0..reading.chapters.len())
+                            .find(|&i| reading.chapter_number(i).is_some_and(|c| c > progress))
+                            .unwrap_or(0);
+                        app.reading = Some(reading);
+                        app.active_media = Some(media);
+                        app.go_to_mode(ListMode::Chapters, true);
+                        app.list_state.select(Some(next));
+                        app.focus = Focus::List;
+                    }
+                }
+                Action::ChapterPagesLoaded(chapter, pages) => {
+                    app.is_loading = false;
+                    app.status_message = None;
+                    if let Some(reading) = &mut app.reading {
+                        reading.chapter = chapter;
+                        reading.pages = pages;
+                    }
+                    // Turning to the next chapter stays on the same screen.
+                    if app.list_mode == ListMode::Reader {
+                        app.list_state.select(Some(0));
+                    } else {
+                        app.go_to_mode(ListMode::Reader, true);
+                    }
+                    app.focus = Focus::List;
                     update_preview(&mut app);
                 }
+                Action::ResolveConflict(resolution) => resolve_conflict(&mut app, resolution),
+                Action::ConflictResolved(media_id, progress) => {
+                    app.conflicts.retain(|c| c.id != media_id);
+                    if app.get_selected_index() >= app.conflicts.len() {
+                        app.list_state
+                            .select(Some(app.conflicts.len().saturating_sub(1)));
+                    }
+                    app.status_message =
+                        Some(t!("status.conflict_resolved", ep = progress).to_string());
+                }
+                Action::EpisodeDetailsLoaded(media_id, episode, details) => {
+                    app.episode_details_pending.remove(&(media_id, episode));
+                    app.episode_details.insert((media_id, episode), details);
+                }
+                Action::EpisodeDetailsFailed(media_id, episode) => {
+                    app.episode_details_pending.remove(&(media_id, episode));
+                }
+                Action::ToggleSpoilers => app.show_spoilers = !app.show_spoilers,
+                Action::TogglePrivate => {
+                    app.private = !app.private;
+                    app.status_message = Some(if app.private {
+                        t!("status.private_on").to_string()
+                    } else {
+                        t!("status.private_off").to_string()
+                    });
+                }
+                Action::ToggleAudioOnly => {
+                    app.audio_only = !app.audio_only;
+                    app.status_message = Some(if app.audio_only {
+                        t!("status.audio_on").to_string()
+                    } else {
+                        t!("status.audio_off").to_string()
+                    });
+                }
+                Action::ToggleTranslation => {
+                    let next = if app.translation_type == "dub" {
+                        "sub"
+                    } else {
+                        "dub"
+                    };
+                    app.translation_type = next.to_string();
+                    app.status_message =
+                        Some(t!("status.translation", kind = next.to_uppercase()).to_string());
+                }
                 Action::SearchError(err) => {
                     app.is_loading = false;
                     app.status_message = Some(err);
@@ -255,19 +1283,146 @@ async fn run_tui(config_manager: ConfigManager) -> Result<()> {
                     if let Some(term) = &mut terminal {
                         let _ = term.clear();
                     }
-                }
-                Action::Select => handle_selection(&mut app)?,
-                Action::ImageLoaded(bytes) => {
-                    if let Some(picker) = &mut app.image_picker
-                        && let Ok(img) = image::load_from_memory(&bytes)
-                    {
-                        let protocol = picker.new_resize_protocol(img);
-                        app.current_cover_image = Some(protocol);
+                    // Queued episodes play on without asking; `R` on the
+                    // episode list rates them afterwards.
+                    let rating = app.pending_rating.take();
+                    if std::mem::take(&mut app.queue_continue) && !app.queue.is_empty() {
+                        app.action_tx.send(Action::PlayQueue)?;
+                    } else {
+                        app.playing_queue = false;
+                        app.rating_prompt = rating;
                     }
-                    app.is_fetching_image = false;
                 }
-                Action::Suspend(notify) => {
-                    suspended = true;
+                Action::PlayQueue => play_queue(&mut app),
+                Action::ChooseShow(query, names, reply) => {
+                    app.show_choice = Some(ShowChoice {
+                        query,
+                        names,
+                        selected: 0,
+                        reply,
+                    });
+                }
+                Action::EpisodeWatched(media, episode) => {
+                    if app.config_manager.config.ui.rate_episodes && !app.private {
+                        app.pending_rating = Some(RatingPrompt {
+                            media_id: media.id,
+                            title: media.preferred_title().to_string(),
+                            episode,
+                        });
+                    }
+                    if let Ok(mut manager) = QueueManager::new() {
+                        manager.data.mark_watched(media.id, episode);
+                        let _ = manager.save();
+                        app.queue = manager.data.entries;
+                    }
+                    app.queue_continue = app.playing_queue;
+                }
+                Action::SaveNote(media, note, tags) => {
+                    match RegistryManager::new().and_then(|mut r| {
+                        r.annotate(&media, note.clone(), tags)?;
+                        Ok(r.data.entries)
+                    }) {
+                        Ok(entries) => {
+                            app.registry_entries = entries;
+                            app.status_message = Some(t!("status.notes_saved").to_string());
+                            let note = note.unwrap_or_default();
+                            sync_notes(&app, &media, note.clone());
+                            // So reopening the editor shows what was saved.
+                            if let Some(active) =
+                                app.active_media.as_mut().filter(|m| m.id == media.id)
+                                && let Some(entry) = Arc::make_mut(active).media_list_entry.as_mut()
+                            {
+                                entry.notes = Some(note);
+                            }
+                        }
+                        Err(e) => app.status_message = Some(e.to_string()),
+                    }
+                }
+                Action::MergeDuplicates => {
+                    match RegistryManager::new().and_then(|mut r| {
+                        let duplicates = registry::find_duplicates(&r.data);
+                        r.merge_duplicates(&duplicates)?;
+                        Ok((duplicates.len(), r.data))
+                    }) {
+                        Ok((count, data)) => {
+                            app.registry_entries = data.entries;
+                            app.episode_ratings = data.ratings;
+                            app.status_message =
+                                Some(t!("status.duplicates_merged", count = count).to_string());
+                        }
+                        Err(e) => app.status_message = Some(e.to_string()),
+                    }
+                }
+                Action::RateEpisode(media_id, episode, rating) => {
+                    match RegistryManager::new().and_then(|mut r| {
+                        r.rate(media_id, episode, rating)?;
+                        Ok(r.data.ratings)
+                    }) {
+                        Ok(ratings) => {
+                            app.episode_ratings = ratings;
+                            app.status_message = Some(
+                                t!("status.episode_rated", ep = episode, rating = rating)
+                                    .to_string(),
+                            );
+                        }
+                        Err(e) => app.status_message = Some(e.to_string()),
+                    }
+                }
+                Action::EpisodeNotUploaded(media, episode) => {
+                    app.pending_retry = Some((media, episode));
+                }
+                Action::ResumeMedia(media, episode) => {
+                    app.active_media = Some(media.clone());
+                    start_stream_task(&app, media, Some(episode));
+                }
+                Action::RetryStream => {
+                    if let Some((media, episode)) = app.pending_retry.take() {
+                        app.go_back();
+                        start_stream_task(&app, media, Some(episode));
+                    }
+                }
+                Action::Select => handle_selection(&mut app)?,
+                #[cfg(feature = "images")]
+                Action::ImageLoaded(img) => {
+                    if let Some(picker) = &mut app.image_picker {
+                        if app.ascii_covers {
+                            app.current_cover_ascii = Some(AsciiCover::new(img));
+                        } else {
+                            app.current_cover_image = Some(picker.new_resize_protocol(img));
+                        }
+                    }
+                    app.is_fetching_image = false;
+                }
+                #[cfg(feature = "images")]
+                Action::MangaPageLoaded(url, img) => {
+                    let current = app
+                        .reading
+                        .as_ref()
+                        .and_then(|r| r.pages.get(app.get_selected_index()));
+                    if app.list_mode == ListMode::Reader
+                        && current == Some(&url)
+                        && let Some(picker) = &mut app.image_picker
+                    {
+                        app.page_image = Some(picker.new_resize_protocol(img));
+                    }
+                    app.is_fetching_image = false;
+                }
+                #[cfg(feature = "images")]
+                Action::BannerLoaded(media_id, img) => {
+                    if app.active_media.as_ref().map(|m| m.id) == Some(media_id) {
+                        // Halfblocks, so the details text can be drawn over it.
+                        let protocol = Picker::halfblocks().new_resize_protocol(img);
+                        app.current_banner_image = Some(protocol);
+                    }
+                }
+                Action::Suspend(notify) => {
+                    suspended = true;
+                    // Nothing is drawn while the player owns the terminal.
+                    #[cfg(feature = "images")]
+                    {
+                        app.current_cover_image = None;
+                        app.current_banner_image = None;
+                    }
                     if let Some(mut term) = terminal.take() {
                         let _ = term.show_cursor();
                         let _ = term.clear();
@@ -304,34 +1459,851 @@ async fn run_tui(config_manager: ConfigManager) -> Result<()> {
 }
 
 fn update_preview(app: &mut App) {
+    if app.list_mode == ListMode::EpisodeSelect {
+        load_episode_details(app, app.get_selected_index() as i32 + 1);
+        return;
+    }
+    if app.list_mode == ListMode::Reader {
+        update_page(app);
+        return;
+    }
     if matches!(
         app.list_mode,
-        ListMode::SearchResults | ListMode::AnimeList(_)
+        ListMode::SearchResults | ListMode::AnimeList(_) | ListMode::Backlog
     ) {
         let idx = app.get_selected_index();
         if idx < app.media_list.len() {
             let media = app.media_list[idx].clone();
             if app.active_media.as_ref().map(|m| m.id) != Some(media.id) {
                 app.active_media = Some(media.clone());
-                app.current_cover_image = None;
+                app.detail_pending = media.slim.then(|| (media.id, Instant::now()));
+                app.clear_cover();
 
-                if let Some(cover) = media.cover_image {
-                    let url_opt = cover.extra_large.or(cover.large).or(cover.medium);
-                    if let Some(url) = url_opt {
+                #[cfg(feature = "images")]
+                {
+                    if let Some(url) = media.cover_url().map(str::to_string) {
+                        app.ensure_image_picker();
                         app.is_fetching_image = true;
-                        let tx = app.action_tx.clone();
-                        tokio::task::spawn_blocking(move || {
-                            if let Ok(resp) = reqwest::blocking::get(url)
-                                && let Ok(bytes) = resp.bytes()
-                            {
-                                let _ = tx.send(Action::ImageLoaded(bytes.to_vec()));
-                            }
+                        let target = app.pixel_size(app.cover_area);
+                        load_image(app, url, target, Action::ImageLoaded);
+                    }
+                    if app.config_manager.config.ui.banner_background
+                        && let Some(url) = media.banner_image.clone()
+                    {
+                        let media_id = media.id;
+                        // Cropped rather than scaled to its area, so only
+                        // decoded here.
+                        load_image(app, url, None, move |img| {
+                            Action::BannerLoaded(media_id, img.brighten(-110))
                         });
                     }
                 }
             }
         }
+        #[cfg(feature = "images")]
+        prefetch_visible_covers(app);
+    }
+}
+
+/// Fetches the highlighted slim show's details once it has stayed
+/// highlighted for `DETAIL_DELAY`.
+fn load_pending_details(app: &mut App) {
+    let Some((media_id, since)) = app.detail_pending else {
+        return;
+    };
+    if since.elapsed() < DETAIL_DELAY {
+        return;
+    }
+    app.detail_pending = None;
+    if app.active_media.as_ref().map(|m| m.id) != Some(media_id) {
+        return;
+    }
+    let tx = app.action_tx.clone();
+    let token = app.config_manager.auth.anilist_token.clone();
+    tokio::spawn(async move {
+        if let Ok(Some(media)) = api::fetch_details(media_id, token.as_deref()).await {
+            let _ = tx.send(Action::DetailsLoaded(Box::new(media)));
+        }
+    });
+}
+
+/// Fetches an episode's synopsis for the active media, once per episode
+/// unless the lookup fails.
+fn load_episode_details(app: &mut App, episode: i32) {
+    let Some(media) = &app.active_media else {
+        return;
+    };
+    let key = (media.id, episode);
+    let Some(mal_id) = media.id_mal else {
+        return;
+    };
+    if app.episode_details.contains_key(&key) || !app.episode_details_pending.insert(key) {
+        return;
+    }
+    let tx = app.action_tx.clone();
+    tokio::spawn(async move {
+        let action = match api::fetch_episode_details(mal_id, episode).await {
+            Ok(details) => Action::EpisodeDetailsLoaded(key.0, episode, details),
+            Err(e) => {
+                log::debug!("No details for episode {}: {:#}", episode, e);
+                Action::EpisodeDetailsFailed(key.0, episode)
+            }
+        };
+        let _ = tx.send(action);
+    });
+}
+
+/// Sends the image at `url` back as an action, from the cache when possible.
+/// Fetching, decoding and scaling to `target` pixels all happen on a
+/// blocking thread, leaving the UI thread only the protocol conversion.
+#[cfg(feature = "images")]
+fn load_image(
+    app: &App,
+    url: String,
+    target: Option<(u32, u32)>,
+    action: impl FnOnce(DynamicImage) -> Action + Send + 'static,
+) {
+    let tx = app.action_tx.clone();
+    let cache = app.image_cache.clone();
+    tokio::task::spawn_blocking(move || {
+        let bytes = match cache.get(&url) {
+            Some(bytes) => bytes,
+            None => {
+                let Ok(bytes) = reqwest::blocking::get(&url).and_then(|r| r.bytes()) else {
+                    return;
+                };
+                cache.insert(url, bytes.to_vec());
+                Arc::new(bytes.to_vec())
+            }
+        };
+        if let Some(img) = images::decode(&bytes, target) {
+            let _ = tx.send(action(img));
+        }
+    });
+}
+
+/// Prefetches covers for the rows currently on screen plus a few either side.
+#[cfg(feature = "images")]
+fn prefetch_visible_covers(app: &mut App) {
+    const MARGIN: usize = 5;
+    let height = app.list_viewport_height.max(1);
+    let start = app.list_state.offset().saturating_sub(MARGIN);
+    let end = (app.list_state.offset() + height + MARGIN).min(app.media_list.len());
+
+    let urls = app
+        .media_list
+        .get(start..end)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|m| m.cover_url().map(str::to_string))
+        .collect();
+    app.image_prefetcher.prefetch(urls);
+}
+
+/// Records a finished episode locally and pushes it to AniList when that moves
+/// the list forward. When AniList is further along (e.g. watched on another
+/// device), the difference is left for the Sync Conflicts screen.
+async fn sync_progress(
+    config: &ConfigManager,
+    tx: &mpsc::UnboundedSender<Action>,
+    media: &crate::models::Media,
+    episode: i32,
+) {
+    let mut registry = RegistryManager::new().ok();
+    if let Some(registry) = &mut registry {
+        let _ = registry.record_progress(media, episode);
+    }
+    let (Some(token), Some(username)) = (&config.auth.anilist_token, &config.auth.username) else {
+        return;
+    };
+
+    let _ = tx.send(Action::StreamLog(t!("logs.updating_anilist").to_string()));
+    let (mut remote, on_list) = match api::get_user_progress(token, media.id, username).await {
+        Ok(progress) => (progress.unwrap_or(0), progress.is_some()),
+        Err(e) => {
+            let _ = tx.send(Action::StreamLog(
+                t!("logs.sync_error", err = e).to_string(),
+            ));
+            return;
+        }
+    };
+    if episode > remote {
+        let private = config.config.anilist.private_for(on_list);
+        let status = api::progress_status(episode, media.episodes.or(media.chapters));
+        match api::update_user_entry(token, media.id, episode, status, private).await {
+            Ok(_) => {
+                let _ = tx.send(Action::StreamLog(
+                    t!("logs.updated_to_ep", ep = episode).to_string(),
+                ));
+                remote = episode;
+            }
+            Err(e) => {
+                let _ = tx.send(Action::StreamLog(
+                    t!("logs.update_failed", err = e).to_string(),
+                ));
+            }
+        }
+    }
+
+    let Some(registry) = &mut registry else {
+        return;
+    };
+    let local = registry.get_entry(media.id).map_or(episode, |e| e.progress);
+    if local == remote {
+        let _ = registry.mark_synced(media.id);
+    } else if local < remote {
+        let _ = tx.send(Action::StreamLog(
+            t!("logs.progress_conflict", remote = remote).to_string(),
+        ));
+    }
+}
+
+/// Shows picked at random from AniList, in random order. Sampled ids often
+/// miss, so a few draws are made before giving up.
+async fn fetch_random(rng: &mut StdRng, token: Option<&str>) -> Result<Vec<crate::models::Media>> {
+    const DRAWS: usize = 3;
+    for _ in 0..DRAWS {
+        let ids = random::sample_ids(rng, random::SAMPLE_SIZE);
+        let variables = serde_json::json!({ "perPage": random::SAMPLE_SIZE, "id_in": ids });
+        let mut media = api::fetch_media(variables, token)
+            .await?
+            .data
+            .page
+            .map(|p| p.media)
+            .unwrap_or_default();
+        if !media.is_empty() {
+            media.shuffle(rng);
+            return Ok(media);
+        }
+    }
+    Err(Failure::NoResults.into())
+}
+
+/// Fetches the active show's cast for the Characters screen, with the voice
+/// actors of the language being streamed.
+fn load_characters(app: &mut App, media_id: i32) -> Result<()> {
+    let language = if app.translation_type == "dub" {
+        "ENGLISH"
+    } else {
+        "JAPANESE"
+    };
+    app.action_tx.send(Action::SearchStarted)?;
+    let tx = app.action_tx.clone();
+    tokio::spawn(async move {
+        match api::fetch_characters(media_id, language).await {
+            Ok(characters) => {
+                let _ = tx.send(Action::CharactersLoaded(characters));
+            }
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Gathers the active show's franchise into a watch-order tree. Entries
+/// missing from the viewer's AniList list take their progress from the
+/// local registry.
+fn load_franchise(app: &mut App, media_id: i32) -> Result<()> {
+    let token = app.config_manager.auth.anilist_token.clone();
+    app.action_tx.send(Action::SearchStarted)?;
+    let tx = app.action_tx.clone();
+    tokio::spawn(async move {
+        match api::fetch_franchise(media_id, token.as_deref()).await {
+            Ok(shows) => {
+                let mut entries = franchise::timeline(shows, media_id);
+                if let Ok(registry) = RegistryManager::new() {
+                    for entry in entries
+                        .iter_mut()
+                        .filter(|e| e.media.media_list_entry.is_none())
+                    {
+                        if let Some(local) = registry.data.entries.get(&entry.media.id) {
+                            entry.media.media_list_entry = Some(crate::models::MediaListEntry {
+                                id: None,
+                                media_id: Some(local.id),
+                                status: Some(format!("{:?}", local.status)),
+                                progress: Some(local.progress),
+                                score: None,
+                                private: None,
+                                notes: None,
+                            });
+                        }
+                    }
+                }
+                let _ = tx.send(Action::FranchiseLoaded(entries));
+            }
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Finds a manga at the manga provider and lists its chapters. Progress
+/// comes from the viewer's AniList list, or the local registry without one.
+fn load_chapters(app: &mut App, media: Arc<crate::models::Media>) -> Result<()> {
+    let provider = MangaProvider::new(&app.config_manager.config)?;
+    app.action_tx.send(Action::SearchStarted)?;
+    let tx = app.action_tx.clone();
+    tokio::spawn(async move {
+        let title = media.preferred_title().to_string();
+        let found = async {
+            let results = provider.search(&title).await?;
+            let Some((best, _)) = choices::rank("allanime", &results, &title)
+                .into_iter()
+                .next()
+            else {
+                return Ok(None);
+            };
+            let chapters = provider.chapter_list(&best.id).await?;
+            anyhow::Ok(Some((best.id.clone(), chapters)))
+        };
+        match found.await {
+            Ok(Some((manga_id, chapters))) => {
+                let _ = tx.send(Action::ChaptersLoaded(media, manga_id, chapters));
+            }
+            Ok(None) => {
+                let _ = tx.send(Action::ChaptersLoaded(media, String::new(), Vec::new()));
+            }
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Fetches the page list of a chapter of the manga being read.
+fn load_chapter_pages(app: &mut App, chapter: usize) -> Result<()> {
+    let Some(reading) = &app.reading else {
+        return Ok(());
+    };
+    let Some(chapter_string) = reading.chapters.get(chapter).cloned() else {
+        return Ok(());
+    };
+    let manga_id = reading.manga_id.clone();
+    let provider = MangaProvider::new(&app.config_manager.config)?;
+    app.action_tx.send(Action::SearchStarted)?;
+    let tx = app.action_tx.clone();
+    tokio::spawn(async move {
+        match provider.chapter_pages(&manga_id, &chapter_string).await {
+            Ok(pages) => {
+                let _ = tx.send(Action::ChapterPagesLoaded(chapter, pages));
+            }
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Shows the selected page of the reader. Reaching the last page of a
+/// chapter counts it as read.
+fn update_page(app: &mut App) {
+    let index = app.get_selected_index();
+    let Some(reading) = &mut app.reading else {
+        return;
+    };
+    let Some(url) = reading.pages.get(index).cloned() else {
+        return;
+    };
+
+    if index + 1 == reading.pages.len()
+        && let Some(chapter) = reading.chapter_number(reading.chapter)
+        && chapter > reading.progress
+    {
+        reading.progress = chapter;
+        if !app.private {
+            let config = app.config_manager.clone();
+            let tx = app.action_tx.clone();
+            let media = reading.media.clone();
+            let guard = app.pending_work.start();
+            tokio::spawn(async move {
+                let _guard = guard;
+                sync_progress(&config, &tx, &media, chapter).await;
+            });
+        }
+    }
+
+    #[cfg(feature = "images")]
+    {
+        app.page_image = None;
+        app.ensure_image_picker();
+        let provider = match MangaProvider::new(&app.config_manager.config) {
+            Ok(provider) => provider,
+            Err(e) => {
+                let _ = app.action_tx.send(Action::SearchError(format!("{:#}", e)));
+                return;
+            }
+        };
+        app.is_fetching_image = true;
+        let tx = app.action_tx.clone();
+        let cache = app.image_cache.clone();
+        let target = app.pixel_size(app.page_area);
+        tokio::spawn(async move {
+            let bytes = match cache.get(&url) {
+                Some(bytes) => bytes,
+                None => match provider.fetch_page(&url).await {
+                    Ok(bytes) => {
+                        cache.insert(url.clone(), bytes.clone());
+                        Arc::new(bytes)
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Action::SearchError(e.to_string()));
+                        return;
+                    }
+                },
+            };
+            if let Ok(Some(img)) =
+                tokio::task::spawn_blocking(move || images::decode(&bytes, target)).await
+            {
+                let _ = tx.send(Action::MangaPageLoaded(url, img));
+            }
+        });
     }
+    #[cfg(not(feature = "images"))]
+    let _ = url;
+}
+
+/// Copies a show's note to its AniList list entry when it differs from the
+/// one there. Shows not on the list keep theirs locally rather than being
+/// added to it.
+fn sync_notes(app: &App, media: &crate::models::Media, note: String) {
+    let Some(token) = app.config_manager.auth.anilist_token.clone() else {
+        return;
+    };
+    let Some(entry) = &media.media_list_entry else {
+        return;
+    };
+    if app.private || entry.notes.as_deref().unwrap_or_default() == note {
+        return;
+    }
+    let media_id = media.id;
+    let tx = app.action_tx.clone();
+    let guard = app.pending_work.start();
+    tokio::spawn(async move {
+        let _guard = guard;
+        if let Err(e) = api::update_user_notes(&token, media_id, &note).await {
+            let _ = tx.send(Action::SearchError(
+                t!("status.notes_sync_failed", err = e).to_string(),
+            ));
+        }
+    });
+}
+
+/// Lists the shows whose registry note or tags match `query`.
+fn load_annotated(app: &mut App, query: String) -> Result<()> {
+    let ids = RegistryManager::new()
+        .map(|r| registry::search_notes(&r.data, &query))
+        .unwrap_or_default();
+    if ids.is_empty() {
+        app.status_message = Some(t!("status.no_annotated", query = query).to_string());
+        return Ok(());
+    }
+    let title = t!("titles.annotated", query = query).to_string();
+    load_registry_matches(app, ids, title)
+}
+
+/// Searches only the viewer's library: registry entries by title, tag or
+/// note first, then the shows on their AniList lists by title.
+fn search_library(app: &mut App, query: String) -> Result<()> {
+    let ids = RegistryManager::new()
+        .map(|r| registry::search_library(&r.data, &query))
+        .unwrap_or_default();
+    let title = t!("titles.library_search", query = query).to_string();
+    if !ids.is_empty() {
+        return load_registry_matches(app, ids, title);
+    }
+    let Some(token) = app.config_manager.auth.anilist_token.clone() else {
+        app.status_message = Some(t!("status.no_library_matches", query = query).to_string());
+        return Ok(());
+    };
+    app.action_tx.send(Action::SearchStarted)?;
+    let tx = app.action_tx.clone();
+    let kind = if app.manga { "MANGA" } else { "ANIME" };
+    tokio::spawn(async move {
+        let variables = serde_json::json!({
+            "search": query, "onList": true, "perPage": 50, "type": kind
+        });
+        match api::fetch_media(variables, Some(&token)).await {
+            Ok(res) => {
+                let shows = res.data.page.map(|p| p.media).unwrap_or_default();
+                let _ = if shows.is_empty() {
+                    tx.send(Action::SearchError(
+                        t!("status.no_library_matches", query = query).to_string(),
+                    ))
+                } else {
+                    tx.send(Action::SearchCompleted(shows, Some(title), None))
+                };
+            }
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Fetches the registry entries `ids` and lists them, in that order, under
+/// `title`.
+fn load_registry_matches(app: &mut App, ids: Vec<i32>, title: String) -> Result<()> {
+    let token = app.config_manager.auth.anilist_token.clone();
+    app.action_tx.send(Action::SearchStarted)?;
+    let tx = app.action_tx.clone();
+    tokio::spawn(async move {
+        let variables = serde_json::json!({ "id_in": ids, "perPage": 50 });
+        match api::fetch_media(variables, token.as_deref()).await {
+            Ok(res) => {
+                let mut shows = res.data.page.map(|p| p.media).unwrap_or_default();
+                shows.sort_by_key(|m| ids.iter().position(|&id| id == m.id));
+                let _ = tx.send(Action::SearchCompleted(shows, Some(title), None));
+            }
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Fetches the shows of a watch order, by MyAnimeList id, so they can be
+/// queued in its order.
+fn load_watch_order(app: &mut App, mal_ids: Vec<i32>) -> Result<()> {
+    let token = app.config_manager.auth.anilist_token.clone();
+    app.action_tx.send(Action::SearchStarted)?;
+    let tx = app.action_tx.clone();
+    tokio::spawn(async move {
+        let variables = serde_json::json!({ "idMal_in": mal_ids, "perPage": 50 });
+        match api::fetch_media(variables, token.as_deref()).await {
+            Ok(res) => {
+                let mut shows = res.data.page.map(|p| p.media).unwrap_or_default();
+                shows.sort_by_key(|m| mal_ids.iter().position(|&id| Some(id) == m.id_mal));
+                let _ = tx.send(Action::WatchOrderLoaded(shows));
+            }
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Looks up the selected character's voice actor in the other shows the
+/// viewer completed, going by their AniList list and the local registry.
+fn load_voice_roles(app: &mut App) -> Result<()> {
+    let Some(actor) = app
+        .characters
+        .get(app.get_selected_index())
+        .and_then(|c| c.voice_actors.first())
+        .cloned()
+    else {
+        app.status_message = Some(t!("status.no_voice_actor").to_string());
+        return Ok(());
+    };
+    let origin = app.active_media.as_ref().map_or(0, |m| m.id);
+    let token = app.config_manager.auth.anilist_token.clone();
+    let username = app.config_manager.auth.username.clone();
+    app.action_tx.send(Action::SearchStarted)?;
+    let tx = app.action_tx.clone();
+    tokio::spawn(async move {
+        let mut completed: HashSet<i32> = RegistryManager::new()
+            .map(|r| {
+                r.data
+                    .entries
+                    .values()
+                    .filter(|e| matches!(e.status, registry::WatchStatus::COMPLETED))
+                    .map(|e| e.id)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let (Some(token), Some(username)) = (&token, &username)
+            && let Ok(ids) = api::fetch_list_ids(token, username, "COMPLETED").await
+        {
+            completed.extend(ids);
+        }
+        match api::fetch_voice_roles(actor.id, token.as_deref()).await {
+            Ok(edges) => {
+                let roles = voices::completed_roles(edges, origin, &completed);
+                let _ = tx.send(Action::VoiceRolesLoaded(actor.name.to_string(), roles));
+            }
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Compares every local entry, anime and manga, with AniList.
+async fn load_conflicts(token: &str) -> Result<Vec<registry::Conflict>> {
+    let registry = RegistryManager::new()?;
+    let media = fetch_tracked_media(&registry.data, token).await?;
+    Ok(registry::find_conflicts(&registry.data, &media))
+}
+
+/// The AniList side of every registry entry, 50 shows a request.
+async fn fetch_tracked_media(
+    registry: &registry::Registry,
+    token: &str,
+) -> Result<Vec<crate::models::Media>> {
+    let mut ids: Vec<i32> = registry.entries.values().map(|e| e.id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    let mut media = Vec::new();
+    for chunk in ids.chunks(50) {
+        // Null lifts the query's ANIME default, so manga entries come back.
+        let response = api::fetch_media(
+            serde_json::json!({ "id_in": chunk, "perPage": 50, "type": null }),
+            Some(token),
+        )
+        .await?;
+        media.extend(response.data.page.map(|p| p.media).unwrap_or_default());
+    }
+    Ok(media)
+}
+
+/// Resolving overwrites progress on at least one side, so it asks first.
+fn confirm_conflict(app: &mut App, resolution: Resolution) {
+    let Some(conflict) = app.conflicts.get(app.get_selected_index()) else {
+        return;
+    };
+    let message = t!(
+        "confirm.resolve_conflict",
+        title = conflict.title,
+        ep = conflict.resolved(resolution)
+    )
+    .to_string();
+    app.confirm_then(message, Action::ResolveConflict(resolution));
+}
+
+/// Applies the chosen progress to whichever side of the selected conflict
+/// differs from it.
+fn resolve_conflict(app: &mut App, resolution: Resolution) {
+    let Some(conflict) = app.conflicts.get(app.get_selected_index()).cloned() else {
+        return;
+    };
+    let Some(token) = app.config_manager.auth.anilist_token.clone() else {
+        return;
+    };
+    let progress = conflict.resolved(resolution);
+    let private = app
+        .config_manager
+        .config
+        .anilist
+        .private_for(conflict.on_list);
+    let tx = app.action_tx.clone();
+    let work = app.pending_work.start();
+    tokio::spawn(async move {
+        let _work = work;
+        let result = async {
+            if progress != conflict.remote {
+                let status = api::progress_status(progress, conflict.total);
+                api::update_user_entry(&token, conflict.id, progress, status, private).await?;
+            }
+            RegistryManager::new()?.resolve(&conflict, progress)
+        }
+        .await;
+        let _ = tx.send(match result {
+            Ok(()) => Action::ConflictResolved(conflict.id, progress),
+            Err(e) => Action::SearchError(e.to_string()),
+        });
+    });
+}
+
+/// Sends `edit` for the organizer's selection to AniList a batch at a time,
+/// reporting progress after each. Stops at the first failed batch.
+fn start_bulk_edit(app: &mut App, edit: BulkEdit) {
+    let Some(token) = app.config_manager.auth.anilist_token.clone() else {
+        return;
+    };
+    let entries: Vec<(i32, i32)> = app
+        .organizer_targets()
+        .iter()
+        .filter_map(|m| Some((m.id, m.media_list_entry.as_ref()?.id?)))
+        .collect();
+    if entries.is_empty() {
+        return;
+    }
+    app.is_loading = true;
+    let tx = app.action_tx.clone();
+    let work = app.pending_work.start();
+    tokio::spawn(async move {
+        let _work = work;
+        let mut done = Vec::new();
+        for batch in entries.chunks(api::BULK_BATCH) {
+            if let Err(e) = api::bulk_edit(&token, edit, batch).await {
+                let _ = tx.send(Action::BulkFinished(edit, done, Some(e.to_string())));
+                return;
+            }
+            done.extend(batch.iter().map(|&(id, _)| id));
+            let _ = tx.send(Action::BulkProgress(done.len(), entries.len()));
+        }
+        let _ = tx.send(Action::BulkFinished(edit, done, None));
+    });
+}
+
+/// Mirrors a bulk edit AniList accepted in the organizer's list.
+fn apply_bulk_edit(app: &mut App, edit: BulkEdit, done: &[i32]) {
+    for id in done {
+        app.organizer_selected.remove(id);
+    }
+    // Keep the local entries from coming back as conflicts.
+    let saved = RegistryManager::new().and_then(|mut registry| {
+        match edit {
+            BulkEdit::Delete => registry.remove_entries(done)?,
+            BulkEdit::Pause => registry.set_status(done, registry::WatchStatus::PAUSED)?,
+            BulkEdit::Score(_) => {}
+        }
+        Ok(registry.data.entries)
+    });
+    match saved {
+        Ok(entries) => app.registry_entries = entries,
+        Err(e) => log::warn!("Could not update the registry after a bulk edit: {:#}", e),
+    }
+    if edit == BulkEdit::Delete {
+        app.media_list.retain(|m| !done.contains(&m.id));
+        let last = app.list_len().saturating_sub(1);
+        app.list_state
+            .select(Some(app.get_selected_index().min(last)));
+        return;
+    }
+    for media in app.media_list.iter_mut().filter(|m| done.contains(&m.id)) {
+        let media = Arc::make_mut(media);
+        if let Some(entry) = media.media_list_entry.as_mut() {
+            match edit {
+                BulkEdit::Pause => entry.status = Some("PAUSED".to_string()),
+                BulkEdit::Score(score) => entry.score = Some(score as f64),
+                BulkEdit::Delete => {}
+            }
+        }
+    }
+}
+
+/// Runs a key press through the macro layer, returning it if the usual
+/// handlers should still see it.
+fn macro_input(app: &mut App, key: KeyEvent) -> Option<KeyEvent> {
+    let macros = &app.config_manager.config.macros;
+    app.status_message = match app.macros.intercept(key, macros) {
+        Intercept::Pass(key) => return Some(key),
+        Intercept::ChooseSlot => Some(t!("status.macro_choose_slot").to_string()),
+        Intercept::Recording(slot) => Some(t!("status.macro_recording", slot = slot).to_string()),
+        Intercept::Recorded(slot, notation) => {
+            let message = if notation.is_empty() {
+                app.config_manager.config.macros.remove(&slot);
+                t!("status.macro_cleared", slot = slot)
+            } else {
+                app.config_manager
+                    .config
+                    .macros
+                    .insert(slot.clone(), notation);
+                t!("status.macro_saved", slot = slot)
+            };
+            match app.config_manager.save_config() {
+                Ok(()) => Some(message.to_string()),
+                Err(e) => Some(t!("status.macro_save_failed", err = e.to_string()).to_string()),
+            }
+        }
+        Intercept::Cancelled => Some(t!("status.macro_cancelled").to_string()),
+        Intercept::Replaying(_) => None,
+        Intercept::Unbound(slot) => Some(t!("status.macro_unbound", slot = slot).to_string()),
+    };
+    None
+}
+
+/// Rows from the end of a paged list at which the next page is requested.
+const PAGE_PREFETCH_ROWS: usize = 5;
+
+/// Fetches the next page of search, trending or popular results once the
+/// selection gets close to the bottom.
+fn load_next_page(app: &mut App) {
+    if !matches!(
+        app.list_mode,
+        ListMode::SearchResults | ListMode::AnimeList(_)
+    ) || app.get_selected_index() + PAGE_PREFETCH_ROWS < app.media_list.len()
+    {
+        return;
+    }
+    let Some(pager) = app.pager.as_mut().filter(|p| !p.loading) else {
+        return;
+    };
+    pager.loading = true;
+    let variables = pager.next_variables();
+    let tx = app.action_tx.clone();
+    let token = app.config_manager.auth.anilist_token.clone();
+    tokio::spawn(async move {
+        let page = api::fetch_media(variables, token.as_deref())
+            .await
+            .ok()
+            .and_then(|res| res.data.page);
+        let _ = tx.send(match page {
+            Some(p) => Action::PageLoaded(p.media, Some(p.page_info)),
+            None => Action::PageLoaded(Vec::new(), None),
+        });
+    });
+}
+
+/// Opens Trending, Popular or Manga. The copy cached from last time shows
+/// straight away when there is one, and the fresh list replaces it in place
+/// once it arrives; without one the list loads as usual. Lists carry the
+/// viewer's list entries, so each AniList account has its own copy.
+fn open_ranked_list(
+    app: &mut App,
+    key: &'static str,
+    title: String,
+    variables: serde_json::Value,
+) -> Result<()> {
+    let auth = &app.config_manager.auth;
+    let key = match (&auth.anilist_token, &auth.username) {
+        (Some(_), Some(username)) => format!("{}@{}", key, username),
+        _ => key.to_string(),
+    };
+    let cached = ListCacheManager::new()
+        .ok()
+        .and_then(|mut cache| cache.data.lists.remove(&key));
+    let stale = cached.is_some();
+    if stale {
+        app.perf.list_cache_hits += 1;
+    } else {
+        app.perf.list_cache_misses += 1;
+    }
+    match cached {
+        Some(list) => {
+            let pager = MediaPager::after(variables.clone(), &list.page_info);
+            app.action_tx.send(Action::SearchCompleted(
+                list.media,
+                Some(title.clone()),
+                pager,
+            ))?;
+        }
+        None => app.action_tx.send(Action::SearchStarted)?,
+    }
+    let tx = app.action_tx.clone();
+    let token = app.config_manager.auth.anilist_token.clone();
+    tokio::spawn(async move {
+        match api::fetch_media(variables.clone(), token.as_deref()).await {
+            Ok(res) => {
+                if let Some(p) = res.data.page {
+                    if let Ok(mut cache) = ListCacheManager::new() {
+                        let _ = cache.store(&key, &p);
+                    }
+                    let pager = MediaPager::after(variables, &p.page_info);
+                    let _ = tx.send(if stale {
+                        Action::ListRefreshed(title, p.media, pager)
+                    } else {
+                        Action::SearchCompleted(p.media, Some(title), pager)
+                    });
+                }
+            }
+            Err(e) if stale => log::debug!("Couldn't refresh {}: {:#}", key, e),
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
+            }
+        }
+    });
+    Ok(())
 }
 
 fn handle_selection(app: &mut App) -> Result<()> {
@@ -340,46 +2312,126 @@ fn handle_selection(app: &mut App) -> Result<()> {
             let idx = app.get_selected_index();
             if idx < app.main_menu_items.len() {
                 let item = &app.main_menu_items[idx];
+                app.manga = item == &t!("main_menu.manga");
                 if item == &t!("main_menu.exit") {
-                    app.running = false;
+                    app.action_tx.send(Action::Quit)?;
                 } else if item == &t!("main_menu.trending") {
+                    let variables = serde_json::json!({
+                        "perPage": 20,
+                        "sort": "TRENDING_DESC",
+                        "slim": true
+                    });
+                    open_ranked_list(
+                        app,
+                        "trending",
+                        t!("main_menu.trending").to_string(),
+                        variables,
+                    )?;
+                } else if item == &t!("main_menu.popular") {
+                    let variables = serde_json::json!({
+                        "perPage": 20,
+                        "sort": "POPULARITY_DESC",
+                        "slim": true
+                    });
+                    open_ranked_list(
+                        app,
+                        "popular",
+                        t!("main_menu.popular").to_string(),
+                        variables,
+                    )?;
+                } else if item == &t!("main_menu.random") {
                     app.action_tx.send(Action::SearchStarted)?;
                     let tx = app.action_tx.clone();
+                    let token = app.config_manager.auth.anilist_token.clone();
+                    // The task gets its own generator, seeded from the
+                    // session's, so seeded sessions stay reproducible.
+                    let mut rng = StdRng::seed_from_u64(app.random.r#gen());
                     tokio::spawn(async move {
-                        match api::fetch_media(
-                            serde_json::json!({ "perPage": 20, "sort": "TRENDING_DESC" }),
-                        )
-                        .await
-                        {
-                            Ok(res) => {
-                                if let Some(p) = res.data.page {
-                                    let _ = tx.send(Action::SearchCompleted(
-                                        p.media,
-                                        Some(t!("main_menu.trending").to_string()),
-                                    ));
-                                }
+                        match fetch_random(&mut rng, token.as_deref()).await {
+                            Ok(media) => {
+                                let _ = tx.send(Action::SearchCompleted(
+                                    media,
+                                    Some(t!("main_menu.random").to_string()),
+                                    None,
+                                ));
                             }
                             Err(e) => {
                                 let _ = tx.send(Action::SearchError(e.to_string()));
                             }
                         }
                     });
-                } else if item == &t!("main_menu.popular") {
+                } else if item == &t!("main_menu.manga") {
+                    let variables = serde_json::json!({
+                        "perPage": 20,
+                        "sort": "TRENDING_DESC",
+                        "type": "MANGA",
+                        "slim": true
+                    });
+                    open_ranked_list(app, "manga", t!("main_menu.manga").to_string(), variables)?;
+                } else if cfg!(feature = "downloads") && item == &t!("main_menu.downloads") {
+                    #[cfg(feature = "downloads")]
+                    {
+                        app.downloads = DownloadManager::new(&app.config_manager.config)
+                            .map(|m| m.manifest.entries)
+                            .unwrap_or_default();
+                        app.go_to_mode(ListMode::Downloads, true);
+                    }
+                } else if item == &t!("main_menu.backlog") {
+                    let auth = &app.config_manager.auth;
+                    let (Some(token), Some(username)) =
+                        (auth.anilist_token.clone(), auth.username.clone())
+                    else {
+                        app.status_message = Some(t!("status.login_required").to_string());
+                        return Ok(());
+                    };
                     app.action_tx.send(Action::SearchStarted)?;
                     let tx = app.action_tx.clone();
                     tokio::spawn(async move {
-                        match api::fetch_media(
-                            serde_json::json!({ "perPage": 20, "sort": "POPULARITY_DESC" }),
-                        )
-                        .await
-                        {
-                            Ok(res) => {
-                                if let Some(p) = res.data.page {
-                                    let _ = tx.send(Action::SearchCompleted(
-                                        p.media,
-                                        Some(t!("main_menu.popular").to_string()),
-                                    ));
-                                }
+                        match api::fetch_user_list(&token, &username, "PLANNING").await {
+                            Ok(media) => {
+                                let _ = tx.send(Action::BacklogLoaded(media));
+                            }
+                            Err(e) => {
+                                let _ = tx.send(Action::SearchError(e.to_string()));
+                            }
+                        }
+                    });
+                } else if item == &t!("main_menu.queue") {
+                    app.queue = QueueManager::new()
+                        .map(|m| m.data.entries)
+                        .unwrap_or_default();
+                    app.go_to_mode(ListMode::Queue, true);
+                } else if item == &t!("main_menu.conflicts") {
+                    let Some(token) = app.config_manager.auth.anilist_token.clone() else {
+                        app.status_message = Some(t!("status.login_required").to_string());
+                        return Ok(());
+                    };
+                    app.action_tx.send(Action::SearchStarted)?;
+                    let tx = app.action_tx.clone();
+                    tokio::spawn(async move {
+                        match load_conflicts(&token).await {
+                            Ok(conflicts) => {
+                                let _ = tx.send(Action::ConflictsLoaded(conflicts));
+                            }
+                            Err(e) => {
+                                let _ = tx.send(Action::SearchError(e.to_string()));
+                            }
+                        }
+                    });
+                } else if item == &t!("main_menu.organizer") {
+                    let auth = &app.config_manager.auth;
+                    let (Some(token), Some(username)) =
+                        (auth.anilist_token.clone(), auth.username.clone())
+                    else {
+                        app.status_message = Some(t!("status.login_required").to_string());
+                        return Ok(());
+                    };
+                    app.action_tx.send(Action::SearchStarted)?;
+                    let tx = app.action_tx.clone();
+                    tokio::spawn(async move {
+                        match api::fetch_library(&token, &username).await {
+                            Ok(media) => {
+                                let _ = tx.send(Action::LibraryLoaded(media));
                             }
                             Err(e) => {
                                 let _ = tx.send(Action::SearchError(e.to_string()));
@@ -387,15 +2439,24 @@ fn handle_selection(app: &mut App) -> Result<()> {
                         }
                     });
                 } else if item == &t!("main_menu.options") {
+                    app.provider_health = HealthManager::new().ok();
                     app.go_to_mode(ListMode::Options, true);
                 }
             }
         }
-        ListMode::SearchResults | ListMode::AnimeList(_) => {
+        ListMode::SearchResults
+        | ListMode::AnimeList(_)
+        | ListMode::Backlog
+        | ListMode::Organizer => {
             let idx = app.get_selected_index();
             if idx < app.media_list.len() {
-                app.active_media = Some(app.media_list[idx].clone());
-                app.go_to_mode(ListMode::AnimeActions, true);
+                let media = app.media_list[idx].clone();
+                if media.is_manga() {
+                    load_chapters(app, media)?;
+                } else {
+                    app.active_media = Some(media);
+                    app.go_to_mode(ListMode::AnimeActions, true);
+                }
             }
         }
         ListMode::AnimeActions => {
@@ -404,19 +2465,109 @@ fn handle_selection(app: &mut App) -> Result<()> {
                 let action = &app.anime_action_items[idx];
                 if action == &t!("actions.stream") {
                     if let Some(media) = app.active_media.clone() {
-                        start_stream_task(app, media, None);
+                        let weeks = app.config_manager.config.stream.recap_after_weeks;
+                        app.recap = HistoryManager::new().ok().and_then(|h| {
+                            h.data
+                                .stale_session(media.id, weeks, chrono::Utc::now())
+                                .cloned()
+                        });
+                        match app.recap.as_ref().map(|s| s.episode) {
+                            Some(last) => {
+                                app.go_to_mode(ListMode::Recap, true);
+                                for episode in (last - RECAP_EPISODES + 1).max(1)..=last {
+                                    load_episode_details(app, episode);
+                                }
+                            }
+                            None => start_stream_task(app, media, None),
+                        }
+                    }
+                } else if action == &t!("actions.play_all") {
+                    if let Some(media) = app.active_media.clone() {
+                        queue_remaining(app, &media)?;
                     }
                 } else if action == &t!("actions.episodes") {
+                    app.episodes_selected.clear();
                     app.go_to_mode(ListMode::EpisodeSelect, true);
+                    update_preview(app);
+                } else if action == &t!("actions.characters") {
+                    if let Some(media) = app.active_media.clone() {
+                        load_characters(app, media.id)?;
+                    }
+                } else if action == &t!("actions.notes") {
+                    if let Some(media) = app.active_media.clone() {
+                        let entry = app.registry_entries.get(&media.id);
+                        // What's on AniList wins over the local copy, which
+                        // may predate an edit made on the website.
+                        let remote = media
+                            .media_list_entry
+                            .as_ref()
+                            .and_then(|e| e.notes.clone())
+                            .filter(|n| !n.is_empty());
+                        app.note_editor = Some(NoteEditor {
+                            note: remote
+                                .or_else(|| entry.and_then(|e| e.note.clone()))
+                                .unwrap_or_default(),
+                            tags: entry.map(|e| e.tags.join(", ")).unwrap_or_default(),
+                            editing_tags: false,
+                            media,
+                        });
+                    }
+                } else if action == &t!("actions.related") {
+                    if let Some(media) = app.active_media.clone() {
+                        load_franchise(app, media.id)?;
+                    }
                 } else {
                     app.go_to_mode(ListMode::SubMenu(action.clone()), true);
                 }
             }
         }
+        ListMode::Recap => {
+            if let Some(media) = app.active_media.clone() {
+                start_stream_task(app, media, None);
+            }
+        }
+        ListMode::Characters => load_voice_roles(app)?,
+        ListMode::Chapters => load_chapter_pages(app, app.get_selected_index())?,
+        ListMode::Reader => {
+            if let Some(reading) = &app.reading {
+                let next = reading.chapter + 1;
+                if next < reading.chapters.len() {
+                    load_chapter_pages(app, next)?;
+                } else {
+                    app.status_message = Some(t!("status.last_chapter").to_string());
+                }
+            }
+        }
+        ListMode::Franchise => {
+            let index = app.get_selected_index();
+            if let Some(order) = app.watch_orders.get(index) {
+                load_watch_order(app, order.mal_ids())?;
+            } else if let Some(entry) = app.franchise.get(index - app.watch_orders.len()) {
+                let media = Arc::new(entry.media.clone());
+                app.go_to_mode(ListMode::AnimeActions, true);
+                app.active_media = Some(media);
+                app.clear_cover();
+                update_preview(app);
+            }
+        }
+        ListMode::VoiceRoles(_) => {
+            if let Some(role) = app.voice_roles.get(app.get_selected_index()) {
+                let media = Arc::new(role.media.clone());
+                app.go_to_mode(ListMode::AnimeActions, true);
+                app.active_media = Some(media);
+                app.clear_cover();
+                update_preview(app);
+            }
+        }
+        ListMode::Queue => app.action_tx.send(Action::PlayQueue)?,
         ListMode::EpisodeSelect => {
             let ep_num = (app.get_selected_index() + 1).to_string();
             if let Some(media) = app.active_media.clone() {
-                start_stream_task(app, media, Some(ep_num));
+                if app.episodes_selected.is_empty() {
+                    start_stream_task(app, media, Some(ep_num));
+                } else {
+                    queue_episodes(app, &media)?;
+                }
             }
         }
         ListMode::Options => {
@@ -436,6 +2587,7 @@ fn handle_selection(app: &mut App) -> Result<()> {
                     if let Some(pos) = types.iter().position(|&t| t == current) {
                         let next = (pos + 1) % types.len();
                         app.config_manager.config.stream.translation_type = types[next].to_string();
+                        app.translation_type = types[next].to_string();
                     }
                 }
                 2 => {
@@ -448,6 +2600,15 @@ fn handle_selection(app: &mut App) -> Result<()> {
                         app.update_localized_items();
                     }
                 }
+                3 => {
+                    let keys = provider::keys();
+                    let current = app.config_manager.config.general.provider.as_str();
+                    let next = keys
+                        .iter()
+                        .position(|&k| k == current)
+                        .map_or(0, |pos| (pos + 1) % keys.len());
+                    app.config_manager.config.general.provider = keys[next].to_string();
+                }
                 _ => {}
             }
             app.config_manager.save_config()?;
@@ -457,36 +2618,1092 @@ fn handle_selection(app: &mut App) -> Result<()> {
     Ok(())
 }
 
+/// Resolves a playable stream for one episode. `display_title` is the name
+/// shown to the user (the AniList preferred title where one is known), not
+/// the provider's internal show name.
 async fn resolve_stream_for_episode(
-    provider: &AllAnimeProvider,
+    provider: &AnyProvider,
     show_id: &str,
-    show_name: &str,
+    display_title: &str,
     episode: &str,
+    episode_offset: i32,
 ) -> Result<Option<PlayOptions>> {
-    let sources = provider.get_episode_sources(show_id, episode).await?;
-    let priorities = ["S-mp4", "Luf-mp4", "Luf-Mp4", "Sak", "Default", "Yt-mp4"];
+    let provider_episode = numbering::provider_episode(episode, episode_offset);
+    let sources = provider
+        .get_episode_sources(show_id, &provider_episode)
+        .await?;
+    let Some(mut options) = provider.extract_first(&sources).await else {
+        return Ok(None);
+    };
+    options.title = Some(window_title(
+        display_title,
+        episode,
+        provider.translation_type(),
+    ));
+    options.show_title = Some(display_title.to_string());
+    options.episode = Some(episode.to_string());
+    Ok(Some(options))
+}
+
+/// Stands in for a pinned show the provider's search didn't return. The
+/// episode count comes from the provider's list, or from AniList without one.
+async fn pinned_show(
+    provider: &AnyProvider,
+    show_id: &str,
+    media: &crate::models::Media,
+) -> ShowEdge {
+    let listed = provider
+        .episode_list(show_id)
+        .await
+        .unwrap_or_default()
+        .len();
+    let count = if listed > 0 {
+        listed
+    } else {
+        media
+            .aired_episodes()
+            .or(media.episodes)
+            .unwrap_or(0)
+            .max(0) as usize
+    };
+    ShowEdge {
+        id: show_id.to_string(),
+        name: media.preferred_title().to_string(),
+        available_episodes: AvailableEpisodes {
+            sub: count,
+            dub: count,
+            raw: count,
+        },
+    }
+}
+
+/// Opening/ending chapters for an episode, or none when turned off, the
+/// show has no MAL id or AniSkip doesn't know the episode.
+async fn episode_chapters(enabled: bool, mal_id: Option<i32>, episode: &str) -> Vec<Chapter> {
+    let Some(mal_id) = mal_id.filter(|_| enabled) else {
+        return Vec::new();
+    };
+    match api::fetch_skip_times(mal_id, episode).await {
+        Ok(times) => chapters::from_skip_times(&times),
+        Err(e) => {
+            log::debug!("No AniSkip chapters for episode {}: {:#}", episode, e);
+            Vec::new()
+        }
+    }
+}
+
+fn best_provider_match<'a>(
+    provider: &AnyProvider,
+    results: &'a [ShowEdge],
+    query: &str,
+) -> Option<&'a ShowEdge> {
+    choices::rank(provider.key(), results, query)
+        .first()
+        .map(|(show, _)| *show)
+}
+
+/// The show a command-line search means. When several results look alike
+/// the user picks one on the terminal, and the pick is remembered. With an
+/// external picker every search with more than one result is put to it.
+fn choose_show<'a>(
+    provider: &AnyProvider,
+    picker: &PickerConfig,
+    results: &'a [ShowEdge],
+    query: &str,
+) -> Result<&'a ShowEdge> {
+    if picker.mode == PickerMode::External && results.len() > 1 {
+        let ranked: Vec<&ShowEdge> = choices::rank(provider.key(), results, query)
+            .into_iter()
+            .map(|(show, _)| show)
+            .collect();
+        let names: Vec<String> = ranked.iter().map(|show| show.name.clone()).collect();
+        return match picker::pick(picker, &t!("picker.show"), &names)? {
+            Some(i) => Ok(ranked[i]),
+            None => Err(Failure::NoResults).context("Nothing picked"),
+        };
+    }
+    let mut choices = ShowChoices::new().ok();
+    let remembered = choices.as_ref().and_then(|c| c.get(provider.key(), query));
+    let candidates = match choices::match_show(provider.key(), results, query, remembered) {
+        Some(ShowMatch::Found(show)) => return Ok(show),
+        Some(ShowMatch::Ambiguous(candidates)) => candidates,
+        None => return Err(Failure::NoResults.into()),
+    };
+    if !output::interactive() {
+        return Ok(candidates[0]);
+    }
+
+    say!("❓ Several shows match '{}':", query);
+    for (i, show) in candidates.iter().enumerate() {
+        say!("   {}. {}", i + 1, show.name);
+    }
+    let show = loop {
+        print!("Pick one [1]: ");
+        io::stdout().flush().unwrap_or(());
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).is_err() {
+            break candidates[0];
+        }
+        match input.trim() {
+            "" => break candidates[0],
+            n => match n.parse::<usize>() {
+                Ok(n) if (1..=candidates.len()).contains(&n) => break candidates[n - 1],
+                _ => say!("❌ Enter a number from 1 to {}.", candidates.len()),
+            },
+        }
+    };
+    if let Some(choices) = &mut choices {
+        let _ = choices.remember(provider.key(), query, &show.id);
+    }
+    Ok(show)
+}
+
+/// The show a stream search means. Look-alikes are put to the viewer in a
+/// popup and the pick is remembered; `None` without results or when the
+/// viewer backs out.
+async fn pick_show<'a>(
+    tx: &mpsc::UnboundedSender<Action>,
+    provider: &AnyProvider,
+    results: &'a [ShowEdge],
+    query: &str,
+) -> Option<&'a ShowEdge> {
+    let mut choices = ShowChoices::new().ok();
+    let remembered = choices.as_ref().and_then(|c| c.get(provider.key(), query));
+    let candidates = match choices::match_show(provider.key(), results, query, remembered)? {
+        ShowMatch::Found(show) => return Some(show),
+        ShowMatch::Ambiguous(candidates) => candidates,
+    };
+
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
+    let names = candidates.iter().map(|s| s.name.clone()).collect();
+    let _ = tx.send(Action::ChooseShow(query.to_string(), names, reply_tx));
+    let Some(show) = reply_rx
+        .recv()
+        .await
+        .flatten()
+        .and_then(|i| candidates.get(i).copied())
+    else {
+        let _ = tx.send(Action::StreamLog(
+            t!("logs.show_choice_cancelled").to_string(),
+        ));
+        return None;
+    };
+    if let Some(choices) = &mut choices {
+        let _ = choices.remember(provider.key(), query, &show.id);
+    }
+    Some(show)
+}
+
+/// Plays (or downloads) each item in turn. Episodes watched past
+/// `episode_complete_at` are tracked as in the TUI; stopping one earlier
+/// ends the run, like quitting the Watch Queue.
+/// With `pick`, the single item's episode is put to the external picker.
+async fn run_watch(
+    config: &ConfigManager,
+    mut items: Vec<BatchItem>,
+    pick: bool,
+    download: bool,
+    private: bool,
+    audio_only: bool,
+) -> Result<()> {
+    if download && !cfg!(feature = "downloads") {
+        anyhow::bail!("This build of ani-l can't download");
+    }
+    let provider = AnyProvider::new(
+        config.config.stream.translation_type.clone(),
+        &config.config,
+    )?;
+    // A range is one show: search, pick and look it up on AniList once.
+    let mut targets: HashMap<String, WatchTarget> = HashMap::new();
+    if pick && config.config.picker.mode == PickerMode::External {
+        let target = find_target(config, &provider, &items[0].title).await?;
+        items[0].episode =
+            choose_episode(&provider, &config.config.picker, &target.show, None).await?;
+        targets.insert(items[0].title.clone(), target);
+    }
+    let mut failed = 0;
+    for (i, item) in items.iter().enumerate() {
+        if items.len() > 1 {
+            say!(
+                "📋 [{}/{}] {} episode {}",
+                i + 1,
+                items.len(),
+                item.title,
+                item.episode
+            );
+        }
+        let target = match targets.entry(item.title.clone()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => find_target(config, &provider, &item.title)
+                .await
+                .map(|target| entry.insert(target)),
+        };
+        let watched = match target {
+            Ok(target) => {
+                watch_one(
+                    config, &provider, target, item, download, private, audio_only,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match watched {
+            Ok(true) => {}
+            Ok(false) => {
+                let left = items.len() - i - 1;
+                if left > 0 {
+                    say!("⏹️  Stopped early; skipping the {} left", left);
+                }
+                break;
+            }
+            Err(e) if items.len() == 1 => return Err(e),
+            Err(e) => {
+                say_err!("❌ {} episode {}: {:#}", item.title, item.episode, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow::Error::new(Failure::Provider).context(format!(
+            "{} of {} episode(s) failed",
+            failed,
+            items.len()
+        )));
+    }
+    Ok(())
+}
+
+/// A batch title's show at the provider, and its AniList entry once an
+/// episode of it has been watched.
+struct WatchTarget {
+    show: ShowEdge,
+    media: tokio::sync::OnceCell<Option<crate::models::Media>>,
+}
+
+async fn find_target(
+    config: &ConfigManager,
+    provider: &AnyProvider,
+    title: &str,
+) -> Result<WatchTarget> {
+    say!("🔍 Searching for '{}'...", title);
+    let results = provider.search(title).await.context(Failure::Provider)?;
+    let show = choose_show(provider, &config.config.picker, &results, title)?.clone();
+    say!("📺 Found: {} (ID: {})", show.name, show.id);
+    Ok(WatchTarget {
+        show,
+        media: tokio::sync::OnceCell::new(),
+    })
+}
+
+/// `Ok(false)` when playback stopped before `episode_complete_at`.
+async fn watch_one(
+    config: &ConfigManager,
+    provider: &AnyProvider,
+    target: &WatchTarget,
+    item: &BatchItem,
+    download: bool,
+    private: bool,
+    audio_only: bool,
+) -> Result<bool> {
+    let show = &target.show;
+    let options = resolve_stream_for_episode(provider, &show.id, &show.name, &item.episode, 0)
+        .await
+        .context(Failure::Provider)?
+        .ok_or(Failure::NoResults)
+        .context("No stream found")?;
+
+    #[cfg(feature = "downloads")]
+    if download {
+        let mut manager = DownloadManager::new(&config.config)?;
+        let id = manager.enqueue(
+            &show.name,
+            &show.id,
+            &item.episode,
+            provider.translation_type(),
+            &options,
+        )?;
+        let downloaded = download_with_refresh(&mut manager, &config.config, &id).await;
+        provider.release_streams().await;
+        downloaded?;
+        say!("✅ Download complete.");
+        return Ok(true);
+    }
+    #[cfg(not(feature = "downloads"))]
+    let _ = download;
+
+    let choice = player::choose_player(&config.config.stream)?;
+    if let Some(missing) = &choice.replaced {
+        say!(
+            "⚠️  {} is not installed, using {} instead",
+            missing,
+            choice.name
+        );
+    }
+    let mut player = choice.player;
+    if audio_only {
+        player.set_audio_only();
+    }
+    let played = player.play(options, None).await;
+    provider.release_streams().await;
+    let percentage = played.context(Failure::Player)?;
+    say!("✅ Finished at {:.1}%", percentage);
+
+    let complete = percentage >= config.config.stream.episode_complete_at as f64;
+    if complete
+        && !private
+        && !audio_only
+        && let Some(episode) = numbering::progress_episode(&item.episode)
+    {
+        let media = target
+            .media
+            .get_or_init(|| async {
+                anilist_media_for(config, provider.key(), show)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::debug!("AniList lookup for {} failed: {:#}", show.name, e);
+                        None
+                    })
+            })
+            .await;
+        track_watched(config, &show.name, media.as_ref(), episode, percentage).await;
+    }
+    Ok(complete)
+}
+
+/// The AniList entry of a provider show: the one pinned to it in the
+/// registry, or else the only search result titled exactly like it. `None`
+/// when nothing matches that surely, rather than crediting a sequel.
+async fn anilist_media_for(
+    config: &ConfigManager,
+    provider_key: &str,
+    show: &ShowEdge,
+) -> Result<Option<crate::models::Media>> {
+    let token = config.auth.anilist_token.as_deref();
+    let pinned = RegistryManager::new().ok().and_then(|r| {
+        r.data
+            .pins
+            .iter()
+            .find(|(_, pin)| {
+                pin.provider == provider_key && pin.show_id.as_deref() == Some(&show.id)
+            })
+            .map(|(&id, _)| id)
+    });
+    if let Some(id) = pinned {
+        let res = api::fetch_media(serde_json::json!({ "id_in": [id] }), token).await?;
+        return Ok(res.data.page.and_then(|p| p.media.into_iter().next()));
+    }
+
+    let name = normalizer::normalize(provider_key, &show.name).to_lowercase();
+    let variables = serde_json::json!({ "perPage": 10, "search": name });
+    let res = api::fetch_media(variables, token).await?;
+    let mut exact = res
+        .data
+        .page
+        .map(|p| p.media)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|m| {
+            [&m.title.romaji, &m.title.english, &m.title.native]
+                .into_iter()
+                .flatten()
+                .any(|title| title.to_lowercase() == name)
+        });
+    Ok(match (exact.next(), exact.next()) {
+        (Some(media), None) => Some(media),
+        _ => None,
+    })
+}
+
+/// Records a command-line watch in the history, registry and AniList, the
+/// way the TUI does after playback.
+async fn track_watched(
+    config: &ConfigManager,
+    name: &str,
+    media: Option<&crate::models::Media>,
+    episode: i32,
+    percentage: f64,
+) {
+    let Some(media) = media else {
+        say!(
+            "⚠️  Couldn't tell which AniList entry {} is; progress not saved",
+            name
+        );
+        return;
+    };
+    if let Ok(mut history) = HistoryManager::new() {
+        let _ = history.record(WatchSession {
+            media_id: media.id,
+            title: media.preferred_title().to_string(),
+            episode,
+            minutes: media.duration.unwrap_or(24) as f64 * percentage.min(100.0) / 100.0,
+            watched_at: chrono::Utc::now(),
+        });
+    }
+    // Same sync as the TUI, with its log lines printed instead.
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    sync_progress(config, &tx, media, episode).await;
+    drop(tx);
+    while let Some(action) = rx.recv().await {
+        if let Action::StreamLog(line) = action {
+            say!("   {}", line);
+        }
+    }
+}
+
+/// Progress goes to stderr so the playlist itself can be piped.
+async fn run_playlist(
+    config: &Config,
+    query: &str,
+    episodes: &str,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let episodes = playlist::parse_episodes(episodes)?;
+    let provider = AnyProvider::new(config.stream.translation_type.clone(), config)?;
+
+    say_err!("🔍 Searching for '{}'...", query);
+    let results = provider.search(query).await.context(Failure::Provider)?;
+    let show = choose_show(&provider, &config.picker, &results, query)?;
+    say_err!("📺 Found: {} (ID: {})", show.name, show.id);
+
+    let mut entries = Vec::new();
+    for episode in episodes {
+        let episode = episode.to_string();
+        match resolve_stream_for_episode(&provider, &show.id, &show.name, &episode, 0).await {
+            Ok(Some(options)) => entries.push(options),
+            Ok(None) => say_err!("⚠️  No stream found for episode {}", episode),
+            Err(e) => say_err!("⚠️  Episode {}: {:#}", episode, e),
+        }
+    }
+    if entries.is_empty() {
+        return Err(Failure::NoResults).context("No stream found");
+    }
+
+    let m3u = playlist::render(&entries);
+    match output {
+        Some(path) => {
+            std::fs::write(&path, m3u)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            say_err!("✅ Wrote {} episodes to {}", entries.len(), path.display());
+        }
+        None => print!("{}", m3u),
+    }
+    Ok(())
+}
+
+/// Shows or changes the provider a show is pinned to.
+async fn run_pin(
+    config: &ConfigManager,
+    query: &str,
+    provider: Option<String>,
+    show_id: Option<String>,
+    clear: bool,
+) -> Result<()> {
+    let variables = match query.parse::<i32>() {
+        Ok(id) => serde_json::json!({ "perPage": 1, "id_in": [id] }),
+        Err(_) => serde_json::json!({ "perPage": 1, "search": query }),
+    };
+    let media = api::fetch_media(variables, config.auth.anilist_token.as_deref())
+        .await?
+        .data
+        .page
+        .and_then(|p| p.media.into_iter().next())
+        .ok_or(Failure::NoResults)
+        .with_context(|| format!("No AniList show matches '{}'", query))?;
+    let title = media.preferred_title();
+    let mut registry = RegistryManager::new()?;
+
+    if clear {
+        registry.pin(media.id, None)?;
+        say!("✅ {} uses {} again", title, config.config.general.provider);
+    } else if let Some(provider) = provider {
+        // Turns away unknown keys.
+        let mut pinned = config.config.clone();
+        pinned.general.provider = provider.clone();
+        AnyProvider::new(String::new(), &pinned)?;
+        say!("📌 {} now plays from {}", title, provider);
+        registry.pin(media.id, Some(ProviderPin { provider, show_id }))?;
+    } else {
+        match registry.get_pin(media.id) {
+            Some(ProviderPin {
+                provider,
+                show_id: Some(show_id),
+            }) => say!("📌 {} plays from {} (show {})", title, provider, show_id),
+            Some(pin) => say!("📌 {} plays from {}", title, pin.provider),
+            None => say!(
+                "{} isn't pinned and uses {}",
+                title,
+                config.config.general.provider
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Brings the registry and AniList together, never lowering either side.
+/// Each change is printed with its reason before it is made.
+async fn run_sync(config: &ConfigManager, dry_run: bool, verbose: bool) -> Result<()> {
+    use registry::SyncAction;
+
+    let Some(token) = &config.auth.anilist_token else {
+        return Err(anyhow::Error::new(Failure::Auth)
+            .context("Syncing needs your AniList list; log in first: ani-l auth"));
+    };
+    let mut registry = RegistryManager::new()?;
+    let remote = fetch_tracked_media(&registry.data, token).await?;
+
+    let steps = registry::plan_sync(&registry.data, &remote);
+    let mut changed = 0;
+    for step in &steps {
+        let (label, reason) = match step.action {
+            SyncAction::Push => ("push", "watched in ani-l, not on AniList yet"),
+            SyncAction::Pull => ("pull", "AniList is further along"),
+            SyncAction::MarkSynced => ("mark", "already equal, clearing the pending flag"),
+            SyncAction::Skip => (
+                "skip",
+                "AniList went back; resolve it in the Sync Conflicts screen",
+            ),
+            SyncAction::UpToDate => ("ok", "in sync"),
+        };
+        let quiet = matches!(step.action, SyncAction::Skip | SyncAction::UpToDate);
+        if quiet && !verbose {
+            continue;
+        }
+        say!(
+            "{:<5} {} (AniList {}; ani-l E{}, AniList E{}): {}",
+            label,
+            step.title,
+            step.id,
+            step.local,
+            step.remote,
+            reason
+        );
+        if dry_run || quiet {
+            continue;
+        }
+        match step.action {
+            SyncAction::Push => {
+                let private = config.config.anilist.private_for(step.on_list);
+                let total = remote
+                    .iter()
+                    .find(|m| m.id == step.id)
+                    .and_then(|m| m.episodes.or(m.chapters))
+                    .or_else(|| registry.get_entry(step.id)?.total_episodes);
+                let status = api::progress_status(step.local, total);
+                api::update_user_entry(token, step.id, step.local, status, private).await?;
+                registry.mark_synced(step.id)?;
+            }
+            SyncAction::Pull => registry.set_synced(step.id, &step.title, step.remote)?,
+            SyncAction::MarkSynced => registry.mark_synced(step.id)?,
+            SyncAction::Skip | SyncAction::UpToDate => {}
+        }
+        changed += 1;
+    }
+    let pending = steps
+        .iter()
+        .filter(|s| !matches!(s.action, SyncAction::Skip | SyncAction::UpToDate))
+        .count();
+    if dry_run {
+        say!(
+            "Dry run: {} of {} show(s) would change",
+            pending,
+            steps.len()
+        );
+    } else {
+        say!("✅ Synced {} of {} show(s)", changed, steps.len());
+    }
+    Ok(())
+}
+
+/// Saves the local files to an archive, or puts an archive's files back
+/// after showing what it holds.
+fn run_backup(action: BackupAction) -> Result<()> {
+    match action {
+        BackupAction::Create { file, include_auth } => {
+            let files = backup::create(&file, include_auth)?;
+            say!("✅ Saved {} to {}", files.join(", "), file.display());
+        }
+        BackupAction::Restore {
+            file,
+            include_auth,
+            yes,
+        } => {
+            let archive = backup::read(&file)?;
+            say!(
+                "📦 Backup from {} (ani-l {}): {}",
+                archive
+                    .created
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M"),
+                archive.app_version,
+                archive.files.keys().cloned().collect::<Vec<_>>().join(", ")
+            );
+            if !yes {
+                if !output::interactive() {
+                    say!("Run `ani-l backup restore --yes` to overwrite the local files");
+                    return Ok(());
+                }
+                print!("Overwrite the local files with these? [y/N]: ");
+                io::stdout().flush().unwrap_or(());
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+                    return Ok(());
+                }
+            }
+            let files = backup::restore(&archive, &backup::Dirs::new()?, include_auth)?;
+            say!("✅ Restored {}", files.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Lists the shows filed twice in the registry and merges them once the
+/// user agrees (or straight away with `--yes`).
+fn run_dedupe(yes: bool) -> Result<()> {
+    let mut registry = RegistryManager::new()?;
+    let duplicates = registry::find_duplicates(&registry.data);
+    if duplicates.is_empty() {
+        say!("✅ Every show is in the registry once");
+        return Ok(());
+    }
+    for duplicate in &duplicates {
+        let titles: Vec<&str> = duplicate
+            .keys
+            .iter()
+            .map(|key| registry.data.entries[key].title.as_str())
+            .collect();
+        say!("🔁 AniList {}: {}", duplicate.id, titles.join(" / "));
+    }
+    if !yes {
+        if !output::interactive() {
+            say!("Run `ani-l dedupe --yes` to merge them");
+            return Ok(());
+        }
+        print!("Merge them, keeping the first title? [y/N]: ");
+        io::stdout().flush().unwrap_or(());
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            return Ok(());
+        }
+    }
+    registry.merge_duplicates(&duplicates)?;
+    say!("✅ Merged {} show(s)", duplicates.len());
+    Ok(())
+}
+
+/// Probes every configured provider and records the results, failing when
+/// the chosen one is unreachable.
+async fn run_doctor(config: &Config) -> Result<()> {
+    let mut health = HealthManager::new()?;
+    let mut chosen_down = false;
+    for key in health::configured_providers(config) {
+        let result = health::probe(config, &key).await;
+        let stream = match result.extractor {
+            Some(true) => ", stream ok",
+            Some(false) => ", no stream",
+            None => "",
+        };
+        match result.search_ms {
+            Some(ms) if result.extractor != Some(false) => say!(
+                "✅ {:<10} search {} ms, {} results{}",
+                key,
+                ms,
+                result.results,
+                stream
+            ),
+            Some(ms) => say!(
+                "⚠️  {:<10} search {} ms, {} results{}: {}",
+                key,
+                ms,
+                result.results,
+                stream,
+                result.error.as_deref().unwrap_or("no source worked")
+            ),
+            None => say!(
+                "❌ {:<10} unreachable: {}",
+                key,
+                result.error.as_deref().unwrap_or_default()
+            ),
+        }
+        chosen_down |= key == config.general.provider && !result.reachable;
+        health.record(&key, result)?;
+    }
+    if chosen_down {
+        let hint = if config.general.fallback_providers.is_empty() {
+            "; add general.fallback_providers to use another meanwhile"
+        } else {
+            ""
+        };
+        return Err(Failure::Provider).context(format!(
+            "{} is unreachable{}",
+            config.general.provider, hint
+        ));
+    }
+    Ok(())
+}
+
+async fn run_feed(config: &ConfigManager, days: u32, output: Option<PathBuf>) -> Result<()> {
+    let (Some(token), Some(username)) = (&config.auth.anilist_token, &config.auth.username) else {
+        return Err(anyhow::Error::new(Failure::Auth)
+            .context("The feed follows your AniList list; log in first: ani-l auth"));
+    };
+    let mut watching = api::fetch_user_list(token, username, "CURRENT").await?;
+    // Feeds tend to end up somewhere public; private entries stay out.
+    watching.retain(|m| !m.media_list_entry.as_ref().is_some_and(|e| e.is_private()));
+    let ids: Vec<i32> = watching.iter().map(|m| m.id).collect();
+    let now = chrono::Utc::now();
+    let since = now - chrono::Duration::days(days.into());
+    let aired = if ids.is_empty() {
+        Vec::new()
+    } else {
+        api::fetch_aired(&ids, since.timestamp(), now.timestamp()).await?
+    };
+
+    let items: Vec<feed::FeedItem> = aired
+        .into_iter()
+        .filter_map(|a| {
+            let media = watching.iter().find(|m| m.id == a.media_id)?;
+            Some(feed::FeedItem {
+                media_id: a.media_id,
+                title: media.preferred_title().to_string(),
+                episode: a.episode,
+                aired: chrono::DateTime::from_timestamp(a.airing_at, 0)?,
+            })
+        })
+        .collect();
+
+    let rss = feed::render(&items, now);
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rss)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            say_err!("✅ Wrote {} episodes to {}", items.len(), path.display());
+        }
+        None => print!("{}", rss),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "downloads")]
+async fn run_download(
+    config: &Config,
+    query: Option<String>,
+    episode: Option<String>,
+    resume_all: bool,
+) -> Result<()> {
+    let mut manager = DownloadManager::new(config)?;
+
+    if let Some(query) = query {
+        let translation_type = config.stream.translation_type.clone();
+        let provider = AnyProvider::new(translation_type.clone(), config)?;
+
+        say!("🔍 Searching for '{}'...", query);
+        let results = provider.search(&query).await.context(Failure::Provider)?;
+        let show = choose_show(&provider, &config.picker, &results, &query)?;
+        say!("📺 Found: {} (ID: {})", show.name, show.id);
+        let episode = choose_episode(&provider, &config.picker, show, episode).await?;
+
+        let options = resolve_stream_for_episode(&provider, &show.id, &show.name, &episode, 0)
+            .await
+            .context(Failure::Provider)?
+            .ok_or(Failure::NoResults)
+            .context("No stream found")?;
+        let id = manager.enqueue(&show.name, &show.id, &episode, &translation_type, &options)?;
+        download_with_refresh(&mut manager, config, &id).await?;
+        say!("✅ Download complete.");
+    }
+
+    if resume_all {
+        let pending = manager.pending_ids();
+        if pending.is_empty() {
+            say!("✅ No unfinished downloads.");
+        }
+        let mut failed = 0;
+        for id in pending {
+            if let Some(entry) = manager.get(&id) {
+                say!(
+                    "⏯️  Resuming {} episode {} (last active {})",
+                    entry.title,
+                    entry.episode,
+                    timefmt::relative(entry.last_updated)
+                );
+            }
+            if let Err(e) = download_with_refresh(&mut manager, config, &id).await {
+                say_err!("❌ {}: {}", id, e);
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            return Err(anyhow::Error::new(Failure::Provider)
+                .context(format!("{} download(s) failed", failed)));
+        }
+    }
+
+    Ok(())
+}
+
+/// `episode` when one was asked for, else the show's episodes put to the
+/// external picker, else the first. A provider that can't list episodes
+/// offers as many as the show says it has.
+async fn choose_episode(
+    provider: &AnyProvider,
+    picker: &PickerConfig,
+    show: &ShowEdge,
+    episode: Option<String>,
+) -> Result<String> {
+    match episode {
+        Some(episode) => return Ok(episode),
+        None if picker.mode != PickerMode::External => return Ok("1".to_string()),
+        None => {}
+    }
+    let mut episodes = provider
+        .episode_list(&show.id)
+        .await
+        .context(Failure::Provider)?;
+    if episodes.is_empty() {
+        let count = show
+            .available_episodes
+            .for_translation(provider.translation_type());
+        episodes = (1..=count).map(|ep| ep.to_string()).collect();
+    }
+    episodes.sort_by(|a, b| {
+        let number = |ep: &String| ep.parse::<f64>().unwrap_or(f64::MAX);
+        number(a).total_cmp(&number(b))
+    });
+    if episodes.len() < 2 {
+        return episodes
+            .pop()
+            .ok_or(Failure::NoResults)
+            .context("No episodes found");
+    }
+    let labels: Vec<String> = episodes
+        .iter()
+        .map(|ep| format!("Episode {}", ep))
+        .collect();
+    match picker::pick(picker, &t!("picker.episode"), &labels)? {
+        Some(i) => Ok(episodes.swap_remove(i)),
+        None => Err(Failure::NoResults).context("Nothing picked"),
+    }
+}
+
+/// Runs a download, resolving a fresh stream URL once if the stored one has
+/// expired since the download was queued.
+#[cfg(feature = "downloads")]
+async fn download_with_refresh(
+    manager: &mut DownloadManager,
+    config: &Config,
+    id: &str,
+) -> Result<()> {
+    match manager.run(id).await {
+        Err(e) if download::is_expired(&e) => {
+            let entry = manager.get(id).cloned().context("Unknown download")?;
+            say!("🔄 Stream link expired, resolving a new one...");
+            let provider = AnyProvider::new(entry.translation_type.clone(), config)?;
+            let options = resolve_stream_for_episode(
+                &provider,
+                &entry.show_id,
+                &entry.title,
+                &entry.episode,
+                0,
+            )
+            .await?
+            .context("No stream found")?;
+            manager.enqueue(
+                &entry.title,
+                &entry.show_id,
+                &entry.episode,
+                &entry.translation_type,
+                &options,
+            )?;
+            manager.run(id).await
+        }
+        other => other,
+    }
+}
+
+/// Saves the episode instead of playing it, for sessions without a display.
+#[cfg(feature = "downloads")]
+async fn download_instead(
+    config: &Config,
+    show_id: &str,
+    title: &str,
+    episode: &str,
+    translation_type: &str,
+    options: &PlayOptions,
+) -> Result<()> {
+    let mut manager = DownloadManager::new(config)?;
+    let id = manager.enqueue(title, show_id, episode, translation_type, options)?;
+    manager.run(&id).await
+}
+
+#[cfg(not(feature = "downloads"))]
+async fn download_instead(
+    _config: &Config,
+    _show_id: &str,
+    _title: &str,
+    _episode: &str,
+    _translation_type: &str,
+    _options: &PlayOptions,
+) -> Result<()> {
+    anyhow::bail!("ani-l was built without the downloads feature")
+}
+
+/// Queues every aired episode past the viewer's progress and starts playing.
+fn queue_remaining(app: &mut App, media: &crate::models::Media) -> Result<()> {
+    queue_shows(app, std::slice::from_ref(media))
+}
+
+/// Queues the episodes marked in Episode Select, in order, and starts the
+/// queue; each is tracked as it finishes, like Play All Remaining.
+fn queue_episodes(app: &mut App, media: &crate::models::Media) -> Result<()> {
+    let mut manager = QueueManager::new()?;
+    let added: usize = std::mem::take(&mut app.episodes_selected)
+        .into_iter()
+        .map(|episode| {
+            manager
+                .data
+                .enqueue_show(media.id, media.preferred_title(), episode, episode)
+        })
+        .sum();
+    manager.save()?;
+    app.queue = manager.data.entries;
+    app.status_message = Some(t!("status.queued", count = added).to_string());
+    app.action_tx.send(Action::PlayQueue)?;
+    Ok(())
+}
+
+/// Queues the aired episodes past the viewer's progress of each show, one
+/// show after another, and starts the queue.
+fn queue_shows(app: &mut App, shows: &[crate::models::Media]) -> Result<()> {
+    let remaining: Vec<(&crate::models::Media, i32, i32)> = shows
+        .iter()
+        .filter_map(|media| {
+            let watched = media
+                .media_list_entry
+                .as_ref()
+                .and_then(|e| e.progress)
+                .unwrap_or(0);
+            let last = media.aired_episodes().or(media.episodes).unwrap_or(0);
+            (last > watched).then_some((media, watched, last))
+        })
+        .collect();
+    if remaining.is_empty() {
+        app.status_message = Some(t!("status.nothing_to_queue").to_string());
+        return Ok(());
+    }
+    let mut manager = QueueManager::new()?;
+    let added: usize = remaining
+        .into_iter()
+        .map(|(media, watched, last)| {
+            manager
+                .data
+                .enqueue_show(media.id, media.preferred_title(), watched + 1, last)
+        })
+        .sum();
+    manager.save()?;
+    app.queue = manager.data.entries;
+    app.status_message = Some(t!("status.queued", count = added).to_string());
+    app.action_tx.send(Action::PlayQueue)?;
+    Ok(())
+}
 
-    for source_name in priorities {
-        if let Some(source) = sources.iter().find(|s| s.source_name == source_name) {
-            match provider.extract_clock_stream(&source.source_url).await {
-                Ok(mut options) => {
-                    options.title = Some(format!("{} - Episode {}", show_name, episode));
-                    return Ok(Some(options));
+/// Starts the first queued episode, fetching its show when it isn't the one
+/// already open.
+fn play_queue(app: &mut App) {
+    app.queue = QueueManager::new()
+        .map(|m| m.data.entries)
+        .unwrap_or_default();
+    let Some(entry) = app.queue.first().cloned() else {
+        app.playing_queue = false;
+        app.status_message = Some(t!("status.queue_empty").to_string());
+        return;
+    };
+    app.playing_queue = true;
+    let episode = entry.episode.to_string();
+    if let Some(media) = app.active_media.clone().filter(|m| m.id == entry.media_id) {
+        start_stream_task(app, media, Some(episode));
+        return;
+    }
+    let tx = app.action_tx.clone();
+    let token = app.config_manager.auth.anilist_token.clone();
+    tokio::spawn(async move {
+        let variables = serde_json::json!({ "id_in": [entry.media_id], "perPage": 1 });
+        match api::fetch_media(variables, token.as_deref()).await {
+            Ok(res) => match res.data.page.and_then(|p| p.media.into_iter().next()) {
+                Some(media) => {
+                    let _ = tx.send(Action::ResumeMedia(Arc::new(media), episode));
                 }
-                Err(_) => continue,
+                None => {
+                    let _ = tx.send(Action::SearchError(t!("logs.no_results").to_string()));
+                }
+            },
+            Err(e) => {
+                let _ = tx.send(Action::SearchError(e.to_string()));
             }
         }
+    });
+}
+
+fn remove_from_queue(app: &mut App) {
+    let idx = app.get_selected_index();
+    if idx >= app.queue.len() {
+        return;
+    }
+    if let Ok(mut manager) = QueueManager::new() {
+        let removed = app.queue.remove(idx);
+        manager.data.entries.retain(|e| *e != removed);
+        let _ = manager.save();
+        app.queue = manager.data.entries;
+        if idx >= app.queue.len() && idx > 0 {
+            app.list_state.select(Some(idx - 1));
+        }
     }
-    Ok(None)
 }
 
-fn start_stream_task(app: &App, media: crate::models::Media, episode: Option<String>) {
+fn start_stream_task(app: &App, media: Arc<crate::models::Media>, episode: Option<String>) {
     let tx = app.action_tx.clone();
     let config = app.config_manager.clone();
+    let translation_type = app.translation_type.clone();
+    let private = app.private;
+    let audio_only = app.audio_only;
 
     let _ = tx.send(Action::StreamStarted);
 
+    let work = app.pending_work.start();
     tokio::spawn(async move {
+        let _work = work;
+        // Audio needs no display, so it plays even headless. Downloading
+        // needs no player, so it works on a box without one.
+        let download_only = cfg!(feature = "downloads")
+            && !audio_only
+            && player::is_headless()
+            && config.config.stream.headless_action == "download";
+        let chosen = (!download_only).then(|| player::choose_player(&config.config.stream));
+        let choice = match chosen.transpose() {
+            Ok(choice) => choice,
+            Err(e) => {
+                let _ = tx.send(Action::StreamLog(
+                    t!("logs.player_error", err = e).to_string(),
+                ));
+                tokio::time::sleep(Duration::from_secs(4)).await;
+                let _ = tx.send(Action::StreamFinished);
+                return;
+            }
+        };
+        if let Some(choice) = &choice
+            && let Some(missing) = &choice.replaced
+        {
+            let _ = tx.send(Action::StreamLog(
+                t!(
+                    "logs.player_fallback",
+                    player = missing,
+                    fallback = choice.name
+                )
+                .to_string(),
+            ));
+        }
+
         let episode_to_watch = if let Some(ep) = episode {
             ep
         } else {
@@ -513,35 +3730,116 @@ fn start_stream_task(app: &App, media: crate::models::Media, episode: Option<Str
         };
 
         let query = media.preferred_title();
+        let registry = RegistryManager::new().ok();
+        let pin = registry.as_ref().and_then(|r| r.get_pin(media.id).cloned());
+        let free = pin.is_none()
+            && config.config.general.provider != "youtube"
+            && registry
+                .as_ref()
+                .and_then(|r| r.get_entry(media.id))
+                .is_some_and(|e| e.is_free());
+        let provider = match &pin {
+            Some(pin) => {
+                let mut pinned = config.config.clone();
+                pinned.general.provider = pin.provider.clone();
+                let _ = tx.send(Action::StreamLog(
+                    t!("logs.provider_pinned", provider = pin.provider).to_string(),
+                ));
+                AnyProvider::new(translation_type.clone(), &pinned)
+            }
+            None if free => {
+                let mut youtube = config.config.clone();
+                youtube.general.provider = "youtube".to_string();
+                let _ = tx.send(Action::StreamLog(t!("logs.provider_free").to_string()));
+                AnyProvider::new(translation_type.clone(), &youtube)
+            }
+            None => AnyProvider::new(translation_type.clone(), &config.config),
+        };
+        let mut provider = match provider {
+            Ok(provider) => Arc::new(provider),
+            Err(e) => {
+                let _ = tx.send(Action::StreamLog(
+                    t!("logs.search_error", err = format!("{:#}", e)).to_string(),
+                ));
+                let _ = tx.send(Action::StreamFinished);
+                return;
+            }
+        };
         let _ = tx.send(Action::StreamLog(
-            t!("logs.searching_provider", query = query).to_string(),
+            t!(
+                "logs.searching_provider",
+                provider = provider.name(),
+                query = query
+            )
+            .to_string(),
         ));
 
-        let provider = Arc::new(crate::provider::allanime::AllAnimeProvider::new(
-            config.config.stream.translation_type.clone(),
-        ));
+        let mut found = provider.search(query).await;
+        // Free shows not (or no longer) on the official channels play from
+        // the usual provider.
+        if free && !found.as_ref().is_ok_and(|shows| !shows.is_empty()) {
+            match AnyProvider::new(translation_type, &config.config) {
+                Ok(fallback) => {
+                    provider = Arc::new(fallback);
+                    let _ = tx.send(Action::StreamLog(
+                        t!("logs.free_not_found", provider = provider.name()).to_string(),
+                    ));
+                    found = provider.search(query).await;
+                }
+                Err(e) => found = Err(e),
+            }
+        }
 
-        match provider.search(query).await {
-            Ok(results) => {
-                let best_match = results.iter().max_by(|a, b| {
-                    let name_a = normalizer::normalize("allanime", &a.name);
-                    let name_b = normalizer::normalize("allanime", &b.name);
-                    let score_a =
-                        normalized_levenshtein(&name_a.to_lowercase(), &query.to_lowercase());
-                    let score_b =
-                        normalized_levenshtein(&name_b.to_lowercase(), &query.to_lowercase());
-                    score_a
-                        .partial_cmp(&score_b)
-                        .unwrap_or(std::cmp::Ordering::Equal)
-                });
-
-                if let Some(show) = best_match {
+        match found {
+            Ok(mut results) => {
+                let pinned_id = pin.and_then(|p| p.show_id);
+                if let Some(id) = &pinned_id
+                    && !results.iter().any(|s| &s.id == id)
+                {
+                    results.push(pinned_show(&provider, id, &media).await);
+                }
+                let show = match &pinned_id {
+                    Some(id) => results.iter().find(|s| &s.id == id),
+                    None => pick_show(&tx, &provider, &results, query).await,
+                };
+                if let Some(show) = show {
                     let _ = tx.send(Action::StreamLog(
                         t!("logs.found", name = show.name, id = show.id).to_string(),
                     ));
 
+                    let available = show
+                        .available_episodes
+                        .for_translation(provider.translation_type());
+
+                    let mut episode_offset = 0;
+                    if let Some(season_released) = media.aired_episodes().or(media.episodes)
+                        && available as i32 > season_released
+                        && let Ok(prequels) = api::prequel_episode_counts(media.id).await
+                    {
+                        episode_offset =
+                            numbering::detect_offset(season_released, &prequels, available);
+                        if episode_offset > 0 {
+                            let _ = tx.send(Action::StreamLog(
+                                t!("logs.absolute_numbering", offset = episode_offset).to_string(),
+                            ));
+                        }
+                    }
+
+                    if let Ok(wanted) = episode_to_watch.parse::<usize>()
+                        && wanted + episode_offset as usize > available
+                        && media
+                            .aired_episodes()
+                            .is_some_and(|aired| aired as usize >= wanted)
+                    {
+                        let _ = tx.send(Action::StreamLog(
+                            t!("logs.not_uploaded", ep = wanted, available = available).to_string(),
+                        ));
+                        let _ = tx.send(Action::EpisodeNotUploaded(media, episode_to_watch));
+                        return;
+                    }
+
                     let show_id = show.id.clone();
-                    let show_name = show.name.clone();
+                    let display_title = media.preferred_title().to_string();
 
                     let _ = tx.send(Action::StreamLog(
                         t!("logs.fetching_episode", ep = episode_to_watch).to_string(),
@@ -550,60 +3848,207 @@ fn start_stream_task(app: &App, media: crate::models::Media, episode: Option<Str
                     match resolve_stream_for_episode(
                         &provider,
                         &show_id,
-                        &show_name,
+                        &display_title,
                         &episode_to_watch,
+                        episode_offset,
                     )
                     .await
                     {
-                        Ok(Some(options)) => {
+                        Ok(Some(mut options)) => {
                             let _ = tx.send(Action::StreamLog(t!("logs.stream_found").to_string()));
+                            // Skipping intros needs the same AniSkip times.
+                            let with_chapters = config.config.stream.chapters
+                                || config.config.stream.skip != SkipMode::Off;
+                            let mal_id = media.id_mal;
+                            options.chapters =
+                                episode_chapters(with_chapters, mal_id, &episode_to_watch).await;
 
-                            let current_ep_num = Arc::new(tokio::sync::Mutex::new(
-                                episode_to_watch.parse::<i32>().unwrap_or(1),
-                            ));
+                            let current_ep =
+                                Arc::new(tokio::sync::Mutex::new(episode_to_watch.clone()));
+                            // Fetched on the first Next/Previous; empty when the
+                            // provider can't list episodes.
+                            let episode_list: Arc<tokio::sync::OnceCell<Vec<String>>> =
+                                Arc::default();
                             let provider_clone = provider.clone();
                             let s_id = show_id.clone();
-                            let s_name = show_name.clone();
+                            let s_name = display_title.clone();
+                            // Private sessions leave no trace, hooks included.
+                            let hooks = (!private).then(|| config.config.hooks.clone());
+                            let nav_hooks = hooks.clone();
+                            let media_id = media.id;
 
                             let navigator: EpisodeNavigator = {
-                                let ep_store = current_ep_num.clone();
+                                let ep_store = current_ep.clone();
                                 Box::new(move |action| {
                                     let p = provider_clone.clone();
                                     let s_id = s_id.clone();
                                     let s_name = s_name.clone();
                                     let ep_store = ep_store.clone();
+                                    let episode_list = episode_list.clone();
+                                    let hooks = nav_hooks.clone();
                                     Box::pin(async move {
-                                        let mut num = ep_store.lock().await;
-                                        match action {
-                                            EpisodeAction::Next => *num += 1,
-                                            EpisodeAction::Previous => {
-                                                if *num > 1 {
-                                                    *num -= 1;
-                                                } else {
-                                                    return Ok(None);
-                                                }
-                                            }
-                                        }
-                                        resolve_stream_for_episode(
+                                        let list = episode_list
+                                            .get_or_init(|| async {
+                                                let listed =
+                                                    p.episode_list(&s_id).await.unwrap_or_default();
+                                                numbering::sort_episodes(listed)
+                                            })
+                                            .await;
+                                        let mut current = ep_store.lock().await;
+                                        let here =
+                                            numbering::provider_episode(&current, episode_offset);
+                                        let forward = matches!(action, EpisodeAction::Next);
+                                        let Some(next) =
+                                            numbering::step(list, &here, forward, available)
+                                        else {
+                                            return Ok(None);
+                                        };
+                                        let next =
+                                            numbering::anilist_episode(&next, episode_offset);
+                                        let mut options = resolve_stream_for_episode(
                                             &p,
                                             &s_id,
                                             &s_name,
-                                            &num.to_string(),
+                                            &next,
+                                            episode_offset,
                                         )
-                                        .await
+                                        .await?
+                                        .with_context(|| {
+                                            format!("No stream found for episode {}", next)
+                                        })?;
+                                        options.chapters =
+                                            episode_chapters(with_chapters, mal_id, &next).await;
+                                        if let Some(hooks) = &hooks {
+                                            hooks::run(
+                                                hooks,
+                                                HookEvent::EpisodeStart,
+                                                &HookContext {
+                                                    title: &s_name,
+                                                    episode: &next,
+                                                    media_id,
+                                                    progress: 0.0,
+                                                },
+                                            );
+                                        }
+                                        *current = next;
+                                        Ok(Some(options))
                                     })
                                 })
                             };
 
-                            let player = crate::player::mpv::MpvPlayer;
+                            let mut player = choice.map(|c| c.player);
+                            if audio_only {
+                                if let Some(player) = &mut player {
+                                    player.set_audio_only();
+                                }
+                                let _ = tx
+                                    .send(Action::StreamLog(t!("logs.audio_playback").to_string()));
+                            } else if download_only {
+                                let _ = tx.send(Action::StreamLog(
+                                    t!("logs.headless_download").to_string(),
+                                ));
+                            } else if player::is_headless() {
+                                let _ = tx.send(Action::StreamLog(
+                                    t!("logs.headless_terminal").to_string(),
+                                ));
+                            }
+
+                            let required_percentage =
+                                config.config.stream.episode_complete_at as f64;
+                            // Episodes the player reports watched (each one
+                            // of a Next chain) get their hook, and with
+                            // `sync_during_playback` are saved, straight
+                            // away; the check after it closes skips them.
+                            let sync_early = config.config.stream.sync_during_playback
+                                && !private
+                                && !audio_only
+                                && !download_only;
+                            let hook_early = !download_only
+                                && hooks
+                                    .as_ref()
+                                    .is_some_and(|h| h.on_episode_complete.is_some());
+                            let early_report = (sync_early || hook_early).then(|| {
+                                let (report_tx, mut report_rx) = mpsc::unbounded_channel();
+                                options.report_complete = Some(CompletionReport {
+                                    at: required_percentage,
+                                    tx: report_tx,
+                                });
+                                let config = config.clone();
+                                let tx = tx.clone();
+                                let media = media.clone();
+                                let hooks = hooks.clone();
+                                let title = display_title.clone();
+                                tokio::spawn(async move {
+                                    let mut reported = Vec::new();
+                                    while let Some(episode) = report_rx.recv().await {
+                                        let number = numbering::progress_episode(&episode);
+                                        if sync_early && let Some(number) = number {
+                                            sync_progress(&config, &tx, &media, number).await;
+                                            let _ = tx.send(Action::EpisodeWatched(
+                                                media.clone(),
+                                                number,
+                                            ));
+                                        }
+                                        if let Some(hooks) = &hooks {
+                                            hooks::run(
+                                                hooks,
+                                                HookEvent::EpisodeComplete,
+                                                &HookContext {
+                                                    title: &title,
+                                                    episode: &episode,
+                                                    media_id,
+                                                    progress: required_percentage,
+                                                },
+                                            );
+                                        }
+                                        reported.push(episode);
+                                    }
+                                    reported
+                                })
+                            });
 
                             let notify = Arc::new(Notify::new());
                             let _ = tx.send(Action::Suspend(notify.clone()));
                             notify.notified().await;
 
-                            let play_result = player.play(options, Some(navigator)).await;
+                            if let Some(hooks) = &hooks
+                                && !download_only
+                            {
+                                hooks::run(
+                                    hooks,
+                                    HookEvent::EpisodeStart,
+                                    &HookContext {
+                                        title: &display_title,
+                                        episode: &episode_to_watch,
+                                        media_id,
+                                        progress: 0.0,
+                                    },
+                                );
+                            }
+
+                            let play_result = if let Some(player) = player {
+                                player.play(options, Some(navigator)).await
+                            } else {
+                                download_instead(
+                                    &config.config,
+                                    &show_id,
+                                    &display_title,
+                                    &episode_to_watch,
+                                    provider.translation_type(),
+                                    &options,
+                                )
+                                .await
+                                .map(|_| 0.0)
+                            };
+                            provider.release_streams().await;
 
                             let _ = tx.send(Action::Resume);
+                            // Ends once the player has dropped its options.
+                            let reported = match early_report {
+                                Some(task) => task.await.unwrap_or_default(),
+                                None => Vec::new(),
+                            };
+                            let done_early = reported.contains(&*current_ep.lock().await);
 
                             match play_result {
                                 Ok(percentage) => {
@@ -612,51 +4057,66 @@ fn start_stream_task(app: &App, media: crate::models::Media, episode: Option<Str
                                             .to_string(),
                                     ));
 
-                                    let final_ep_num = *current_ep_num.lock().await;
-                                    let required_percentage =
-                                        config.config.stream.episode_complete_at as f64;
-
-                                    if percentage >= required_percentage
-                                        && let (Some(token), Some(username)) =
-                                            (&config.auth.anilist_token, &config.auth.username)
-                                    {
-                                        let _ = tx.send(Action::StreamLog(
-                                            t!("logs.updating_anilist").to_string(),
-                                        ));
-                                        match api::get_user_progress(token, media.id, username)
-                                            .await
+                                    let final_ep = current_ep.lock().await.clone();
+                                    let run_hook = |event| {
+                                        if let Some(hooks) = &hooks
+                                            && !download_only
                                         {
-                                            Ok(current_progress) => {
-                                                let prog = current_progress.unwrap_or(0);
-                                                if final_ep_num > prog {
-                                                    if let Err(e) = api::update_user_entry(
-                                                        token,
-                                                        media.id,
-                                                        final_ep_num,
-                                                        "CURRENT",
-                                                    )
-                                                    .await
-                                                    {
-                                                        let _ = tx.send(Action::StreamLog(
-                                                            t!("logs.update_failed", err = e)
-                                                                .to_string(),
-                                                        ));
-                                                    } else {
-                                                        let _ = tx.send(Action::StreamLog(
-                                                            t!(
-                                                                "logs.updated_to_ep",
-                                                                ep = final_ep_num
-                                                            )
-                                                            .to_string(),
-                                                        ));
-                                                    }
-                                                }
-                                            }
-                                            Err(e) => {
+                                            hooks::run(
+                                                hooks,
+                                                event,
+                                                &HookContext {
+                                                    title: &display_title,
+                                                    episode: &final_ep,
+                                                    media_id,
+                                                    progress: percentage,
+                                                },
+                                            );
+                                        }
+                                    };
+                                    run_hook(HookEvent::PlaybackEnd);
+                                    // Specials ("5.5", "OVA") aren't counted
+                                    // anywhere an episode number is kept.
+                                    let final_ep_num = numbering::progress_episode(&final_ep);
+                                    if let Some(final_ep_num) = final_ep_num
+                                        && percentage > 0.0
+                                        && !private
+                                        && let Ok(mut history) = HistoryManager::new()
+                                    {
+                                        let episode_minutes = media.duration.unwrap_or(24) as f64;
+                                        let _ = history.record(WatchSession {
+                                            media_id: media.id,
+                                            title: display_title.clone(),
+                                            episode: final_ep_num,
+                                            minutes: episode_minutes * percentage.min(100.0)
+                                                / 100.0,
+                                            watched_at: chrono::Utc::now(),
+                                        });
+                                    }
+                                    if let Some(final_ep_num) = final_ep_num
+                                        && percentage >= required_percentage
+                                    {
+                                        if !done_early {
+                                            run_hook(HookEvent::EpisodeComplete);
+                                        }
+                                        // Otherwise it was saved while it played.
+                                        if !(sync_early && done_early) {
+                                            if private {
+                                                let _ = tx.send(Action::StreamLog(
+                                                    t!("logs.private_skipped").to_string(),
+                                                ));
+                                            } else if audio_only {
                                                 let _ = tx.send(Action::StreamLog(
-                                                    t!("logs.sync_error", err = e).to_string(),
+                                                    t!("logs.audio_only_skipped").to_string(),
                                                 ));
+                                            } else {
+                                                sync_progress(&config, &tx, &media, final_ep_num)
+                                                    .await;
                                             }
+                                            let _ = tx.send(Action::EpisodeWatched(
+                                                media.clone(),
+                                                final_ep_num,
+                                            ));
                                         }
                                     }
                                 }
@@ -676,7 +4136,7 @@ fn start_stream_task(app: &App, media: crate::models::Media, episode: Option<Str
                             ));
                         }
                     }
-                } else {
+                } else if results.is_empty() {
                     let _ = tx.send(Action::StreamLog(t!("logs.no_results").to_string()));
                 }
             }
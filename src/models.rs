@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -18,16 +19,122 @@ pub struct Data {
     pub saved_entry: Option<MediaListEntry>,
     #[serde(rename = "MediaList")]
     pub media_list: Option<MediaListEntry>,
+    #[serde(rename = "MediaListCollection")]
+    pub media_list_collection: Option<MediaListCollection>,
+    #[serde(rename = "Media")]
+    pub media: Option<MediaRelations>,
+    #[serde(rename = "Staff")]
+    pub staff: Option<Staff>,
 }
 
+/// What the single-`Media` queries ask for: relations or characters.
 #[derive(Debug, Deserialize, Clone)]
+pub struct MediaRelations {
+    pub relations: Option<RelationConnection>,
+    pub characters: Option<CharacterConnection>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CharacterConnection {
+    pub edges: Vec<CharacterEdge>,
+}
+
+/// A character in a show, with the people voicing them.
+#[derive(Debug, Deserialize, Clone)]
+pub struct CharacterEdge {
+    /// "MAIN", "SUPPORTING" or "BACKGROUND".
+    pub role: Option<String>,
+    pub node: Character,
+    #[serde(rename = "voiceActors", default)]
+    pub voice_actors: Vec<VoiceActor>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Character {
+    pub name: PersonName,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PersonName {
+    pub full: Option<String>,
+}
+
+impl fmt::Display for PersonName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.full.as_deref().unwrap_or("?"))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VoiceActor {
+    pub id: i32,
+    pub name: PersonName,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Staff {
+    #[serde(rename = "characterMedia")]
+    pub character_media: Option<StaffMediaConnection>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct StaffMediaConnection {
+    #[serde(rename = "pageInfo")]
+    pub page_info: PageInfo,
+    pub edges: Vec<StaffMediaEdge>,
+}
+
+/// A show a staff member voiced characters in.
+#[derive(Debug, Deserialize, Clone)]
+pub struct StaffMediaEdge {
+    pub node: Media,
+    #[serde(default)]
+    pub characters: Vec<Character>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelationConnection {
+    pub edges: Vec<RelationEdge>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelationEdge {
+    #[serde(rename = "relationType")]
+    pub relation_type: String,
+    pub node: RelationNode,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelationNode {
+    pub id: i32,
+    /// "ANIME" or "MANGA".
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub format: Option<String>,
+    pub episodes: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MediaListCollection {
+    pub lists: Vec<MediaListGroup>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MediaListGroup {
+    pub entries: Vec<MediaListEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Page {
     #[serde(rename = "pageInfo")]
     pub page_info: PageInfo,
+    #[serde(default)]
     pub media: Vec<Media>,
+    #[serde(rename = "airingSchedules", default)]
+    pub airing_schedules: Vec<AiringSchedule>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PageInfo {
     pub total: i32,
     #[serde(rename = "currentPage")]
@@ -36,13 +143,25 @@ pub struct PageInfo {
     pub has_next_page: bool,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Media {
     pub id: i32,
+    #[serde(rename = "idMal")]
+    pub id_mal: Option<i32>,
+    /// "ANIME" or "MANGA"; missing means anime.
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
     pub title: MediaTitle,
     #[serde(rename = "coverImage")]
     pub cover_image: Option<CoverImage>,
+    #[serde(rename = "bannerImage")]
+    pub banner_image: Option<String>,
     pub episodes: Option<i32>,
+    pub chapters: Option<i32>,
+    /// Episode length in minutes.
+    pub duration: Option<i32>,
+    #[serde(rename = "mediaListEntry")]
+    pub media_list_entry: Option<MediaListEntry>,
     pub description: Option<String>,
     #[serde(rename = "averageScore")]
     pub average_score: Option<i32>,
@@ -59,34 +178,88 @@ pub struct Media {
     pub end_date: Option<FuzzyDate>,
     pub synonyms: Option<Vec<String>>,
     pub tags: Option<Vec<MediaTag>>,
+    #[serde(rename = "nextAiringEpisode")]
+    pub next_airing_episode: Option<AiringEpisode>,
+    #[serde(rename = "streamingEpisodes", default)]
+    pub streaming_episodes: Vec<StreamingEpisode>,
+    /// Only asked for when gathering a franchise.
+    pub relations: Option<RelationConnection>,
+    /// Fetched for a list without the details pane's fields. AniList never
+    /// sends it; it is kept for lists cached on disk.
+    #[serde(default)]
+    pub slim: bool,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// An episode link from an official streaming site. Titles usually look like
+/// "Episode 5 - The Priest's Lie".
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamingEpisode {
+    pub title: Option<String>,
+}
+
+/// Per-episode information from MyAnimeList, via the Jikan API.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct EpisodeDetails {
+    pub title: Option<String>,
+    pub synopsis: Option<String>,
+    pub aired: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub filler: bool,
+    #[serde(default)]
+    pub recap: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiringEpisode {
+    /// Unix timestamp.
+    #[serde(rename = "airingAt")]
+    pub airing_at: i64,
+    pub episode: i32,
+}
+
+/// An episode that aired (or will air), from AniList's airing schedule.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiringSchedule {
+    #[serde(rename = "mediaId")]
+    pub media_id: i32,
+    pub episode: i32,
+    /// Unix timestamp.
+    #[serde(rename = "airingAt")]
+    pub airing_at: i64,
+}
+
+impl AiringEpisode {
+    pub fn airing_time(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(self.airing_at, 0)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Trailer {
     pub id: Option<String>,
     pub site: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MediaTitle {
     pub romaji: Option<String>,
     pub english: Option<String>,
     pub native: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CoverImage {
     pub extra_large: Option<String>,
     pub large: Option<String>,
     pub medium: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct StudioConnection {
     pub nodes: Vec<Studio>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Studio {
     pub name: String,
 }
@@ -97,7 +270,7 @@ pub struct User {
     pub name: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MediaListEntry {
     pub id: Option<i32>,
     #[serde(rename = "mediaId")]
@@ -105,21 +278,37 @@ pub struct MediaListEntry {
     pub status: Option<String>,
     pub progress: Option<i32>,
     pub score: Option<f64>,
+    /// Hidden from everyone but the viewer.
+    #[serde(default)]
+    pub private: Option<bool>,
+    /// The viewer's note on AniList.
+    #[serde(default)]
+    pub notes: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl MediaListEntry {
+    pub fn is_private(&self) -> bool {
+        self.private == Some(true)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FuzzyDate {
     pub year: Option<i32>,
     pub month: Option<i32>,
     pub day: Option<i32>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MediaTag {
     pub name: String,
 }
 
 impl Media {
+    pub fn is_manga(&self) -> bool {
+        self.kind.as_deref() == Some("MANGA")
+    }
+
     pub fn preferred_title(&self) -> &str {
         self.title
             .english
@@ -129,6 +318,61 @@ impl Media {
             .unwrap_or("Unknown Title")
     }
 
+    pub fn cover_url(&self) -> Option<&str> {
+        let cover = self.cover_image.as_ref()?;
+        cover
+            .extra_large
+            .as_deref()
+            .or(cover.large.as_deref())
+            .or(cover.medium.as_deref())
+    }
+
+    /// Minutes of footage left for a show on the viewer's CURRENT list, based
+    /// on the episodes released so far.
+    pub fn minutes_to_finish(&self) -> Option<i32> {
+        let entry = self.media_list_entry.as_ref()?;
+        if entry.status.as_deref() != Some("CURRENT") {
+            return None;
+        }
+        self.minutes_remaining()
+    }
+
+    /// Episodes AniList reports as aired, when that's known.
+    pub fn aired_episodes(&self) -> Option<i32> {
+        match &self.next_airing_episode {
+            Some(next) => Some(next.episode - 1),
+            None if self.status.as_deref() == Some("FINISHED") => self.episodes,
+            None => None,
+        }
+    }
+
+    /// Minutes of released footage not yet watched according to the viewer's
+    /// list entry (all of it when the show isn't on the list).
+    pub fn minutes_remaining(&self) -> Option<i32> {
+        let progress = self
+            .media_list_entry
+            .as_ref()
+            .and_then(|e| e.progress)
+            .unwrap_or(0);
+        let released = self
+            .next_airing_episode
+            .as_ref()
+            .map(|next| next.episode - 1)
+            .or(self.episodes)?;
+        let remaining = (released - progress).max(0);
+        Some(remaining * self.duration?)
+    }
+
+    /// Title of episode `episode` from the official streaming links, without
+    /// the "Episode N - " prefix.
+    pub fn streaming_episode_title(&self, episode: i32) -> Option<&str> {
+        let prefix = format!("Episode {} - ", episode);
+        self.streaming_episodes
+            .iter()
+            .filter_map(|e| e.title.as_deref())
+            .find_map(|t| t.strip_prefix(&prefix))
+    }
+
     pub fn formatted_start_date(&self) -> String {
         self.start_date
             .as_ref()
@@ -0,0 +1,278 @@
+use crate::config::NetworkConfig;
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, ClientBuilder, Proxy};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Instant, sleep_until};
+
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_AAAA: u16 = 28;
+
+/// Client for AniList and the other metadata APIs, set up by `init`.
+static API_CLIENT: OnceLock<Client> = OnceLock::new();
+/// Client for checking resolved streams, set up by `init`.
+static STREAM_CLIENT: OnceLock<Client> = OnceLock::new();
+
+lazy_static! {
+    static ref LIMITERS: Mutex<HashMap<String, Arc<HostLimiter>>> = Mutex::new(HashMap::new());
+}
+
+/// Caps in-flight requests and enforces a minimum gap between request starts
+/// for a single provider host.
+pub struct HostLimiter {
+    permits: Arc<Semaphore>,
+    min_interval: Duration,
+    next_slot: tokio::sync::Mutex<Instant>,
+}
+
+impl HostLimiter {
+    pub fn new(max_concurrent: usize, min_interval: Duration) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            min_interval,
+            next_slot: tokio::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for a free slot. The request should be sent while the returned
+    /// permit is held.
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("host limiter semaphore closed");
+
+        let start_at = {
+            let mut next = self.next_slot.lock().await;
+            let start_at = (*next).max(Instant::now());
+            *next = start_at + self.min_interval;
+            start_at
+        };
+        sleep_until(start_at).await;
+
+        permit
+    }
+}
+
+/// Returns the shared limiter for `host`, creating it from the network config
+/// on first use.
+pub fn limiter_for(host: &str, config: &NetworkConfig) -> Arc<HostLimiter> {
+    let mut limiters = LIMITERS.lock().unwrap_or_else(|e| e.into_inner());
+    limiters
+        .entry(host.to_string())
+        .or_insert_with(|| {
+            let (max_concurrent, interval_ms) = match config.hosts.get(host) {
+                Some(limit) => (limit.max_concurrent_requests, limit.min_request_interval_ms),
+                None => (
+                    config.max_concurrent_requests,
+                    config.min_request_interval_ms,
+                ),
+            };
+            Arc::new(HostLimiter::new(
+                max_concurrent,
+                Duration::from_millis(interval_ms),
+            ))
+        })
+        .clone()
+}
+
+/// Applies the proxy and extra root certificates.
+fn with_transport(mut builder: ClientBuilder, config: &NetworkConfig) -> Result<ClientBuilder> {
+    if let Some(proxy) = &config.proxy {
+        let proxy =
+            Proxy::all(proxy).with_context(|| format!("Invalid network.proxy \"{}\"", proxy))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &config.ca_certificate {
+        let pem = fs::read(path)
+            .with_context(|| format!("Could not read network.ca_certificate {:?}", path))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("{:?} holds no PEM certificates", path))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(builder)
+}
+
+/// Applies `network.timeout_secs`, which limits a whole request.
+fn with_timeout(builder: ClientBuilder, config: &NetworkConfig) -> ClientBuilder {
+    match config.timeout_secs {
+        Some(secs) => builder.timeout(Duration::from_secs(secs)),
+        None => builder,
+    }
+}
+
+/// Adds a provider header, skipping (with a warning) names or values that
+/// aren't valid in HTTP, such as a mistyped config override.
+pub fn insert_header(headers: &mut HeaderMap, name: &str, value: &str) {
+    match (
+        HeaderName::from_bytes(name.as_bytes()),
+        HeaderValue::from_str(value),
+    ) {
+        (Ok(name), Ok(value)) => {
+            headers.insert(name, value);
+        }
+        _ => warn!("Ignoring invalid provider header override '{}'", name),
+    }
+}
+
+/// Checks the network settings and builds the shared API client from them.
+pub fn init(config: &NetworkConfig) -> Result<()> {
+    let client = with_timeout(with_transport(Client::builder(), config)?, config).build()?;
+    let _ = API_CLIENT.set(client);
+    let _ = STREAM_CLIENT.set(provider_client_builder(config)?.build()?);
+    Ok(())
+}
+
+/// The client for AniList, Jikan and AniSkip requests.
+pub fn api_client() -> Client {
+    API_CLIENT.get().cloned().unwrap_or_default()
+}
+
+/// The client for probing stream hosts before a player is started on them.
+pub fn stream_client() -> Client {
+    STREAM_CLIENT.get().cloned().unwrap_or_default()
+}
+
+/// Returns a client builder for provider traffic with the user's network
+/// settings applied.
+pub fn provider_client_builder(config: &NetworkConfig) -> Result<ClientBuilder> {
+    Ok(with_timeout(download_client_builder(config)?, config))
+}
+
+/// Like `provider_client_builder`, but without `network.timeout_secs`, which
+/// would cut off any file that takes longer than that to fetch.
+pub fn download_client_builder(config: &NetworkConfig) -> Result<ClientBuilder> {
+    let mut builder = with_transport(Client::builder(), config)?;
+    if let Some(endpoint) = &config.dns_over_https {
+        builder = builder.dns_resolver(Arc::new(DohResolver::new(endpoint.clone())));
+    }
+    Ok(builder)
+}
+
+#[derive(Debug, Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    #[serde(rename = "TTL", default)]
+    ttl: u64,
+    data: String,
+}
+
+type DnsCache = HashMap<String, (Instant, Vec<IpAddr>)>;
+
+/// Resolves host names through a DNS-over-HTTPS JSON endpoint
+/// (`application/dns-json`), bypassing the system resolver.
+pub struct DohResolver {
+    endpoint: String,
+    client: Client,
+    cache: Arc<Mutex<DnsCache>>,
+}
+
+impl DohResolver {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            client: Client::new(),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let endpoint = self.endpoint.clone();
+        let client = self.client.clone();
+        let cache = self.cache.clone();
+
+        Box::pin(async move {
+            let cached = cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(&host)
+                .filter(|(expires, _)| *expires > Instant::now())
+                .map(|(_, ips)| ips.clone());
+
+            let ips = match cached {
+                Some(ips) => ips,
+                None => {
+                    let (ips, ttl) = doh_lookup(&client, &endpoint, &host).await?;
+                    cache.lock().unwrap_or_else(|e| e.into_inner()).insert(
+                        host.clone(),
+                        (Instant::now() + Duration::from_secs(ttl), ips.clone()),
+                    );
+                    ips
+                }
+            };
+
+            let addrs: Addrs = Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+async fn doh_lookup(client: &Client, endpoint: &str, host: &str) -> Result<(Vec<IpAddr>, u64)> {
+    for record_type in [DNS_TYPE_A, DNS_TYPE_AAAA] {
+        debug!(
+            "DoH lookup for {} (type {}) via {}",
+            host, record_type, endpoint
+        );
+        let resp: DohResponse = client
+            .get(endpoint)
+            .query(&[("name", host), ("type", &record_type.to_string())])
+            .header("Accept", "application/dns-json")
+            .send()
+            .await?
+            .json()
+            .await
+            .context("Invalid DNS-over-HTTPS response")?;
+
+        let records: Vec<&DohAnswer> = resp
+            .answer
+            .iter()
+            .filter(|a| a.record_type == record_type)
+            .collect();
+        let ips: Vec<IpAddr> = records.iter().filter_map(|a| a.data.parse().ok()).collect();
+
+        if !ips.is_empty() {
+            let ttl = records.iter().map(|a| a.ttl).min().unwrap_or(60).max(30);
+            return Ok((ips, ttl));
+        }
+    }
+    anyhow::bail!("DNS-over-HTTPS returned no addresses for {}", host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limiter_spaces_requests() {
+        let limiter = HostLimiter::new(4, Duration::from_millis(50));
+        let started = Instant::now();
+
+        for _ in 0..3 {
+            let _permit = limiter.acquire().await;
+        }
+
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+}
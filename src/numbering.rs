@@ -0,0 +1,128 @@
+//! Providers sometimes number a long-running franchise continuously
+//! (episode 1025) where AniList numbers each season from 1, or list a split
+//! season's parts as one show. This maps between the two.
+
+/// Episodes the provider may still be missing from the end of a lumped
+/// listing (not uploaded yet) while the offset is still trusted.
+const UPLOAD_LAG: i32 = 2;
+
+/// The provider's number for the first episode of a season is this offset
+/// plus one. `prequels` are the episode counts of the earlier seasons and
+/// parts, nearest first; the provider may lump any number of them in with
+/// this one, so the deepest run whose episodes plus this season's released
+/// ones the provider's listing covers wins. Zero when it lists the season
+/// on its own.
+pub fn detect_offset(season_released: i32, prequels: &[i32], provider_available: usize) -> i32 {
+    let available = provider_available as i32;
+    let mut offset = 0;
+    let mut lumped = 0;
+    for episodes in prequels {
+        lumped += episodes;
+        if lumped < available && lumped + season_released <= available + UPLOAD_LAG {
+            offset = lumped;
+        }
+    }
+    offset
+}
+
+/// AniList episode number to the provider's. Non-numeric episodes (e.g.
+/// "12.5") are passed through unchanged.
+pub fn provider_episode(episode: &str, offset: i32) -> String {
+    match episode.parse::<i32>() {
+        Ok(num) if offset > 0 => (num + offset).to_string(),
+        _ => episode.to_string(),
+    }
+}
+
+/// The provider's episode number back to AniList's.
+pub fn anilist_episode(provider_episode: &str, offset: i32) -> String {
+    match provider_episode.parse::<i32>() {
+        Ok(num) if offset > 0 && num > offset => (num - offset).to_string(),
+        _ => provider_episode.to_string(),
+    }
+}
+
+/// Episode count AniList progress should move to after watching `episode`.
+/// `None` for specials ("5.5", "OVA"), which AniList doesn't count.
+pub fn progress_episode(episode: &str) -> Option<i32> {
+    episode.parse().ok()
+}
+
+/// Puts a provider's episode list in watching order: numbered episodes
+/// (including "5.5") ascending, then anything unnumbered ("OVA", "SP1") as
+/// listed.
+pub fn sort_episodes(mut episodes: Vec<String>) -> Vec<String> {
+    episodes.sort_by(|a, b| match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => std::cmp::Ordering::Equal,
+    });
+    episodes.dedup();
+    episodes
+}
+
+/// The episode after (`forward`) or before `current` in a sorted list.
+/// Without a list, plain episode numbers count up or down by one, no
+/// further than `available` (the provider's episode count; 0 if unknown).
+/// `None` past either end.
+pub fn step(episodes: &[String], current: &str, forward: bool, available: usize) -> Option<String> {
+    if let Some(i) = episodes.iter().position(|e| e == current) {
+        let next = if forward {
+            i.checked_add(1)?
+        } else {
+            i.checked_sub(1)?
+        };
+        return episodes.get(next).cloned();
+    }
+    let num = current.parse::<usize>().ok()?;
+    let next = if forward {
+        num + 1
+    } else {
+        num.checked_sub(1)?
+    };
+    let past_last = available > 0 && next > available;
+    (next >= 1 && !past_last).then(|| next.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_only_for_continuous_numbering() {
+        // Season 2 (12 eps) after a 24 episode first season.
+        assert_eq!(detect_offset(12, &[24], 36), 24);
+        assert_eq!(detect_offset(12, &[24], 35), 24);
+        assert_eq!(provider_episode("3", 24), "27");
+        // Provider lists the season on its own, here with an extra OVA.
+        assert_eq!(detect_offset(12, &[24], 12), 0);
+        assert_eq!(detect_offset(13, &[12, 12], 13), 0);
+        assert_eq!(provider_episode("3", 0), "3");
+        // Season 2 Part 2 listed together with Part 1 but not Season 1.
+        assert_eq!(detect_offset(12, &[12, 24], 24), 12);
+        // Everything from Season 1 on in one listing.
+        assert_eq!(detect_offset(12, &[12, 24], 48), 36);
+        assert_eq!(anilist_episode("27", 24), "3");
+    }
+
+    #[test]
+    fn test_step_through_specials() {
+        let list: Vec<String> = ["OVA", "6", "5.5", "5", "1"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let list = sort_episodes(list);
+        assert_eq!(list, vec!["1", "5", "5.5", "6", "OVA"]);
+        assert_eq!(step(&list, "5", true, 6).as_deref(), Some("5.5"));
+        assert_eq!(step(&list, "6", true, 6).as_deref(), Some("OVA"));
+        assert_eq!(step(&list, "OVA", true, 6), None);
+        assert_eq!(step(&list, "1", false, 6), None);
+        // Unknown list: count up to the available episodes.
+        assert_eq!(step(&[], "3", true, 12).as_deref(), Some("4"));
+        assert_eq!(step(&[], "12", true, 12), None);
+        assert_eq!(step(&[], "12", true, 0).as_deref(), Some("13"));
+        assert_eq!(step(&[], "1", false, 12), None);
+        assert_eq!(step(&[], "SP1", true, 12), None);
+    }
+}
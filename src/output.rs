@@ -0,0 +1,74 @@
+//! Console output that stays script-friendly: when stdout isn't a terminal
+//! (e.g. piped into `grep`), emoji prefixes and colours are dropped and
+//! interactive prompts are skipped.
+
+use std::io::{self, IsTerminal};
+
+/// Like `println!`, minus the leading emoji when output is piped.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        println!("{}", $crate::output::decorate(&format!($($arg)*)))
+    };
+}
+
+/// Like `eprintln!`, minus the leading emoji when stderr is piped.
+macro_rules! say_err {
+    ($($arg:tt)*) => {
+        eprintln!("{}", $crate::output::decorate_err(&format!($($arg)*)))
+    };
+}
+
+pub fn plain() -> bool {
+    !io::stdout().is_terminal()
+}
+
+/// True when a user can answer prompts.
+pub fn interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Turns off colours for piped output. Called once at startup.
+pub fn init() {
+    if plain() {
+        crossterm::style::force_color_output(false);
+    }
+}
+
+pub fn decorate(text: &str) -> String {
+    if plain() {
+        strip_emoji_prefix(text).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn decorate_err(text: &str) -> String {
+    if io::stderr().is_terminal() {
+        text.to_string()
+    } else {
+        strip_emoji_prefix(text).to_string()
+    }
+}
+
+fn strip_emoji_prefix(text: &str) -> &str {
+    let is_decoration = |c: char| {
+        c.is_whitespace()
+            || matches!(c, '\u{200D}' | '\u{FE0F}' | '\u{2190}'..='\u{2BFF}' | '\u{1F000}'..='\u{1FAFF}')
+    };
+    text.trim_start_matches(is_decoration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_emoji_prefix() {
+        assert_eq!(
+            strip_emoji_prefix("✅ Download complete."),
+            "Download complete."
+        );
+        assert_eq!(strip_emoji_prefix("▶️  Starting VLC..."), "Starting VLC...");
+        assert_eq!(strip_emoji_prefix("Привет"), "Привет");
+    }
+}
@@ -0,0 +1,72 @@
+//! `--picker external`: hands command-line choices to fzf, rofi, dmenu or
+//! anything else that reads options on stdin and prints the chosen line, so
+//! ani-l fits into launchers while searching and tracking stay here.
+
+use crate::config::PickerConfig;
+use crate::util::split_args;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs `picker.command` over `options`, returning the index picked or
+/// `None` when the picker was dismissed. Options are numbered so that two
+/// with the same name still map back to the right one.
+pub fn pick(config: &PickerConfig, prompt: &str, options: &[String]) -> Result<Option<usize>> {
+    let args: Vec<String> = split_args(&config.command)
+        .into_iter()
+        .map(|arg| arg.replace("{prompt}", prompt))
+        .collect();
+    let (program, args) = args.split_first().context("picker.command is empty")?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start the picker {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A picker that quits early closes its stdin; its output decides.
+        let _ = stdin.write_all(numbered(options).as_bytes());
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(parse_pick(
+        &String::from_utf8_lossy(&output.stdout),
+        options.len(),
+    ))
+}
+
+fn numbered(options: &[String]) -> String {
+    options
+        .iter()
+        .enumerate()
+        .map(|(i, option)| format!("{}. {}\n", i + 1, option.replace('\n', " ")))
+        .collect()
+}
+
+/// The index behind the picker's first output line.
+fn parse_pick(output: &str, len: usize) -> Option<usize> {
+    let (number, _) = output.lines().next()?.trim_start().split_once(". ")?;
+    match number.parse::<usize>() {
+        Ok(n) if (1..=len).contains(&n) => Some(n - 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_maps_back_to_index() {
+        let options = ["Frieren".to_string(), "Frieren".to_string()];
+        let lines = numbered(&options);
+        assert_eq!(lines, "1. Frieren\n2. Frieren\n");
+        let second = lines.lines().nth(1).unwrap();
+        assert_eq!(parse_pick(&format!("{}\n", second), 2), Some(1));
+        assert_eq!(parse_pick("", 2), None);
+        assert_eq!(parse_pick("3. Frieren", 2), None);
+    }
+}
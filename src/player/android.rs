@@ -0,0 +1,79 @@
+use super::find_executable;
+use super::traits::{EpisodeNavigator, PlayOptions, Player};
+use anyhow::{Context, Result};
+use tokio::process::Command;
+
+const MPV_ANDROID_ACTIVITY: &str = "is.xyz.mpv/.MPVActivity";
+
+/// Hands the stream to mpv-android through an Android intent, for Termux
+/// where a desktop mpv window isn't available. The intent returns
+/// immediately, so no progress is reported.
+pub struct AndroidPlayer;
+
+pub fn is_termux() -> bool {
+    std::env::var_os("TERMUX_VERSION").is_some()
+        || std::env::var("PREFIX").is_ok_and(|p| p.contains("com.termux"))
+}
+
+impl AndroidPlayer {
+    pub fn is_available() -> bool {
+        is_termux() && (find_executable("am").is_some() || find_executable("termux-open").is_some())
+    }
+}
+
+impl Player for AndroidPlayer {
+    async fn play(
+        &self,
+        options: PlayOptions,
+        _navigator: Option<EpisodeNavigator>,
+    ) -> Result<f64> {
+        if find_executable("am").is_none() {
+            Command::new("termux-open")
+                .args(["--view", &options.url])
+                .status()
+                .await
+                .context("Failed to run termux-open")?;
+            return Ok(0.0);
+        }
+
+        let mut cmd = Command::new("am");
+        cmd.args([
+            "start",
+            "--user",
+            "0",
+            "-a",
+            "android.intent.action.VIEW",
+            "-t",
+            "video/*",
+            "-d",
+            &options.url,
+            "-n",
+            MPV_ANDROID_ACTIVITY,
+        ]);
+
+        if let Some(title) = &options.title {
+            cmd.args(["--es", "title", title]);
+        }
+        if let Some(start) = options
+            .start_time
+            .as_deref()
+            .and_then(|s| s.parse::<f64>().ok())
+        {
+            cmd.args(["--ei", "position", &((start * 1000.0) as i64).to_string()]);
+        }
+        // MX Player-style "headers" extra: alternating names and values.
+        // Players that don't understand it ignore it.
+        if let Some(headers) = &options.headers {
+            let flat = headers
+                .iter()
+                .flat_map(|(k, v)| [k.replace(',', "\\,"), v.replace(',', "\\,")])
+                .collect::<Vec<_>>()
+                .join(",");
+            cmd.args(["--esa", "headers", &flat]);
+        }
+
+        say!("▶️  Opening mpv-android...");
+        cmd.status().await.context("Failed to start mpv-android")?;
+        Ok(0.0)
+    }
+}
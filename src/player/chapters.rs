@@ -0,0 +1,155 @@
+//! Chapter markers (Opening, Episode, Ending, Preview) built from AniSkip
+//! times, handed to mpv as an FFmetadata chapters file so its chapter keys
+//! jump over openings and endings. The same chapters drive intro skipping.
+
+use crate::api::SkipTime;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What mpv does on reaching an opening, ending or recap.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipMode {
+    /// Nothing.
+    Off,
+    /// Offer to skip it with a key, shown on screen.
+    #[default]
+    Key,
+    /// Seek past it straight away, once per episode.
+    Auto,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    /// Seconds.
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Gaps shorter than this between skip intervals aren't worth a chapter.
+const MIN_CHAPTER_SECS: f64 = 1.0;
+
+fn skip_title(skip_type: &str) -> &'static str {
+    match skip_type {
+        "op" | "mixed-op" => "Opening",
+        "ed" | "mixed-ed" => "Ending",
+        "recap" => "Recap",
+        _ => "Skip",
+    }
+}
+
+/// The whole episode split into chapters: the skip intervals themselves and
+/// the stretches around them. Empty without any skip times.
+pub fn from_skip_times(times: &[SkipTime]) -> Vec<Chapter> {
+    let mut times: Vec<&SkipTime> = times.iter().collect();
+    times.sort_by(|a, b| a.interval.start_time.total_cmp(&b.interval.start_time));
+    let length = times.iter().map(|t| t.episode_length).fold(0.0, f64::max);
+
+    let mut chapters = Vec::new();
+    let mut cursor = 0.0;
+    let mut seen_ending = false;
+    for time in times {
+        let (start, end) = (time.interval.start_time, time.interval.end_time);
+        if start < cursor || end <= start {
+            continue;
+        }
+        push_gap(&mut chapters, cursor, start, seen_ending);
+        chapters.push(Chapter {
+            title: skip_title(&time.skip_type).to_string(),
+            start,
+            end,
+        });
+        seen_ending |= time.skip_type.ends_with("ed");
+        cursor = end;
+    }
+    if !chapters.is_empty() {
+        push_gap(&mut chapters, cursor, length, seen_ending);
+    }
+    chapters
+}
+
+/// The stretch between skip intervals: the prologue before the first one,
+/// the preview after the ending, the episode itself otherwise.
+fn push_gap(chapters: &mut Vec<Chapter>, start: f64, end: f64, seen_ending: bool) {
+    if end - start < MIN_CHAPTER_SECS {
+        return;
+    }
+    let title = match (chapters.is_empty(), seen_ending) {
+        (true, _) => "Prologue",
+        (_, true) => "Preview",
+        _ => "Episode",
+    };
+    chapters.push(Chapter {
+        title: title.to_string(),
+        start,
+        end,
+    });
+}
+
+/// The opening, ending or recap playing at `position`, by index, while more
+/// than a second of it is left.
+pub fn skip_at(chapters: &[Chapter], position: f64) -> Option<(usize, &Chapter)> {
+    chapters.iter().enumerate().find(|(_, c)| {
+        matches!(c.title.as_str(), "Opening" | "Ending" | "Recap")
+            && position >= c.start
+            && position < c.end - MIN_CHAPTER_SECS
+    })
+}
+
+/// The chapters in FFmetadata form, which mpv reads with `--chapters-file`.
+pub fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        out.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0) as u64,
+            (chapter.end * 1000.0) as u64,
+            chapter.title
+        ));
+    }
+    out
+}
+
+pub fn write_file(chapters: &[Chapter], path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, ffmetadata(chapters))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::SkipInterval;
+
+    fn skip(skip_type: &str, start_time: f64, end_time: f64) -> SkipTime {
+        SkipTime {
+            interval: SkipInterval {
+                start_time,
+                end_time,
+            },
+            skip_type: skip_type.to_string(),
+            episode_length: 1420.0,
+        }
+    }
+
+    #[test]
+    fn test_chapters_from_skip_times() {
+        let chapters = from_skip_times(&[skip("ed", 1300.0, 1390.0), skip("op", 60.0, 150.0)]);
+        let titles: Vec<&str> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec!["Prologue", "Opening", "Episode", "Ending", "Preview"]
+        );
+        assert_eq!(chapters[2].start, 150.0);
+        assert_eq!(chapters[4].end, 1420.0);
+        assert!(from_skip_times(&[]).is_empty());
+        assert_eq!(skip_at(&chapters, 90.0).map(|(i, _)| i), Some(1));
+        assert!(skip_at(&chapters, 149.5).is_none());
+        assert!(skip_at(&chapters, 30.0).is_none());
+
+        let meta = ffmetadata(&chapters[1..2]);
+        assert_eq!(
+            meta,
+            ";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=60000\nEND=150000\ntitle=Opening\n"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use super::find_executable;
+use super::traits::{EpisodeNavigator, PlayOptions, Player};
+use crate::util::split_args;
+use anyhow::{Context, Result};
+use tokio::process::Command;
+
+/// Any other player, run from `stream.custom_command`. Placeholders are
+/// filled in per argument after splitting, so titles with spaces or quotes
+/// stay one argument and nothing goes through a shell. There is no IPC:
+/// exiting successfully counts as watching the whole episode, anything else
+/// as not watching it.
+pub struct CustomPlayer {
+    pub template: String,
+}
+
+impl CustomPlayer {
+    pub fn is_available(&self) -> bool {
+        split_args(&self.template)
+            .first()
+            .is_some_and(|program| find_executable(program).is_some())
+    }
+}
+
+impl Player for CustomPlayer {
+    async fn play(
+        &self,
+        options: PlayOptions,
+        _navigator: Option<EpisodeNavigator>,
+    ) -> Result<f64> {
+        let args = command_line(&self.template, &options);
+        let (program, args) = args
+            .split_first()
+            .context("stream.custom_command is empty")?;
+
+        say!("▶️  Starting {}...", program);
+        let status = Command::new(program)
+            .args(args)
+            .status()
+            .await
+            .with_context(|| format!("Failed to spawn {}", program))?;
+        Ok(if status.success() { 100.0 } else { 0.0 })
+    }
+}
+
+/// The template's arguments with `{url}`, `{title}` and `{headers}`
+/// ("Name: value" pairs joined by commas) filled in.
+fn command_line(template: &str, options: &PlayOptions) -> Vec<String> {
+    let headers = options
+        .headers
+        .iter()
+        .flatten()
+        .map(|(k, v)| format!("{}: {}", k, v))
+        .collect::<Vec<_>>()
+        .join(",");
+    split_args(template)
+        .into_iter()
+        .map(|arg| {
+            arg.replace("{url}", &options.url)
+                .replace("{title}", options.title.as_deref().unwrap_or(""))
+                .replace("{headers}", &headers)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line() {
+        let options = PlayOptions {
+            url: "https://cdn.example/ep1.m3u8".to_string(),
+            title: Some("Frieren — Episode 1 (sub)".to_string()),
+            headers: Some(vec![
+                ("Referer".to_string(), "https://example.com".to_string()),
+                ("User-Agent".to_string(), "Mozilla/5.0".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            command_line(
+                r#"celluloid "--http-header-fields={headers}" --title='{title}' {url}"#,
+                &options
+            ),
+            vec![
+                "celluloid",
+                "--http-header-fields=Referer: https://example.com,User-Agent: Mozilla/5.0",
+                "--title=Frieren — Episode 1 (sub)",
+                "https://cdn.example/ep1.m3u8",
+            ]
+        );
+    }
+}
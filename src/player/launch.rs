@@ -0,0 +1,129 @@
+use super::find_executable;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// How player processes are started.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaunchMode {
+    /// Detect the sandbox/installation at runtime.
+    #[default]
+    Auto,
+    /// Run the player binary from `PATH`.
+    Direct,
+    /// ani-l runs inside Flatpak: start the host's player via `flatpak-spawn --host`.
+    HostSpawn,
+    /// The player itself is a Flatpak: start it via `flatpak run <app-id>`.
+    Flatpak,
+}
+
+/// Resolved way of starting one player binary.
+#[derive(Debug, Clone)]
+pub struct Launcher {
+    pub mode: LaunchMode,
+    binary: String,
+    flatpak_app_id: String,
+}
+
+impl Launcher {
+    pub fn new(binary: &str, mode: LaunchMode, flatpak_app_id: Option<&str>) -> Self {
+        let flatpak_app_id = flatpak_app_id
+            .map(str::to_string)
+            .unwrap_or_else(|| default_flatpak_id(binary).to_string());
+
+        let mode = match mode {
+            LaunchMode::Auto if in_flatpak_sandbox() => LaunchMode::HostSpawn,
+            LaunchMode::Auto
+                if find_executable(binary).is_none() && flatpak_installed(&flatpak_app_id) =>
+            {
+                LaunchMode::Flatpak
+            }
+            LaunchMode::Auto => LaunchMode::Direct,
+            other => other,
+        };
+
+        Self {
+            mode,
+            binary: binary.to_string(),
+            flatpak_app_id,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        match self.mode {
+            LaunchMode::Direct | LaunchMode::Auto => find_executable(&self.binary).is_some(),
+            // The host PATH isn't visible from inside the sandbox; ask the host.
+            LaunchMode::HostSpawn => Command::new("flatpak-spawn")
+                .args(["--host", "which", &self.binary])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success()),
+            LaunchMode::Flatpak => flatpak_installed(&self.flatpak_app_id),
+        }
+    }
+
+    /// Program and leading arguments to run the player.
+    pub fn program(&self) -> (String, Vec<String>) {
+        match self.mode {
+            LaunchMode::Direct | LaunchMode::Auto => (self.binary.clone(), vec![]),
+            LaunchMode::HostSpawn => (
+                "flatpak-spawn".to_string(),
+                vec!["--host".to_string(), self.binary.clone()],
+            ),
+            LaunchMode::Flatpak => (
+                "flatpak".to_string(),
+                vec!["run".to_string(), self.flatpak_app_id.clone()],
+            ),
+        }
+    }
+
+    pub fn command(&self) -> Command {
+        let (program, args) = self.program();
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        cmd
+    }
+
+    /// Directory for IPC sockets that both ani-l and the player can reach.
+    ///
+    /// Flatpak sandboxes get a private `/tmp`, but `$XDG_RUNTIME_DIR/app/<id>`
+    /// is the same path inside the sandbox and on the host.
+    pub fn ipc_dir(&self) -> PathBuf {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+        let shared = match (self.mode, runtime_dir) {
+            (LaunchMode::HostSpawn, Some(dir)) => std::env::var("FLATPAK_ID")
+                .ok()
+                .map(|id| dir.join("app").join(id)),
+            (LaunchMode::Flatpak, Some(dir)) => Some(dir.join("app").join(&self.flatpak_app_id)),
+            _ => None,
+        };
+
+        match shared {
+            Some(dir) if std::fs::create_dir_all(&dir).is_ok() => dir,
+            _ => std::env::temp_dir(),
+        }
+    }
+}
+
+pub fn in_flatpak_sandbox() -> bool {
+    std::env::var_os("FLATPAK_ID").is_some() || Path::new("/.flatpak-info").exists()
+}
+
+fn flatpak_installed(app_id: &str) -> bool {
+    find_executable("flatpak").is_some()
+        && Command::new("flatpak")
+            .args(["info", app_id])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+}
+
+fn default_flatpak_id(binary: &str) -> &'static str {
+    match binary {
+        "vlc" => "org.videolan.VLC",
+        _ => "io.mpv.Mpv",
+    }
+}
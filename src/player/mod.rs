@@ -1,2 +1,232 @@
+pub mod android;
+pub mod chapters;
+pub mod custom;
+pub mod launch;
 pub mod mpv;
+pub mod suspend;
 pub mod traits;
+pub mod vlc;
+
+use crate::config::StreamConfig;
+use crate::exit::Failure;
+use anyhow::Result;
+use launch::Launcher;
+use std::path::PathBuf;
+use traits::{EpisodeNavigator, PlayOptions, Player};
+
+/// The players ani-l can drive, chosen at runtime from the config.
+pub enum AnyPlayer {
+    Mpv(mpv::MpvPlayer),
+    Vlc(vlc::VlcPlayer),
+    Android(android::AndroidPlayer),
+    Custom(custom::CustomPlayer),
+}
+
+impl Player for AnyPlayer {
+    async fn play(&self, options: PlayOptions, navigator: Option<EpisodeNavigator>) -> Result<f64> {
+        match self {
+            AnyPlayer::Mpv(p) => p.play(options, navigator).await,
+            AnyPlayer::Vlc(p) => p.play(options, navigator).await,
+            AnyPlayer::Android(p) => p.play(options, navigator).await,
+            AnyPlayer::Custom(p) => p.play(options, navigator).await,
+        }
+    }
+}
+
+impl AnyPlayer {
+    /// Plays without video. Android hands the stream to another app and
+    /// a custom command is run as written, so both ignore this.
+    pub fn set_audio_only(&mut self) {
+        match self {
+            AnyPlayer::Mpv(p) => p.audio_only = true,
+            AnyPlayer::Vlc(p) => p.audio_only = true,
+            AnyPlayer::Android(_) | AnyPlayer::Custom(_) => {}
+        }
+    }
+}
+
+pub struct PlayerChoice {
+    pub player: AnyPlayer,
+    pub name: String,
+    /// Set when the configured player was missing and a fallback was used.
+    pub replaced: Option<String>,
+}
+
+/// Picks the configured player, or the first installed fallback. Fails with
+/// an install hint when none of them are available, before any stream is
+/// resolved.
+pub fn choose_player(stream: &StreamConfig) -> Result<PlayerChoice> {
+    let candidates = std::iter::once(&stream.player).chain(stream.fallback_players.iter());
+
+    for name in candidates {
+        if name == "mpv-android" {
+            if android::AndroidPlayer::is_available() {
+                return Ok(PlayerChoice {
+                    player: AnyPlayer::Android(android::AndroidPlayer),
+                    name: name.clone(),
+                    replaced: (name != &stream.player).then(|| stream.player.clone()),
+                });
+            }
+            continue;
+        }
+        if name == "custom" {
+            let Some(template) = &stream.custom_command else {
+                log::warn!("player \"custom\" needs stream.custom_command, skipping");
+                continue;
+            };
+            let player = custom::CustomPlayer {
+                template: template.clone(),
+            };
+            if player.is_available() {
+                return Ok(PlayerChoice {
+                    player: AnyPlayer::Custom(player),
+                    name: name.clone(),
+                    replaced: (name != &stream.player).then(|| stream.player.clone()),
+                });
+            }
+            continue;
+        }
+
+        let app_id_override = stream
+            .flatpak_app_id
+            .as_deref()
+            .filter(|_| name == &stream.player);
+        let launcher = Launcher::new(name, stream.launch_mode, app_id_override);
+        if !launcher.is_available() {
+            continue;
+        }
+        let player = match name.as_str() {
+            "mpv" => AnyPlayer::Mpv(mpv::MpvPlayer {
+                launcher,
+                terminal_vo: is_headless().then(|| terminal_vo(&stream.terminal_vo)),
+                ipc_socket: None,
+                pause_on_suspend: stream.pause_on_suspend,
+                pause_on_lock: stream.pause_on_lock,
+                chapter_markers: stream.chapters,
+                skip: stream.skip,
+                autoplay_next: stream.autoplay_next,
+                fullscreen: stream.fullscreen,
+                geometry: stream.geometry.clone(),
+                audio_only: false,
+            }),
+            "vlc" => AnyPlayer::Vlc(vlc::VlcPlayer {
+                launcher,
+                fullscreen: stream.fullscreen.unwrap_or(false),
+                audio_only: false,
+            }),
+            other => {
+                log::warn!("Unknown player '{}' in config, skipping", other);
+                continue;
+            }
+        };
+        return Ok(PlayerChoice {
+            player,
+            name: name.clone(),
+            replaced: (name != &stream.player).then(|| stream.player.clone()),
+        });
+    }
+
+    // On Termux a desktop player usually can't open a window anyway.
+    if android::AndroidPlayer::is_available() {
+        return Ok(PlayerChoice {
+            player: AnyPlayer::Android(android::AndroidPlayer),
+            name: "mpv-android".to_string(),
+            replaced: Some(stream.player.clone()),
+        });
+    }
+
+    if stream.player == "custom" {
+        return Err(anyhow::Error::new(Failure::Player).context(format!(
+            "stream.custom_command ({}) can't be run; check that its program is on PATH",
+            stream.custom_command.as_deref().unwrap_or("not set")
+        )));
+    }
+    Err(anyhow::Error::new(Failure::Player).context(format!(
+        "{} is not installed. Install it with: {}",
+        stream.player,
+        install_hint(&stream.player)
+    )))
+}
+
+/// True when there is no graphical session to open a player window in, e.g.
+/// over SSH or on a server.
+pub fn is_headless() -> bool {
+    if cfg!(any(target_os = "macos", target_os = "windows")) || android::is_termux() {
+        return false;
+    }
+    std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none()
+}
+
+/// mpv video output to use in a terminal. "auto" picks kitty graphics or
+/// sixel where the terminal is known to support them, and `tct`
+/// (true-colour blocks) otherwise.
+pub fn terminal_vo(setting: &str) -> String {
+    if setting != "auto" {
+        return setting.to_string();
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    if term.contains("kitty") || std::env::var_os("KITTY_WINDOW_ID").is_some() {
+        "kitty".to_string()
+    } else if term_program == "WezTerm" || term.contains("foot") || term.contains("mlterm") {
+        "sixel".to_string()
+    } else {
+        "tct".to_string()
+    }
+}
+
+/// Looks `name` up on `PATH` the way a shell would.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).find_map(|dir| {
+        let candidate = dir.join(name);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let exe = dir.join(format!("{}.exe", name));
+            if exe.is_file() {
+                return Some(exe);
+            }
+        }
+        None
+    })
+}
+
+/// The install command for `package` on this OS.
+pub fn install_hint(package: &str) -> String {
+    #[cfg(target_os = "macos")]
+    return format!("brew install {}", package);
+
+    #[cfg(target_os = "windows")]
+    return format!(
+        "scoop install {} (or download it from its website)",
+        package
+    );
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let managers = [
+            ("apt", "sudo apt install"),
+            ("pacman", "sudo pacman -S"),
+            ("dnf", "sudo dnf install"),
+            ("zypper", "sudo zypper install"),
+            ("apk", "sudo apk add"),
+            ("xbps-install", "sudo xbps-install"),
+            ("nix-env", "nix-env -iA nixpkgs."),
+            ("pkg", "pkg install"),
+        ];
+        managers
+            .iter()
+            .find(|(bin, _)| find_executable(bin).is_some())
+            .map(|(_, cmd)| {
+                if cmd.ends_with('.') {
+                    format!("{}{}", cmd, package)
+                } else {
+                    format!("{} {}", cmd, package)
+                }
+            })
+            .unwrap_or_else(|| format!("your package manager ({})", package))
+    }
+}
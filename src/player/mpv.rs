@@ -1,24 +1,68 @@
+use super::chapters::{self, SkipMode};
+use super::launch::Launcher;
+use super::suspend::{PauseReason, SleepWatch};
 use super::traits::{EpisodeAction, EpisodeNavigator, PlayOptions, Player};
+use crate::status::{self, PlaybackStatus};
 use anyhow::{Context, Result};
+use chrono::Utc;
+use directories::ProjectDirs;
 use serde_json::json;
-use std::process::Command;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
+use tokio::net::unix::OwnedWriteHalf;
 use tokio::time::sleep;
 
-pub struct MpvPlayer;
+pub struct MpvPlayer {
+    pub launcher: Launcher,
+    /// Render into the terminal with this `--vo` instead of opening a window.
+    pub terminal_vo: Option<String>,
+    /// Fixed IPC socket path, so something else (e.g. a watch-together room)
+    /// can drive the player alongside ani-l. A random one is used otherwise.
+    pub ipc_socket: Option<String>,
+    pub pause_on_suspend: bool,
+    pub pause_on_lock: bool,
+    /// Hand the episode's chapters to mpv (`stream.chapters`).
+    pub chapter_markers: bool,
+    pub skip: SkipMode,
+    /// Load the next episode when one ends, as if Shift+N were pressed.
+    pub autoplay_next: bool,
+    /// `None` follows the last window's state.
+    pub fullscreen: Option<bool>,
+    pub geometry: Option<String>,
+    /// `--no-video`: sound only, no window.
+    pub audio_only: bool,
+}
 
 impl Player for MpvPlayer {
     async fn play(&self, options: PlayOptions, navigator: Option<EpisodeNavigator>) -> Result<f64> {
-        let socket_id = rand::random::<u32>();
-        let mut socket_path = std::env::temp_dir();
-        socket_path.push(format!("ani-l-mpv-{}.sock", socket_id));
-        let socket_path = socket_path.to_string_lossy().to_string();
-
-        let mut cmd = Command::new("mpv");
-        cmd.arg("--force-window=yes")
-            .arg("--keep-open=yes")
+        let socket_path = self.ipc_socket.clone().unwrap_or_else(|| {
+            let socket_id = rand::random::<u32>();
+            let mut socket_path = self.launcher.ipc_dir();
+            socket_path.push(format!("ani-l-mpv-{}.sock", socket_id));
+            socket_path.to_string_lossy().to_string()
+        });
+
+        let mut cmd = self.launcher.command();
+        match &self.terminal_vo {
+            _ if self.audio_only => {
+                cmd.arg("--no-video");
+            }
+            Some(vo) => {
+                cmd.arg(format!("--vo={}", vo));
+            }
+            None => {
+                cmd.arg("--force-window=yes");
+                if self.fullscreen.or_else(remembered_fullscreen) == Some(true) {
+                    cmd.arg("--fullscreen=yes");
+                }
+                if let Some(geometry) = &self.geometry {
+                    cmd.arg(format!("--geometry={}", geometry));
+                }
+            }
+        }
+        cmd.arg("--keep-open=yes")
             .arg(format!("--input-ipc-server={}", socket_path))
             .arg("--term-osd-bar")
             .arg("--term-status-msg=Status: ${time-pos} / ${duration} (${percent-pos}%)");
@@ -34,7 +78,7 @@ impl Player for MpvPlayer {
             }
         }
         if let Some(title) = &options.title {
-            cmd.arg(format!("--title={}", title));
+            cmd.arg(format!("--title={}", escape_property_expansion(title)));
         }
         if let Some(start) = &options.start_time {
             cmd.arg(format!("--start={}", start));
@@ -45,10 +89,17 @@ impl Player for MpvPlayer {
                 cmd.arg(format!("--sub-file={}", sub));
             }
         }
+        let chapters_path = format!("{}.chapters", socket_path);
+        if self.chapter_markers
+            && !options.chapters.is_empty()
+            && chapters::write_file(&options.chapters, Path::new(&chapters_path)).is_ok()
+        {
+            cmd.arg(format!("--chapters-file={}", chapters_path));
+        }
 
         cmd.arg(&options.url);
 
-        println!("▶️  Starting MPV (IPC)...");
+        say!("▶️  Starting MPV (IPC)...");
         let mut child = cmd.spawn().context("Failed to spawn MPV")?;
 
         let mut stream = None;
@@ -63,6 +114,24 @@ impl Player for MpvPlayer {
         #[allow(unused_mut)]
         let mut max_percentage = 0.0;
 
+        let mut now_playing = PlaybackStatus {
+            title: options
+                .show_title
+                .clone()
+                .or(options.title.clone())
+                .unwrap_or_default(),
+            episode: options.episode.clone(),
+            ..Default::default()
+        };
+        let mut last_status_write = Instant::now() - Duration::from_secs(1);
+        let mut fullscreen = None;
+        // The current episode's chapters, and the skippable ones already
+        // offered or skipped.
+        let mut skips = options.chapters.clone();
+        let mut handled: Vec<usize> = Vec::new();
+        // Whether the current episode has been reported watched.
+        let mut reported = false;
+
         if let Some(stream) = stream {
             let (reader, mut writer) = stream.into_split();
             let buf_reader = BufReader::new(reader);
@@ -73,6 +142,7 @@ impl Player for MpvPlayer {
                 ("N", "script-message next-episode"),
                 ("shift+p", "script-message previous-episode"),
                 ("P", "script-message previous-episode"),
+                ("k", "script-message skip-segment"),
             ];
 
             for (key, cmd_str) in bindings {
@@ -82,12 +152,53 @@ impl Player for MpvPlayer {
             }
             let _ = writer.flush().await;
 
-            let observe_cmd = json!({ "command": ["observe_property", 1, "percent-pos"] });
-            let _ = writer.write_all(observe_cmd.to_string().as_bytes()).await;
-            let _ = writer.write_all(b"\n").await;
+            for (id, property) in [
+                "percent-pos",
+                "time-pos",
+                "duration",
+                "pause",
+                "fullscreen",
+                "eof-reached",
+            ]
+            .iter()
+            .enumerate()
+            {
+                let observe_cmd = json!({ "command": ["observe_property", id + 1, property] });
+                let _ = writer.write_all(observe_cmd.to_string().as_bytes()).await;
+                let _ = writer.write_all(b"\n").await;
+            }
             let _ = writer.flush().await;
 
+            let mut sleep_watch = SleepWatch::new(self.pause_on_suspend, self.pause_on_lock);
+            let mut last_sleep_check = Instant::now();
+
             loop {
+                // Checked here rather than on the timer below, which never
+                // fires while mpv keeps reporting the position.
+                if last_sleep_check.elapsed() >= Duration::from_millis(500) {
+                    last_sleep_check = Instant::now();
+                    if let Some(reason) = sleep_watch.poll().await {
+                        let message = match reason {
+                            PauseReason::Resumed => "Paused after sleep",
+                            PauseReason::Locked => "Paused while locked",
+                        };
+                        for cmd in [
+                            json!({ "command": ["set_property", "pause", true] }),
+                            json!({ "command": ["show-text", message, "5000"] }),
+                        ] {
+                            let _ = writer.write_all(cmd.to_string().as_bytes()).await;
+                            let _ = writer.write_all(b"\n").await;
+                        }
+                        let _ = writer.flush().await;
+                        // Checkpoint where playback stopped in case the
+                        // machine goes back to sleep before mpv answers.
+                        now_playing.paused = true;
+                        now_playing.updated = Utc::now();
+                        let _ = status::write(&now_playing);
+                        last_status_write = Instant::now();
+                    }
+                }
+
                 tokio::select! {
                     _ = sleep(Duration::from_millis(100)) => {
                         if let Ok(Some(_)) = child.try_wait() {
@@ -103,6 +214,11 @@ impl Player for MpvPlayer {
                                             if let Some(args) = val.get("args").and_then(|a| a.as_array())
                                                 && !args.is_empty() {
 
+                                                    if args[0].as_str() == Some("skip-segment")
+                                                        && let Some((_, chapter)) = chapters::skip_at(&skips, now_playing.position) {
+                                                            skip_chapter(&mut writer, chapter).await;
+                                                        }
+
                                                     let action = match args[0].as_str() {
                                                         Some("next-episode") => Some(EpisodeAction::Next),
                                                         Some("previous-episode") => Some(EpisodeAction::Previous),
@@ -122,21 +238,43 @@ impl Player for MpvPlayer {
 
                                                         match nav(act).await {
                                                             Ok(Some(new_opts)) => {
+                                                                // Applies to the next file loaded; an empty path clears the previous episode's.
+                                                                skips = new_opts.chapters.clone();
+                                                                handled.clear();
+                                                                let chapters_file = if self.chapter_markers
+                                                                    && !new_opts.chapters.is_empty()
+                                                                    && chapters::write_file(&new_opts.chapters, Path::new(&chapters_path)).is_ok()
+                                                                {
+                                                                    chapters_path.clone()
+                                                                } else {
+                                                                    String::new()
+                                                                };
+                                                                let chapters_cmd = json!({ "command": ["set_property", "chapters-file", chapters_file] });
+                                                                let _ = writer.write_all(chapters_cmd.to_string().as_bytes()).await;
+                                                                let _ = writer.write_all(b"\n").await;
                                                                 let load_cmd = json!({ "command": ["loadfile", new_opts.url] });
                                                                 let _ = writer.write_all(load_cmd.to_string().as_bytes()).await;
                                                                 let _ = writer.write_all(b"\n").await;
 
+                                                                now_playing.title = new_opts.show_title.clone().or(new_opts.title.clone()).unwrap_or_default();
+                                                                now_playing.episode = new_opts.episode.clone();
+
                                                                 if let Some(t) = new_opts.title {
-                                                                    let title_cmd = json!({ "command": ["set_property", "title", t] });
+                                                                    let title_cmd = json!({ "command": ["set_property", "title", escape_property_expansion(&t)] });
                                                                     let _ = writer.write_all(title_cmd.to_string().as_bytes()).await;
                                                                     let _ = writer.write_all(b"\n").await;
                                                                 }
 
                                                                 max_percentage = 0.0;
+                                                                reported = false;
                                                                 let _ = writer.flush().await;
                                                             }
                                                             Ok(None) => {
-                                                                let _ = writer.write_all(json!({ "command": ["show-text", format!("No {} episode found", label.to_lowercase())] }).to_string().as_bytes()).await;
+                                                                let edge = match act {
+                                                                    EpisodeAction::Next => "Last episode",
+                                                                    EpisodeAction::Previous => "First episode",
+                                                                };
+                                                                let _ = writer.write_all(json!({ "command": ["show-text", edge, "3000"] }).to_string().as_bytes()).await;
                                                                 let _ = writer.write_all(b"\n").await;
                                                                 let _ = writer.flush().await;
                                                             }
@@ -149,10 +287,59 @@ impl Player for MpvPlayer {
                                                     }
                                                 }
                                         } else if event == "property-change"
-                                            && let Some(name) = val.get("name").and_then(|n| n.as_str())
-                                                && name == "percent-pos"
-                                                    && let Some(p) = val.get("data").and_then(|d| d.as_f64())
-                                                        && p > max_percentage { max_percentage = p; }
+                                            && let Some(name) = val.get("name").and_then(|n| n.as_str()) {
+                                                let data = val.get("data");
+                                                match name {
+                                                    "percent-pos" => {
+                                                        if let Some(p) = data.and_then(|d| d.as_f64()) {
+                                                            now_playing.percent = p;
+                                                            if p > max_percentage { max_percentage = p; }
+                                                            if let Some(report) = &options.report_complete
+                                                                && !reported
+                                                                && p >= report.at
+                                                                && let Some(episode) = &now_playing.episode {
+                                                                    reported = true;
+                                                                    let _ = report.tx.send(episode.clone());
+                                                                }
+                                                        }
+                                                    }
+                                                    "time-pos" => {
+                                                        if let Some(t) = data.and_then(|d| d.as_f64()) {
+                                                            now_playing.position = t;
+                                                            if self.skip != SkipMode::Off
+                                                                && let Some((i, chapter)) = chapters::skip_at(&skips, t)
+                                                                && !handled.contains(&i) {
+                                                                    handled.push(i);
+                                                                    if self.skip == SkipMode::Auto {
+                                                                        skip_chapter(&mut writer, chapter).await;
+                                                                    } else {
+                                                                        let shown = ((chapter.end - t).min(10.0) * 1000.0) as i64;
+                                                                        send(&mut writer, json!({ "command": ["show-text", skip_button(chapter), shown.to_string()] })).await;
+                                                                    }
+                                                                }
+                                                        }
+                                                    }
+                                                    "duration" => now_playing.duration = data.and_then(|d| d.as_f64()),
+                                                    "pause" => now_playing.paused = data.and_then(|d| d.as_bool()).unwrap_or(false),
+                                                    "fullscreen" => fullscreen = data.and_then(|d| d.as_bool()),
+                                                    // With --keep-open the last frame stays up; ask
+                                                    // for the next episode the way Shift+N does, so
+                                                    // the handler above loads it.
+                                                    "eof-reached"
+                                                        if self.autoplay_next
+                                                            && navigator.is_some()
+                                                            && data.and_then(|d| d.as_bool()) == Some(true) => {
+                                                            send(&mut writer, json!({ "command": ["script-message", "next-episode"] })).await;
+                                                        }
+                                                    _ => {}
+                                                }
+
+                                                if last_status_write.elapsed() >= Duration::from_secs(1) || name == "pause" {
+                                                    now_playing.updated = Utc::now();
+                                                    let _ = status::write(&now_playing);
+                                                    last_status_write = Instant::now();
+                                                }
+                                            }
                                     }
                             }
                             Ok(None) => break,
@@ -167,10 +354,73 @@ impl Player for MpvPlayer {
 
         let _ = child.wait();
 
-        if std::path::Path::new(&socket_path).exists() {
+        if Path::new(&socket_path).exists() {
             let _ = std::fs::remove_file(&socket_path);
         }
+        let _ = std::fs::remove_file(&chapters_path);
+        status::clear();
+        if self.terminal_vo.is_none()
+            && !self.audio_only
+            && self.fullscreen.is_none()
+            && let Some(fullscreen) = fullscreen
+        {
+            remember_fullscreen(fullscreen);
+        }
 
         Ok(max_percentage)
     }
 }
+
+async fn send(writer: &mut OwnedWriteHalf, command: serde_json::Value) {
+    let _ = writer.write_all(command.to_string().as_bytes()).await;
+    let _ = writer.write_all(b"\n").await;
+    let _ = writer.flush().await;
+}
+
+async fn skip_chapter(writer: &mut OwnedWriteHalf, chapter: &chapters::Chapter) {
+    send(
+        writer,
+        json!({ "command": ["seek", chapter.end, "absolute"] }),
+    )
+    .await;
+    let message = match chapter.title.as_str() {
+        "Opening" => t!("mpv.skipped_opening"),
+        "Ending" => t!("mpv.skipped_ending"),
+        _ => t!("mpv.skipped_recap"),
+    };
+    send(writer, json!({ "command": ["show-text", message, "2000"] })).await;
+}
+
+/// The offer to skip, drawn in the bottom-right corner where streaming
+/// sites put their skip button.
+fn skip_button(chapter: &chapters::Chapter) -> String {
+    let text = match chapter.title.as_str() {
+        "Opening" => t!("mpv.skip_opening"),
+        "Ending" => t!("mpv.skip_ending"),
+        _ => t!("mpv.skip_recap"),
+    };
+    format!("${{osd-ass-cc/0}}{{\\an3}}{{\\fs36}}{}", text)
+}
+
+fn window_state_path() -> Option<PathBuf> {
+    ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+        .map(|dirs| dirs.data_dir().join("mpv_window.json"))
+}
+
+/// Whether the last mpv window was fullscreen when it closed.
+fn remembered_fullscreen() -> Option<bool> {
+    let content = std::fs::read_to_string(window_state_path()?).ok()?;
+    let state: serde_json::Value = serde_json::from_str(&content).ok()?;
+    state.get("fullscreen")?.as_bool()
+}
+
+fn remember_fullscreen(fullscreen: bool) {
+    if let Some(path) = window_state_path() {
+        let _ = std::fs::write(path, json!({ "fullscreen": fullscreen }).to_string());
+    }
+}
+
+/// mpv expands `${...}` in the title option; a literal `$` must be doubled.
+fn escape_property_expansion(title: &str) -> String {
+    title.replace('$', "$$")
+}
@@ -0,0 +1,91 @@
+//! Notices when the machine slept or the session was locked, so playback
+//! pauses instead of running on with nobody watching.
+
+use std::time::{Duration, Instant, SystemTime};
+use tokio::process::Command;
+
+/// The wall clock running this much further ahead than the monotonic clock
+/// (which stops during suspend) means the system slept.
+const SUSPEND_GAP: Duration = Duration::from_secs(5);
+
+const LOCK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq)]
+pub enum PauseReason {
+    Resumed,
+    Locked,
+}
+
+pub struct SleepWatch {
+    on_suspend: bool,
+    on_lock: bool,
+    wall: SystemTime,
+    mono: Instant,
+    locked: bool,
+    last_lock_poll: Instant,
+}
+
+impl SleepWatch {
+    pub fn new(on_suspend: bool, on_lock: bool) -> Self {
+        Self {
+            on_suspend,
+            on_lock,
+            wall: SystemTime::now(),
+            mono: Instant::now(),
+            locked: false,
+            last_lock_poll: Instant::now(),
+        }
+    }
+
+    /// Why playback should pause now, if it should. Meant to be called
+    /// every few hundred milliseconds.
+    pub async fn poll(&mut self) -> Option<PauseReason> {
+        let (wall, mono) = (SystemTime::now(), Instant::now());
+        let wall_elapsed = wall.duration_since(self.wall).unwrap_or_default();
+        let slept = slept_between(wall_elapsed, mono - self.mono);
+        self.wall = wall;
+        self.mono = mono;
+        if self.on_suspend && slept {
+            return Some(PauseReason::Resumed);
+        }
+
+        if self.on_lock && self.last_lock_poll.elapsed() >= LOCK_POLL_INTERVAL {
+            self.last_lock_poll = Instant::now();
+            let was_locked = std::mem::replace(&mut self.locked, session_locked().await);
+            if self.locked && !was_locked {
+                return Some(PauseReason::Locked);
+            }
+        }
+        None
+    }
+}
+
+fn slept_between(wall_elapsed: Duration, mono_elapsed: Duration) -> bool {
+    wall_elapsed > mono_elapsed + SUSPEND_GAP
+}
+
+/// Asks logind whether the screen locker is up. Anything that goes wrong
+/// (no systemd, not Linux) counts as unlocked.
+async fn session_locked() -> bool {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".to_string());
+    let output = Command::new("loginctl")
+        .args(["show-session", &session, "--property=LockedHint", "--value"])
+        .output();
+    match tokio::time::timeout(Duration::from_secs(2), output).await {
+        Ok(Ok(output)) => String::from_utf8_lossy(&output.stdout).trim() == "yes",
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slept_between() {
+        let secs = Duration::from_secs;
+        assert!(!slept_between(secs(1), secs(1)));
+        assert!(!slept_between(secs(4), Duration::ZERO));
+        assert!(slept_between(secs(3600), Duration::from_millis(100)));
+    }
+}
@@ -1,6 +1,8 @@
+use super::chapters::Chapter;
 use anyhow::Result;
 use std::future::Future;
 use std::pin::Pin;
+use tokio::sync::mpsc;
 
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
@@ -10,6 +12,8 @@ pub enum EpisodeAction {
     Previous,
 }
 
+/// Resolves the next or previous episode. `Ok(None)` means there is none:
+/// the current episode is the first or last one available.
 pub type EpisodeNavigator =
     Box<dyn Fn(EpisodeAction) -> BoxFuture<'static, Result<Option<PlayOptions>>> + Send + Sync>;
 
@@ -17,9 +21,44 @@ pub type EpisodeNavigator =
 pub struct PlayOptions {
     pub url: String,
     pub title: Option<String>,
+    /// Show name and episode, kept separately from the window title for the
+    /// playback status file.
+    pub show_title: Option<String>,
+    pub episode: Option<String>,
     pub start_time: Option<String>,
     pub headers: Option<Vec<(String, String)>>,
     pub subtitles: Option<Vec<String>>,
+    /// Episode chapters for players that take them (mpv).
+    pub chapters: Vec<Chapter>,
+    /// Players that track the position while playing (mpv) report episodes
+    /// here as soon as they're watched.
+    pub report_complete: Option<CompletionReport>,
+}
+
+/// Where to send each episode's `PlayOptions::episode` the first time
+/// playback passes `at` percent, including episodes reached with Next.
+#[derive(Debug, Clone)]
+pub struct CompletionReport {
+    pub at: f64,
+    pub tx: mpsc::UnboundedSender<String>,
+}
+
+/// Builds the player window title as "<Title> — Episode N (sub|dub)",
+/// stripping characters that window managers and status bars choke on.
+pub fn window_title(title: &str, episode: &str, translation_type: &str) -> String {
+    let raw = format!(
+        "{} — Episode {} ({})",
+        title.trim(),
+        episode.trim(),
+        translation_type
+    );
+    raw.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .filter(|c| !matches!(c, '\u{200B}'..='\u{200F}' | '\u{FEFF}'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 pub trait Player {
@@ -29,3 +68,16 @@ pub trait Player {
         navigator: Option<EpisodeNavigator>,
     ) -> impl Future<Output = Result<f64>> + Send;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_title_is_sanitized() {
+        assert_eq!(
+            window_title("Frieren:\tBeyond\nJourney's End\u{200B} ", "5", "sub"),
+            "Frieren: Beyond Journey's End — Episode 5 (sub)"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use super::launch::Launcher;
+use super::traits::{EpisodeNavigator, PlayOptions, Player};
+use anyhow::{Context, Result};
+use tokio::process::Command;
+
+/// Fallback player for systems without mpv. VLC has no IPC we use, so episode
+/// navigation isn't available and no watch progress is reported.
+pub struct VlcPlayer {
+    pub launcher: Launcher,
+    pub fullscreen: bool,
+    pub audio_only: bool,
+}
+
+impl Player for VlcPlayer {
+    async fn play(
+        &self,
+        options: PlayOptions,
+        _navigator: Option<EpisodeNavigator>,
+    ) -> Result<f64> {
+        let mut cmd = Command::from(self.launcher.command());
+        cmd.arg("--play-and-exit");
+        if self.audio_only {
+            cmd.arg("--no-video");
+        } else if self.fullscreen {
+            cmd.arg("--fullscreen");
+        }
+
+        for (key, value) in options.headers.iter().flatten() {
+            match key.to_lowercase().as_str() {
+                "user-agent" => {
+                    cmd.arg(format!("--http-user-agent={}", value));
+                }
+                "referer" => {
+                    cmd.arg(format!("--http-referrer={}", value));
+                }
+                _ => {}
+            }
+        }
+        if let Some(title) = &options.title {
+            cmd.arg(format!("--meta-title={}", title));
+        }
+        if let Some(start) = &options.start_time {
+            cmd.arg(format!("--start-time={}", start));
+        }
+        for sub in options.subtitles.iter().flatten() {
+            cmd.arg(format!("--sub-file={}", sub));
+        }
+        cmd.arg(&options.url);
+
+        say!("▶️  Starting VLC...");
+        cmd.status().await.context("Failed to spawn VLC")?;
+        Ok(0.0)
+    }
+}
@@ -0,0 +1,78 @@
+//! M3U export of resolved streams, for players and TV boxes that can't run
+//! ani-l themselves.
+
+use crate::player::traits::PlayOptions;
+use anyhow::{Context, Result};
+
+/// Episodes named by a range like "1-12", a list like "1,3,5", or a mix.
+pub fn parse_episodes(spec: &str) -> Result<Vec<u32>> {
+    let mut episodes = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let invalid = || format!("Invalid episode range '{}'", part);
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let from: u32 = from.trim().parse().with_context(invalid)?;
+                let to: u32 = to.trim().parse().with_context(invalid)?;
+                if from > to {
+                    anyhow::bail!(invalid());
+                }
+                episodes.extend(from..=to);
+            }
+            None => episodes.push(part.parse().with_context(invalid)?),
+        }
+    }
+    if episodes.is_empty() {
+        anyhow::bail!("No episodes in '{}'", spec);
+    }
+    Ok(episodes)
+}
+
+/// An extended M3U. Request headers become `#EXTVLCOPT` lines, which VLC,
+/// Kodi and most IPTV apps understand.
+pub fn render(entries: &[PlayOptions]) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+    for entry in entries {
+        let title = entry.title.as_deref().unwrap_or(&entry.url);
+        m3u.push_str(&format!("#EXTINF:-1,{}\n", title.replace('\n', " ")));
+        for (name, value) in entry.headers.iter().flatten() {
+            let option = match name.to_ascii_lowercase().as_str() {
+                "user-agent" => "http-user-agent",
+                "referer" => "http-referrer",
+                _ => continue,
+            };
+            m3u.push_str(&format!("#EXTVLCOPT:{}={}\n", option, value));
+        }
+        m3u.push_str(&entry.url);
+        m3u.push('\n');
+    }
+    m3u
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playlist() {
+        assert_eq!(parse_episodes("1-3, 7").unwrap(), vec![1, 2, 3, 7]);
+        assert!(parse_episodes("3-1").is_err());
+        assert!(parse_episodes("").is_err());
+
+        let entry = PlayOptions {
+            url: "https://cdn.example/ep1.m3u8".to_string(),
+            title: Some("Frieren — Episode 1 (sub)".to_string()),
+            headers: Some(vec![
+                ("Referer".to_string(), "https://allanime.day/".to_string()),
+                ("X-Other".to_string(), "skipped".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            render(&[entry]),
+            "#EXTM3U\n\
+             #EXTINF:-1,Frieren — Episode 1 (sub)\n\
+             #EXTVLCOPT:http-referrer=https://allanime.day/\n\
+             https://cdn.example/ep1.m3u8\n"
+        );
+    }
+}
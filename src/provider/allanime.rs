@@ -1,34 +1,178 @@
+use crate::cassette;
+use crate::config::{Config, NetworkConfig};
+use crate::net;
 use crate::player::traits::PlayOptions;
+use crate::provider::hls;
 use crate::provider::models::*;
+use crate::provider::schema::{self, QueryVariant};
 use anyhow::{Context, Result};
 use log::{debug, warn};
-use reqwest::{Client, header};
-use serde_json::json;
+use reqwest::{Client, Url, header};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
 use urlencoding::encode;
 
 const API_ENDPOINT: &str = "https://api.allanime.day/api";
 const REFERER: &str = "https://allanime.to/";
+/// What the video hosts expect the player to have come from.
+const STREAM_REFERER: &str = "https://allanime.day/";
 const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
 
+/// Source names in preference order.
+pub const SOURCE_PRIORITY: &[&str] = &[
+    "S-mp4", "Luf-mp4", "Luf-Mp4", "Sak", "Kir", "Default", "Yt-mp4",
+];
+
+const WETRANSFER_API: &str = "https://wetransfer.com/api/v4/transfers";
+
+/// Where manga page paths without a host of their own live.
+const PAGE_HOST: &str = "https://ytimgf.youtube-anime.com/";
+
+/// Manga come in "sub" (translated) and "raw"; ani-l reads translations.
+const MANGA_TRANSLATION: &str = "sub";
+
 pub struct AllAnimeProvider {
     client: Client,
     translation_type: String,
+    network: NetworkConfig,
+    user_agent: String,
+    stream_referer: String,
+    quality: u32,
 }
 
 impl AllAnimeProvider {
-    pub fn new(translation_type: String) -> Self {
+    pub fn new(translation_type: String, config: &Config) -> Result<Self> {
+        let provider_config = config.provider_config("allanime");
+        let user_agent = provider_config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| USER_AGENT.to_string());
+        let referer = provider_config
+            .referer
+            .clone()
+            .unwrap_or_else(|| REFERER.to_string());
+
         let mut headers = header::HeaderMap::new();
-        headers.insert(header::REFERER, header::HeaderValue::from_static(REFERER));
-        headers.insert(
-            header::USER_AGENT,
-            header::HeaderValue::from_static(USER_AGENT),
-        );
+        net::insert_header(&mut headers, header::REFERER.as_str(), &referer);
+        net::insert_header(&mut headers, header::USER_AGENT.as_str(), &user_agent);
+        for (name, value) in &provider_config.headers {
+            net::insert_header(&mut headers, name, value);
+        }
 
-        let client = Client::builder().default_headers(headers).build().unwrap();
-        Self {
+        let client = net::provider_client_builder(&config.network)?
+            .default_headers(headers)
+            .build()?;
+        Ok(Self {
             client,
             translation_type,
+            network: config.network.clone(),
+            user_agent,
+            stream_referer: provider_config
+                .stream_referer
+                .clone()
+                .unwrap_or_else(|| STREAM_REFERER.to_string()),
+            quality: config.stream.quality.parse().unwrap_or(1080),
+        })
+    }
+
+    pub fn translation_type(&self) -> &str {
+        &self.translation_type
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let host = Url::parse(url)?
+            .host_str()
+            .context("Request URL has no host")?
+            .to_string();
+        if let Some((_, body)) = cassette::replay(url)? {
+            return Ok(serde_json::from_str(&body)?);
         }
+        let _permit = net::limiter_for(&host, &self.network).acquire().await;
+        let resp = self.client.get(url).send().await?;
+        let status = resp.status().as_u16();
+        let body = resp.text().await?;
+        cassette::record(url, status, &body);
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    fn query_url(variant: QueryVariant, gql: &str, mut variables: Value) -> String {
+        let gql = variant.apply(gql, &mut variables);
+        format!(
+            "{}?variables={}&query={}",
+            API_ENDPOINT,
+            encode(&variables.to_string()),
+            encode(&gql)
+        )
+    }
+
+    /// Runs a GraphQL query written for `QueryVariant::Vaild` in the
+    /// session's variant. A response that doesn't parse before any has
+    /// probes for a variant that does and retries with it.
+    async fn query<T: DeserializeOwned>(&self, gql: &str, variables: Value) -> Result<T> {
+        let variant = schema::current();
+        let err = match self
+            .get_json(&Self::query_url(variant, gql, variables.clone()))
+            .await
+        {
+            Ok(resp) => {
+                schema::settle(variant);
+                return Ok(resp);
+            }
+            Err(e) if schema::is_schema_error(&e) && !schema::is_settled() => e,
+            Err(e) => return Err(e),
+        };
+
+        {
+            let _probing = schema::PROBING.lock().await;
+            if !schema::is_settled() {
+                warn!(
+                    "AllAnime didn't understand the query ({}), probing the schema...",
+                    err
+                );
+                let found = self.probe().await;
+                match found {
+                    Some(v) => warn!(
+                        "AllAnime answers {} queries, using them from now on",
+                        v.key()
+                    ),
+                    None => warn!("No known AllAnime query variant works"),
+                }
+                schema::probed(found);
+            }
+        }
+        let retry = schema::current();
+        if retry == variant {
+            return Err(err);
+        }
+        self.get_json(&Self::query_url(retry, gql, variables)).await
+    }
+
+    /// The first variant a one-result search parses with.
+    async fn probe(&self) -> Option<QueryVariant> {
+        let gql = r#"
+        query($search: SearchInput, $limit: Int, $translationType: VaildTranslationTypeEnumType, $countryOrigin: VaildCountryOriginEnumType) {
+            shows(search: $search, limit: $limit, translationType: $translationType, countryOrigin: $countryOrigin) {
+                edges {
+                    _id
+                }
+            }
+        }
+        "#;
+        let variables = json!({
+            "search": { "query": "a" },
+            "limit": 1,
+            "translationType": "sub",
+            "countryOrigin": "ALL"
+        });
+        for variant in QueryVariant::ALL {
+            let url = Self::query_url(variant, gql, variables.clone());
+            match self.get_json::<AllAnimeResponse<Value>>(&url).await {
+                Ok(resp) if !resp.data.is_null() => return Some(variant),
+                Ok(_) => debug!("Schema probe: {} returned no data", variant.key()),
+                Err(e) => debug!("Schema probe: {} failed: {}", variant.key(), e),
+            }
+        }
+        None
     }
 
     pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
@@ -61,21 +205,30 @@ impl AllAnimeProvider {
             "countryOrigin": "ALL"
         });
 
-        let url = format!(
-            "{}?variables={}&query={}",
-            API_ENDPOINT,
-            encode(&variables.to_string()),
-            encode(gql)
-        );
-
         debug!("Sending search request to: {}", API_ENDPOINT);
-        let resp: AllAnimeResponse<SearchResultData> =
-            self.client.get(&url).send().await?.json().await?;
+        let resp: AllAnimeResponse<SearchResultData> = self.query(gql, variables).await?;
 
         debug!("Received {} results", resp.data.shows.edges.len());
         Ok(resp.data.shows.edges)
     }
 
+    /// Episode strings the show has in this translation, e.g. "5.5" or
+    /// "SP1" next to the plain numbers.
+    pub async fn episode_list(&self, show_id: &str) -> Result<Vec<String>> {
+        let gql = r#"
+        query($showId: String!) {
+            show(_id: $showId) {
+                availableEpisodesDetail
+            }
+        }
+        "#;
+
+        let variables = json!({ "showId": show_id });
+        let resp: AllAnimeResponse<ShowDetailData> = self.query(gql, variables).await?;
+        let mut detail = resp.data.show.available_episodes_detail;
+        Ok(detail.remove(&self.translation_type).unwrap_or_default())
+    }
+
     pub async fn get_episode_sources(
         &self,
         show_id: &str,
@@ -100,15 +253,7 @@ impl AllAnimeProvider {
             "episodeString": episode_num
         });
 
-        let url = format!(
-            "{}?variables={}&query={}",
-            API_ENDPOINT,
-            encode(&variables.to_string()),
-            encode(gql)
-        );
-
-        let resp: AllAnimeResponse<EpisodeResultData> =
-            self.client.get(&url).send().await?.json().await?;
+        let resp: AllAnimeResponse<EpisodeResultData> = self.query(gql, variables).await?;
 
         match resp.data.episode {
             Some(ep) => {
@@ -132,40 +277,278 @@ impl AllAnimeProvider {
             source_url.to_string()
         };
 
-        let base_path = if clean_url.starts_with("/") {
+        // Some sources decode straight to a file instead of a clock path.
+        let link = if clean_url.starts_with("http") {
             clean_url
         } else {
-            format!("/{}", clean_url)
-        };
+            let base_path = if clean_url.starts_with("/") {
+                clean_url
+            } else {
+                format!("/{}", clean_url)
+            };
 
-        let clock_url = format!(
-            "https://allanime.day{}",
-            base_path.replace("clock", "clock.json")
-        );
+            let clock_url = format!(
+                "https://allanime.day{}",
+                base_path.replace("clock", "clock.json")
+            );
 
-        debug!("Resolving stream from clock URL: {}", clock_url);
+            debug!("Resolving stream from clock URL: {}", clock_url);
 
-        let resp: GogoStreamResponse = self.client.get(&clock_url).send().await?.json().await?;
+            let resp: GogoStreamResponse = self.get_json(&clock_url).await?;
 
-        let best_link = resp
-            .links
-            .iter()
-            .find(|l| l.resolution == "1080p")
-            .or(resp.links.last())
-            .context("No stream links found")?;
+            // Links without a resolution ("auto", "hls") are usually master
+            // playlists, and go last after any that match `stream.quality`.
+            resp.links
+                .iter()
+                .min_by_key(|l| match l.resolution.trim_end_matches('p').parse() {
+                    Ok(resolution) => hls::quality_rank(resolution, self.quality),
+                    Err(_) => (true, u32::MAX),
+                })
+                .context("No stream links found")?
+                .link
+                .clone()
+        };
+        let url = self.resolve_link(&link).await?;
 
         let headers = vec![
-            ("User-Agent".to_string(), USER_AGENT.to_string()),
-            ("Referer".to_string(), "https://allanime.day/".to_string()),
+            ("User-Agent".to_string(), self.user_agent.clone()),
+            ("Referer".to_string(), self.stream_referer.clone()),
         ];
 
-        Ok(PlayOptions {
-            url: best_link.link.clone(),
-            title: Some("Anime Stream".to_string()),
+        let mut options = PlayOptions {
+            url,
+            title: None,
             start_time: None,
             headers: Some(headers),
             subtitles: None,
-        })
+            ..Default::default()
+        };
+        hls::select_variant(&self.client, &self.network, &mut options, self.quality).await;
+        Ok(options)
+    }
+
+    /// Turns a link from `clock.json` into one a player can open. Most are
+    /// already playable; file hosts need their download link instead.
+    async fn resolve_link(&self, link: &str) -> Result<String> {
+        let url = Url::parse(link)?;
+        let host = url.host_str().unwrap_or_default();
+        if host == "repackager.wixmp.com" {
+            return wixmp_file(link, self.quality).context("Unrecognised wixmp link");
+        }
+        if host.ends_with("sharepoint.com") {
+            return Ok(sharepoint_download(link));
+        }
+        if host.ends_with("wetransfer.com") {
+            return self.wetransfer_download(&url).await;
+        }
+        Ok(link.to_string())
+    }
+
+    async fn wetransfer_download(&self, url: &Url) -> Result<String> {
+        let (id, recipient, hash) = wetransfer_ids(url).context("Unrecognised WeTransfer link")?;
+        let api = format!("{}/{}/download", WETRANSFER_API, id);
+        let body = match cassette::replay(&api)? {
+            Some((_, body)) => body,
+            None => {
+                let _permit = net::limiter_for("wetransfer.com", &self.network)
+                    .acquire()
+                    .await;
+                let mut payload = json!({ "security_hash": hash, "intent": "entire_transfer" });
+                if let Some(recipient) = recipient {
+                    payload["recipient_id"] = json!(recipient);
+                }
+                let resp = self.client.post(&api).json(&payload).send().await?;
+                let status = resp.status().as_u16();
+                let body = resp.text().await?;
+                cassette::record(&api, status, &body);
+                body
+            }
+        };
+        let resp: serde_json::Value = serde_json::from_str(&body)?;
+        resp["direct_link"]
+            .as_str()
+            .map(str::to_string)
+            .context("WeTransfer didn't return a download link")
+    }
+
+    /// Manga matching `query`, as shows counting chapters instead of
+    /// episodes.
+    pub async fn search_manga(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        let gql = r#"
+        query($search: SearchInput, $limit: Int, $page: Int, $translationType: VaildTranslationTypeMangaEnumType, $countryOrigin: VaildCountryOriginEnumType) {
+            mangas(search: $search, limit: $limit, page: $page, translationType: $translationType, countryOrigin: $countryOrigin) {
+                edges {
+                    _id
+                    name
+                    availableChapters
+                }
+            }
+        }
+        "#;
+
+        let variables = json!({
+            "search": {
+                "allowAdult": false,
+                "allowUnknown": false,
+                "query": query
+            },
+            "limit": 50,
+            "page": 1,
+            "translationType": MANGA_TRANSLATION,
+            "countryOrigin": "ALL"
+        });
+        let resp: AllAnimeResponse<MangaSearchData> = self.query(gql, variables).await?;
+        Ok(resp
+            .data
+            .mangas
+            .edges
+            .into_iter()
+            .map(|m| {
+                let chapters = |kind: &str| m.available_chapters.get(kind).copied().unwrap_or(0);
+                ShowEdge {
+                    available_episodes: AvailableEpisodes {
+                        sub: chapters("sub"),
+                        dub: 0,
+                        raw: chapters("raw"),
+                    },
+                    id: m.id,
+                    name: m.name,
+                }
+            })
+            .collect())
+    }
+
+    /// Chapter strings of a manga, first chapter first.
+    pub async fn chapter_list(&self, manga_id: &str) -> Result<Vec<String>> {
+        let gql = r#"
+        query($mangaId: String!) {
+            manga(_id: $mangaId) {
+                availableChaptersDetail
+            }
+        }
+        "#;
+
+        let variables = json!({ "mangaId": manga_id });
+        let mut resp: AllAnimeResponse<MangaDetailData> = self.query(gql, variables).await?;
+        let mut chapters = resp
+            .data
+            .manga
+            .available_chapters_detail
+            .remove(MANGA_TRANSLATION)
+            .unwrap_or_default();
+        chapters.sort_by(|a, b| {
+            let number = |c: &str| c.parse::<f64>().unwrap_or(f64::MAX);
+            number(a).total_cmp(&number(b))
+        });
+        Ok(chapters)
+    }
+
+    /// Image URLs of a chapter's pages, in reading order.
+    pub async fn chapter_pages(&self, manga_id: &str, chapter: &str) -> Result<Vec<String>> {
+        let gql = r#"
+        query($mangaId: String!, $translationType: VaildTranslationTypeMangaEnumType!, $chapterString: String!) {
+            chapterPages(mangaId: $mangaId, translationType: $translationType, chapterString: $chapterString) {
+                edges {
+                    pictureUrls
+                    pictureUrlHead
+                }
+            }
+        }
+        "#;
+
+        let variables = json!({
+            "mangaId": manga_id,
+            "translationType": MANGA_TRANSLATION,
+            "chapterString": chapter
+        });
+        let resp: AllAnimeResponse<ChapterPagesData> = self.query(gql, variables).await?;
+        // Several scanlations may be listed; the first complete one will do.
+        let edge = resp
+            .data
+            .chapter_pages
+            .map(|p| p.edges)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|e| !e.picture_urls.is_empty())
+            .context("No pages found for this chapter")?;
+        let head = edge.picture_url_head.as_deref().unwrap_or(PAGE_HOST);
+        let mut pictures = edge.picture_urls;
+        pictures.sort_by(|a, b| a.num.total_cmp(&b.num));
+        Ok(pictures
+            .into_iter()
+            .map(|p| page_url(head, &p.url))
+            .collect())
+    }
+
+    #[cfg(feature = "images")]
+    pub async fn fetch_page(&self, url: &str) -> Result<Vec<u8>> {
+        let host = Url::parse(url)?
+            .host_str()
+            .context("Page URL has no host")?
+            .to_string();
+        let _permit = net::limiter_for(&host, &self.network).acquire().await;
+        let resp = self.client.get(url).send().await?.error_for_status()?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+/// A page's full URL from `chapterPages`, whose paths are usually relative.
+fn page_url(head: &str, path: &str) -> String {
+    if path.starts_with("http") {
+        path.to_string()
+    } else {
+        format!(
+            "{}/{}",
+            head.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+}
+
+/// wixmp "repackager" links name every resolution in one URL, e.g.
+/// `https://repackager.wixmp.com/video.wixstatic.com/video/x/,1080p,720p,/mp4/file.mp4.urlset/master.m3u8`.
+/// Returns the file of the one closest to `quality`, e.g.
+/// `https://video.wixstatic.com/video/x/1080p/mp4/file.mp4`.
+fn wixmp_file(link: &str, quality: u32) -> Option<String> {
+    let path = link.split_once("repackager.wixmp.com/")?.1;
+    let path = path.split(".urlset").next()?;
+    let start = path.find("/,")? + 1;
+    let end = start + path[start..].find(",/")?;
+    let best = path[start + 1..end].split(',').min_by_key(|r| {
+        let resolution = r.trim_end_matches('p').parse().unwrap_or(0);
+        hls::quality_rank(resolution, quality)
+    })?;
+    Some(format!(
+        "https://{}{}{}",
+        &path[..start],
+        best,
+        &path[end + 1..]
+    ))
+}
+
+/// SharePoint share links open a viewer page unless asked for the file.
+fn sharepoint_download(link: &str) -> String {
+    if link.contains("download=1") {
+        link.to_string()
+    } else if link.contains('?') {
+        format!("{}&download=1", link)
+    } else {
+        format!("{}?download=1", link)
+    }
+}
+
+/// Transfer id, recipient id and security hash of a
+/// `wetransfer.com/downloads/<id>[/<recipient>]/<hash>` link.
+fn wetransfer_ids(url: &Url) -> Option<(String, Option<String>, String)> {
+    let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    match segments.as_slice() {
+        ["downloads", id, hash] => Some((id.to_string(), None, hash.to_string())),
+        ["downloads", id, recipient, hash] => Some((
+            id.to_string(),
+            Some(recipient.to_string()),
+            hash.to_string(),
+        )),
+        _ => None,
     }
 }
 
@@ -186,3 +569,37 @@ fn decrypt_source_url(hex_string: &str) -> Result<String> {
 
     Ok(decoded)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_host_links() {
+        assert_eq!(
+            wixmp_file(
+                "https://repackager.wixmp.com/video.wixstatic.com/video/abc/,480p,1080p,720p,/mp4/file.mp4.urlset/master.m3u8",
+                1080
+            )
+            .as_deref(),
+            Some("https://video.wixstatic.com/video/abc/1080p/mp4/file.mp4")
+        );
+        assert_eq!(
+            sharepoint_download("https://x.sharepoint.com/:v:/g/personal/a/ID?e=1"),
+            "https://x.sharepoint.com/:v:/g/personal/a/ID?e=1&download=1"
+        );
+        let url = Url::parse("https://wetransfer.com/downloads/t1/r2/h3").unwrap();
+        assert_eq!(
+            wetransfer_ids(&url),
+            Some(("t1".to_string(), Some("r2".to_string()), "h3".to_string()))
+        );
+        assert_eq!(
+            page_url(PAGE_HOST, "/images/m1/1/p1.jpg"),
+            "https://ytimgf.youtube-anime.com/images/m1/1/p1.jpg"
+        );
+        assert_eq!(
+            page_url(PAGE_HOST, "https://cdn/p.png"),
+            "https://cdn/p.png"
+        );
+    }
+}
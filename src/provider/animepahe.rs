@@ -0,0 +1,371 @@
+use crate::cassette;
+use crate::config::{Config, NetworkConfig};
+use crate::net;
+use crate::player::traits::PlayOptions;
+use crate::provider::hls::quality_rank;
+use crate::provider::html::{attr, decode_entities};
+use crate::provider::models::*;
+use anyhow::{Context, Result};
+use log::debug;
+use reqwest::{Client, Url, header};
+use serde::Deserialize;
+use urlencoding::encode;
+
+const BASE_URL: &str = "https://animepahe.ru";
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// Source names in preference order: the encode closest to
+/// `stream.quality`, then the next best.
+pub const SOURCE_PRIORITY: &[&str] = &["Kwik", "Kwik-alt"];
+
+pub struct AnimePaheProvider {
+    client: Client,
+    translation_type: String,
+    network: NetworkConfig,
+    user_agent: String,
+    /// Overrides the Kwik origin sent as the stream Referer.
+    stream_referer: Option<String>,
+    base_url: String,
+    quality: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    #[serde(default)]
+    data: Vec<SearchItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchItem {
+    title: String,
+    session: String,
+    #[serde(default)]
+    episodes: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseResponse {
+    last_page: u32,
+    per_page: u32,
+    #[serde(default)]
+    data: Vec<Release>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    /// Usually an integer, but specials can be numbered `12.5`.
+    episode: serde_json::Value,
+    session: String,
+}
+
+impl Release {
+    fn number(&self) -> Option<f64> {
+        self.episode
+            .as_f64()
+            .or_else(|| self.episode.as_str()?.parse().ok())
+    }
+}
+
+/// One encode offered on a play page.
+#[derive(Debug, PartialEq)]
+struct KwikLink {
+    url: String,
+    resolution: u32,
+    audio: String,
+}
+
+impl AnimePaheProvider {
+    pub fn new(translation_type: String, config: &Config) -> Result<Self> {
+        let provider_config = config.provider_config("animepahe");
+        let base_url = provider_config
+            .base_url
+            .clone()
+            .unwrap_or_else(|| BASE_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let user_agent = provider_config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| USER_AGENT.to_string());
+        let referer = provider_config
+            .referer
+            .clone()
+            .unwrap_or_else(|| format!("{}/", base_url));
+
+        let mut headers = header::HeaderMap::new();
+        net::insert_header(&mut headers, header::REFERER.as_str(), &referer);
+        net::insert_header(&mut headers, header::USER_AGENT.as_str(), &user_agent);
+        // DDoS-Guard lets requests through once this cookie is present.
+        net::insert_header(&mut headers, header::COOKIE.as_str(), "__ddg2_=");
+        for (name, value) in &provider_config.headers {
+            net::insert_header(&mut headers, name, value);
+        }
+
+        let client = net::provider_client_builder(&config.network)?
+            .default_headers(headers)
+            .build()?;
+        Ok(Self {
+            client,
+            translation_type,
+            network: config.network.clone(),
+            user_agent,
+            stream_referer: provider_config.stream_referer.clone(),
+            base_url,
+            quality: config.stream.quality.parse().unwrap_or(1080),
+        })
+    }
+
+    pub fn translation_type(&self) -> &str {
+        &self.translation_type
+    }
+
+    async fn get_text(&self, url: &str) -> Result<String> {
+        let host = Url::parse(url)?
+            .host_str()
+            .context("Request URL has no host")?
+            .to_string();
+        let (status, body) = match cassette::replay(url)? {
+            Some(recorded) => recorded,
+            None => {
+                let _permit = net::limiter_for(&host, &self.network).acquire().await;
+                let resp = self.client.get(url).send().await?;
+                let status = resp.status().as_u16();
+                let body = resp.text().await?;
+                cassette::record(url, status, &body);
+                (status, body)
+            }
+        };
+        if !(200..300).contains(&status) {
+            anyhow::bail!("AnimePahe returned HTTP {} for {}", status, url);
+        }
+        Ok(body)
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        debug!("Searching AnimePahe for '{}'...", query);
+        let url = format!("{}/api?m=search&q={}", self.base_url, encode(query));
+        let resp: SearchResponse = serde_json::from_str(&self.get_text(&url).await?)?;
+        debug!("Received {} results", resp.data.len());
+        // Dubs are listed per episode on the play page, so both counts
+        // report every episode.
+        Ok(resp
+            .data
+            .into_iter()
+            .map(|item| {
+                let episodes = item.episodes.unwrap_or(0);
+                ShowEdge {
+                    id: item.session,
+                    name: decode_entities(&item.title),
+                    available_episodes: AvailableEpisodes {
+                        sub: episodes,
+                        dub: episodes,
+                        raw: 0,
+                    },
+                }
+            })
+            .collect())
+    }
+
+    async fn releases(&self, show_id: &str, page: u32) -> Result<ReleaseResponse> {
+        let url = format!(
+            "{}/api?m=release&id={}&sort=episode_asc&page={}",
+            self.base_url, show_id, page
+        );
+        Ok(serde_json::from_str(&self.get_text(&url).await?)?)
+    }
+
+    /// Finds the session of one episode. Continuing seasons keep counting
+    /// from the previous one, so the page is worked out from the first
+    /// episode listed rather than from 1.
+    async fn episode_session(&self, show_id: &str, episode: f64) -> Result<Option<String>> {
+        let first = self.releases(show_id, 1).await?;
+        if let Some(release) = first.data.iter().find(|r| r.number() == Some(episode)) {
+            return Ok(Some(release.session.clone()));
+        }
+        let start = first.data.first().and_then(Release::number).unwrap_or(1.0);
+        let page = ((episode - start).max(0.0) as u32) / first.per_page.max(1) + 1;
+        if page <= 1 || page > first.last_page {
+            return Ok(None);
+        }
+        let resp = self.releases(show_id, page).await?;
+        Ok(resp
+            .data
+            .into_iter()
+            .find(|r| r.number() == Some(episode))
+            .map(|r| r.session))
+    }
+
+    pub async fn get_episode_sources(
+        &self,
+        show_id: &str,
+        episode_num: &str,
+    ) -> Result<Vec<SourceUrl>> {
+        let episode: f64 = episode_num
+            .parse()
+            .with_context(|| format!("Invalid episode number {}", episode_num))?;
+        let session = self
+            .episode_session(show_id, episode)
+            .await?
+            .with_context(|| {
+                format!("Episode {} not found for show ID {}", episode_num, show_id)
+            })?;
+
+        let page = self
+            .get_text(&format!("{}/play/{}/{}", self.base_url, show_id, session))
+            .await?;
+        let audio = if self.translation_type == "dub" {
+            "eng"
+        } else {
+            "jpn"
+        };
+        let mut links: Vec<KwikLink> = parse_kwik_links(&page)
+            .into_iter()
+            .filter(|l| l.audio == audio)
+            .collect();
+        links.sort_by_key(|l| quality_rank(l.resolution, self.quality));
+
+        Ok(links
+            .into_iter()
+            .take(SOURCE_PRIORITY.len())
+            .zip(SOURCE_PRIORITY)
+            .map(|(link, name)| SourceUrl {
+                source_name: name.to_string(),
+                source_url: link.url,
+            })
+            .collect())
+    }
+
+    /// Resolves a Kwik embed: the page hides the HLS URL inside a packed
+    /// script.
+    pub async fn extract_clock_stream(&self, source_url: &str) -> Result<PlayOptions> {
+        let url = Url::parse(source_url)?;
+        let origin = url.origin().ascii_serialization();
+        let page = self.get_text(source_url).await?;
+        let stream = page
+            .split("<script>")
+            .filter_map(unpack)
+            .find_map(|script| between(&script, "source='", "'").map(str::to_string))
+            .context("Kwik page has no stream source")?;
+
+        Ok(PlayOptions {
+            url: stream,
+            headers: Some(vec![
+                ("User-Agent".to_string(), self.user_agent.clone()),
+                (
+                    "Referer".to_string(),
+                    self.stream_referer
+                        .clone()
+                        .unwrap_or_else(|| format!("{}/", origin)),
+                ),
+            ]),
+            ..Default::default()
+        })
+    }
+}
+
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let from = text.find(start)? + start.len();
+    let to = text[from..].find(end)? + from;
+    Some(&text[from..to])
+}
+
+fn parse_kwik_links(html: &str) -> Vec<KwikLink> {
+    html.split("<button")
+        .skip(1)
+        .filter_map(|button| {
+            let url = attr(button, "data-src")?;
+            if !url.contains("kwik") {
+                return None;
+            }
+            Some(KwikLink {
+                url: url.to_string(),
+                resolution: attr(button, "data-resolution")?.parse().ok()?,
+                audio: attr(button, "data-audio").unwrap_or("jpn").to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Unpacks Dean Edwards' `eval(function(p,a,c,k,e,d){...}(...))` packer,
+/// returning `None` if `script` isn't packed.
+fn unpack(script: &str) -> Option<String> {
+    let body = &script[script.find("}('")? + 3..];
+    let keywords_end = body.find("'.split('|')")?;
+    let keywords_start = body[..keywords_end].rfind('\'')? + 1;
+    let keywords: Vec<&str> = body[keywords_start..keywords_end].split('|').collect();
+
+    // What's left is `payload',radix,count,`.
+    let mut head = body[..keywords_start - 1]
+        .trim_end_matches(',')
+        .rsplitn(3, ',');
+    let _count = head.next()?;
+    let radix: u32 = head.next()?.trim().parse().ok()?;
+    let payload = head.next()?.strip_suffix('\'')?;
+    let payload = payload.replace("\\'", "'").replace("\\\\", "\\");
+
+    let mut out = String::with_capacity(payload.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        let replacement = from_base(word, radix)
+            .and_then(|i| keywords.get(i))
+            .filter(|k| !k.is_empty());
+        out.push_str(replacement.copied().unwrap_or(word));
+        word.clear();
+    };
+    for c in payload.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut word, &mut out);
+    Some(out)
+}
+
+/// Parses a packer word: digits, then `a-z`, then `A-Z`.
+fn from_base(word: &str, radix: u32) -> Option<usize> {
+    if word.is_empty() {
+        return None;
+    }
+    word.chars().try_fold(0usize, |acc, c| {
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            _ => return None,
+        };
+        (digit < radix).then(|| acc * radix as usize + digit as usize)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kwik_links_and_unpack() {
+        let page = r#"<button class="dropdown-item" data-src="https://kwik.si/e/abc" data-fansub="SubsPlease" data-resolution="720" data-audio="jpn">
+                      <button class="dropdown-item" data-src="https://kwik.si/e/def" data-resolution="1080" data-audio="eng">"#;
+        let links = parse_kwik_links(page);
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].url, "https://kwik.si/e/abc");
+        assert_eq!(
+            (links[1].resolution, links[1].audio.as_str()),
+            (1080, "eng")
+        );
+
+        let mut resolutions = vec![360, 1080, 720];
+        resolutions.sort_by_key(|&r| quality_rank(r, 720));
+        assert_eq!(resolutions, vec![720, 360, 1080]);
+
+        let script = r#"eval(function(p,a,c,k,e,d){while(c--)if(k[c])p=p.replace(new RegExp('\\b'+c.toString(a)+'\\b','g'),k[c]);return p}('0 1=\'2://3.4/5.6\';',7,7,'const|source|https|cdn|example|uwu|m3u8'.split('|'),0,{}))"#;
+        let unpacked = unpack(script).unwrap();
+        assert_eq!(unpacked, "const source='https://cdn.example/uwu.m3u8';");
+        assert_eq!(
+            between(&unpacked, "source='", "'"),
+            Some("https://cdn.example/uwu.m3u8")
+        );
+    }
+}
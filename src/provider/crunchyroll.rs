@@ -0,0 +1,545 @@
+//! Crunchyroll for subscribers, selected with `general.provider =
+//! "crunchyroll"` and signed in with the account under
+//! `[providers.crunchyroll]`. Only DRM-free streams can be played; episodes
+//! Crunchyroll serves with Widevine fail with an error saying so.
+//!
+//! Older shows list each dub as a season of its own; only one season per
+//! number is kept and the dub is picked from the episode's versions. Seasons
+//! are numbered on from each other, specials listed as "SP1", "SP2", ...
+
+use crate::cassette;
+use crate::config::{Config, NetworkConfig, ProviderConfig};
+use crate::net;
+use crate::player::traits::PlayOptions;
+use crate::provider::models::*;
+use anyhow::{Context, Result};
+use log::debug;
+use rand::Rng;
+use reqwest::{Client, Url, header};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use urlencoding::encode;
+
+const BASE_URL: &str = "https://www.crunchyroll.com";
+const PLAY_URL: &str = "https://cr-play-service.prd.crunchyrollsvc.com";
+/// The web player's OAuth client, which signs in without a secret.
+const CLIENT_ID: &str = "noaihdevm_6iyg0a8l0q";
+const LOCALE: &str = "en-US";
+const DUB_LOCALE: &str = "en-US";
+const SEARCH_LIMIT: usize = 20;
+
+pub const SOURCE_PRIORITY: &[&str] = &["Crunchyroll"];
+
+/// An access token and when to stop using it.
+struct Session {
+    token: String,
+    expires: Instant,
+}
+
+pub struct CrunchyrollProvider {
+    client: Client,
+    translation_type: String,
+    network: NetworkConfig,
+    config: ProviderConfig,
+    base_url: String,
+    subtitle_language: Option<String>,
+    /// Identifies ani-l among the account's devices.
+    device_id: String,
+    session: Mutex<Option<Session>>,
+    /// Numbered episodes per show id, so resolving one doesn't list every
+    /// season again.
+    episodes: Mutex<HashMap<String, Vec<(String, String)>>>,
+    /// Play-service tokens of resolved streams, by episode version id, until
+    /// `release_streams` hands them back.
+    streams: Mutex<Vec<(String, String)>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Token {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Listing<T> {
+    #[serde(default = "Vec::new")]
+    data: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchGroup {
+    #[serde(default)]
+    items: Vec<Series>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Series {
+    id: String,
+    title: String,
+    #[serde(default)]
+    series_metadata: Option<SeriesMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeriesMetadata {
+    #[serde(default)]
+    episode_count: usize,
+    #[serde(default)]
+    is_dubbed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Season {
+    id: String,
+    season_number: i32,
+    #[serde(default)]
+    season_sequence_number: i32,
+    #[serde(default)]
+    is_subbed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Episode {
+    id: String,
+    /// Missing for specials and recaps.
+    episode_number: Option<i32>,
+    #[serde(default)]
+    sequence_number: f64,
+    #[serde(default)]
+    versions: Vec<Version>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Version {
+    audio_locale: String,
+    guid: String,
+    #[serde(default)]
+    original: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Play {
+    url: String,
+    /// Holds one of the account's concurrent streams until deleted.
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    subtitles: HashMap<String, Subtitle>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Subtitle {
+    url: String,
+}
+
+impl CrunchyrollProvider {
+    pub fn new(translation_type: String, config: &Config) -> Result<Self> {
+        let provider_config = config.provider_config("crunchyroll");
+        let base_url = provider_config
+            .base_url
+            .clone()
+            .unwrap_or_else(|| BASE_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+
+        let mut headers = header::HeaderMap::new();
+        net::insert_header(&mut headers, header::ACCEPT.as_str(), "application/json");
+        if let Some(user_agent) = &provider_config.user_agent {
+            net::insert_header(&mut headers, header::USER_AGENT.as_str(), user_agent);
+        }
+        for (name, value) in &provider_config.headers {
+            net::insert_header(&mut headers, name, value);
+        }
+
+        let client = net::provider_client_builder(&config.network)?
+            .default_headers(headers)
+            .build()?;
+        let device_id = format!("{:032x}", rand::thread_rng().r#gen::<u128>());
+        Ok(Self {
+            client,
+            translation_type,
+            network: config.network.clone(),
+            config: provider_config,
+            base_url,
+            subtitle_language: config.stream.subtitle_language.clone(),
+            device_id,
+            session: Mutex::new(None),
+            episodes: Mutex::new(HashMap::new()),
+            streams: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn translation_type(&self) -> &str {
+        &self.translation_type
+    }
+
+    /// A valid access token, signing in again once the last one is about to
+    /// expire (they last minutes).
+    async fn token(&self) -> Result<String> {
+        let mut session = self.session.lock().await;
+        if let Some(s) = session.as_ref().filter(|s| s.expires > Instant::now()) {
+            return Ok(s.token.clone());
+        }
+        let token = self.log_in().await?;
+        let token_string = token.access_token.clone();
+        *session = Some(Session {
+            token: token.access_token,
+            expires: Instant::now() + Duration::from_secs(token.expires_in.saturating_sub(30)),
+        });
+        Ok(token_string)
+    }
+
+    /// Signs in with the configured refresh token, or the username and
+    /// password.
+    async fn log_in(&self) -> Result<Token> {
+        let mut form = vec![
+            ("scope", "offline_access".to_string()),
+            ("device_id", self.device_id.clone()),
+            ("device_type", "ani-l".to_string()),
+        ];
+        match (
+            &self.config.token,
+            &self.config.username,
+            &self.config.password,
+        ) {
+            (Some(refresh_token), _, _) => {
+                form.push(("grant_type", "refresh_token".to_string()));
+                form.push(("refresh_token", refresh_token.clone()));
+            }
+            (None, Some(username), Some(password)) => {
+                form.push(("grant_type", "password".to_string()));
+                form.push(("username", username.clone()));
+                form.push(("password", password.clone()));
+            }
+            _ => anyhow::bail!(
+                "Set providers.crunchyroll.username and password (or a refresh token as token) to sign in to Crunchyroll"
+            ),
+        }
+        let client_id = self.config.client_id.as_deref().unwrap_or(CLIENT_ID);
+        let resp = self
+            .client
+            .post(format!("{}/auth/v1/token", self.base_url))
+            .basic_auth(client_id, Some(""))
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()
+            .context("Crunchyroll rejected the sign-in")?;
+        Ok(resp.json().await?)
+    }
+
+    async fn get_text(&self, url: &str) -> Result<String> {
+        let host = Url::parse(url)?
+            .host_str()
+            .context("Request URL has no host")?
+            .to_string();
+        let (status, body) = match cassette::replay(url)? {
+            Some(recorded) => recorded,
+            None => {
+                let token = self.token().await?;
+                let _permit = net::limiter_for(&host, &self.network).acquire().await;
+                let resp = self.client.get(url).bearer_auth(token).send().await?;
+                let status = resp.status().as_u16();
+                let body = resp.text().await?;
+                cassette::record(url, status, &body);
+                (status, body)
+            }
+        };
+        match status {
+            200..300 => Ok(body),
+            403 => anyhow::bail!(
+                "Crunchyroll refused {} (premium-only, or not in your region)",
+                url
+            ),
+            _ => anyhow::bail!("Crunchyroll returned HTTP {} for {}", status, url),
+        }
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T> {
+        Ok(serde_json::from_str(&self.get_text(url).await?)?)
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        debug!("Searching Crunchyroll for '{}'...", query);
+        let url = format!(
+            "{}/content/v2/discover/search?q={}&n={}&type=series&locale={}",
+            self.base_url,
+            encode(query),
+            SEARCH_LIMIT,
+            LOCALE
+        );
+        let groups: Listing<SearchGroup> = self.get_json(&url).await?;
+        let shows: Vec<ShowEdge> = groups
+            .data
+            .into_iter()
+            .flat_map(|g| g.items)
+            .map(|series| {
+                let (episodes, dubbed) = series
+                    .series_metadata
+                    .map_or((0, false), |m| (m.episode_count, m.is_dubbed));
+                ShowEdge {
+                    id: series.id,
+                    name: series.title,
+                    available_episodes: AvailableEpisodes {
+                        sub: episodes,
+                        dub: if dubbed { episodes } else { 0 },
+                        raw: 0,
+                    },
+                }
+            })
+            .collect();
+        debug!("Received {} results", shows.len());
+        Ok(shows)
+    }
+
+    /// Every episode of the show, numbered, with the id of the version in
+    /// the wanted language.
+    async fn episodes(&self, show_id: &str) -> Result<Vec<(String, String)>> {
+        if let Some(episodes) = self.episodes.lock().await.get(show_id) {
+            return Ok(episodes.clone());
+        }
+        let episodes = self.fetch_episodes(show_id).await?;
+        self.episodes
+            .lock()
+            .await
+            .insert(show_id.to_string(), episodes.clone());
+        Ok(episodes)
+    }
+
+    async fn fetch_episodes(&self, show_id: &str) -> Result<Vec<(String, String)>> {
+        let url = format!(
+            "{}/content/v2/cms/series/{}/seasons?locale={}",
+            self.base_url, show_id, LOCALE
+        );
+        let seasons: Listing<Season> = self.get_json(&url).await?;
+        let mut episodes = Vec::new();
+        for season in pick_seasons(seasons.data) {
+            let url = format!(
+                "{}/content/v2/cms/seasons/{}/episodes?locale={}",
+                self.base_url, season.id, LOCALE
+            );
+            let mut listed: Listing<Episode> = self.get_json(&url).await?;
+            listed
+                .data
+                .sort_by(|a, b| a.sequence_number.total_cmp(&b.sequence_number));
+            episodes.extend(listed.data);
+        }
+
+        let dub = self.translation_type == "dub";
+        Ok(number_episodes(&episodes)
+            .into_iter()
+            .zip(episodes)
+            .map(|(label, episode)| {
+                let version = episode
+                    .versions
+                    .iter()
+                    .find(|v| {
+                        if dub {
+                            v.audio_locale == DUB_LOCALE
+                        } else {
+                            v.original
+                        }
+                    })
+                    .map_or(episode.id, |v| v.guid.clone());
+                (label, version)
+            })
+            .collect())
+    }
+
+    pub async fn episode_list(&self, show_id: &str) -> Result<Vec<String>> {
+        Ok(self
+            .episodes(show_id)
+            .await?
+            .into_iter()
+            .map(|(episode, _)| episode)
+            .collect())
+    }
+
+    pub async fn get_episode_sources(
+        &self,
+        show_id: &str,
+        episode_num: &str,
+    ) -> Result<Vec<SourceUrl>> {
+        let id = self
+            .episodes(show_id)
+            .await?
+            .into_iter()
+            .find(|(episode, _)| episode == episode_num)
+            .map(|(_, id)| id)
+            .with_context(|| {
+                format!("Episode {} not found for show ID {}", episode_num, show_id)
+            })?;
+        Ok(vec![SourceUrl {
+            source_name: "Crunchyroll".to_string(),
+            source_url: id,
+        }])
+    }
+
+    /// Asks the play service for the episode's manifest, refusing it when
+    /// it's DRM-protected since no player ani-l drives can decrypt it.
+    pub async fn extract_clock_stream(&self, source_url: &str) -> Result<PlayOptions> {
+        let url = format!("{}/v1/{}/web/firefox/play", PLAY_URL, source_url);
+        let play: Play = self.get_json(&url).await?;
+        if let Some(token) = &play.token {
+            self.streams
+                .lock()
+                .await
+                .push((source_url.to_string(), token.clone()));
+        }
+
+        // The manifest is on the CDN, which wants no token.
+        let checked = async {
+            let resp = self.client.get(&play.url).send().await?;
+            let manifest = resp.error_for_status()?.text().await?;
+            if is_drm_protected(&manifest) {
+                anyhow::bail!("This episode is DRM-protected on Crunchyroll and can't be played");
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = checked {
+            self.release_streams().await;
+            return Err(e);
+        }
+
+        let mut subtitles: Vec<(String, String)> = play
+            .subtitles
+            .into_iter()
+            .filter(|(locale, _)| locale != "none")
+            .map(|(locale, s)| (locale, s.url))
+            .collect();
+        if let Some(language) = &self.subtitle_language
+            && subtitles.iter().any(|(l, _)| locale_matches(l, language))
+        {
+            subtitles.retain(|(l, _)| locale_matches(l, language));
+        }
+        subtitles.sort();
+        Ok(PlayOptions {
+            url: play.url,
+            subtitles: Some(subtitles.into_iter().map(|(_, url)| url).collect())
+                .filter(|s: &Vec<String>| !s.is_empty()),
+            ..Default::default()
+        })
+    }
+
+    /// Deletes the play-service tokens of the streams resolved so far, so
+    /// they stop counting against the account's concurrent streams.
+    pub async fn release_streams(&self) {
+        let streams: Vec<_> = self.streams.lock().await.drain(..).collect();
+        for (id, token) in streams {
+            let url = format!("{}/v1/token/{}/{}", PLAY_URL, id, token);
+            let released = async {
+                let bearer = self.token().await?;
+                self.client
+                    .delete(&url)
+                    .bearer_auth(bearer)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                anyhow::Ok(())
+            }
+            .await;
+            if let Err(e) = released {
+                debug!(
+                    "Could not release the Crunchyroll stream of {}: {:#}",
+                    id, e
+                );
+            }
+        }
+    }
+}
+
+/// One season per number, in order, preferring the subtitled one over
+/// dub-only copies.
+fn pick_seasons(mut seasons: Vec<Season>) -> Vec<Season> {
+    seasons.sort_by_key(|s| (s.season_sequence_number, s.season_number, !s.is_subbed));
+    let mut picked: Vec<Season> = Vec::new();
+    for season in seasons {
+        if !picked
+            .iter()
+            .any(|p| p.season_number == season.season_number)
+        {
+            picked.push(season);
+        }
+    }
+    picked
+}
+
+/// Episode strings in watching order: numbered episodes counted on across
+/// seasons, the rest as "SP<n>".
+fn number_episodes(episodes: &[Episode]) -> Vec<String> {
+    let (mut regular, mut special) = (0, 0);
+    episodes
+        .iter()
+        .map(|e| {
+            if e.episode_number.is_some() {
+                regular += 1;
+                regular.to_string()
+            } else {
+                special += 1;
+                format!("SP{}", special)
+            }
+        })
+        .collect()
+}
+
+fn is_drm_protected(manifest: &str) -> bool {
+    manifest.contains("<ContentProtection") || manifest.contains("METHOD=SAMPLE-AES")
+}
+
+/// Whether a locale like "pt-BR" is the subtitle language asked for, given
+/// as a locale, a language code or an English name.
+fn locale_matches(locale: &str, language: &str) -> bool {
+    let language = language.trim().to_lowercase();
+    let locale = locale.to_lowercase();
+    if language.contains('-') {
+        return locale == language;
+    }
+    let code = super::hianime::LANGUAGE_CODES
+        .iter()
+        .find(|(_, name)| *name == language)
+        .map_or(language.as_str(), |(code, _)| code);
+    locale.split('-').next() == Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seasons_and_numbering() {
+        let season = |id: &str, number, subbed| Season {
+            id: id.to_string(),
+            season_number: number,
+            season_sequence_number: number,
+            is_subbed: subbed,
+        };
+        let seasons = pick_seasons(vec![
+            season("s2", 2, true),
+            season("s1-dub", 1, false),
+            season("s1", 1, true),
+        ]);
+        let ids: Vec<&str> = seasons.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["s1", "s2"]);
+
+        let episode = |number| Episode {
+            id: String::new(),
+            episode_number: number,
+            sequence_number: 0.0,
+            versions: Vec::new(),
+        };
+        assert_eq!(
+            number_episodes(&[episode(Some(1)), episode(None), episode(Some(1))]),
+            ["1", "SP1", "2"]
+        );
+
+        assert!(locale_matches("pt-BR", "portuguese"));
+        assert!(locale_matches("es-419", "es"));
+        assert!(!locale_matches("es-ES", "es-419"));
+        assert!(is_drm_protected(
+            "<AdaptationSet><ContentProtection schemeIdUri=\"urn:uuid:edef8ba9\"/>"
+        ));
+    }
+}
@@ -0,0 +1,298 @@
+//! A deterministic offline provider for CI and development, selected with
+//! `--provider fake`. It serves the bundled sample shows, streams an mpv
+//! generated test pattern, and keeps AniList progress in memory, so the
+//! whole watch pipeline can run without touching real services. The sample
+//! manga reads as a few chapters of generated striped pages.
+
+use crate::models::{AiringSchedule, AniListResponse, Media, MediaListEntry};
+use crate::player::traits::PlayOptions;
+use crate::provider::models::{AvailableEpisodes, ShowEdge, SourceUrl};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const SAMPLE_MEDIA: &str = include_str!("fake_media.json");
+
+/// Ten seconds of test pattern, generated by mpv itself. Override with
+/// `ANI_L_FAKE_VIDEO` to use a local file instead.
+const SAMPLE_VIDEO: &str = "av://lavfi:testsrc=duration=10:size=640x360:rate=24";
+
+const PAGES_PER_CHAPTER: usize = 4;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref PROGRESS: Mutex<HashMap<i32, i32>> = Mutex::new(HashMap::new());
+}
+
+/// Routes AniList calls to the sample data as well.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn sample() -> AniListResponse {
+    serde_json::from_str(SAMPLE_MEDIA).expect("bundled sample metadata is valid")
+}
+
+fn sample_media() -> Vec<Media> {
+    sample().data.page.map(|p| p.media).unwrap_or_default()
+}
+
+/// Stand-in for `api::fetch_media`: honours `search`, `id_in` and `type`,
+/// ignores sorting and paging.
+pub fn fetch_media(variables: &Value) -> AniListResponse {
+    let search = variables["search"].as_str().map(str::to_lowercase);
+    let ids: Option<Vec<i64>> = variables["id_in"]
+        .as_array()
+        .map(|ids| ids.iter().filter_map(Value::as_i64).collect());
+    let kind = variables["type"].as_str().unwrap_or("ANIME");
+
+    let mut response = sample();
+    if let Some(page) = &mut response.data.page {
+        page.media.retain(|m| {
+            let title_matches = search.as_ref().is_none_or(|s| {
+                [&m.title.english, &m.title.romaji]
+                    .into_iter()
+                    .flatten()
+                    .any(|t| t.to_lowercase().contains(s))
+            });
+            let id_matches = ids.as_ref().is_none_or(|ids| ids.contains(&(m.id as i64)));
+            title_matches && id_matches && m.kind.as_deref().unwrap_or("ANIME") == kind
+        });
+        page.page_info.total = page.media.len() as i32;
+    }
+    response
+}
+
+/// Stand-in for `api::fetch_aired`: airing shows are taken to release one
+/// episode a week up to their next airing episode.
+pub fn aired(media_ids: &[i32], since: i64, until: i64) -> Vec<AiringSchedule> {
+    const WEEK: i64 = 7 * 24 * 60 * 60;
+    let mut aired: Vec<AiringSchedule> = sample_media()
+        .into_iter()
+        .filter(|m| media_ids.contains(&m.id))
+        .filter_map(|m| Some((m.id, m.next_airing_episode?)))
+        .flat_map(|(media_id, next)| {
+            (1..next.episode).map(move |episode| AiringSchedule {
+                media_id,
+                episode,
+                airing_at: next.airing_at - (next.episode - episode) as i64 * WEEK,
+            })
+        })
+        .filter(|a| a.airing_at > since && a.airing_at < until)
+        .collect();
+    aired.sort_by_key(|a| std::cmp::Reverse(a.airing_at));
+    aired
+}
+
+pub fn get_progress(media_id: i32) -> Option<i32> {
+    PROGRESS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&media_id)
+        .copied()
+}
+
+pub fn save_progress(media_id: i32, progress: i32, status: &str) -> MediaListEntry {
+    PROGRESS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(media_id, progress);
+    MediaListEntry {
+        id: Some(media_id),
+        media_id: Some(media_id),
+        status: Some(status.to_string()),
+        progress: Some(progress),
+        score: None,
+        private: None,
+        notes: None,
+    }
+}
+
+/// Stand-in for `api::fetch_library`: every sample anime, on the watching
+/// list at the progress saved this session.
+pub fn library() -> Vec<Media> {
+    sample_media()
+        .into_iter()
+        .filter(|m| !m.is_manga())
+        .map(|mut m| {
+            m.media_list_entry = Some(MediaListEntry {
+                id: Some(m.id),
+                media_id: Some(m.id),
+                status: Some("CURRENT".to_string()),
+                progress: get_progress(m.id),
+                score: None,
+                private: None,
+                notes: None,
+            });
+            m
+        })
+        .collect()
+}
+
+pub struct FakeProvider {
+    translation_type: String,
+}
+
+impl FakeProvider {
+    pub fn new(translation_type: String) -> Self {
+        Self { translation_type }
+    }
+
+    pub fn translation_type(&self) -> &str {
+        &self.translation_type
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        let query = query.to_lowercase();
+        Ok(sample_media()
+            .into_iter()
+            .filter(|m| !m.is_manga() && m.preferred_title().to_lowercase().contains(&query))
+            .map(|m| {
+                let episodes = m.aired_episodes().or(m.episodes).unwrap_or(0) as usize;
+                ShowEdge {
+                    id: format!("fake-{}", m.id),
+                    name: m.preferred_title().to_string(),
+                    available_episodes: AvailableEpisodes {
+                        sub: episodes,
+                        dub: episodes / 2,
+                        raw: 0,
+                    },
+                }
+            })
+            .collect())
+    }
+
+    pub async fn get_episode_sources(
+        &self,
+        show_id: &str,
+        episode_num: &str,
+    ) -> Result<Vec<SourceUrl>> {
+        Ok(vec![SourceUrl {
+            source_name: "S-mp4".to_string(),
+            source_url: format!("fake://{}/{}", show_id, episode_num),
+        }])
+    }
+
+    pub async fn extract_clock_stream(&self, source_url: &str) -> Result<PlayOptions> {
+        source_url
+            .strip_prefix("fake://")
+            .context("Not a fake provider source")?;
+        Ok(PlayOptions {
+            url: std::env::var("ANI_L_FAKE_VIDEO").unwrap_or_else(|_| SAMPLE_VIDEO.to_string()),
+            ..Default::default()
+        })
+    }
+
+    pub async fn search_manga(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        let query = query.to_lowercase();
+        Ok(sample_media()
+            .into_iter()
+            .filter(|m| m.is_manga() && m.preferred_title().to_lowercase().contains(&query))
+            .map(|m| ShowEdge {
+                id: format!("fake-manga-{}", m.id),
+                name: m.preferred_title().to_string(),
+                available_episodes: AvailableEpisodes {
+                    sub: m.chapters.unwrap_or(0) as usize,
+                    dub: 0,
+                    raw: 0,
+                },
+            })
+            .collect())
+    }
+
+    pub async fn chapter_list(&self, manga_id: &str) -> Result<Vec<String>> {
+        let id: i32 = manga_id
+            .strip_prefix("fake-manga-")
+            .and_then(|id| id.parse().ok())
+            .context("Not a fake manga")?;
+        let chapters = sample_media()
+            .into_iter()
+            .find(|m| m.id == id)
+            .and_then(|m| m.chapters)
+            .unwrap_or(0);
+        Ok((1..=chapters).map(|c| c.to_string()).collect())
+    }
+
+    pub async fn chapter_pages(&self, _manga_id: &str, chapter: &str) -> Result<Vec<String>> {
+        Ok((1..=PAGES_PER_CHAPTER)
+            .map(|page| format!("fake://page/{}/{}", chapter, page))
+            .collect())
+    }
+
+    /// A portrait page of horizontal stripes, shaded by page number so
+    /// turning the page visibly changes it.
+    #[cfg(feature = "images")]
+    pub async fn fetch_page(&self, url: &str) -> Result<Vec<u8>> {
+        const WIDTH: usize = 240;
+        const HEIGHT: usize = 360;
+        let page: usize = url
+            .rsplit('/')
+            .next()
+            .and_then(|p| p.parse().ok())
+            .context("Not a fake page")?;
+        let mut image = format!("P6\n{} {}\n255\n", WIDTH, HEIGHT).into_bytes();
+        for y in 0..HEIGHT {
+            let shade = if (y / 30) % 2 == 0 {
+                (page * 60 % 256) as u8
+            } else {
+                240
+            };
+            for _ in 0..WIDTH {
+                image.extend_from_slice(&[shade, 255 - shade, 128]);
+            }
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fake_pipeline_is_deterministic() {
+        let provider = FakeProvider::new("sub".to_string());
+        let shows = provider.search("test adventure").await.unwrap();
+        assert_eq!(shows.len(), 2);
+        assert_eq!(shows[0].id, "fake-900001");
+        // Season 2 is still airing: episodes 1-5 are out.
+        assert_eq!(shows[1].available_episodes.sub, 5);
+
+        let sources = provider
+            .get_episode_sources(&shows[0].id, "3")
+            .await
+            .unwrap();
+        let stream = provider
+            .extract_clock_stream(&sources[0].source_url)
+            .await
+            .unwrap();
+        assert!(!stream.url.is_empty());
+
+        let manga = provider.search_manga("test manga").await.unwrap();
+        let chapters = provider.chapter_list(&manga[0].id).await.unwrap();
+        assert_eq!(chapters, ["1", "2", "3"]);
+        let pages = provider
+            .chapter_pages(&manga[0].id, &chapters[0])
+            .await
+            .unwrap();
+        assert_eq!(pages.len(), PAGES_PER_CHAPTER);
+        #[cfg(feature = "images")]
+        assert!(
+            provider
+                .fetch_page(&pages[0])
+                .await
+                .unwrap()
+                .starts_with(b"P6")
+        );
+
+        let by_id = fetch_media(&serde_json::json!({ "id_in": [900003] }));
+        assert_eq!(by_id.data.page.unwrap().media[0].id, 900003);
+    }
+}
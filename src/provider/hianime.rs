@@ -0,0 +1,411 @@
+use crate::cassette;
+use crate::config::{Config, NetworkConfig};
+use crate::net;
+use crate::player::traits::PlayOptions;
+use crate::provider::hls;
+use crate::provider::html::{attr, decode_entities};
+use crate::provider::models::*;
+use anyhow::{Context, Result};
+use log::debug;
+use reqwest::{Client, Url, header};
+use serde::Deserialize;
+use urlencoding::encode;
+
+const BASE_URL: &str = "https://hianime.to";
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// Server names in preference order. HD-1 is VidStreaming and HD-2 is
+/// MegaCloud; both are served by the MegaCloud player.
+pub const SOURCE_PRIORITY: &[&str] = &["HD-1", "HD-2"];
+
+/// Length of the client key MegaCloud embeds in its player page.
+const CLIENT_KEY_LEN: usize = 48;
+
+pub struct HiAnimeProvider {
+    client: Client,
+    translation_type: String,
+    network: NetworkConfig,
+    user_agent: String,
+    base_url: String,
+    /// Overrides the MegaCloud origin sent as the stream Referer.
+    stream_referer: Option<String>,
+    quality: u32,
+    subtitle_language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HtmlResponse {
+    html: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbedResponse {
+    link: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MegaCloudResponse {
+    /// A list of streams, or an encrypted string on older player versions.
+    sources: serde_json::Value,
+    #[serde(default)]
+    tracks: Vec<MegaCloudTrack>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MegaCloudSource {
+    file: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MegaCloudTrack {
+    file: String,
+    kind: Option<String>,
+    /// Language name, sometimes with a variant ("Portuguese - Brazilian").
+    label: Option<String>,
+    #[serde(default)]
+    default: bool,
+}
+
+impl HiAnimeProvider {
+    pub fn new(translation_type: String, config: &Config) -> Result<Self> {
+        let provider_config = config.provider_config("hianime");
+        let base_url = provider_config
+            .base_url
+            .clone()
+            .unwrap_or_else(|| BASE_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let user_agent = provider_config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| USER_AGENT.to_string());
+        let referer = provider_config
+            .referer
+            .clone()
+            .unwrap_or_else(|| format!("{}/", base_url));
+
+        let mut headers = header::HeaderMap::new();
+        net::insert_header(&mut headers, header::REFERER.as_str(), &referer);
+        net::insert_header(&mut headers, header::USER_AGENT.as_str(), &user_agent);
+        net::insert_header(&mut headers, "X-Requested-With", "XMLHttpRequest");
+        for (name, value) in &provider_config.headers {
+            net::insert_header(&mut headers, name, value);
+        }
+
+        let client = net::provider_client_builder(&config.network)?
+            .default_headers(headers)
+            .build()?;
+        Ok(Self {
+            client,
+            translation_type,
+            network: config.network.clone(),
+            user_agent,
+            base_url,
+            stream_referer: provider_config.stream_referer.clone(),
+            quality: config.stream.quality.parse().unwrap_or(1080),
+            subtitle_language: config.stream.subtitle_language.clone(),
+        })
+    }
+
+    pub fn translation_type(&self) -> &str {
+        &self.translation_type
+    }
+
+    async fn get_text(&self, url: &str, referer: Option<&str>) -> Result<String> {
+        let host = Url::parse(url)?
+            .host_str()
+            .context("Request URL has no host")?
+            .to_string();
+        let (status, body) = match cassette::replay(url)? {
+            Some(recorded) => recorded,
+            None => {
+                let _permit = net::limiter_for(&host, &self.network).acquire().await;
+                let mut request = self.client.get(url);
+                if let Some(referer) = referer {
+                    request = request.header(header::REFERER, referer);
+                }
+                let resp = request.send().await?;
+                let status = resp.status().as_u16();
+                let body = resp.text().await?;
+                cassette::record(url, status, &body);
+                (status, body)
+            }
+        };
+        if !(200..300).contains(&status) {
+            anyhow::bail!("HiAnime returned HTTP {} for {}", status, url);
+        }
+        Ok(body)
+    }
+
+    async fn get_html(&self, url: &str) -> Result<String> {
+        let body = self.get_text(url, None).await?;
+        let resp: HtmlResponse = serde_json::from_str(&body)?;
+        Ok(resp.html)
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        debug!("Searching HiAnime for '{}'...", query);
+        let url = format!("{}/search?keyword={}", self.base_url, encode(query));
+        let html = self.get_text(&url, None).await?;
+        let shows = parse_search(&html);
+        debug!("Received {} results", shows.len());
+        Ok(shows)
+    }
+
+    /// Episode numbers from the show's episode list, as the site labels them.
+    pub async fn episode_list(&self, show_id: &str) -> Result<Vec<String>> {
+        let numeric_id = show_id.rsplit('-').next().unwrap_or(show_id);
+        let list = self
+            .get_html(&format!(
+                "{}/ajax/v2/episode/list/{}",
+                self.base_url, numeric_id
+            ))
+            .await?;
+        Ok(list
+            .split("ep-item")
+            .skip(1)
+            .filter_map(|item| attr(item, "data-number"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Returns the servers carrying `episode_num`, named after
+    /// `SOURCE_PRIORITY`, with the server id as the source URL.
+    pub async fn get_episode_sources(
+        &self,
+        show_id: &str,
+        episode_num: &str,
+    ) -> Result<Vec<SourceUrl>> {
+        let numeric_id = show_id.rsplit('-').next().unwrap_or(show_id);
+        let list = self
+            .get_html(&format!(
+                "{}/ajax/v2/episode/list/{}",
+                self.base_url, numeric_id
+            ))
+            .await?;
+        let episode_id = parse_episode_id(&list, episode_num).with_context(|| {
+            format!("Episode {} not found for show ID {}", episode_num, show_id)
+        })?;
+
+        let servers = self
+            .get_html(&format!(
+                "{}/ajax/v2/episode/servers?episodeId={}",
+                self.base_url, episode_id
+            ))
+            .await?;
+        Ok(parse_servers(&servers, &self.translation_type))
+    }
+
+    pub async fn extract_clock_stream(&self, source_url: &str) -> Result<PlayOptions> {
+        let body = self
+            .get_text(
+                &format!(
+                    "{}/ajax/v2/episode/sources?id={}",
+                    self.base_url, source_url
+                ),
+                None,
+            )
+            .await?;
+        let embed: EmbedResponse = serde_json::from_str(&body)?;
+        self.extract_megacloud(&embed.link).await
+    }
+
+    /// Resolves a MegaCloud embed to its HLS stream and subtitle tracks.
+    async fn extract_megacloud(&self, embed_url: &str) -> Result<PlayOptions> {
+        let url = Url::parse(embed_url)?;
+        let origin = url.origin().ascii_serialization();
+        let id = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .context("MegaCloud embed has no id")?;
+        let referer = format!("{}/", self.base_url);
+
+        let page = self.get_text(embed_url, Some(&referer)).await?;
+        let key = client_key(&page).context("MegaCloud player key not found")?;
+        let api = format!("{}/embed-2/v3/e-1/getSources?id={}&_k={}", origin, id, key);
+        debug!("Resolving MegaCloud sources for {}", id);
+        let body = self.get_text(&api, Some(embed_url)).await?;
+        let resp: MegaCloudResponse = serde_json::from_str(&body)?;
+
+        let sources: Vec<MegaCloudSource> = serde_json::from_value(resp.sources)
+            .context("MegaCloud sent encrypted sources, which ani-l can't read yet")?;
+        let stream = sources
+            .into_iter()
+            .next()
+            .context("No stream links found")?;
+
+        let mut tracks: Vec<MegaCloudTrack> = resp
+            .tracks
+            .into_iter()
+            .filter(|t| matches!(t.kind.as_deref(), Some("captions") | Some("subtitles")))
+            .collect();
+        if let Some(language) = &self.subtitle_language {
+            let wanted =
+                |t: &MegaCloudTrack| is_language(t.label.as_deref().unwrap_or(""), language);
+            // Better the wrong language than no subtitles at all.
+            if tracks.iter().any(wanted) {
+                tracks.retain(wanted);
+            } else {
+                debug!("No {} subtitles among {} tracks", language, tracks.len());
+            }
+        }
+        // mpv selects the first subtitle file it is given.
+        tracks.sort_by_key(|t| !t.default);
+        let subtitles: Vec<String> = tracks.into_iter().map(|t| t.file).collect();
+
+        let mut options = PlayOptions {
+            url: stream.file,
+            headers: Some(vec![
+                ("User-Agent".to_string(), self.user_agent.clone()),
+                (
+                    "Referer".to_string(),
+                    self.stream_referer
+                        .clone()
+                        .unwrap_or_else(|| format!("{}/", origin)),
+                ),
+            ]),
+            subtitles: (!subtitles.is_empty()).then_some(subtitles),
+            ..Default::default()
+        };
+        hls::select_variant(&self.client, &self.network, &mut options, self.quality).await;
+        Ok(options)
+    }
+}
+
+/// Codes for the languages MegaCloud labels its tracks with.
+pub(super) const LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("ar", "arabic"),
+    ("de", "german"),
+    ("en", "english"),
+    ("es", "spanish"),
+    ("fr", "french"),
+    ("id", "indonesian"),
+    ("it", "italian"),
+    ("ja", "japanese"),
+    ("pt", "portuguese"),
+    ("ru", "russian"),
+    ("th", "thai"),
+    ("vi", "vietnamese"),
+];
+
+/// Whether a track label like "Spanish - Latin" is in `language`, given as
+/// a name or a two-letter code.
+fn is_language(label: &str, language: &str) -> bool {
+    let language = language.trim().to_lowercase();
+    let name = LANGUAGE_CODES
+        .iter()
+        .find(|(code, _)| *code == language)
+        .map_or(language.as_str(), |(_, name)| name);
+    let label = label.trim().to_lowercase();
+    !name.is_empty() && label.split([' ', '-', '(']).next() == Some(name)
+}
+
+/// Number shown in a `tick-sub`/`tick-dub` badge of a result card.
+fn tick_count(card: &str, class: &str) -> usize {
+    card.find(class)
+        .and_then(|at| {
+            let rest = &card[at..];
+            let rest = &rest[rest.find("</i>")? + 4..];
+            rest[..rest.find('<')?].trim().parse().ok()
+        })
+        .unwrap_or(0)
+}
+
+fn parse_search(html: &str) -> Vec<ShowEdge> {
+    html.split("class=\"flw-item")
+        .skip(1)
+        .filter_map(|card| {
+            let name_tag = &card[card.find("class=\"film-name")?..];
+            let href = attr(name_tag, "href")?;
+            let id = href.trim_start_matches('/').split('?').next()?.to_string();
+            let name = decode_entities(attr(name_tag, "title")?);
+            Some(ShowEdge {
+                id,
+                name,
+                available_episodes: AvailableEpisodes {
+                    sub: tick_count(card, "tick-sub"),
+                    dub: tick_count(card, "tick-dub"),
+                    raw: 0,
+                },
+            })
+        })
+        .collect()
+}
+
+fn parse_episode_id(html: &str, episode_num: &str) -> Option<String> {
+    html.split("ep-item")
+        .skip(1)
+        .find(|item| attr(item, "data-number") == Some(episode_num))
+        .and_then(|item| attr(item, "data-id"))
+        .map(str::to_string)
+}
+
+fn parse_servers(html: &str, translation_type: &str) -> Vec<SourceUrl> {
+    html.split("server-item")
+        .skip(1)
+        .filter(|item| attr(item, "data-type") == Some(translation_type))
+        .filter_map(|item| {
+            let source_name = match attr(item, "data-server-id")? {
+                "4" => "HD-1".to_string(),
+                "1" => "HD-2".to_string(),
+                other => format!("Server {}", other),
+            };
+            Some(SourceUrl {
+                source_name,
+                source_url: attr(item, "data-id")?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The player page carries a 48-character alphanumeric key that
+/// `getSources` expects as `_k`.
+fn client_key(page: &str) -> Option<&str> {
+    page.split(|c: char| !c.is_ascii_alphanumeric())
+        .find(|token| token.len() == CLIENT_KEY_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pages() {
+        let search = r#"
+            <div class="flw-item"><div class="tick-item tick-sub"><i class="fas"></i>28</div>
+            <div class="tick-item tick-dub"><i class="fas"></i>12</div>
+            <h3 class="film-name"><a href="/frieren-beyond-journeys-end-18542?ref=search"
+               title="Frieren: Beyond Journey&#39;s End">Frieren</a></h3></div>"#;
+        let shows = parse_search(search);
+        assert_eq!(shows.len(), 1);
+        assert_eq!(shows[0].id, "frieren-beyond-journeys-end-18542");
+        assert_eq!(shows[0].name, "Frieren: Beyond Journey's End");
+        assert_eq!(shows[0].available_episodes.sub, 28);
+        assert_eq!(shows[0].available_episodes.dub, 12);
+
+        let list = r#"<a class="ssl-item ep-item" data-number="1" data-id="100">
+                      <a class="ssl-item ep-item" data-number="2" data-id="101">"#;
+        assert_eq!(parse_episode_id(list, "2").as_deref(), Some("101"));
+
+        assert!(is_language("Spanish - Latin", "es"));
+        assert!(is_language("English", "english"));
+        assert!(!is_language("English", "es"));
+        assert!(!is_language("Portuguese", ""));
+
+        let servers = r#"<div class="item server-item" data-type="sub" data-id="9" data-server-id="4">
+                         <div class="item server-item" data-type="dub" data-id="8" data-server-id="1">"#;
+        let sources = parse_servers(servers, "sub");
+        assert_eq!(sources.len(), 1);
+        assert_eq!(
+            (
+                sources[0].source_name.as_str(),
+                sources[0].source_url.as_str()
+            ),
+            ("HD-1", "9")
+        );
+
+        let key = "a".repeat(CLIENT_KEY_LEN);
+        let page = format!("<script>window._xy_ws = \"{}\";</script>", key);
+        assert_eq!(client_key(&page), Some(key.as_str()));
+    }
+}
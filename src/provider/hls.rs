@@ -0,0 +1,153 @@
+//! HLS master playlists: pick the variant matching `stream.quality` instead
+//! of leaving the choice to the player.
+
+use crate::config::NetworkConfig;
+use crate::net;
+use crate::player::traits::PlayOptions;
+use log::debug;
+use reqwest::{Client, Url};
+
+#[derive(Debug, PartialEq)]
+struct Variant {
+    bandwidth: u64,
+    /// Vertical resolution, when the playlist says.
+    height: Option<u32>,
+    url: String,
+}
+
+/// Sort key for an encode: the wanted resolution first, then lower ones
+/// from best down, then higher ones from smallest up.
+pub(crate) fn quality_rank(resolution: u32, wanted: u32) -> (bool, u32) {
+    if resolution <= wanted {
+        (false, wanted - resolution)
+    } else {
+        (true, resolution - wanted)
+    }
+}
+
+/// Points `options` at the variant closest to `quality` when its URL is a
+/// master playlist. The URL is left alone if the playlist can't be fetched
+/// or isn't a master; the player copes with those itself.
+pub(crate) async fn select_variant(
+    client: &Client,
+    network: &NetworkConfig,
+    options: &mut PlayOptions,
+    quality: u32,
+) {
+    if !options.url.contains(".m3u8") {
+        return;
+    }
+    let Ok(base) = Url::parse(&options.url) else {
+        return;
+    };
+    let mut request = client.get(base.clone());
+    for (name, value) in options.headers.iter().flatten() {
+        request = request.header(name, value);
+    }
+    let _permit = net::limiter_for(base.host_str().unwrap_or_default(), network)
+        .acquire()
+        .await;
+    let body = match request.send().await {
+        Ok(resp) if resp.status().is_success() => resp.text().await.unwrap_or_default(),
+        _ => return,
+    };
+    let variants = parse_master(&body, &base);
+    if let Some(variant) = pick(&variants, quality) {
+        debug!(
+            "Picked {}p ({} bps) of {} HLS variants",
+            variant.height.unwrap_or(0),
+            variant.bandwidth,
+            variants.len()
+        );
+        options.url = variant.url.clone();
+    }
+}
+
+/// Value of `name=` in an attribute list such as
+/// `BANDWIDTH=1280000,RESOLUTION=1280x720,CODECS="avc1.4d401f,mp4a.40.2"`.
+fn attribute<'a>(list: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = list;
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], quoted[end + 1..].trim_start_matches(','))
+            }
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+        if key.trim() == name {
+            return Some(value);
+        }
+        rest = next;
+    }
+    None
+}
+
+fn parse_master(body: &str, base: &Url) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    let mut lines = body.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let Some(list) = line.strip_prefix("#EXT-X-STREAM-INF:") else {
+            continue;
+        };
+        let Some(uri) = lines
+            .by_ref()
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+        else {
+            break;
+        };
+        let Ok(url) = base.join(uri) else {
+            continue;
+        };
+        variants.push(Variant {
+            bandwidth: attribute(list, "BANDWIDTH")
+                .and_then(|b| b.parse().ok())
+                .unwrap_or(0),
+            height: attribute(list, "RESOLUTION")
+                .and_then(|r| r.split_once('x'))
+                .and_then(|(_, h)| h.parse().ok()),
+            url: url.to_string(),
+        });
+    }
+    variants
+}
+
+/// The variant closest to `quality`, by resolution where the playlist gives
+/// one. Among equal resolutions the higher bitrate wins.
+fn pick(variants: &[Variant], quality: u32) -> Option<&Variant> {
+    variants.iter().min_by_key(|v| {
+        let rank = match v.height {
+            Some(height) => quality_rank(height, quality),
+            None => (true, u32::MAX),
+        };
+        (rank, std::cmp::Reverse(v.bandwidth))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_variant() {
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\"\n\
+            360/index.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=2500000,RESOLUTION=1280x720\n\
+            720/index.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080\n\
+            https://cdn.example/1080/index.m3u8\n";
+        let base = Url::parse("https://cdn.example/hls/master.m3u8").unwrap();
+        let variants = parse_master(master, &base);
+        assert_eq!(variants.len(), 3);
+        assert_eq!(variants[0].url, "https://cdn.example/hls/360/index.m3u8");
+
+        let url = |quality| pick(&variants, quality).map(|v| v.url.as_str());
+        assert_eq!(url(720), Some("https://cdn.example/hls/720/index.m3u8"));
+        assert_eq!(url(1080), Some("https://cdn.example/1080/index.m3u8"));
+        assert_eq!(url(480), Some("https://cdn.example/hls/360/index.m3u8"));
+        assert_eq!(url(240), Some("https://cdn.example/hls/360/index.m3u8"));
+        assert!(parse_master("#EXTM3U\n#EXTINF:4.0,\nseg0.ts\n", &base).is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+//! Just enough HTML scraping for providers without a JSON API.
+
+/// Value of the first `name="..."` attribute in `html`.
+pub fn attr<'a>(html: &'a str, name: &str) -> Option<&'a str> {
+    let needle = format!("{}=\"", name);
+    let start = html.find(&needle)? + needle.len();
+    let end = html[start..].find('"')? + start;
+    Some(&html[start..end])
+}
+
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
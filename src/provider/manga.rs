@@ -0,0 +1,58 @@
+//! Manga sources for the reader: chapter lists and page images. Only
+//! AllAnime (and the fake provider) serve manga, whatever
+//! `general.provider` is set to.
+
+use crate::config::Config;
+use crate::provider::models::ShowEdge;
+use crate::provider::{allanime, fake};
+use anyhow::Result;
+
+pub enum MangaProvider {
+    AllAnime(Box<allanime::AllAnimeProvider>),
+    Fake(fake::FakeProvider),
+}
+
+impl MangaProvider {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(if fake::enabled() {
+            MangaProvider::Fake(fake::FakeProvider::new("sub".to_string()))
+        } else {
+            MangaProvider::AllAnime(Box::new(allanime::AllAnimeProvider::new(
+                "sub".to_string(),
+                config,
+            )?))
+        })
+    }
+
+    /// Manga matching `query`; `available_episodes` counts chapters.
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        match self {
+            MangaProvider::AllAnime(p) => p.search_manga(query).await,
+            MangaProvider::Fake(p) => p.search_manga(query).await,
+        }
+    }
+
+    /// Chapter strings, first chapter first.
+    pub async fn chapter_list(&self, manga_id: &str) -> Result<Vec<String>> {
+        match self {
+            MangaProvider::AllAnime(p) => p.chapter_list(manga_id).await,
+            MangaProvider::Fake(p) => p.chapter_list(manga_id).await,
+        }
+    }
+
+    /// Page URLs of a chapter in reading order.
+    pub async fn chapter_pages(&self, manga_id: &str, chapter: &str) -> Result<Vec<String>> {
+        match self {
+            MangaProvider::AllAnime(p) => p.chapter_pages(manga_id, chapter).await,
+            MangaProvider::Fake(p) => p.chapter_pages(manga_id, chapter).await,
+        }
+    }
+
+    #[cfg(feature = "images")]
+    pub async fn fetch_page(&self, url: &str) -> Result<Vec<u8>> {
+        match self {
+            MangaProvider::AllAnime(p) => p.fetch_page(url).await,
+            MangaProvider::Fake(p) => p.fetch_page(url).await,
+        }
+    }
+}
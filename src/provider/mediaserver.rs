@@ -0,0 +1,640 @@
+//! Jellyfin and Plex servers as providers, for browsing a self-hosted
+//! collection in the same TUI. Selected with `general.provider = "jellyfin"`
+//! or `"plex"`, configured under `[providers.jellyfin]` / `[providers.plex]`.
+//!
+//! A show's regular seasons are numbered as one continuous run, the way
+//! AniList continuations and the other providers count them; specials
+//! (season 0) are listed as "SP1", "SP2", ...
+
+use crate::cassette;
+use crate::config::{Config, NetworkConfig, ProviderConfig};
+use crate::net;
+use crate::player::traits::PlayOptions;
+use crate::provider::models::*;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use log::debug;
+use rand::Rng;
+use reqwest::{Client, Url, header};
+use serde::Deserialize;
+use serde_json::json;
+use std::fs;
+use std::sync::OnceLock;
+use tokio::sync::OnceCell;
+use urlencoding::encode;
+
+const CLIENT_NAME: &str = "ani-l";
+const PLEX_SIGN_IN_URL: &str = "https://plex.tv/users/sign_in.json";
+const SEARCH_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Server {
+    Jellyfin,
+    Plex,
+}
+
+impl Server {
+    pub fn key(self) -> &'static str {
+        match self {
+            Server::Jellyfin => "jellyfin",
+            Server::Plex => "plex",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Server::Jellyfin => "Jellyfin",
+            Server::Plex => "Plex",
+        }
+    }
+
+    /// Libraries of this kind hold series.
+    fn show_library_type(self) -> &'static str {
+        match self {
+            Server::Jellyfin => "tvshows",
+            Server::Plex => "show",
+        }
+    }
+}
+
+/// This install's id on the servers, made up once and kept, so they list
+/// one device for it rather than one per ani-l user.
+fn device_id() -> &'static str {
+    static DEVICE_ID: OnceLock<String> = OnceLock::new();
+    DEVICE_ID.get_or_init(|| {
+        let path = ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+            .map(|dirs| dirs.data_dir().join("device_id"));
+        if let Some(id) = path.as_ref().and_then(|p| fs::read_to_string(p).ok()) {
+            let id = id.trim();
+            if !id.is_empty() {
+                return id.to_string();
+            }
+        }
+        let id = format!("{:032x}", rand::thread_rng().r#gen::<u128>());
+        if let Some(path) = path {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            let _ = fs::write(path, &id);
+        }
+        id
+    })
+}
+
+pub const JELLYFIN_SOURCES: &[&str] = &["Jellyfin"];
+pub const PLEX_SOURCES: &[&str] = &["Plex"];
+
+/// Who we are on the server and where to look, worked out on first use.
+struct Session {
+    token: String,
+    /// Jellyfin user, when logged in with a password rather than an API key.
+    user_id: Option<String>,
+    libraries: Vec<String>,
+}
+
+pub struct MediaServerProvider {
+    client: Client,
+    server: Server,
+    translation_type: String,
+    network: NetworkConfig,
+    config: ProviderConfig,
+    base_url: String,
+    session: OnceCell<Session>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinItems {
+    #[serde(default)]
+    items: Vec<JellyfinItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinItem {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    collection_type: Option<String>,
+    /// Episodes, for a series.
+    #[serde(default)]
+    recursive_item_count: Option<usize>,
+    #[serde(default)]
+    index_number: Option<i32>,
+    #[serde(default)]
+    parent_index_number: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinAuth {
+    access_token: String,
+    user: JellyfinUser,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinUser {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexResponse {
+    #[serde(rename = "MediaContainer")]
+    container: PlexContainer,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexContainer {
+    #[serde(rename = "Directory", default)]
+    directories: Vec<PlexDirectory>,
+    #[serde(rename = "Metadata", default)]
+    metadata: Vec<PlexMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexDirectory {
+    key: String,
+    title: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlexMetadata {
+    rating_key: String,
+    #[serde(default)]
+    title: String,
+    /// Episodes, for a show.
+    #[serde(default)]
+    leaf_count: Option<usize>,
+    #[serde(default)]
+    index: Option<i32>,
+    #[serde(default)]
+    parent_index: Option<i32>,
+    #[serde(rename = "Media", default)]
+    media: Vec<PlexMedia>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexMedia {
+    #[serde(rename = "Part", default)]
+    parts: Vec<PlexPart>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexPart {
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexSignIn {
+    user: PlexUser,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlexUser {
+    auth_token: String,
+}
+
+/// One file of a show: its season (0 for specials), its number within the
+/// season, and whatever the server needs to stream it.
+struct ServerEpisode {
+    season: i32,
+    index: i32,
+    stream: String,
+}
+
+impl MediaServerProvider {
+    pub fn new(server: Server, translation_type: String, config: &Config) -> Result<Self> {
+        let provider_config = config.provider_config(server.key());
+        let base_url = provider_config
+            .base_url
+            .clone()
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_string();
+
+        let mut headers = header::HeaderMap::new();
+        net::insert_header(&mut headers, header::ACCEPT.as_str(), "application/json");
+        if server == Server::Plex {
+            net::insert_header(&mut headers, "X-Plex-Product", CLIENT_NAME);
+            net::insert_header(&mut headers, "X-Plex-Client-Identifier", device_id());
+        }
+        if let Some(user_agent) = &provider_config.user_agent {
+            net::insert_header(&mut headers, header::USER_AGENT.as_str(), user_agent);
+        }
+        for (name, value) in &provider_config.headers {
+            net::insert_header(&mut headers, name, value);
+        }
+
+        let client = net::provider_client_builder(&config.network)?
+            .default_headers(headers)
+            .build()?;
+        Ok(Self {
+            client,
+            server,
+            translation_type,
+            network: config.network.clone(),
+            config: provider_config,
+            base_url,
+            session: OnceCell::new(),
+        })
+    }
+
+    pub fn translation_type(&self) -> &str {
+        &self.translation_type
+    }
+
+    pub fn server(&self) -> Server {
+        self.server
+    }
+
+    /// The `Authorization` value Jellyfin expects, with or without a token.
+    fn jellyfin_authorization(token: Option<&str>) -> String {
+        let mut value = format!(
+            "MediaBrowser Client=\"{}\", Device=\"{}\", DeviceId=\"{}\", Version=\"{}\"",
+            CLIENT_NAME,
+            CLIENT_NAME,
+            device_id(),
+            env!("CARGO_PKG_VERSION")
+        );
+        if let Some(token) = token {
+            value.push_str(&format!(", Token=\"{}\"", token));
+        }
+        value
+    }
+
+    async fn get_text(&self, url: &str, token: &str) -> Result<String> {
+        let host = Url::parse(url)?
+            .host_str()
+            .context("Request URL has no host")?
+            .to_string();
+        let (status, body) = match cassette::replay(url)? {
+            Some(recorded) => recorded,
+            None => {
+                let _permit = net::limiter_for(&host, &self.network).acquire().await;
+                let request = match self.server {
+                    Server::Jellyfin => self.client.get(url).header(
+                        header::AUTHORIZATION,
+                        Self::jellyfin_authorization(Some(token)),
+                    ),
+                    Server::Plex => self.client.get(url).header("X-Plex-Token", token),
+                };
+                let resp = request.send().await?;
+                let status = resp.status().as_u16();
+                let body = resp.text().await?;
+                cassette::record(url, status, &body);
+                (status, body)
+            }
+        };
+        if !(200..300).contains(&status) {
+            anyhow::bail!(
+                "{} returned HTTP {} for {}",
+                self.server.name(),
+                status,
+                url
+            );
+        }
+        Ok(body)
+    }
+
+    async fn session(&self) -> Result<&Session> {
+        self.session.get_or_try_init(|| self.connect()).await
+    }
+
+    /// Logs in (unless a token is configured) and picks the libraries to
+    /// search.
+    async fn connect(&self) -> Result<Session> {
+        if self.base_url.is_empty() {
+            anyhow::bail!(
+                "Set providers.{}.base_url to your {} server's address",
+                self.server.key(),
+                self.server.name()
+            );
+        }
+        let (token, user_id) = match &self.config.token {
+            Some(token) => (token.clone(), None),
+            None => self.log_in().await?,
+        };
+
+        let libraries: Vec<(String, String, String)> = match self.server {
+            Server::Jellyfin => {
+                let url = format!("{}/Library/MediaFolders", self.base_url);
+                let items: JellyfinItems =
+                    serde_json::from_str(&self.get_text(&url, &token).await?)?;
+                items
+                    .items
+                    .into_iter()
+                    .map(|i| (i.id, i.name, i.collection_type.unwrap_or_default()))
+                    .collect()
+            }
+            Server::Plex => {
+                let url = format!("{}/library/sections", self.base_url);
+                let resp: PlexResponse = serde_json::from_str(&self.get_text(&url, &token).await?)?;
+                resp.container
+                    .directories
+                    .into_iter()
+                    .map(|d| (d.key, d.title, d.kind))
+                    .collect()
+            }
+        };
+        let libraries = pick_libraries(
+            &libraries,
+            self.server.show_library_type(),
+            &self.config.libraries,
+        );
+        debug!("Searching {} libraries {:?}", self.server.name(), libraries);
+        Ok(Session {
+            token,
+            user_id,
+            libraries,
+        })
+    }
+
+    async fn log_in(&self) -> Result<(String, Option<String>)> {
+        let (Some(username), Some(password)) = (&self.config.username, &self.config.password)
+        else {
+            anyhow::bail!(
+                "Set providers.{}.token, or username and password, to sign in to {}",
+                self.server.key(),
+                self.server.name()
+            );
+        };
+        match self.server {
+            Server::Jellyfin => {
+                let resp = self
+                    .client
+                    .post(format!("{}/Users/AuthenticateByName", self.base_url))
+                    .header(header::AUTHORIZATION, Self::jellyfin_authorization(None))
+                    .json(&json!({ "Username": username, "Pw": password }))
+                    .send()
+                    .await?
+                    .error_for_status()
+                    .context("Jellyfin rejected the username or password")?;
+                let auth: JellyfinAuth = resp.json().await?;
+                Ok((auth.access_token, Some(auth.user.id)))
+            }
+            Server::Plex => {
+                let resp = self
+                    .client
+                    .post(PLEX_SIGN_IN_URL)
+                    .basic_auth(username, Some(password))
+                    .send()
+                    .await?
+                    .error_for_status()
+                    .context("plex.tv rejected the username or password")?;
+                let sign_in: PlexSignIn = resp.json().await?;
+                Ok((sign_in.user.auth_token, None))
+            }
+        }
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        debug!("Searching {} for '{}'...", self.server.name(), query);
+        let session = self.session().await?;
+        // No libraries picked means search the whole server.
+        let scopes: Vec<Option<&String>> = if session.libraries.is_empty() {
+            vec![None]
+        } else {
+            session.libraries.iter().map(Some).collect()
+        };
+
+        let mut shows = Vec::new();
+        for library in scopes {
+            let found: Vec<(String, String, usize)> = match self.server {
+                Server::Jellyfin => {
+                    let mut url = format!(
+                        "{}/Items?searchTerm={}&IncludeItemTypes=Series&Recursive=true&Fields=RecursiveItemCount&Limit={}",
+                        self.base_url,
+                        encode(query),
+                        SEARCH_LIMIT
+                    );
+                    if let Some(library) = library {
+                        url.push_str(&format!("&ParentId={}", library));
+                    }
+                    if let Some(user_id) = &session.user_id {
+                        url.push_str(&format!("&userId={}", user_id));
+                    }
+                    let items: JellyfinItems =
+                        serde_json::from_str(&self.get_text(&url, &session.token).await?)?;
+                    items
+                        .items
+                        .into_iter()
+                        .map(|i| (i.id, i.name, i.recursive_item_count.unwrap_or(0)))
+                        .collect()
+                }
+                Server::Plex => {
+                    let url = match library {
+                        Some(library) => format!(
+                            "{}/library/sections/{}/all?type=2&title={}",
+                            self.base_url,
+                            library,
+                            encode(query)
+                        ),
+                        None => format!("{}/search?type=2&query={}", self.base_url, encode(query)),
+                    };
+                    let resp: PlexResponse =
+                        serde_json::from_str(&self.get_text(&url, &session.token).await?)?;
+                    resp.container
+                        .metadata
+                        .into_iter()
+                        .map(|m| (m.rating_key, m.title, m.leaf_count.unwrap_or(0)))
+                        .collect()
+                }
+            };
+            shows.extend(found);
+        }
+        debug!("Received {} results", shows.len());
+        // The files are whatever the server has, so every translation
+        // reports the same count.
+        Ok(shows
+            .into_iter()
+            .map(|(id, name, episodes)| ShowEdge {
+                id,
+                name,
+                available_episodes: AvailableEpisodes {
+                    sub: episodes,
+                    dub: episodes,
+                    raw: episodes,
+                },
+            })
+            .collect())
+    }
+
+    async fn episodes(&self, show_id: &str) -> Result<Vec<ServerEpisode>> {
+        let session = self.session().await?;
+        match self.server {
+            Server::Jellyfin => {
+                let mut url = format!("{}/Shows/{}/Episodes", self.base_url, show_id);
+                if let Some(user_id) = &session.user_id {
+                    url.push_str(&format!("?userId={}", user_id));
+                }
+                let items: JellyfinItems =
+                    serde_json::from_str(&self.get_text(&url, &session.token).await?)?;
+                Ok(items
+                    .items
+                    .into_iter()
+                    .filter_map(|i| {
+                        Some(ServerEpisode {
+                            season: i.parent_index_number.unwrap_or(1),
+                            index: i.index_number?,
+                            stream: format!("{}/Videos/{}/stream?static=true", self.base_url, i.id),
+                        })
+                    })
+                    .collect())
+            }
+            Server::Plex => {
+                let url = format!("{}/library/metadata/{}/allLeaves", self.base_url, show_id);
+                let resp: PlexResponse =
+                    serde_json::from_str(&self.get_text(&url, &session.token).await?)?;
+                Ok(resp
+                    .container
+                    .metadata
+                    .into_iter()
+                    .filter_map(|m| {
+                        let part = m.media.first()?.parts.first()?;
+                        Some(ServerEpisode {
+                            season: m.parent_index.unwrap_or(1),
+                            index: m.index?,
+                            stream: format!("{}{}", self.base_url, part.key),
+                        })
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    pub async fn episode_list(&self, show_id: &str) -> Result<Vec<String>> {
+        Ok(number_episodes(self.episodes(show_id).await?)
+            .into_iter()
+            .map(|(episode, _)| episode)
+            .collect())
+    }
+
+    pub async fn get_episode_sources(
+        &self,
+        show_id: &str,
+        episode_num: &str,
+    ) -> Result<Vec<SourceUrl>> {
+        let stream = number_episodes(self.episodes(show_id).await?)
+            .into_iter()
+            .find(|(episode, _)| episode == episode_num)
+            .map(|(_, stream)| stream)
+            .with_context(|| {
+                format!("Episode {} not found for show ID {}", episode_num, show_id)
+            })?;
+        Ok(vec![SourceUrl {
+            source_name: self.server.name().to_string(),
+            source_url: stream,
+        }])
+    }
+
+    /// The server streams the file itself. The token goes in a header
+    /// rather than the URL, which players print and downloads keep.
+    pub async fn extract_clock_stream(&self, source_url: &str) -> Result<PlayOptions> {
+        let token = &self.session().await?.token;
+        let header = match self.server {
+            Server::Jellyfin => (
+                header::AUTHORIZATION.to_string(),
+                Self::jellyfin_authorization(Some(token)),
+            ),
+            Server::Plex => ("X-Plex-Token".to_string(), token.clone()),
+        };
+        Ok(PlayOptions {
+            url: source_url.to_string(),
+            headers: Some(vec![header]),
+            ..Default::default()
+        })
+    }
+}
+
+/// The libraries to search, by id. Configured names win; otherwise series
+/// libraries with "anime" in their name, or every series library when none
+/// is.
+fn pick_libraries(
+    libraries: &[(String, String, String)],
+    show_type: &str,
+    wanted: &[String],
+) -> Vec<String> {
+    let ids = |keep: &dyn Fn(&str, &str) -> bool| -> Vec<String> {
+        libraries
+            .iter()
+            .filter(|(_, name, kind)| keep(name, kind))
+            .map(|(id, _, _)| id.clone())
+            .collect()
+    };
+    if !wanted.is_empty() {
+        return ids(&|name, _| wanted.iter().any(|w| w.eq_ignore_ascii_case(name)));
+    }
+    let anime = ids(&|name, kind| kind == show_type && name.to_lowercase().contains("anime"));
+    if !anime.is_empty() {
+        return anime;
+    }
+    ids(&|_, kind| kind == show_type)
+}
+
+/// Episode strings for the server's files, in watching order: regular
+/// seasons counted on from one another, then specials as "SP<n>".
+fn number_episodes(mut episodes: Vec<ServerEpisode>) -> Vec<(String, String)> {
+    // Specials last, everything else by season then number.
+    episodes.sort_by_key(|e| (e.season == 0, e.season, e.index));
+    let mut numbered = Vec::with_capacity(episodes.len());
+    let mut absolute = 0;
+    for episode in episodes {
+        let label = if episode.season == 0 {
+            format!("SP{}", episode.index)
+        } else {
+            absolute += 1;
+            absolute.to_string()
+        };
+        numbered.push((label, episode.stream));
+    }
+    numbered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbering_and_libraries() {
+        let episode = |season, index| ServerEpisode {
+            season,
+            index,
+            stream: format!("s{}e{}", season, index),
+        };
+        let numbered = number_episodes(vec![
+            episode(2, 1),
+            episode(0, 1),
+            episode(1, 2),
+            episode(1, 1),
+        ]);
+        assert_eq!(
+            numbered,
+            vec![
+                ("1".to_string(), "s1e1".to_string()),
+                ("2".to_string(), "s1e2".to_string()),
+                ("3".to_string(), "s2e1".to_string()),
+                ("SP1".to_string(), "s0e1".to_string()),
+            ]
+        );
+
+        let library =
+            |id: &str, name: &str, kind: &str| (id.to_string(), name.to_string(), kind.to_string());
+        let libraries = vec![
+            library("1", "Movies", "movies"),
+            library("2", "TV Shows", "tvshows"),
+            library("3", "Anime", "tvshows"),
+        ];
+        assert_eq!(pick_libraries(&libraries, "tvshows", &[]), vec!["3"]);
+        assert_eq!(pick_libraries(&libraries[..2], "tvshows", &[]), vec!["2"]);
+        assert_eq!(
+            pick_libraries(&libraries, "tvshows", &["tv shows".to_string()]),
+            vec!["2"]
+        );
+    }
+}
@@ -1,2 +1,304 @@
 pub mod allanime;
+pub mod animepahe;
+pub mod crunchyroll;
+pub mod fake;
+pub mod hianime;
+pub mod hls;
+pub mod html;
+pub mod manga;
+pub mod mediaserver;
 pub mod models;
+#[cfg(feature = "torrent")]
+pub mod nyaa;
+pub mod probe;
+pub mod schema;
+pub mod youtube;
+
+use crate::config::Config;
+use crate::player::traits::PlayOptions;
+use anyhow::{Context, Result};
+use log::debug;
+use models::{ShowEdge, SourceUrl};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+
+/// Every `general.provider` value this build takes, "fake" aside, in the
+/// order the Options screen cycles through them.
+pub fn keys() -> Vec<&'static str> {
+    let mut keys = vec![
+        "allanime",
+        "animepahe",
+        "crunchyroll",
+        "hianime",
+        "jellyfin",
+        "plex",
+        "youtube",
+    ];
+    #[cfg(feature = "torrent")]
+    keys.push("nyaa");
+    keys.sort_unstable();
+    keys
+}
+
+/// The stream providers ani-l can use, chosen by `general.provider`.
+pub enum AnyProvider {
+    AllAnime(allanime::AllAnimeProvider),
+    AnimePahe(animepahe::AnimePaheProvider),
+    Crunchyroll(Box<crunchyroll::CrunchyrollProvider>),
+    Fake(fake::FakeProvider),
+    HiAnime(hianime::HiAnimeProvider),
+    MediaServer(Box<mediaserver::MediaServerProvider>),
+    #[cfg(feature = "torrent")]
+    Nyaa(Box<nyaa::NyaaProvider>),
+    YouTube(youtube::YouTubeProvider),
+}
+
+impl AnyProvider {
+    pub fn new(translation_type: String, config: &Config) -> Result<Self> {
+        Ok(match config.general.provider.as_str() {
+            "animepahe" => {
+                AnyProvider::AnimePahe(animepahe::AnimePaheProvider::new(translation_type, config)?)
+            }
+            "crunchyroll" => AnyProvider::Crunchyroll(Box::new(
+                crunchyroll::CrunchyrollProvider::new(translation_type, config)?,
+            )),
+            "fake" => AnyProvider::Fake(fake::FakeProvider::new(translation_type)),
+            "hianime" => {
+                AnyProvider::HiAnime(hianime::HiAnimeProvider::new(translation_type, config)?)
+            }
+            "jellyfin" => {
+                AnyProvider::MediaServer(Box::new(mediaserver::MediaServerProvider::new(
+                    mediaserver::Server::Jellyfin,
+                    translation_type,
+                    config,
+                )?))
+            }
+            "plex" => AnyProvider::MediaServer(Box::new(mediaserver::MediaServerProvider::new(
+                mediaserver::Server::Plex,
+                translation_type,
+                config,
+            )?)),
+            #[cfg(feature = "torrent")]
+            "nyaa" => {
+                AnyProvider::Nyaa(Box::new(nyaa::NyaaProvider::new(translation_type, config)?))
+            }
+            "youtube" => {
+                AnyProvider::YouTube(youtube::YouTubeProvider::new(translation_type, config))
+            }
+            "allanime" => {
+                AnyProvider::AllAnime(allanime::AllAnimeProvider::new(translation_type, config)?)
+            }
+            other => anyhow::bail!("Unknown provider '{}'", other),
+        })
+    }
+
+    pub fn translation_type(&self) -> &str {
+        match self {
+            AnyProvider::AllAnime(p) => p.translation_type(),
+            AnyProvider::AnimePahe(p) => p.translation_type(),
+            AnyProvider::Crunchyroll(p) => p.translation_type(),
+            AnyProvider::Fake(p) => p.translation_type(),
+            AnyProvider::HiAnime(p) => p.translation_type(),
+            AnyProvider::MediaServer(p) => p.translation_type(),
+            #[cfg(feature = "torrent")]
+            AnyProvider::Nyaa(p) => p.translation_type(),
+            AnyProvider::YouTube(p) => p.translation_type(),
+        }
+    }
+
+    /// The `general.provider` value that selects this provider.
+    pub fn key(&self) -> &'static str {
+        match self {
+            AnyProvider::AllAnime(_) => "allanime",
+            AnyProvider::AnimePahe(_) => "animepahe",
+            AnyProvider::Crunchyroll(_) => "crunchyroll",
+            AnyProvider::Fake(_) => "fake",
+            AnyProvider::HiAnime(_) => "hianime",
+            AnyProvider::MediaServer(p) => p.server().key(),
+            #[cfg(feature = "torrent")]
+            AnyProvider::Nyaa(_) => "nyaa",
+            AnyProvider::YouTube(_) => "youtube",
+        }
+    }
+
+    /// Hands back what the provider holds for the streams it resolved, once
+    /// they've played. Only Crunchyroll holds anything: a play-service token
+    /// per stream, which counts against the account's concurrent streams.
+    pub async fn release_streams(&self) {
+        if let AnyProvider::Crunchyroll(p) = self {
+            p.release_streams().await;
+        }
+    }
+
+    /// Display name for logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnyProvider::AllAnime(_) => "AllAnime",
+            AnyProvider::AnimePahe(_) => "AnimePahe",
+            AnyProvider::Crunchyroll(_) => "Crunchyroll",
+            AnyProvider::Fake(_) => "Fake",
+            AnyProvider::HiAnime(_) => "HiAnime",
+            AnyProvider::MediaServer(p) => p.server().name(),
+            #[cfg(feature = "torrent")]
+            AnyProvider::Nyaa(_) => "Nyaa",
+            AnyProvider::YouTube(_) => "YouTube",
+        }
+    }
+
+    /// Source names to try, best first.
+    pub fn source_priority(&self) -> &'static [&'static str] {
+        match self {
+            AnyProvider::AllAnime(_) | AnyProvider::Fake(_) => allanime::SOURCE_PRIORITY,
+            AnyProvider::HiAnime(_) => hianime::SOURCE_PRIORITY,
+            AnyProvider::MediaServer(p) => match p.server() {
+                mediaserver::Server::Jellyfin => mediaserver::JELLYFIN_SOURCES,
+                mediaserver::Server::Plex => mediaserver::PLEX_SOURCES,
+            },
+            #[cfg(feature = "torrent")]
+            AnyProvider::Nyaa(_) => nyaa::SOURCE_PRIORITY,
+            AnyProvider::AnimePahe(_) => animepahe::SOURCE_PRIORITY,
+            AnyProvider::Crunchyroll(_) => crunchyroll::SOURCE_PRIORITY,
+            AnyProvider::YouTube(_) => youtube::SOURCE_PRIORITY,
+        }
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        match self {
+            AnyProvider::AllAnime(p) => p.search(query).await,
+            AnyProvider::AnimePahe(p) => p.search(query).await,
+            AnyProvider::Crunchyroll(p) => p.search(query).await,
+            AnyProvider::Fake(p) => p.search(query).await,
+            AnyProvider::HiAnime(p) => p.search(query).await,
+            AnyProvider::MediaServer(p) => p.search(query).await,
+            #[cfg(feature = "torrent")]
+            AnyProvider::Nyaa(p) => p.search(query).await,
+            AnyProvider::YouTube(p) => p.search(query).await,
+        }
+    }
+
+    /// Episode strings the provider lists for the show, in no particular
+    /// order. Empty when the provider can't list them cheaply; callers then
+    /// count episode numbers instead.
+    pub async fn episode_list(&self, show_id: &str) -> Result<Vec<String>> {
+        match self {
+            AnyProvider::AllAnime(p) => p.episode_list(show_id).await,
+            AnyProvider::Crunchyroll(p) => p.episode_list(show_id).await,
+            AnyProvider::HiAnime(p) => p.episode_list(show_id).await,
+            AnyProvider::MediaServer(p) => p.episode_list(show_id).await,
+            AnyProvider::YouTube(p) => p.episode_list(show_id).await,
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub async fn get_episode_sources(
+        &self,
+        show_id: &str,
+        episode_num: &str,
+    ) -> Result<Vec<SourceUrl>> {
+        match self {
+            AnyProvider::AllAnime(p) => p.get_episode_sources(show_id, episode_num).await,
+            AnyProvider::AnimePahe(p) => p.get_episode_sources(show_id, episode_num).await,
+            AnyProvider::Crunchyroll(p) => p.get_episode_sources(show_id, episode_num).await,
+            AnyProvider::Fake(p) => p.get_episode_sources(show_id, episode_num).await,
+            AnyProvider::HiAnime(p) => p.get_episode_sources(show_id, episode_num).await,
+            AnyProvider::MediaServer(p) => p.get_episode_sources(show_id, episode_num).await,
+            #[cfg(feature = "torrent")]
+            AnyProvider::Nyaa(p) => p.get_episode_sources(show_id, episode_num).await,
+            AnyProvider::YouTube(p) => p.get_episode_sources(show_id, episode_num).await,
+        }
+    }
+
+    pub async fn extract_clock_stream(&self, source_url: &str) -> Result<PlayOptions> {
+        match self {
+            AnyProvider::AllAnime(p) => p.extract_clock_stream(source_url).await,
+            AnyProvider::AnimePahe(p) => p.extract_clock_stream(source_url).await,
+            AnyProvider::Crunchyroll(p) => p.extract_clock_stream(source_url).await,
+            AnyProvider::Fake(p) => p.extract_clock_stream(source_url).await,
+            AnyProvider::HiAnime(p) => p.extract_clock_stream(source_url).await,
+            AnyProvider::MediaServer(p) => p.extract_clock_stream(source_url).await,
+            #[cfg(feature = "torrent")]
+            AnyProvider::Nyaa(p) => p.extract_clock_stream(source_url).await,
+            AnyProvider::YouTube(p) => p.extract_clock_stream(source_url).await,
+        }
+    }
+
+    /// Extracts every prioritized source at once and returns the first
+    /// stream that comes back and answers a probe, so dead hosts early in the
+    /// list don't hold up the rest. Sources finishing together go by
+    /// priority; extractions still running are dropped.
+    pub async fn extract_first(&self, sources: &[SourceUrl]) -> Option<PlayOptions> {
+        let attempts = self
+            .source_priority()
+            .iter()
+            .filter_map(|&name| sources.iter().find(|s| s.source_name == name))
+            .map(|source| async move {
+                let options = self.extract_clock_stream(&source.source_url).await?;
+                probe::check(&options)
+                    .await
+                    .with_context(|| format!("{} stream is unplayable", source.source_name))?;
+                Ok(options)
+            });
+        first_ok(attempts).await
+    }
+}
+
+/// Polls the futures side by side and resolves to the first `Ok`, or `None`
+/// once all of them have failed.
+async fn first_ok<T, F: Future<Output = Result<T>>>(
+    futures: impl IntoIterator<Item = F>,
+) -> Option<T> {
+    let mut pending: Vec<Pin<Box<F>>> = futures.into_iter().map(Box::pin).collect();
+    std::future::poll_fn(|cx| {
+        let mut i = 0;
+        while i < pending.len() {
+            match pending[i].as_mut().poll(cx) {
+                Poll::Ready(Ok(value)) => return Poll::Ready(Some(value)),
+                Poll::Ready(Err(e)) => {
+                    debug!("Source failed: {:#}", e);
+                    pending.remove(i);
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+        if pending.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_first_ok() {
+        let attempt = |delay_ms: u64, ok: bool, value: u32| async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            if ok {
+                Ok(value)
+            } else {
+                anyhow::bail!("dead host")
+            }
+        };
+        // A slow dead source first doesn't delay a quick working one.
+        assert_eq!(
+            first_ok([
+                attempt(500, false, 1),
+                attempt(10, true, 2),
+                attempt(50, true, 3)
+            ])
+            .await,
+            Some(2)
+        );
+        assert_eq!(
+            first_ok([attempt(0, true, 1), attempt(0, true, 2)]).await,
+            Some(1)
+        );
+        assert_eq!(first_ok([attempt(0, false, 1)]).await, None);
+    }
+}
@@ -17,7 +17,7 @@ pub struct ShowsConnection {
     pub edges: Vec<ShowEdge>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ShowEdge {
     #[serde(rename = "_id")]
     pub id: String,
@@ -26,13 +26,35 @@ pub struct ShowEdge {
     pub available_episodes: AvailableEpisodes,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AvailableEpisodes {
     pub sub: usize,
     pub dub: usize,
     pub raw: usize,
 }
 
+impl AvailableEpisodes {
+    pub fn for_translation(&self, translation_type: &str) -> usize {
+        match translation_type {
+            "dub" => self.dub,
+            "raw" => self.raw,
+            _ => self.sub,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShowDetailData {
+    pub show: ShowDetail,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShowDetail {
+    /// Episode strings per translation type.
+    #[serde(rename = "availableEpisodesDetail", default)]
+    pub available_episodes_detail: std::collections::HashMap<String, Vec<String>>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EpisodeResultData {
     // FIX: Wrapped in Option to handle null API responses gracefully
@@ -64,3 +86,62 @@ pub struct GogoLink {
     #[serde(rename = "resolutionStr")]
     pub resolution: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct MangaSearchData {
+    pub mangas: MangasConnection,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MangasConnection {
+    pub edges: Vec<MangaEdge>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MangaEdge {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    /// Chapter counts per translation type ("sub", "raw").
+    #[serde(rename = "availableChapters", default)]
+    pub available_chapters: std::collections::HashMap<String, usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MangaDetailData {
+    pub manga: MangaDetail,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MangaDetail {
+    /// Chapter strings per translation type.
+    #[serde(rename = "availableChaptersDetail", default)]
+    pub available_chapters_detail: std::collections::HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChapterPagesData {
+    #[serde(rename = "chapterPages")]
+    pub chapter_pages: Option<ChapterPagesConnection>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChapterPagesConnection {
+    pub edges: Vec<ChapterPagesEdge>,
+}
+
+/// One scan of a chapter. Picture URLs may be relative to `picture_url_head`.
+#[derive(Debug, Deserialize)]
+pub struct ChapterPagesEdge {
+    #[serde(rename = "pictureUrls", default)]
+    pub picture_urls: Vec<PictureUrl>,
+    #[serde(rename = "pictureUrlHead")]
+    pub picture_url_head: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PictureUrl {
+    pub url: String,
+    #[serde(default)]
+    pub num: f64,
+}
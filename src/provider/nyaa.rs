@@ -0,0 +1,346 @@
+//! Nyaa torrent search, streamed through `webtorrent`. Covers shows no
+//! streaming site carries, at the cost of needing peers.
+
+use crate::cassette;
+use crate::config::{Config, NetworkConfig};
+use crate::net;
+use crate::player::traits::PlayOptions;
+use crate::provider::html::decode_entities;
+use crate::provider::models::*;
+use anyhow::{Context, Result};
+use log::debug;
+use rand::Rng;
+use reqwest::{Client, Url, header};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use urlencoding::encode;
+
+const BASE_URL: &str = "https://nyaa.si";
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// Source names in preference order: the best matching release, then the
+/// runner-up.
+pub const SOURCE_PRIORITY: &[&str] = &["Torrent", "Torrent-alt"];
+
+const TRACKERS: &[&str] = &[
+    "http://nyaa.tracker.wf:7777/announce",
+    "udp://open.stealth.si:80/announce",
+    "udp://tracker.opentrackr.org:1337/announce",
+];
+
+/// How long `webtorrent` gets to find peers and start its server.
+const STREAM_START_TIMEOUT: Duration = Duration::from_secs(90);
+
+pub struct NyaaProvider {
+    client: Client,
+    translation_type: String,
+    network: NetworkConfig,
+    base_url: String,
+    quality: String,
+    release_groups: Vec<String>,
+    /// The running `webtorrent`, killed when replaced or dropped.
+    session: Mutex<Option<Child>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Release {
+    title: String,
+    info_hash: String,
+    seeders: u32,
+}
+
+impl Release {
+    fn group(&self) -> Option<&str> {
+        self.title.strip_prefix('[')?.split(']').next()
+    }
+
+    fn magnet(&self) -> String {
+        let mut magnet = format!(
+            "magnet:?xt=urn:btih:{}&dn={}",
+            self.info_hash,
+            encode(&self.title)
+        );
+        for tracker in TRACKERS {
+            magnet.push_str(&format!("&tr={}", encode(tracker)));
+        }
+        magnet
+    }
+}
+
+impl NyaaProvider {
+    pub fn new(translation_type: String, config: &Config) -> Result<Self> {
+        let provider_config = config.provider_config("nyaa");
+        let base_url = provider_config
+            .base_url
+            .clone()
+            .unwrap_or_else(|| BASE_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let user_agent = provider_config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| USER_AGENT.to_string());
+
+        let mut headers = header::HeaderMap::new();
+        net::insert_header(&mut headers, header::USER_AGENT.as_str(), &user_agent);
+        for (name, value) in &provider_config.headers {
+            net::insert_header(&mut headers, name, value);
+        }
+
+        let client = net::provider_client_builder(&config.network)?
+            .default_headers(headers)
+            .build()?;
+        Ok(Self {
+            client,
+            translation_type,
+            network: config.network.clone(),
+            base_url,
+            quality: format!("{}p", config.stream.quality),
+            release_groups: provider_config.release_groups,
+            session: Mutex::new(None),
+        })
+    }
+
+    pub fn translation_type(&self) -> &str {
+        &self.translation_type
+    }
+
+    async fn releases(&self, query: &str) -> Result<Vec<Release>> {
+        // 1_2 is English-translated anime, 1_4 raws.
+        let category = if self.translation_type == "raw" {
+            "1_4"
+        } else {
+            "1_2"
+        };
+        let url = format!(
+            "{}/?page=rss&c={}&f=0&q={}",
+            self.base_url,
+            category,
+            encode(query)
+        );
+        let host = Url::parse(&url)?
+            .host_str()
+            .context("Request URL has no host")?
+            .to_string();
+        let body = match cassette::replay(&url)? {
+            Some((_, body)) => body,
+            None => {
+                let _permit = net::limiter_for(&host, &self.network).acquire().await;
+                let resp = self.client.get(&url).send().await?;
+                let status = resp.status().as_u16();
+                let body = resp.text().await?;
+                cassette::record(&url, status, &body);
+                body
+            }
+        };
+        Ok(parse_rss(&body))
+    }
+
+    /// Nyaa has no notion of a show, so the query itself stands in for one,
+    /// with as many episodes as the newest numbered release.
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        debug!("Searching Nyaa for '{}'...", query);
+        let releases = self.releases(query).await?;
+        debug!("Received {} releases", releases.len());
+        let Some(latest) = releases.iter().filter_map(|r| episode_of(&r.title)).max() else {
+            return Ok(Vec::new());
+        };
+        let latest = latest as usize;
+        Ok(vec![ShowEdge {
+            id: query.to_string(),
+            name: query.to_string(),
+            available_episodes: AvailableEpisodes {
+                sub: latest,
+                dub: latest,
+                raw: latest,
+            },
+        }])
+    }
+
+    pub async fn get_episode_sources(
+        &self,
+        show_id: &str,
+        episode_num: &str,
+    ) -> Result<Vec<SourceUrl>> {
+        let episode: u32 = episode_num
+            .parse()
+            .with_context(|| format!("Invalid episode number {}", episode_num))?;
+        let releases = self
+            .releases(&format!("{} {:02}", show_id, episode))
+            .await?;
+        let picked = pick_releases(releases, episode, &self.quality, &self.release_groups);
+        Ok(picked
+            .into_iter()
+            .zip(SOURCE_PRIORITY)
+            .map(|(release, name)| SourceUrl {
+                source_name: name.to_string(),
+                source_url: release.magnet(),
+            })
+            .collect())
+    }
+
+    /// Starts `webtorrent` on the magnet link and plays from its local HTTP
+    /// server.
+    pub async fn extract_clock_stream(&self, source_url: &str) -> Result<PlayOptions> {
+        let mut session = self.session.lock().await;
+        // Only one torrent at a time; the previous episode's client goes.
+        *session = None;
+
+        let mut child = Command::new("webtorrent")
+            .arg("download")
+            .arg(source_url)
+            .arg("--port")
+            .arg(rand::thread_rng().gen_range(20000..40000).to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start webtorrent (install it with `npm i -g webtorrent-cli`)")?;
+
+        let stdout = child.stdout.take().context("webtorrent has no stdout")?;
+        let mut lines = BufReader::new(stdout).lines();
+        let url = tokio::time::timeout(STREAM_START_TIMEOUT, async {
+            while let Some(line) = lines.next_line().await? {
+                if let Some(url) = server_url(&line) {
+                    return Ok(url);
+                }
+            }
+            anyhow::bail!("webtorrent exited before it started streaming")
+        })
+        .await
+        .context("No peers found for this torrent")??;
+
+        // Keep draining progress output so webtorrent never blocks on it.
+        tokio::spawn(async move { while let Ok(Some(_)) = lines.next_line().await {} });
+        *session = Some(child);
+
+        Ok(PlayOptions {
+            url,
+            ..Default::default()
+        })
+    }
+}
+
+/// Contents of the first `<tag>...</tag>` in `xml`.
+fn tag<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&format!("</{}>", tag))? + start;
+    Some(&xml[start..end])
+}
+
+fn parse_rss(xml: &str) -> Vec<Release> {
+    xml.split("<item>")
+        .skip(1)
+        .filter_map(|item| {
+            Some(Release {
+                title: decode_entities(tag(item, "title")?),
+                info_hash: tag(item, "nyaa:infoHash")?.to_string(),
+                seeders: tag(item, "nyaa:seeders")?.parse().unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// Episode number of a single-episode release named the usual fansub way,
+/// e.g. "[Group] Show - 05 (1080p)" or "Show S01E05". Batches like
+/// "01-12" aren't single episodes and give `None`.
+fn episode_of(title: &str) -> Option<u32> {
+    let number_after = |rest: &str| {
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        let next = rest[digits.len()..].chars().next();
+        let ends = next.is_none_or(|c| matches!(c, ' ' | '(' | '[' | 'v' | '.'));
+        (!digits.is_empty() && digits.len() <= 4 && ends)
+            .then(|| digits.parse().ok())
+            .flatten()
+    };
+    title
+        .match_indices(" - ")
+        .find_map(|(at, sep)| number_after(&title[at + sep.len()..]))
+        .or_else(|| {
+            let upper = title.to_uppercase();
+            upper.match_indices('E').find_map(|(at, _)| {
+                let season = upper[..at].rsplit('S').next()?;
+                (!season.is_empty() && season.chars().all(|c| c.is_ascii_digit()))
+                    .then(|| number_after(&upper[at + 1..]))
+                    .flatten()
+            })
+        })
+}
+
+/// Releases of `episode`, best first: in the wanted quality when there is
+/// one, then by position in `groups`, then by seeders.
+fn pick_releases(
+    releases: Vec<Release>,
+    episode: u32,
+    quality: &str,
+    groups: &[String],
+) -> Vec<Release> {
+    let mut matching: Vec<Release> = releases
+        .into_iter()
+        .filter(|r| episode_of(&r.title) == Some(episode) && r.seeders > 0)
+        .collect();
+    if matching.iter().any(|r| r.title.contains(quality)) {
+        matching.retain(|r| r.title.contains(quality));
+    }
+    matching.sort_by_key(|r| {
+        let rank = r
+            .group()
+            .and_then(|g| groups.iter().position(|p| p.eq_ignore_ascii_case(g)))
+            .unwrap_or(groups.len());
+        (rank, std::cmp::Reverse(r.seeders))
+    });
+    matching
+}
+
+/// The streaming address in a line of `webtorrent` output.
+fn server_url(line: &str) -> Option<String> {
+    let start = line.find("http://")?;
+    let url: String = line[start..]
+        .chars()
+        .take_while(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_release() {
+        let rss = r#"<rss><channel>
+            <item><title>[SubsPlease] Frieren - 05 (720p) [AAAA].mkv</title>
+              <nyaa:seeders>900</nyaa:seeders><nyaa:infoHash>aaaa</nyaa:infoHash></item>
+            <item><title>[SubsPlease] Frieren - 05 (1080p) [BBBB].mkv</title>
+              <nyaa:seeders>500</nyaa:seeders><nyaa:infoHash>bbbb</nyaa:infoHash></item>
+            <item><title>[Erai-raws] Frieren - 05 [1080p][Multiple Subtitle]</title>
+              <nyaa:seeders>300</nyaa:seeders><nyaa:infoHash>cccc</nyaa:infoHash></item>
+            <item><title>[Judas] Frieren - 01-12 (1080p) [Batch]</title>
+              <nyaa:seeders>999</nyaa:seeders><nyaa:infoHash>dddd</nyaa:infoHash></item>
+        </channel></rss>"#;
+        let releases = parse_rss(rss);
+        assert_eq!(releases.len(), 4);
+        assert_eq!(episode_of(&releases[3].title), None);
+        assert_eq!(episode_of("Frieren S01E12 1080p WEB"), Some(12));
+
+        let groups = vec!["erai-raws".to_string()];
+        let picked = pick_releases(releases, 5, "1080p", &groups);
+        let hashes: Vec<&str> = picked.iter().map(|r| r.info_hash.as_str()).collect();
+        assert_eq!(hashes, vec!["cccc", "bbbb"]);
+        assert!(
+            picked[0]
+                .magnet()
+                .starts_with("magnet:?xt=urn:btih:cccc&dn=")
+        );
+
+        assert_eq!(
+            server_url("Server running at: http://localhost:8000/webtorrent/abc/ep.mkv\x1b[0m"),
+            Some("http://localhost:8000/webtorrent/abc/ep.mkv".to_string())
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Checks that a resolved stream answers before a player is started on it,
+//! so a dead link moves on to the next source instead of opening a player
+//! window that errors out straight away.
+
+use crate::net;
+use crate::player::traits::PlayOptions;
+use anyhow::{Context, Result};
+use log::debug;
+use reqwest::header;
+use std::time::Duration;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Asks for the stream's first byte with the headers the player will send.
+/// Only HTTP(S) URLs are checked; local files and mpv's own protocols are
+/// left to the player.
+pub async fn check(options: &PlayOptions) -> Result<()> {
+    if !options.url.starts_with("http://") && !options.url.starts_with("https://") {
+        return Ok(());
+    }
+    let mut request = net::stream_client()
+        .get(&options.url)
+        .header(header::RANGE, "bytes=0-0");
+    for (name, value) in options.headers.iter().flatten() {
+        request = request.header(name, value);
+    }
+    let response = tokio::time::timeout(PROBE_TIMEOUT, request.send())
+        .await
+        .context("Stream host didn't answer")??;
+
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("Stream host answered {}", status);
+    }
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !playable(content_type) {
+        anyhow::bail!("Stream host sent a {} page instead of video", content_type);
+    }
+    debug!("Stream answered {} ({})", status, content_type);
+    Ok(())
+}
+
+/// Whether a response of `content_type` can be a stream. Error and
+/// challenge pages come back as HTML or JSON; anything else, including no
+/// type at all, is given the benefit of the doubt.
+fn playable(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    !matches!(
+        mime.as_str(),
+        "text/html" | "application/xhtml+xml" | "application/json"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playable() {
+        assert!(playable("video/mp4"));
+        assert!(playable("application/vnd.apple.mpegurl"));
+        assert!(playable(""));
+        assert!(!playable("text/html; charset=UTF-8"));
+        assert!(!playable("Application/JSON"));
+    }
+}
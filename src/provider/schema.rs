@@ -0,0 +1,180 @@
+//! AllAnime's API has renamed its enum types before (the `Vaild...` typos
+//! may not last). When the first request of a session comes back in a shape
+//! that doesn't parse, the known ways of writing the queries are tried with
+//! a one-result search, and the one that works is used from then on and
+//! remembered for the next run.
+
+use anyhow::Error;
+use directories::ProjectDirs;
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Ways of writing the queries, in the order they're tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryVariant {
+    /// `VaildTranslationTypeEnumType` and friends, as the API spells them.
+    Vaild,
+    /// The same types with the typo fixed.
+    Valid,
+    /// Enum values written into the query itself, so no type names at all.
+    Inline,
+}
+
+/// Enum-typed variables, which are the part that changes between variants.
+const ENUM_VARIABLES: &[&str] = &["translationType", "countryOrigin"];
+
+impl QueryVariant {
+    pub const ALL: [QueryVariant; 3] = [Self::Vaild, Self::Valid, Self::Inline];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Vaild => "vaild",
+            Self::Valid => "valid",
+            Self::Inline => "inline",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.key() == key)
+    }
+
+    /// Rewrites a query written for `Vaild`, moving inlined values out of
+    /// `variables`.
+    pub fn apply(self, gql: &str, variables: &mut Value) -> String {
+        match self {
+            Self::Vaild => gql.to_string(),
+            Self::Valid => gql
+                .replace("$translationType: Vaild", "$translationType: Valid")
+                .replace("$countryOrigin: Vaild", "$countryOrigin: Valid"),
+            Self::Inline => {
+                let mut gql = gql.to_string();
+                for name in ENUM_VARIABLES {
+                    let Some(value) = variables
+                        .as_object_mut()
+                        .and_then(|vars| vars.remove(*name))
+                    else {
+                        continue;
+                    };
+                    let Some(value) = value.as_str() else {
+                        continue;
+                    };
+                    gql = drop_declaration(&gql, name).replace(&format!("${}", name), value);
+                }
+                gql
+            }
+        }
+    }
+}
+
+/// Removes `, $name: Type` from the query's variable list.
+fn drop_declaration(gql: &str, name: &str) -> String {
+    let marker = format!(", ${}:", name);
+    let Some(start) = gql.find(&marker) else {
+        return gql.to_string();
+    };
+    let rest = &gql[start + marker.len()..];
+    let end = rest.find([',', ')']).unwrap_or(rest.len());
+    format!("{}{}", &gql[..start], &rest[end..])
+}
+
+struct Session {
+    variant: QueryVariant,
+    /// Set once a request has parsed or a probe has run; later failures are
+    /// the show's or the network's, not the schema's.
+    settled: bool,
+}
+
+lazy_static! {
+    static ref SESSION: Mutex<Session> = Mutex::new(Session {
+        variant: saved().unwrap_or(QueryVariant::Vaild),
+        settled: false,
+    });
+    /// Held while probing, so requests fired together probe once.
+    pub static ref PROBING: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+fn saved_path() -> Option<PathBuf> {
+    ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+        .map(|dirs| dirs.data_dir().join("allanime_schema"))
+}
+
+fn saved() -> Option<QueryVariant> {
+    QueryVariant::from_key(fs::read_to_string(saved_path()?).ok()?.trim())
+}
+
+pub fn current() -> QueryVariant {
+    SESSION.lock().unwrap().variant
+}
+
+pub fn is_settled() -> bool {
+    SESSION.lock().unwrap().settled
+}
+
+/// Call after a response parsed with `variant`.
+pub fn settle(variant: QueryVariant) {
+    let mut session = SESSION.lock().unwrap();
+    if session.variant == variant {
+        session.settled = true;
+    }
+}
+
+/// Switches the session to what the probe found (or keeps the current
+/// variant when nothing worked) and stops further probing.
+pub fn probed(found: Option<QueryVariant>) {
+    let mut session = SESSION.lock().unwrap();
+    session.settled = true;
+    let Some(variant) = found else {
+        return;
+    };
+    session.variant = variant;
+    if let Some(path) = saved_path() {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, variant.key());
+    }
+}
+
+/// A response that was JSON but not the shape asked for, which is what a
+/// GraphQL error about the query itself looks like (`errors` but no `data`).
+pub fn is_schema_error(err: &Error) -> bool {
+    err.downcast_ref::<serde_json::Error>()
+        .is_some_and(|e| e.classify() == serde_json::error::Category::Data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::models::AllAnimeResponse;
+    use serde_json::json;
+
+    #[test]
+    fn test_variants_rewrite_enum_types() {
+        let gql = "query($showId: String!, $translationType: VaildTranslationTypeEnumType!, $episodeString: String!) { episode(showId: $showId, translationType: $translationType, episodeString: $episodeString) { sourceUrls } }";
+        let vars = json!({ "showId": "x", "translationType": "sub", "episodeString": "1" });
+
+        let mut same = vars.clone();
+        assert_eq!(QueryVariant::Vaild.apply(gql, &mut same), gql);
+        assert!(
+            QueryVariant::Valid
+                .apply(gql, &mut same)
+                .contains("$translationType: ValidTranslationTypeEnumType!")
+        );
+        assert_eq!(same, vars);
+
+        let mut inline = vars.clone();
+        assert_eq!(
+            QueryVariant::Inline.apply(gql, &mut inline),
+            "query($showId: String!, $episodeString: String!) { episode(showId: $showId, translationType: sub, episodeString: $episodeString) { sourceUrls } }"
+        );
+        assert_eq!(inline, json!({ "showId": "x", "episodeString": "1" }));
+
+        let err = Error::from(
+            serde_json::from_str::<AllAnimeResponse<Value>>("{\"errors\": []}").unwrap_err(),
+        );
+        assert!(is_schema_error(&err));
+    }
+}
@@ -0,0 +1,286 @@
+//! Episodes published for free on official YouTube channels (Muse Asia,
+//! Ani-One), found and resolved through `yt-dlp`. Selected with
+//! `general.provider = "youtube"`, and used on its own for shows tagged
+//! `free` in the registry.
+//!
+//! Like Nyaa there is no notion of a show: each channel whose uploads match
+//! the title stands in for one, its episodes numbered from the video titles.
+
+use crate::config::Config;
+use crate::player::traits::PlayOptions;
+use crate::provider::models::*;
+use anyhow::{Context, Result};
+use log::debug;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use urlencoding::encode;
+
+/// Channel handles searched when `providers.youtube.channels` isn't set.
+const CHANNELS: &[&str] = &["@MuseAsia", "@AniOneAsia"];
+/// Uploads looked at per channel search.
+const SEARCH_LIMIT: usize = 100;
+
+pub const SOURCE_PRIORITY: &[&str] = &["YouTube"];
+
+pub struct YouTubeProvider {
+    translation_type: String,
+    channels: Vec<String>,
+    quality: String,
+    proxy: Option<String>,
+    /// Numbered uploads per show id, kept from the search so listing and
+    /// resolving episodes doesn't run yt-dlp again.
+    videos: Mutex<HashMap<String, Vec<(u32, String)>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Playlist {
+    #[serde(default)]
+    entries: Vec<Video>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Video {
+    id: String,
+    #[serde(default)]
+    title: String,
+}
+
+impl YouTubeProvider {
+    pub fn new(translation_type: String, config: &Config) -> Self {
+        let provider_config = config.provider_config("youtube");
+        let channels = if provider_config.channels.is_empty() {
+            CHANNELS.iter().map(|c| c.to_string()).collect()
+        } else {
+            provider_config.channels
+        };
+        Self {
+            translation_type,
+            channels,
+            quality: config.stream.quality.clone(),
+            proxy: config.network.proxy.clone(),
+            videos: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn translation_type(&self) -> &str {
+        &self.translation_type
+    }
+
+    async fn yt_dlp(&self, args: &[&str]) -> Result<String> {
+        let mut command = Command::new("yt-dlp");
+        command.args(args);
+        if let Some(proxy) = &self.proxy {
+            command.arg("--proxy").arg(proxy);
+        }
+        let output =
+            command.stdin(Stdio::null()).output().await.context(
+                "Failed to start yt-dlp (install it from https://github.com/yt-dlp/yt-dlp)",
+            )?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "yt-dlp failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// The channel's uploads matching `query`, as (episode, video id).
+    async fn channel_episodes(&self, channel: &str, query: &str) -> Result<Vec<(u32, String)>> {
+        let url = format!(
+            "https://www.youtube.com/{}/search?query={}",
+            channel,
+            encode(query)
+        );
+        let limit = SEARCH_LIMIT.to_string();
+        let json = self
+            .yt_dlp(&["--flat-playlist", "-J", "--playlist-end", &limit, &url])
+            .await?;
+        let playlist: Playlist = serde_json::from_str(&json)?;
+        Ok(number_videos(playlist.entries, query))
+    }
+
+    /// One show per channel that has numbered uploads of `query`. Fails
+    /// only when no channel could be searched at all.
+    pub async fn search(&self, query: &str) -> Result<Vec<ShowEdge>> {
+        let mut shows = Vec::new();
+        let mut failures = Vec::new();
+        for channel in &self.channels {
+            debug!("Searching YouTube channel {} for '{}'...", channel, query);
+            let episodes = match self.channel_episodes(channel, query).await {
+                Ok(episodes) if !episodes.is_empty() => episodes,
+                Ok(_) => continue,
+                Err(e) => {
+                    debug!("Searching {} failed: {:#}", channel, e);
+                    failures.push(e);
+                    continue;
+                }
+            };
+            let latest = episodes.iter().map(|(n, _)| *n).max().unwrap_or(0) as usize;
+            let id = format!("{}/{}", channel, query);
+            shows.push(ShowEdge {
+                id: id.clone(),
+                name: format!("{} ({})", query, channel.trim_start_matches('@')),
+                // Official uploads are subtitled; the count stands for every
+                // translation like Nyaa's.
+                available_episodes: AvailableEpisodes {
+                    sub: latest,
+                    dub: latest,
+                    raw: latest,
+                },
+            });
+            self.videos.lock().await.insert(id, episodes);
+        }
+        if failures.len() == self.channels.len()
+            && let Some(e) = failures.pop()
+        {
+            return Err(e);
+        }
+        Ok(shows)
+    }
+
+    async fn episodes(&self, show_id: &str) -> Result<Vec<(u32, String)>> {
+        if let Some(episodes) = self.videos.lock().await.get(show_id) {
+            return Ok(episodes.clone());
+        }
+        let (channel, query) = show_id
+            .split_once('/')
+            .with_context(|| format!("Invalid YouTube show ID {}", show_id))?;
+        let episodes = self.channel_episodes(channel, query).await?;
+        self.videos
+            .lock()
+            .await
+            .insert(show_id.to_string(), episodes.clone());
+        Ok(episodes)
+    }
+
+    pub async fn episode_list(&self, show_id: &str) -> Result<Vec<String>> {
+        Ok(self
+            .episodes(show_id)
+            .await?
+            .into_iter()
+            .map(|(n, _)| n.to_string())
+            .collect())
+    }
+
+    pub async fn get_episode_sources(
+        &self,
+        show_id: &str,
+        episode_num: &str,
+    ) -> Result<Vec<SourceUrl>> {
+        let episode: u32 = episode_num
+            .parse()
+            .with_context(|| format!("Invalid episode number {}", episode_num))?;
+        let id = self
+            .episodes(show_id)
+            .await?
+            .into_iter()
+            .find(|(n, _)| *n == episode)
+            .map(|(_, id)| id)
+            .with_context(|| {
+                format!("Episode {} not found for show ID {}", episode_num, show_id)
+            })?;
+        Ok(vec![SourceUrl {
+            source_name: "YouTube".to_string(),
+            source_url: format!("https://www.youtube.com/watch?v={}", id),
+        }])
+    }
+
+    /// Resolves the video to a single file with audio, so players without
+    /// YouTube support of their own can play it too.
+    pub async fn extract_clock_stream(&self, source_url: &str) -> Result<PlayOptions> {
+        let format = format!("b[height<={}]/b", self.quality);
+        let urls = self
+            .yt_dlp(&["--get-url", "-f", &format, source_url])
+            .await?;
+        let url = urls
+            .lines()
+            .next()
+            .filter(|l| !l.is_empty())
+            .context("yt-dlp found no playable format")?;
+        Ok(PlayOptions {
+            url: url.to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Uploads whose title contains every word of `query` and an episode
+/// number, as (episode, video id) in episode order. Re-uploads of an
+/// episode keep the first.
+fn number_videos(videos: Vec<Video>, query: &str) -> Vec<(u32, String)> {
+    let wanted = words(query);
+    let mut episodes: Vec<(u32, String)> = Vec::new();
+    for video in videos {
+        let title = words(&video.title);
+        if !wanted.iter().all(|w| title.contains(w)) {
+            continue;
+        }
+        if let Some(n) = episode_of(&video.title)
+            && !episodes.iter().any(|(e, _)| *e == n)
+        {
+            episodes.push((n, video.id));
+        }
+    }
+    episodes.sort_by_key(|(n, _)| *n);
+    episodes
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Episode number in an upload title, e.g. "Show - Episode 5 [English
+/// Sub]", "Show EP05" or "Show #05".
+fn episode_of(title: &str) -> Option<u32> {
+    let lower = title.to_lowercase();
+    ["episode", "ep.", "ep", "#"].iter().find_map(|marker| {
+        lower.match_indices(marker).find_map(|(at, _)| {
+            let starts_word = lower[..at]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !c.is_alphanumeric());
+            let rest = lower[at + marker.len()..].trim_start();
+            let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            (starts_word && !digits.is_empty() && digits.len() <= 4)
+                .then(|| digits.parse().ok())
+                .flatten()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_videos() {
+        let video = |id: &str, title: &str| Video {
+            id: id.to_string(),
+            title: title.to_string(),
+        };
+        let videos = vec![
+            video("b", "Frieren: Beyond Journey's End EP02 | Muse Asia"),
+            video(
+                "a",
+                "Frieren: Beyond Journey's End - Episode 1 [English Sub]",
+            ),
+            video("c", "Frieren: Beyond Journey's End #02 (re-upload)"),
+            video("d", "Frieren: Beyond Journey's End | Trailer"),
+            video("e", "Deep Sea Monster Raiga EP 7"),
+        ];
+        assert_eq!(
+            number_videos(videos, "Frieren: Beyond Journey's End"),
+            vec![(1, "a".to_string()), (2, "b".to_string())]
+        );
+        assert_eq!(episode_of("Deep Insanity"), None);
+        assert_eq!(episode_of("Show Ep. 12 (Eng Sub)"), Some(12));
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// One episode waiting to be played.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QueueEntry {
+    pub media_id: i32,
+    pub title: String,
+    pub episode: i32,
+}
+
+/// Episodes to play one after another, kept across sessions so a marathon
+/// can pick up where it stopped.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct WatchQueue {
+    pub entries: Vec<QueueEntry>,
+}
+
+impl WatchQueue {
+    /// Queues episodes `from..=to` of a show, skipping ones already queued.
+    /// Returns how many were added.
+    pub fn enqueue_show(&mut self, media_id: i32, title: &str, from: i32, to: i32) -> usize {
+        let before = self.entries.len();
+        for episode in from.max(1)..=to {
+            let queued = self
+                .entries
+                .iter()
+                .any(|e| e.media_id == media_id && e.episode == episode);
+            if !queued {
+                self.entries.push(QueueEntry {
+                    media_id,
+                    title: title.to_string(),
+                    episode,
+                });
+            }
+        }
+        self.entries.len() - before
+    }
+
+    /// Drops `episode` of the show and everything queued before it, since
+    /// the player can skip ahead on its own.
+    pub fn mark_watched(&mut self, media_id: i32, episode: i32) {
+        self.entries
+            .retain(|e| e.media_id != media_id || e.episode > episode);
+    }
+}
+
+pub struct QueueManager {
+    file_path: PathBuf,
+    pub data: WatchQueue,
+}
+
+impl QueueManager {
+    pub fn new() -> Result<Self> {
+        let proj_dirs = ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+            .context("Could not determine data directory")?;
+        let data_dir = proj_dirs.data_dir();
+        fs::create_dir_all(data_dir)?;
+        let file_path = data_dir.join("queue.json");
+
+        let data = if file_path.exists() {
+            let content = fs::read_to_string(&file_path)?;
+            serde_json::from_str(&content).unwrap_or_default()
+        } else {
+            WatchQueue::default()
+        };
+
+        Ok(Self { file_path, data })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let json_str = serde_json::to_string_pretty(&self.data)?;
+        fs::write(&self.file_path, json_str)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enqueue_and_watch() {
+        let mut queue = WatchQueue::default();
+        assert_eq!(queue.enqueue_show(1, "Frieren", 3, 5), 3);
+        assert_eq!(queue.enqueue_show(2, "Dandadan", 0, 1), 1);
+        assert_eq!(queue.enqueue_show(1, "Frieren", 4, 6), 1);
+
+        queue.mark_watched(1, 4);
+        let left: Vec<(i32, i32)> = queue
+            .entries
+            .iter()
+            .map(|e| (e.media_id, e.episode))
+            .collect();
+        assert_eq!(left, vec![(1, 5), (2, 1), (1, 6)]);
+    }
+}
@@ -0,0 +1,51 @@
+//! Randomness for the Random menu. A session's generator can be seeded with
+//! `--seed`, so the same seed gives the same picks in the same order.
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index;
+
+/// AniList ids are handed out to anime and manga alike, roughly up to here.
+const MAX_MEDIA_ID: usize = 180_000;
+
+/// Ids drawn per try. Many belong to manga or nothing at all, so this is
+/// several times the number of shows it tends to find.
+pub const SAMPLE_SIZE: usize = 50;
+
+pub fn session_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// `count` distinct AniList ids, sampled straight from the id range.
+pub fn sample_ids(rng: &mut StdRng, count: usize) -> Vec<i32> {
+    index::sample(rng, MAX_MEDIA_ID, count.min(MAX_MEDIA_ID))
+        .into_iter()
+        .map(|i| i as i32 + 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_sampling_repeats() {
+        let first = sample_ids(&mut session_rng(Some(7)), SAMPLE_SIZE);
+        let again = sample_ids(&mut session_rng(Some(7)), SAMPLE_SIZE);
+        assert_eq!(first, again);
+        assert_eq!(first.len(), SAMPLE_SIZE);
+        assert!(
+            first
+                .iter()
+                .all(|&id| (1..=MAX_MEDIA_ID as i32).contains(&id))
+        );
+
+        let mut unique = first.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), SAMPLE_SIZE);
+    }
+}
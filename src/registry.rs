@@ -1,10 +1,25 @@
-use anyhow::{Context, Result};
+use crate::models::Media;
+use anyhow::{Context, Result, bail};
 use chrono::{DateTime, Utc};
 use directories::ProjectDirs;
+use log::warn;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Layout version of registry.json. Bump it, with a step in `MIGRATIONS`,
+/// for any change a `#[serde(default)]` field can't absorb: a rename, a
+/// move or a new meaning for existing data.
+pub const VERSION: u32 = 1;
+
+/// Steps upgrading the registry's JSON from version `i` to `i + 1`.
+const MIGRATIONS: &[fn(&mut serde_json::Map<String, Value>)] = &[
+    // 0 → 1: files from before versioning. Everything added since has a
+    // default, so only the version is new.
+    |_| {},
+];
 
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,17 +43,378 @@ pub struct RegistryEntry {
     pub last_updated: DateTime<Utc>,
     #[serde(default)]
     pub dirty: bool,
+    /// Free-form note, kept in sync with the AniList list entry's notes.
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Personal tags ("rewatch candidate"); these stay local.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Tag for shows published for free on official YouTube channels, which
+/// then play from the YouTube provider unless pinned elsewhere.
+pub const FREE_TAG: &str = "free";
+
+impl RegistryEntry {
+    pub fn is_free(&self) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(FREE_TAG))
+    }
+}
+
+/// A show pinned to one provider, overriding `general.provider`, for shows
+/// only complete on certain sources.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProviderPin {
+    pub provider: String,
+    /// The provider's own id for the show, for when searching by title
+    /// doesn't find it.
+    #[serde(default)]
+    pub show_id: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Registry {
+    /// Layout version the file was written with; missing before versioning.
+    #[serde(default)]
+    pub version: u32,
     pub entries: HashMap<i32, RegistryEntry>,
+    /// Provider pins by AniList id.
+    #[serde(default)]
+    pub pins: HashMap<i32, ProviderPin>,
+    /// Episode ratings from 1 to 10 by AniList id, then episode. AniList only
+    /// scores whole shows, so these never leave the registry.
+    #[serde(default)]
+    pub ratings: HashMap<i32, BTreeMap<i32, u8>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            entries: HashMap::new(),
+            pins: HashMap::new(),
+            ratings: HashMap::new(),
+        }
+    }
+}
+
+/// Brings a registry's JSON up to `VERSION`.
+fn migrate(mut value: Value) -> Result<Registry> {
+    let object = value
+        .as_object_mut()
+        .context("The registry isn't a JSON object")?;
+    let version = object.get("version").and_then(Value::as_u64).unwrap_or(0) as usize;
+    for step in MIGRATIONS.iter().skip(version) {
+        step(object);
+    }
+    object.insert("version".to_string(), VERSION.into());
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Reads and migrates the registry at `path`. A file from a newer ani-l is
+/// an error rather than something to overwrite; one that can't be read is
+/// copied aside, under a name with the time in it so an earlier copy isn't
+/// replaced, before starting over.
+fn load(path: &Path) -> Result<Registry> {
+    let content = fs::read_to_string(path)?;
+    let value: Option<Value> = serde_json::from_str(&content).ok();
+    let version = value
+        .as_ref()
+        .and_then(|v| v.get("version"))
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    if version > VERSION as u64 {
+        bail!(
+            "{} was written by a newer ani-l (registry version {}); update ani-l to use it",
+            path.display(),
+            version
+        );
+    }
+    match value.context("Invalid JSON").and_then(migrate) {
+        Ok(registry) => Ok(registry),
+        Err(e) => {
+            let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+            let backup = path.with_extension(format!("json.{}.bak", stamp));
+            fs::copy(path, &backup)?;
+            warn!(
+                "Could not read {} ({:#}); kept it as {} and started a new registry",
+                path.display(),
+                e,
+                backup.display()
+            );
+            Ok(Registry::default())
+        }
+    }
+}
+
+/// A show whose progress in ani-l and on AniList disagree, e.g. after
+/// watching an episode on another device.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub id: i32,
+    pub title: String,
+    pub local: i32,
+    pub remote: i32,
+    /// Whether the show is on the AniList list at all.
+    pub on_list: bool,
+    /// Episodes (or chapters) the show has, when known.
+    pub total: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    KeepLocal,
+    KeepRemote,
+    Max,
+}
+
+impl Conflict {
+    pub fn resolved(&self, resolution: Resolution) -> i32 {
+        match resolution {
+            Resolution::KeepLocal => self.local,
+            Resolution::KeepRemote => self.remote,
+            Resolution::Max => self.local.max(self.remote),
+        }
+    }
+}
+
+/// A show filed under more than one registry key, or under a key other than
+/// its AniList id, as fuzzy matches in older versions left some.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    pub id: i32,
+    /// Keys holding the show, the canonical entry's first.
+    pub keys: Vec<i32>,
+}
+
+/// Shows whose entries need merging, by AniList id. The canonical entry is
+/// the one filed under the id itself, else the most recently updated.
+pub fn find_duplicates(registry: &Registry) -> Vec<Duplicate> {
+    let mut by_id: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (&key, entry) in &registry.entries {
+        by_id.entry(entry.id).or_default().push(key);
+    }
+    let mut duplicates: Vec<Duplicate> = by_id
+        .into_iter()
+        .filter(|(id, keys)| keys.len() > 1 || keys[0] != *id)
+        .map(|(id, mut keys)| {
+            keys.sort_by_key(|key| {
+                let entry = &registry.entries[key];
+                (*key != id, std::cmp::Reverse(entry.last_updated), *key)
+            });
+            Duplicate { id, keys }
+        })
+        .collect();
+    duplicates.sort_by_key(|d| d.id);
+    duplicates
+}
+
+/// Folds each duplicate's entries into one under its AniList id, keeping
+/// the canonical title, the furthest progress and every note and tag.
+/// Ratings and pins filed under the other keys move along. Every entry is
+/// taken out before any is put back, since one show's AniList id can be the
+/// key another show was misfiled under; a key still held by an unrelated
+/// entry leaves that duplicate as it was.
+fn merge(registry: &mut Registry, duplicates: &[Duplicate]) {
+    let taken: Vec<Taken> = duplicates
+        .iter()
+        .map(|duplicate| {
+            let mut taken = Taken::default();
+            for &key in &duplicate.keys {
+                if let Some(entry) = registry.entries.remove(&key) {
+                    taken.entries.push((key, entry));
+                }
+                if key == duplicate.id {
+                    continue;
+                }
+                if let Some(ratings) = registry.ratings.remove(&key) {
+                    taken.ratings.push((key, ratings));
+                }
+                if let Some(pin) = registry.pins.remove(&key) {
+                    taken.pins.push((key, pin));
+                }
+            }
+            taken
+        })
+        .collect();
+    for (duplicate, taken) in duplicates.iter().zip(taken) {
+        let Taken {
+            entries,
+            ratings,
+            pins,
+        } = taken;
+        if registry.entries.contains_key(&duplicate.id) {
+            log::warn!("Not merging {}: its key holds another show", duplicate.id);
+            registry.entries.extend(entries);
+            registry.ratings.extend(ratings);
+            registry.pins.extend(pins);
+            continue;
+        }
+        if entries.is_empty() {
+            continue;
+        }
+        let mut entries = entries.into_iter().map(|(_, entry)| entry);
+        let mut merged = entries.next().expect("checked non-empty");
+        merged.id = duplicate.id;
+        for entry in entries {
+            if entry.progress > merged.progress {
+                merged.progress = entry.progress;
+                merged.status = entry.status;
+            }
+            merged.total_episodes = merged.total_episodes.or(entry.total_episodes);
+            if merged.score == 0.0 {
+                merged.score = entry.score;
+            }
+            merged.last_updated = merged.last_updated.max(entry.last_updated);
+            merged.dirty |= entry.dirty;
+            merged.note = match (merged.note.take(), entry.note) {
+                (Some(a), Some(b)) if a != b => Some(format!("{}\n{}", a, b)),
+                (a, b) => a.or(b),
+            };
+            for tag in entry.tags {
+                if !merged.tags.contains(&tag) {
+                    merged.tags.push(tag);
+                }
+            }
+        }
+        registry.entries.insert(duplicate.id, merged);
+
+        for (_, ratings) in ratings {
+            let target = registry.ratings.entry(duplicate.id).or_default();
+            for (episode, rating) in ratings {
+                target.entry(episode).or_insert(rating);
+            }
+        }
+        for (_, pin) in pins {
+            registry.pins.entry(duplicate.id).or_insert(pin);
+        }
+    }
+}
+
+/// What `merge` took out from under one duplicate's keys.
+#[derive(Default)]
+struct Taken {
+    entries: Vec<(i32, RegistryEntry)>,
+    ratings: Vec<(i32, BTreeMap<i32, u8>)>,
+    pins: Vec<(i32, ProviderPin)>,
+}
+
+/// Ids of the entries whose note or tags contain `query`, ignoring case,
+/// by title.
+pub fn search_notes(registry: &Registry, query: &str) -> Vec<i32> {
+    search_entries(registry, query, false)
+}
+
+/// Like `search_notes`, but also matching entry titles: a search scoped to
+/// the viewer's own library.
+pub fn search_library(registry: &Registry, query: &str) -> Vec<i32> {
+    search_entries(registry, query, true)
+}
+
+fn search_entries(registry: &Registry, query: &str, titles: bool) -> Vec<i32> {
+    let query = query.trim().to_lowercase();
+    let mut found: Vec<&RegistryEntry> = registry
+        .entries
+        .values()
+        .filter(|e| {
+            (titles && e.title.to_lowercase().contains(&query))
+                || e.tags.iter().any(|t| t.to_lowercase().contains(&query))
+                || e.note
+                    .as_ref()
+                    .is_some_and(|n| n.to_lowercase().contains(&query))
+        })
+        .collect();
+    found.sort_by(|a, b| a.title.cmp(&b.title));
+    found.into_iter().map(|e| e.id).collect()
+}
+
+/// Compares local entries with the viewer's list entries carried by `remote`
+/// (a show missing from the list counts as progress 0).
+pub fn find_conflicts(registry: &Registry, remote: &[Media]) -> Vec<Conflict> {
+    let mut conflicts: Vec<Conflict> = remote
+        .iter()
+        .filter_map(|media| {
+            let local = registry.entries.get(&media.id)?;
+            let list_entry = media.media_list_entry.as_ref();
+            let remote = list_entry.and_then(|e| e.progress).unwrap_or(0);
+            (local.progress != remote).then(|| Conflict {
+                id: media.id,
+                title: local.title.clone(),
+                local: local.progress,
+                remote,
+                on_list: list_entry.is_some(),
+                total: media.episodes.or(media.chapters).or(local.total_episodes),
+            })
+        })
+        .collect();
+    conflicts.sort_by(|a, b| a.title.cmp(&b.title));
+    conflicts
+}
+
+/// What `ani-l sync` does with one show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncAction {
+    /// Episodes watched in ani-l that AniList doesn't have yet.
+    Push,
+    /// AniList is further along, e.g. after watching elsewhere.
+    Pull,
+    /// Already equal; only the local pending flag clears.
+    MarkSynced,
+    /// AniList went back (a reset or rewatch) without ani-l having watched
+    /// anything since; left for the Sync Conflicts screen.
+    Skip,
+    UpToDate,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncStep {
+    pub id: i32,
+    pub title: String,
+    pub local: i32,
+    pub remote: i32,
+    pub on_list: bool,
+    pub action: SyncAction,
+}
+
+/// Decides how each local entry syncs with the viewer's list entries carried
+/// by `remote`. Shows missing from `remote` count as progress 0. Never plans
+/// to lower progress on either side.
+pub fn plan_sync(registry: &Registry, remote: &[Media]) -> Vec<SyncStep> {
+    let remote: HashMap<i32, Option<i32>> = remote
+        .iter()
+        .filter(|m| m.media_list_entry.is_some())
+        .map(|m| (m.id, m.media_list_entry.as_ref().and_then(|e| e.progress)))
+        .collect();
+    let mut steps: Vec<SyncStep> = registry
+        .entries
+        .iter()
+        .filter(|(key, entry)| **key == entry.id)
+        .map(|(_, entry)| {
+            let on_list = remote.contains_key(&entry.id);
+            let remote = remote.get(&entry.id).copied().flatten().unwrap_or(0);
+            let action = match entry.progress.cmp(&remote) {
+                std::cmp::Ordering::Less => SyncAction::Pull,
+                std::cmp::Ordering::Greater if entry.dirty => SyncAction::Push,
+                std::cmp::Ordering::Greater => SyncAction::Skip,
+                std::cmp::Ordering::Equal if entry.dirty => SyncAction::MarkSynced,
+                std::cmp::Ordering::Equal => SyncAction::UpToDate,
+            };
+            SyncStep {
+                id: entry.id,
+                title: entry.title.clone(),
+                local: entry.progress,
+                remote,
+                on_list,
+                action,
+            }
+        })
+        .collect();
+    steps.sort_by(|a, b| a.title.cmp(&b.title));
+    steps
 }
 
 pub struct RegistryManager {
-    #[allow(dead_code)]
     file_path: PathBuf,
-    #[allow(dead_code)]
     pub data: Registry,
 }
 
@@ -49,8 +425,7 @@ impl RegistryManager {
         let file_path = proj_dirs.config_dir().join("registry.json");
 
         let data = if file_path.exists() {
-            let content = fs::read_to_string(&file_path)?;
-            serde_json::from_str(&content).unwrap_or_default()
+            load(&file_path)?
         } else {
             Registry::default()
         };
@@ -58,23 +433,169 @@ impl RegistryManager {
         Ok(Self { file_path, data })
     }
 
-    #[allow(dead_code)]
     pub fn save(&self) -> Result<()> {
         let json_str = serde_json::to_string_pretty(&self.data)?;
         fs::write(&self.file_path, json_str)?;
         Ok(())
     }
 
-    #[allow(dead_code)]
     pub fn update_entry(&mut self, entry: RegistryEntry) -> Result<()> {
         self.data.entries.insert(entry.id, entry);
         self.save()
     }
 
-    #[allow(dead_code)]
     pub fn get_entry(&self, id: i32) -> Option<&RegistryEntry> {
         self.data.entries.get(&id)
     }
+
+    pub fn get_pin(&self, id: i32) -> Option<&ProviderPin> {
+        self.data.pins.get(&id)
+    }
+
+    /// Pins the show to a provider, or unpins it with `None`.
+    pub fn pin(&mut self, id: i32, pin: Option<ProviderPin>) -> Result<()> {
+        match pin {
+            Some(pin) => self.data.pins.insert(id, pin),
+            None => self.data.pins.remove(&id),
+        };
+        self.save()
+    }
+
+    /// Merges each duplicate's entries into one under its AniList id.
+    pub fn merge_duplicates(&mut self, duplicates: &[Duplicate]) -> Result<()> {
+        merge(&mut self.data, duplicates);
+        self.save()
+    }
+
+    /// Rates an episode, replacing any earlier rating of it.
+    pub fn rate(&mut self, id: i32, episode: i32, rating: u8) -> Result<()> {
+        self.data
+            .ratings
+            .entry(id)
+            .or_default()
+            .insert(episode, rating.clamp(1, 10));
+        self.save()
+    }
+
+    /// Sets a show's note and tags. A show ani-l hasn't tracked yet gets an
+    /// entry matching its AniList list entry, so it doesn't show up as a
+    /// conflict.
+    pub fn annotate(
+        &mut self,
+        media: &Media,
+        note: Option<String>,
+        tags: Vec<String>,
+    ) -> Result<()> {
+        let entry = self.data.entries.entry(media.id).or_insert_with(|| {
+            let list_entry = media.media_list_entry.as_ref();
+            RegistryEntry {
+                id: media.id,
+                title: media.preferred_title().to_string(),
+                status: list_entry
+                    .and_then(|e| serde_json::from_value(e.status.clone().into()).ok())
+                    .unwrap_or(WatchStatus::PLANNING),
+                progress: list_entry.and_then(|e| e.progress).unwrap_or(0),
+                total_episodes: media.episodes.or(media.chapters),
+                score: 0.0,
+                last_updated: Utc::now(),
+                dirty: false,
+                note: None,
+                tags: Vec::new(),
+            }
+        });
+        entry.note = note.filter(|n| !n.trim().is_empty());
+        entry.tags = tags;
+        self.save()
+    }
+
+    /// Records an episode watched in ani-l. Rewatching an earlier episode
+    /// never lowers the stored progress. The entry stays dirty until AniList
+    /// is known to match.
+    pub fn record_progress(&mut self, media: &Media, episode: i32) -> Result<()> {
+        let progress = self
+            .data
+            .entries
+            .get(&media.id)
+            .map_or(episode, |e| e.progress.max(episode));
+        self.set_progress(
+            media.id,
+            media.preferred_title(),
+            media.episodes.or(media.chapters),
+            progress,
+            true,
+        )
+    }
+
+    /// Forgets the shows, as after removing them from the AniList list.
+    pub fn remove_entries(&mut self, ids: &[i32]) -> Result<()> {
+        self.data.entries.retain(|id, _| !ids.contains(id));
+        self.save()
+    }
+
+    /// Sets the status of those of the shows ani-l tracks, in sync with
+    /// AniList.
+    pub fn set_status(&mut self, ids: &[i32], status: WatchStatus) -> Result<()> {
+        for id in ids {
+            if let Some(entry) = self.data.entries.get_mut(id) {
+                entry.status = status.clone();
+                entry.last_updated = Utc::now();
+            }
+        }
+        self.save()
+    }
+
+    pub fn mark_synced(&mut self, id: i32) -> Result<()> {
+        if let Some(entry) = self.data.entries.get_mut(&id) {
+            entry.dirty = false;
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Stores the progress a conflict was resolved to, in sync with AniList.
+    pub fn resolve(&mut self, conflict: &Conflict, progress: i32) -> Result<()> {
+        self.set_synced(conflict.id, &conflict.title, progress)
+    }
+
+    /// Stores `progress` as matching AniList.
+    pub fn set_synced(&mut self, id: i32, title: &str, progress: i32) -> Result<()> {
+        let total = self.data.entries.get(&id).and_then(|e| e.total_episodes);
+        self.set_progress(id, title, total, progress, false)
+    }
+
+    fn set_progress(
+        &mut self,
+        id: i32,
+        title: &str,
+        total_episodes: Option<i32>,
+        progress: i32,
+        dirty: bool,
+    ) -> Result<()> {
+        let status = if total_episodes.is_some_and(|total| progress >= total) {
+            WatchStatus::COMPLETED
+        } else {
+            WatchStatus::CURRENT
+        };
+        let existing = self.data.entries.get(&id);
+        // The first title recorded stays, whichever language later titles
+        // come in.
+        let title = existing.map_or(title, |e| &e.title).to_string();
+        let score = existing.map_or(0.0, |e| e.score);
+        let note = existing.and_then(|e| e.note.clone());
+        let tags = existing.map(|e| e.tags.clone()).unwrap_or_default();
+        self.update_entry(RegistryEntry {
+            id,
+            title,
+            status,
+            progress,
+            total_episodes,
+            score,
+            last_updated: Utc::now(),
+            dirty,
+            note,
+            tags,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -93,6 +614,8 @@ mod tests {
             score: 85.5,
             last_updated: now,
             dirty: true,
+            note: None,
+            tags: Vec::new(),
         };
 
         assert_eq!(entry.id, 12345);
@@ -113,11 +636,156 @@ mod tests {
             score: 0.0,
             last_updated: Utc::now(),
             dirty: false,
+            note: None,
+            tags: Vec::new(),
         };
 
         registry.entries.insert(entry.id, entry.clone());
 
         assert!(registry.entries.contains_key(&1));
         assert_eq!(registry.entries.get(&1).unwrap().title, "One Piece");
+
+        // Registries written before pins and versioning still load.
+        let old = migrate(serde_json::json!({ "entries": {} })).unwrap();
+        assert!(old.pins.is_empty());
+        assert!(old.ratings.is_empty());
+        assert_eq!(old.version, VERSION);
+        assert!(migrate(serde_json::json!([])).is_err());
+
+        let entry = registry.entries.get_mut(&1).unwrap();
+        entry.tags = vec!["Watch with Sam".to_string()];
+        entry.note = Some("Skip the filler arcs".to_string());
+        assert_eq!(search_notes(&registry, "sam"), vec![1]);
+        assert_eq!(search_notes(&registry, "FILLER"), vec![1]);
+        assert!(search_notes(&registry, "rewatch").is_empty());
+        assert!(search_notes(&registry, "piece").is_empty());
+        assert_eq!(search_library(&registry, "piece"), vec![1]);
+        assert_eq!(search_library(&registry, "sam"), vec![1]);
+        assert!(!registry.entries[&1].is_free());
+        registry
+            .entries
+            .get_mut(&1)
+            .unwrap()
+            .tags
+            .push("Free".to_string());
+        assert!(registry.entries[&1].is_free());
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let mut registry = Registry::default();
+        for (id, progress) in [(1, 5), (2, 3)] {
+            registry.entries.insert(
+                id,
+                RegistryEntry {
+                    id,
+                    title: format!("Show {}", id),
+                    status: WatchStatus::CURRENT,
+                    progress,
+                    total_episodes: Some(12),
+                    score: 0.0,
+                    last_updated: Utc::now(),
+                    dirty: false,
+                    note: None,
+                    tags: Vec::new(),
+                },
+            );
+        }
+        let remote: Vec<Media> = serde_json::from_value(serde_json::json!([
+            { "id": 1, "title": {}, "genres": [], "mediaListEntry": { "progress": 7 } },
+            { "id": 2, "title": {}, "genres": [], "mediaListEntry": { "progress": 3 } }
+        ]))
+        .unwrap();
+
+        let conflicts = find_conflicts(&registry, &remote);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].resolved(Resolution::KeepLocal), 5);
+        assert_eq!(conflicts[0].resolved(Resolution::KeepRemote), 7);
+        assert_eq!(conflicts[0].resolved(Resolution::Max), 7);
+
+        registry.entries.get_mut(&2).unwrap().dirty = true;
+        let mut ahead = registry.entries[&2].clone();
+        ahead.id = 3;
+        ahead.title = "Show 3".to_string();
+        registry.entries.insert(3, ahead);
+        let actions: Vec<SyncAction> = plan_sync(&registry, &remote)
+            .iter()
+            .map(|s| s.action)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![SyncAction::Pull, SyncAction::MarkSynced, SyncAction::Push]
+        );
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let mut registry = Registry::default();
+        // Show 1 under its own key and, from an old fuzzy match, under 900.
+        for (key, title, progress, tag) in [
+            (1, "Sousou no Frieren", 3, "cozy"),
+            (900, "Frieren: Beyond Journey's End", 5, "rewatch"),
+            (2, "Dandadan", 1, "cozy"),
+        ] {
+            registry.entries.insert(
+                key,
+                RegistryEntry {
+                    id: if key == 900 { 1 } else { key },
+                    title: title.to_string(),
+                    status: WatchStatus::CURRENT,
+                    progress,
+                    total_episodes: None,
+                    score: 0.0,
+                    last_updated: Utc::now(),
+                    dirty: false,
+                    note: None,
+                    tags: vec![tag.to_string()],
+                },
+            );
+        }
+        registry.ratings.insert(900, BTreeMap::from([(5, 9)]));
+
+        let duplicates = find_duplicates(&registry);
+        assert_eq!(
+            duplicates,
+            vec![Duplicate {
+                id: 1,
+                keys: vec![1, 900]
+            }]
+        );
+
+        merge(&mut registry, &duplicates);
+        assert!(find_duplicates(&registry).is_empty());
+        let merged = &registry.entries[&1];
+        assert_eq!(merged.title, "Sousou no Frieren");
+        assert_eq!(merged.progress, 5);
+        assert_eq!(merged.tags, vec!["cozy", "rewatch"]);
+        assert_eq!(registry.ratings[&1][&5], 9);
+        assert_eq!(registry.entries.len(), 2);
+
+        // Show 7 misfiled under 1, the key show 1 belongs under.
+        let mut registry = Registry::default();
+        for (key, id) in [(1, 7), (900, 1)] {
+            registry.entries.insert(
+                key,
+                RegistryEntry {
+                    id,
+                    title: format!("Show {}", id),
+                    status: WatchStatus::CURRENT,
+                    progress: 1,
+                    total_episodes: None,
+                    score: 0.0,
+                    last_updated: Utc::now(),
+                    dirty: false,
+                    note: None,
+                    tags: Vec::new(),
+                },
+            );
+        }
+        let duplicates = find_duplicates(&registry);
+        merge(&mut registry, &duplicates);
+        assert_eq!(registry.entries[&1].title, "Show 1");
+        assert_eq!(registry.entries[&7].title, "Show 7");
+        assert_eq!(registry.entries.len(), 2);
     }
 }
@@ -0,0 +1,551 @@
+//! Watch-together rooms. The host shares a room code; each guest resolves the
+//! same episode through its own provider and mirrors the host's play/pause
+//! and position over a direct TCP connection.
+
+use crate::config::Config;
+use crate::exit::Failure;
+use crate::output;
+use crate::player::{self, AnyPlayer, traits::Player};
+use crate::provider::AnyProvider;
+use anyhow::{Context, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf as IpcWriter};
+use tokio::net::{TcpListener, TcpStream, UnixStream};
+use tokio::sync::{Mutex, mpsc};
+use tokio::time::{interval, sleep};
+
+pub const DEFAULT_PORT: u16 = 47800;
+
+/// Guests only seek when they drift further than this from the host.
+const MAX_DRIFT_SECS: f64 = 2.0;
+
+/// How often the host re-sends its state so late or drifting guests catch up.
+const HEARTBEAT: Duration = Duration::from_secs(3);
+
+/// How long to wait before accepting again after a failed accept, which
+/// usually means the process ran out of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How long a guest has to say hello with the room password.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where `room join` looks for the password before asking for it, so it
+/// never has to go on the command line.
+pub const PASSWORD_ENV: &str = "ANI_L_ROOM_PASSWORD";
+
+/// Length of the password made up for rooms without `remote.password`.
+const PASSWORD_LEN: usize = 6;
+
+/// Crockford base32, which avoids the easily confused I, L, O and U.
+const CODE_ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomMessage {
+    /// The first line a guest sends.
+    Hello {
+        password: Option<String>,
+    },
+    /// Sent instead of `Episode` to a guest that may not join.
+    Rejected {
+        reason: String,
+    },
+    /// Sent to every guest as it joins.
+    Episode {
+        title: String,
+        episode: String,
+        translation_type: String,
+    },
+    State {
+        paused: bool,
+        position: f64,
+    },
+}
+
+/// Packs an IPv4 address and port into a short code like `0K3F9-Q2ZP1`.
+/// Other addresses are shared as plain `host:port`.
+pub fn encode_code(addr: SocketAddr) -> String {
+    let SocketAddr::V4(v4) = addr else {
+        return addr.to_string();
+    };
+    let mut value = ((u32::from(*v4.ip()) as u64) << 16) | v4.port() as u64;
+    let mut chars = [0u8; 10];
+    for c in chars.iter_mut().rev() {
+        *c = CODE_ALPHABET[(value & 31) as usize];
+        value >>= 5;
+    }
+    let code = String::from_utf8_lossy(&chars).to_string();
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+/// Accepts a code from `encode_code` or a plain `host:port`.
+pub fn decode_code(code: &str) -> Result<SocketAddr> {
+    let code = code.trim();
+    if code.contains(':') {
+        return code
+            .to_socket_addrs()?
+            .next()
+            .context("Could not resolve the room address");
+    }
+
+    let mut value: u64 = 0;
+    let mut digits = 0;
+    for c in code.chars().filter(|c| *c != '-') {
+        let c = match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            other => other,
+        };
+        let digit = CODE_ALPHABET
+            .iter()
+            .position(|&a| a as char == c)
+            .with_context(|| format!("Invalid room code '{}'", code))?;
+        value = (value << 5) | digit as u64;
+        digits += 1;
+    }
+    if digits != 10 {
+        anyhow::bail!("Invalid room code '{}'", code);
+    }
+    let ip = Ipv4Addr::from((value >> 16) as u32);
+    Ok(SocketAddr::new(IpAddr::V4(ip), value as u16))
+}
+
+/// The address to hand out for a server listening on `bind`: that address
+/// itself unless it is the wildcard.
+pub(crate) fn reachable_ip(bind: IpAddr) -> IpAddr {
+    if bind.is_unspecified() {
+        local_ip()
+    } else {
+        bind
+    }
+}
+
+/// The address guests should use: the interface that routes to the
+/// internet. No packets are sent to find it.
+fn local_ip() -> IpAddr {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("1.1.1.1:80")?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/// Resolves the episode, opens the room and plays, mirroring the player's
+/// state to every guest until it closes. Without `address` the code holds
+/// this machine's LAN address, so only guests on the same network can use it.
+pub async fn host(
+    config: &Config,
+    query: &str,
+    episode: Option<String>,
+    port: u16,
+    address: Option<&str>,
+) -> Result<()> {
+    let translation_type = config.stream.translation_type.clone();
+    let provider = AnyProvider::new(translation_type.clone(), config)?;
+
+    say!("🔍 Searching for '{}'...", query);
+    let results = provider.search(query).await.context(Failure::Provider)?;
+    let show = crate::choose_show(&provider, &config.picker, &results, query)?;
+    let episode = crate::choose_episode(&provider, &config.picker, show, episode).await?;
+    let options = crate::resolve_stream_for_episode(&provider, &show.id, &show.name, &episode, 0)
+        .await
+        .context(Failure::Provider)?
+        .ok_or(Failure::NoResults)
+        .context("No stream found")?;
+
+    let bind = config.remote.bind_addr()?;
+    let listener = TcpListener::bind((bind, port))
+        .await
+        .with_context(|| format!("Could not listen on port {}", port))?;
+    let code = match address {
+        Some(address) => match address.parse() {
+            Ok(ip) => encode_code(SocketAddr::new(ip, port)),
+            Err(_) => format!("{}:{}", address, port),
+        },
+        None => encode_code(SocketAddr::new(reachable_ip(bind), port)),
+    };
+    // Every room has a password; without one in the config, make one up.
+    let mut remote = config.remote.clone();
+    let generated = remote.password.is_none();
+    let password = remote
+        .password
+        .get_or_insert_with(generate_password)
+        .clone();
+    say!("🏠 Room open: {} episode {}", show.name, episode);
+    say!("🔑 Guests join with: ani-l room join {}", code);
+    if generated {
+        say!("🔒 Room password: {}", password);
+    }
+    if address.is_none() {
+        say!(
+            "   (on this network only; for guests elsewhere, forward TCP port {} \
+             and pass --address with your public IP)",
+            port
+        );
+    } else {
+        say!("   (forward TCP port {} to this machine)", port);
+    }
+
+    let welcome = RoomMessage::Episode {
+        title: show.name.clone(),
+        episode: episode.to_string(),
+        translation_type,
+    };
+    let guests: Arc<Mutex<Vec<mpsc::UnboundedSender<RoomMessage>>>> = Arc::default();
+    let state = Arc::new(Mutex::new(RoomMessage::State {
+        paused: false,
+        position: 0.0,
+    }));
+
+    let accept_guests = {
+        let guests = guests.clone();
+        let state = state.clone();
+        let remote = Arc::new(remote);
+        async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        log::debug!("Could not accept a room guest: {}", e);
+                        sleep(ACCEPT_BACKOFF).await;
+                        continue;
+                    }
+                };
+                if !remote.allows(addr.ip()) {
+                    log::debug!("Refused room guest {} (not in remote.allow)", addr);
+                    continue;
+                }
+                let (guests, state, remote, welcome) = (
+                    guests.clone(),
+                    state.clone(),
+                    remote.clone(),
+                    welcome.clone(),
+                );
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    let hello = tokio::time::timeout(HELLO_TIMEOUT, lines.next_line()).await;
+                    let password = match hello {
+                        Ok(Ok(Some(line))) => match serde_json::from_str(&line) {
+                            Ok(RoomMessage::Hello { password }) => password,
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if !remote.password_matches(password.as_deref()) {
+                        say!("🚫 {} gave the wrong room password", addr);
+                        let reason = "Wrong room password".to_string();
+                        let _ = send(&mut writer, &RoomMessage::Rejected { reason }).await;
+                        return;
+                    }
+                    let current = state.lock().await.clone();
+                    if send(&mut writer, &welcome).await.is_err()
+                        || send(&mut writer, &current).await.is_err()
+                    {
+                        return;
+                    }
+                    say!("👋 {} joined", addr);
+                    // Each guest is written to from its own task, so a slow
+                    // one can't hold up the others.
+                    let (tx, mut rx) = mpsc::unbounded_channel();
+                    guests.lock().await.push(tx);
+                    while let Some(message) = rx.recv().await {
+                        if send(&mut writer, &message).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        }
+    };
+
+    let (player, socket) = room_player(config)?;
+    let mirror = async {
+        let mut ipc = MpvIpc::connect(&socket).await?;
+        ipc.observe(&["pause", "time-pos"]).await?;
+        let mut heartbeat = interval(HEARTBEAT);
+        let (mut paused, mut position) = (false, 0.0);
+        loop {
+            let changed = tokio::select! {
+                _ = heartbeat.tick() => true,
+                event = ipc.next_event() => match event? {
+                    Some(event) => match property(&event) {
+                        Some(("pause", value)) => {
+                            paused = value.as_bool().unwrap_or(paused);
+                            true
+                        }
+                        Some(("time-pos", value)) => {
+                            position = value.as_f64().unwrap_or(position);
+                            false
+                        }
+                        // Fired after every seek.
+                        _ => {
+                            event.get("event").and_then(Value::as_str)
+                                == Some("playback-restart")
+                        }
+                    },
+                    None => return Ok::<_, anyhow::Error>(()),
+                },
+            };
+            if changed {
+                let message = RoomMessage::State { paused, position };
+                *state.lock().await = message.clone();
+                // Guests that hung up have dropped their receivers.
+                guests
+                    .lock()
+                    .await
+                    .retain(|guest| guest.send(message.clone()).is_ok());
+            }
+        }
+    };
+
+    let result = tokio::select! {
+        result = player.play(options, None) => result.map(|_| ()),
+        result = mirror => result,
+        _ = accept_guests => Ok(()),
+    };
+    provider.release_streams().await;
+    result
+}
+
+/// A short password for a room, from the same alphabet as room codes.
+fn generate_password() -> String {
+    let mut rng = rand::thread_rng();
+    (0..PASSWORD_LEN)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// The room password from `PASSWORD_ENV`, or typed in at a prompt.
+fn read_password() -> Result<String> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+    if !output::interactive() {
+        anyhow::bail!("Set {} to the room password", PASSWORD_ENV);
+    }
+    print!("Room password: ");
+    io::stdout().flush().unwrap_or(());
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// Joins the room behind `code`, finds the host's episode through this
+/// machine's own provider and follows the host's playback.
+pub async fn join(config: &Config, code: &str) -> Result<()> {
+    let addr = decode_code(code)?;
+    let password = Some(read_password()?);
+    say!("🔗 Connecting to {}...", addr);
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .with_context(|| format!("Could not reach the room at {}", addr))?;
+    send(&mut stream, &RoomMessage::Hello { password }).await?;
+    let mut lines = BufReader::new(stream).lines();
+
+    let (title, episode, translation_type) = match read(&mut lines).await? {
+        Some(RoomMessage::Episode {
+            title,
+            episode,
+            translation_type,
+        }) => (title, episode, translation_type),
+        Some(RoomMessage::Rejected { reason }) => {
+            return Err(anyhow::Error::new(Failure::Auth).context(reason));
+        }
+        _ => anyhow::bail!("The host did not say what is playing"),
+    };
+    say!("📺 Host is playing {} episode {}", title, episode);
+
+    // Keep only the newest host state while the stream is being resolved.
+    let (state_tx, mut state_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok(Some(message)) = read(&mut lines).await {
+            if state_tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let provider = AnyProvider::new(translation_type, config)?;
+    let results = provider.search(&title).await.context(Failure::Provider)?;
+    let show = crate::best_provider_match(&provider, &results, &title).ok_or(Failure::NoResults)?;
+    let mut options =
+        crate::resolve_stream_for_episode(&provider, &show.id, &show.name, &episode, 0)
+            .await
+            .context(Failure::Provider)?
+            .ok_or(Failure::NoResults)
+            .context("No stream found")?;
+
+    let mut latest = None;
+    while let Ok(message) = state_rx.try_recv() {
+        latest = Some(message);
+    }
+    if let Some(RoomMessage::State { position, .. }) = &latest {
+        options.start_time = Some(format!("{:.0}", position));
+    }
+
+    let (player, socket) = room_player(config)?;
+    let follow = async {
+        let mut ipc = MpvIpc::connect(&socket).await?;
+        ipc.observe(&["time-pos"]).await?;
+        let mut local_position = 0.0;
+        if let Some(message) = latest {
+            apply(&mut ipc, &message, local_position).await?;
+        }
+        loop {
+            tokio::select! {
+                message = state_rx.recv() => match message {
+                    Some(message) => apply(&mut ipc, &message, local_position).await?,
+                    None => {
+                        say!("🚪 The host closed the room");
+                        return Ok::<_, anyhow::Error>(());
+                    }
+                },
+                event = ipc.next_event() => match event? {
+                    Some(event) => {
+                        if let Some(("time-pos", value)) = property(&event) {
+                            local_position = value.as_f64().unwrap_or(local_position);
+                        }
+                    }
+                    None => return Ok(()),
+                },
+            }
+        }
+    };
+
+    let result = tokio::select! {
+        result = player.play(options, None) => result.map(|_| ()),
+        result = follow => result,
+    };
+    provider.release_streams().await;
+    result
+}
+
+/// Whether a guest at `local` should jump to the host's `remote` position.
+fn needs_seek(local: f64, remote: f64) -> bool {
+    (local - remote).abs() > MAX_DRIFT_SECS
+}
+
+async fn apply(ipc: &mut MpvIpc, message: &RoomMessage, local_position: f64) -> Result<()> {
+    if let RoomMessage::State { paused, position } = message {
+        ipc.command(json!(["set_property", "pause", paused]))
+            .await?;
+        if needs_seek(local_position, *position) {
+            ipc.command(json!(["seek", position, "absolute"])).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Rooms need mpv, started on a socket the room can also talk to.
+fn room_player(config: &Config) -> Result<(AnyPlayer, String)> {
+    let AnyPlayer::Mpv(mut mpv) = player::choose_player(&config.stream)?.player else {
+        return Err(anyhow::Error::new(Failure::Player).context("Watch-together rooms need mpv"));
+    };
+    let socket = mpv
+        .launcher
+        .ipc_dir()
+        .join(format!("ani-l-room-{}.sock", rand::random::<u32>()))
+        .to_string_lossy()
+        .to_string();
+    mpv.ipc_socket = Some(socket.clone());
+    Ok((AnyPlayer::Mpv(mpv), socket))
+}
+
+async fn send(writer: &mut (impl AsyncWrite + Unpin), message: &RoomMessage) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+async fn read(lines: &mut Lines<BufReader<TcpStream>>) -> Result<Option<RoomMessage>> {
+    match lines.next_line().await? {
+        Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+        None => Ok(None),
+    }
+}
+
+/// `(name, value)` of an mpv `property-change` event.
+fn property(event: &Value) -> Option<(&str, &Value)> {
+    if event.get("event")?.as_str()? != "property-change" {
+        return None;
+    }
+    Some((event.get("name")?.as_str()?, event.get("data")?))
+}
+
+/// A second client on mpv's JSON IPC socket, next to the one the player
+/// itself uses.
+struct MpvIpc {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: IpcWriter,
+}
+
+impl MpvIpc {
+    async fn connect(path: &str) -> Result<Self> {
+        for _ in 0..50 {
+            if let Ok(stream) = UnixStream::connect(path).await {
+                let (reader, writer) = stream.into_split();
+                return Ok(Self {
+                    lines: BufReader::new(reader).lines(),
+                    writer,
+                });
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        anyhow::bail!("Could not connect to mpv")
+    }
+
+    async fn command(&mut self, command: Value) -> Result<()> {
+        let mut line = json!({ "command": command }).to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn observe(&mut self, properties: &[&str]) -> Result<()> {
+        for (id, property) in properties.iter().enumerate() {
+            self.command(json!(["observe_property", id + 1, property]))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Next event from mpv; command replies are skipped. `None` once mpv exits.
+    async fn next_event(&mut self) -> Result<Option<Value>> {
+        while let Some(line) = self.lines.next_line().await? {
+            if let Ok(value) = serde_json::from_str::<Value>(&line)
+                && value.get("event").is_some()
+            {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_code_round_trip() {
+        let addr: SocketAddr = "203.0.113.7:47800".parse().unwrap();
+        let code = encode_code(addr);
+        assert_eq!(code.len(), 11);
+        assert_eq!(decode_code(&code).unwrap(), addr);
+        assert_eq!(decode_code(&code.to_lowercase()).unwrap(), addr);
+        assert_eq!(decode_code("127.0.0.1:9000").unwrap().port(), 9000);
+        assert!(decode_code("NOT-A-CODE").is_err());
+        assert!(needs_seek(10.0, 13.0) && !needs_seek(10.0, 11.5));
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A status older than this (while not paused) is left over from a player
+/// that exited without cleaning up.
+const STALE_AFTER_SECS: i64 = 10;
+
+/// What is currently playing, written to the runtime directory so status bars
+/// (waybar, polybar, ...) can display it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PlaybackStatus {
+    pub title: String,
+    pub episode: Option<String>,
+    /// Playback position in seconds.
+    pub position: f64,
+    /// Episode length in seconds, once the player knows it.
+    pub duration: Option<f64>,
+    pub percent: f64,
+    pub paused: bool,
+    pub updated: DateTime<Utc>,
+}
+
+impl PlaybackStatus {
+    /// One-line summary, e.g. "Frieren — Episode 5 [12:04/24:10]".
+    pub fn summary(&self) -> String {
+        let mut line = self.title.clone();
+        if let Some(ep) = &self.episode {
+            line.push_str(&format!(" — Episode {}", ep));
+        }
+        match self.duration {
+            Some(duration) => line.push_str(&format!(
+                " [{}/{}]",
+                format_clock(self.position),
+                format_clock(duration)
+            )),
+            None => line.push_str(&format!(" [{}]", format_clock(self.position))),
+        }
+        if self.paused {
+            line.push_str(" (paused)");
+        }
+        line
+    }
+
+    /// Fills a user template. Supported placeholders: `{title}`, `{ep}`,
+    /// `{percent}`, `{position}`, `{duration}` and `{state}`.
+    pub fn render(&self, template: &str) -> String {
+        template
+            .replace("{title}", &self.title)
+            .replace("{ep}", self.episode.as_deref().unwrap_or("?"))
+            .replace("{percent}", &format!("{:.0}", self.percent))
+            .replace("{position}", &format_clock(self.position))
+            .replace(
+                "{duration}",
+                &self.duration.map(format_clock).unwrap_or_default(),
+            )
+            .replace("{state}", if self.paused { "paused" } else { "playing" })
+    }
+}
+
+fn format_clock(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (h, m, s) = (total / 3600, (total % 3600) / 60, total % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+/// Directory holding the status files: `$XDG_RUNTIME_DIR/ani-l` where
+/// available, the system temp directory otherwise.
+pub fn status_dir() -> PathBuf {
+    ProjectDirs::from("com", "sleepy-foundry", "ani-l")
+        .and_then(|dirs| dirs.runtime_dir().map(|d| d.to_path_buf()))
+        .unwrap_or_else(|| std::env::temp_dir().join("ani-l"))
+}
+
+pub fn status_path() -> PathBuf {
+    status_dir().join("status.json")
+}
+
+pub fn write(status: &PlaybackStatus) -> Result<()> {
+    let dir = status_dir();
+    fs::create_dir_all(&dir)?;
+
+    replace(&dir.join("status.json"), serde_json::to_string(status)?)?;
+    replace(&dir.join("status.txt"), format!("{}\n", status.summary()))?;
+    Ok(())
+}
+
+/// Write-then-rename so readers (status bar modules poll these files) never
+/// see a half-written file.
+fn replace(path: &Path, contents: String) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Returns the live status, or `None` when nothing is playing.
+pub fn read() -> Option<PlaybackStatus> {
+    let content = fs::read_to_string(status_path()).ok()?;
+    let status: PlaybackStatus = serde_json::from_str(&content).ok()?;
+    let age = Utc::now() - status.updated;
+    if !status.paused && age.num_seconds() > STALE_AFTER_SECS {
+        return None;
+    }
+    Some(status)
+}
+
+pub fn clear() {
+    let dir = status_dir();
+    let _ = fs::remove_file(dir.join("status.json"));
+    let _ = fs::remove_file(dir.join("status.txt"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let status = PlaybackStatus {
+            title: "Frieren".to_string(),
+            episode: Some("5".to_string()),
+            position: 724.0,
+            duration: Some(1450.0),
+            percent: 49.93,
+            paused: false,
+            updated: Utc::now(),
+        };
+
+        assert_eq!(status.render("{title} E{ep} {percent}%"), "Frieren E5 50%");
+        assert_eq!(
+            status.render("{position}/{duration} {state}"),
+            "12:04/24:10 playing"
+        );
+    }
+}
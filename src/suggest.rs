@@ -0,0 +1,148 @@
+use crate::api;
+use crate::models::Media;
+use anyhow::Result;
+use serde_json::json;
+use std::collections::HashSet;
+
+const MIN_SIMILARITY: f64 = 0.3;
+const MAX_SUGGESTIONS: usize = 8;
+
+/// Builds "Did you mean…?" candidates for a query that returned no results.
+///
+/// The candidate pool is the current trending/popular pages plus an AniList
+/// search for each individual word of the query, ranked by trigram similarity.
+pub async fn did_you_mean(query: &str) -> Result<Vec<Media>> {
+    let mut pool: Vec<Media> = Vec::new();
+
+    for sort in ["TRENDING_DESC", "POPULARITY_DESC"] {
+        if let Ok(res) = api::fetch_media(json!({ "perPage": 50, "sort": sort }), None).await
+            && let Some(page) = res.data.page
+        {
+            pool.extend(page.media);
+        }
+    }
+
+    for word in relaxed_terms(query) {
+        if let Ok(res) = api::fetch_media(
+            json!({ "search": word, "perPage": 10, "sort": "SEARCH_MATCH" }),
+            None,
+        )
+        .await
+            && let Some(page) = res.data.page
+        {
+            pool.extend(page.media);
+        }
+    }
+
+    Ok(rank_candidates(query, pool))
+}
+
+fn relaxed_terms(query: &str) -> Vec<String> {
+    let words: Vec<&str> = query
+        .split_whitespace()
+        .filter(|w| w.chars().count() >= 3)
+        .collect();
+
+    if words.len() <= 1 {
+        // A single misspelled word: retry with a shorter prefix so AniList's
+        // own fuzzy matching gets a chance.
+        return words
+            .first()
+            .map(|w| w.chars().take(4).collect::<String>())
+            .into_iter()
+            .collect();
+    }
+
+    words.iter().map(|w| w.to_string()).collect()
+}
+
+fn rank_candidates(query: &str, pool: Vec<Media>) -> Vec<Media> {
+    let mut seen = HashSet::new();
+    let mut scored: Vec<(f64, Media)> = pool
+        .into_iter()
+        .filter(|m| seen.insert(m.id))
+        .map(|m| (best_title_similarity(query, &m), m))
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .collect();
+
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, m)| m)
+        .collect()
+}
+
+fn best_title_similarity(query: &str, media: &Media) -> f64 {
+    let mut titles: Vec<&str> = [
+        media.title.english.as_deref(),
+        media.title.romaji.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if let Some(synonyms) = &media.synonyms {
+        titles.extend(synonyms.iter().map(|s| s.as_str()));
+    }
+
+    titles
+        .into_iter()
+        .map(|t| trigram_similarity(query, t))
+        .fold(0.0, f64::max)
+}
+
+fn trigrams(s: &str) -> HashSet<String> {
+    let normalized: String = s
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+
+    let mut grams = HashSet::new();
+    for word in normalized.split_whitespace() {
+        let padded: Vec<char> = format!("  {} ", word).chars().collect();
+        for window in padded.windows(3) {
+            grams.insert(window.iter().collect());
+        }
+    }
+    grams
+}
+
+/// Jaccard similarity of the padded word trigrams of both strings, in `0.0..=1.0`.
+pub fn trigram_similarity(a: &str, b: &str) -> f64 {
+    let ta = trigrams(a);
+    let tb = trigrams(b);
+    if ta.is_empty() || tb.is_empty() {
+        return 0.0;
+    }
+    let shared = ta.intersection(&tb).count() as f64;
+    let total = ta.union(&tb).count() as f64;
+    shared / total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigram_similarity_ranks_typos_close() {
+        let exact = trigram_similarity("frieren", "Frieren");
+        let typo = trigram_similarity("freiren", "Frieren");
+        let unrelated = trigram_similarity("freiren", "One Piece");
+
+        assert_eq!(exact, 1.0);
+        assert!(typo > unrelated);
+        assert_eq!(unrelated, 0.0);
+    }
+
+    #[test]
+    fn test_relaxed_terms() {
+        assert_eq!(
+            relaxed_terms("shingeki no kyojin"),
+            vec!["shingeki", "kyojin"]
+        );
+        assert_eq!(relaxed_terms("freiren"), vec!["frei"]);
+        assert!(relaxed_terms("").is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Local, Utc};
+
+/// "3d ago" / "in 6h" relative to now, in the current locale.
+pub fn relative(when: DateTime<Utc>) -> String {
+    relative_to(when, Utc::now())
+}
+
+pub fn relative_to(when: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (when - now).num_seconds();
+    let magnitude = seconds.unsigned_abs();
+    if magnitude < 60 {
+        return t!("time.just_now").to_string();
+    }
+
+    let span = compact_duration(magnitude);
+    if seconds < 0 {
+        t!("time.ago", val = span).to_string()
+    } else {
+        t!("time.in", val = span).to_string()
+    }
+}
+
+/// Local wall-clock time, shown next to relative times.
+pub fn absolute(when: DateTime<Utc>) -> String {
+    when.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// "9h 20m" / "45m" for a watch time in minutes.
+pub fn runtime(minutes: i32) -> String {
+    let minutes = minutes.max(0);
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h {}m", h, m),
+    }
+}
+
+fn compact_duration(seconds: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    const WEEK: u64 = 7 * DAY;
+    const MONTH: u64 = 30 * DAY;
+    const YEAR: u64 = 365 * DAY;
+
+    match seconds {
+        s if s >= YEAR => format!("{}y", s / YEAR),
+        s if s >= MONTH => format!("{}mo", s / MONTH),
+        s if s >= WEEK => format!("{}w", s / WEEK),
+        s if s >= DAY => format!("{}d", s / DAY),
+        s if s >= HOUR => format!("{}h", s / HOUR),
+        s => format!("{}m", s / MINUTE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_relative_to() {
+        let now = Utc::now();
+
+        assert_eq!(relative_to(now - Duration::days(3), now), "3d ago");
+        assert_eq!(relative_to(now + Duration::hours(6), now), "in 6h");
+        assert_eq!(relative_to(now - Duration::seconds(10), now), "just now");
+    }
+
+    #[test]
+    fn test_runtime() {
+        assert_eq!(runtime(560), "9h 20m");
+        assert_eq!(runtime(120), "2h");
+        assert_eq!(runtime(24), "24m");
+    }
+}
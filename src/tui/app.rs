@@ -1,35 +1,295 @@
+use crate::api::BulkEdit;
+use crate::config::BlockMode;
 use crate::config::ConfigManager;
-use crate::models::Media;
+#[cfg(feature = "downloads")]
+use crate::download::DownloadEntry;
+use crate::franchise::FranchiseEntry;
+use crate::health::HealthManager;
+use crate::history::WatchSession;
+use crate::models::{CharacterEdge, EpisodeDetails, Media, PageInfo};
+use crate::queue::QueueEntry;
+use crate::registry::{Conflict, RegistryEntry, Resolution};
+use crate::tui::count::CountPrefix;
+#[cfg(feature = "images")]
+use crate::tui::images::{self, AsciiCover, ImageCache, ImagePrefetcher};
+use crate::tui::macros::MacroRecorder;
+use crate::tui::perf::PerfStats;
+use crate::voices::VoiceRole;
+use crate::watch_order::WatchOrder;
+use chrono::NaiveDate;
+#[cfg(feature = "images")]
+use image::DynamicImage;
+use rand::rngs::StdRng;
+#[cfg(feature = "images")]
+use ratatui::layout::Rect;
 use ratatui::widgets::ListState;
-use ratatui_image::picker::Picker;
+#[cfg(feature = "images")]
+use ratatui_image::picker::{Picker, ProtocolType};
+#[cfg(feature = "images")]
 use ratatui_image::protocol::StatefulProtocol;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::{Notify, mpsc};
 
+/// Oldest navigation history entries are dropped beyond this depth.
+const MAX_HISTORY_DEPTH: usize = 32;
+
+/// Episodes listed on the recap screen, ending with the last one watched.
+pub const RECAP_EPISODES: i32 = 5;
+
+/// How long a slim list row stays highlighted before its full details are
+/// fetched, so scrolling past rows doesn't fetch each one.
+pub const DETAIL_DELAY: Duration = Duration::from_millis(300);
+
 #[derive(Debug, Clone)]
 pub enum Action {
     Tick,
     Quit,
     ToggleFocus,
-    NavigateUp,
-    NavigateDown,
-    NavigatePageUp,
-    NavigatePageDown,
+    /// Each navigation action carries its count prefix (1 without one).
+    NavigateUp(usize),
+    NavigateDown(usize),
+    NavigatePageUp(usize),
+    NavigatePageDown(usize),
+    /// Row index, clamped to the end of the list.
+    NavigateTo(usize),
+    NavigateToEnd,
     GoBack,
     Select,
     SearchStarted,
-    SearchCompleted(Vec<Media>, Option<String>),
+    SearchCompleted(Vec<Media>, Option<String>, Option<MediaPager>),
+    /// The next page of the list on screen, and whether there are more.
+    PageLoaded(Vec<Media>, Option<PageInfo>),
     SearchError(String),
-    ImageLoaded(Vec<u8>),
+    #[cfg(feature = "images")]
+    ImageLoaded(DynamicImage),
+    BacklogLoaded(Vec<Media>),
+    /// Every show on the viewer's lists, for the organizer.
+    LibraryLoaded(Vec<Media>),
+    /// Applies the edit to the organizer's selection.
+    BulkEdit(BulkEdit),
+    /// Entries done so far and in all.
+    BulkProgress(usize, usize),
+    /// Media ids the edit reached, and the error that stopped it early.
+    BulkFinished(BulkEdit, Vec<i32>, Option<String>),
+    ConflictsLoaded(Vec<Conflict>),
+    CharactersLoaded(Vec<CharacterEdge>),
+    /// A voice actor's name and their roles in completed shows.
+    VoiceRolesLoaded(String, Vec<VoiceRole>),
+    FranchiseLoaded(Vec<FranchiseEntry>),
+    /// The shows of a watch order, in that order, to queue.
+    WatchOrderLoaded(Vec<Media>),
+    /// A manga, its id at the manga provider and its chapters.
+    ChaptersLoaded(Arc<Media>, String, Vec<String>),
+    /// Index of a chapter and its page URLs.
+    ChapterPagesLoaded(usize, Vec<String>),
+    /// A page image and the URL it came from.
+    #[cfg(feature = "images")]
+    MangaPageLoaded(String, DynamicImage),
+    ResolveConflict(Resolution),
+    /// Media id and the progress both sides now agree on.
+    ConflictResolved(i32, i32),
+    /// Media id, episode number and what MyAnimeList knows about it.
+    EpisodeDetailsLoaded(i32, i32, Option<EpisodeDetails>),
+    /// The lookup failed (e.g. rate limited); it is tried again next time.
+    EpisodeDetailsFailed(i32, i32),
+    ToggleSpoilers,
+    ToggleTranslation,
+    TogglePrivate,
+    ToggleAudioOnly,
+    /// Quit without asking about background work.
+    QuitNow,
+    #[cfg(feature = "images")]
+    BannerLoaded(i32, DynamicImage),
+    /// A slim list row's full details.
+    DetailsLoaded(Box<Media>),
+    /// A fresh copy of a list shown from the cache, by title.
+    ListRefreshed(String, Vec<Media>, Option<MediaPager>),
     UpdateAvailable(String),
     StreamStarted,
     StreamLog(String),
     StreamFinished,
+    /// AniList says the episode aired but the provider doesn't have it yet.
+    EpisodeNotUploaded(Arc<Media>, String),
+    RetryStream,
+    /// Start streaming straight away, e.g. for `ani-l continue`.
+    ResumeMedia(Arc<Media>, String),
+    /// Plays the head of the watch queue.
+    PlayQueue,
+    /// Several provider results look like the searched show; the index of
+    /// the one picked (or `None`) goes back on the channel.
+    ChooseShow(String, Vec<String>, mpsc::UnboundedSender<Option<usize>>),
+    /// An episode was watched far enough to count.
+    EpisodeWatched(Arc<Media>, i32),
+    /// Media id, episode and its 1–10 rating.
+    RateEpisode(i32, i32, u8),
+    /// A show's edited note and tags.
+    SaveNote(Arc<Media>, Option<String>, Vec<String>),
+    /// Merges the registry's duplicate entries.
+    MergeDuplicates,
     Suspend(Arc<Notify>),
     Resume,
 }
 
+/// The AniList query behind the media list on screen, so scrolling near its
+/// end can fetch the next page.
+#[derive(Debug, Clone)]
+pub struct MediaPager {
+    pub variables: serde_json::Value,
+    /// Last page fetched.
+    pub page: i32,
+    pub loading: bool,
+}
+
+impl MediaPager {
+    /// `None` when `info` says there is nothing after this page.
+    pub fn after(variables: serde_json::Value, info: &PageInfo) -> Option<Self> {
+        info.has_next_page.then_some(Self {
+            variables,
+            page: info.current_page,
+            loading: false,
+        })
+    }
+
+    /// Variables for the page after the last one fetched.
+    pub fn next_variables(&self) -> serde_json::Value {
+        let mut variables = self.variables.clone();
+        variables["page"] = serde_json::json!(self.page + 1);
+        variables
+    }
+}
+
+/// Drops entries of `incoming` already in `existing` (or earlier in
+/// `incoming`), by AniList id or, failing that, by title. Pages can shift
+/// between requests as rankings change, so the same show may come back.
+pub fn dedup_media(existing: &[Arc<Media>], incoming: Vec<Media>) -> Vec<Media> {
+    let key = |m: &Media| m.preferred_title().to_lowercase();
+    let mut ids: HashSet<i32> = existing.iter().map(|m| m.id).collect();
+    let mut titles: HashSet<String> = existing.iter().map(|m| key(m)).collect();
+    incoming
+        .into_iter()
+        .filter(|m| {
+            let new_id = ids.insert(m.id);
+            let new_title = titles.insert(key(m));
+            new_id && new_title
+        })
+        .collect()
+}
+
+/// A yes/no question; `on_yes` is sent if the user agrees.
+#[derive(Debug, Clone)]
+pub struct Confirm {
+    pub message: String,
+    pub on_yes: Action,
+}
+
+/// Asks for an episode's rating; digits answer it.
+#[derive(Debug, Clone)]
+pub struct RatingPrompt {
+    pub media_id: i32,
+    pub title: String,
+    pub episode: i32,
+}
+
+/// Edits a show's note and comma-separated tags; Tab switches between them.
+#[derive(Debug, Clone)]
+pub struct NoteEditor {
+    pub media: Arc<Media>,
+    pub note: String,
+    pub tags: String,
+    pub editing_tags: bool,
+}
+
+impl NoteEditor {
+    pub fn field(&mut self) -> &mut String {
+        if self.editing_tags {
+            &mut self.tags
+        } else {
+            &mut self.note
+        }
+    }
+
+    pub fn tag_list(&self) -> Vec<String> {
+        self.tags
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Provider results to pick the searched show from.
+#[derive(Debug)]
+pub struct ShowChoice {
+    pub query: String,
+    pub names: Vec<String>,
+    pub selected: usize,
+    pub reply: mpsc::UnboundedSender<Option<usize>>,
+}
+
+/// Counts background tasks that write somewhere (AniList syncs,
+/// downloads), so quitting can warn before cutting them short.
+#[derive(Debug, Clone, Default)]
+pub struct PendingWork(Arc<AtomicUsize>);
+
+impl PendingWork {
+    /// Counts as running until the returned guard is dropped.
+    pub fn start(&self) -> PendingGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        PendingGuard(self.0.clone())
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+pub struct PendingGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The manga open in the reader.
+#[derive(Debug, Clone)]
+pub struct MangaSession {
+    pub media: Arc<Media>,
+    /// Id at the manga provider.
+    pub manga_id: String,
+    pub chapters: Vec<String>,
+    /// Index into `chapters` of the chapter being read.
+    pub chapter: usize,
+    pub pages: Vec<String>,
+    /// AniList chapter progress, raised as chapters are finished.
+    pub progress: i32,
+}
+
+impl MangaSession {
+    /// The chapter's number, rounded down for AniList (which counts whole
+    /// chapters), or `None` for extras like "Oneshot".
+    pub fn chapter_number(&self, index: usize) -> Option<i32> {
+        self.chapters
+            .get(index)?
+            .parse::<f64>()
+            .ok()
+            .map(|c| c.floor() as i32)
+    }
+}
+
+/// Where keystrokes go, shown at the left of the status bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputMode {
+    Search,
+    List,
+    /// A modal dialog has the keyboard.
+    Dialog,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Focus {
     SearchBar,
@@ -45,9 +305,38 @@ pub enum ListMode {
     EpisodeSelect,
     Options,
     StreamLogging,
+    #[cfg(feature = "downloads")]
+    Downloads,
+    Backlog,
+    Conflicts,
+    Characters,
+    /// Roles of the named voice actor in shows the viewer completed.
+    VoiceRoles(String),
+    /// The active show's franchise in watch order.
+    Franchise,
+    Queue,
+    /// Every show on the viewer's lists, to select and edit in bulk.
+    Organizer,
+    /// The open manga's chapters.
+    Chapters,
+    /// Pages of a chapter, the selected one drawn in place of the cover.
+    Reader,
+    /// "Previously on" screen shown before resuming a stale show.
+    Recap,
     SubMenu(String),
 }
 
+impl ListMode {
+    /// Screens listing the viewer's own shows, where searching stays within
+    /// their library.
+    pub fn is_library(&self) -> bool {
+        matches!(
+            self,
+            ListMode::Backlog | ListMode::Conflicts | ListMode::Organizer
+        )
+    }
+}
+
 pub struct App {
     pub running: bool,
     pub focus: Focus,
@@ -56,21 +345,110 @@ pub struct App {
     pub list_state: ListState,
     pub main_menu_items: Vec<String>,
     pub anime_action_items: Vec<String>,
-    pub media_list: Vec<Media>,
-    pub active_media: Option<Media>,
+    pub media_list: Vec<Arc<Media>>,
+    pub active_media: Option<Arc<Media>>,
+    /// Slim show waiting for `DETAIL_DELAY` before its details are fetched,
+    /// with when it was highlighted.
+    pub detail_pending: Option<(i32, Instant)>,
     pub config_manager: ConfigManager,
-    pub history_stack: Vec<(ListMode, usize, Option<Media>)>,
+    pub history_stack: VecDeque<(ListMode, usize, Option<Arc<Media>>)>,
     pub action_tx: mpsc::UnboundedSender<Action>,
     pub action_rx: mpsc::UnboundedReceiver<Action>,
     pub cube_angle: f64,
     pub is_loading: bool,
     pub status_message: Option<String>,
     pub stream_logs: VecDeque<String>,
+    #[cfg(feature = "images")]
     pub image_picker: Option<Picker>,
+    #[cfg(feature = "images")]
     pub current_cover_image: Option<StatefulProtocol>,
+    /// Cover drawn as text when the terminal has no usable graphics.
+    #[cfg(feature = "images")]
+    pub current_cover_ascii: Option<AsciiCover>,
+    #[cfg(feature = "images")]
+    pub ascii_covers: bool,
+    #[cfg(feature = "images")]
+    pub current_banner_image: Option<StatefulProtocol>,
+    #[cfg(feature = "images")]
+    pub page_image: Option<StatefulProtocol>,
+    /// Where the cover and manga page were last drawn, so images can be
+    /// scaled for them before they arrive.
+    #[cfg(feature = "images")]
+    pub cover_area: Rect,
+    #[cfg(feature = "images")]
+    pub page_area: Rect,
     pub is_fetching_image: bool,
     pub new_version: Option<String>,
     pub show_update_modal: bool,
+    #[cfg(feature = "downloads")]
+    pub downloads: Vec<DownloadEntry>,
+    /// Stream that can be retried with `r` from the stream log.
+    pub pending_retry: Option<(Arc<Media>, String)>,
+    /// Estimated finish date per backlog row, when there is watch history.
+    pub backlog_finish: Vec<NaiveDate>,
+    pub conflicts: Vec<Conflict>,
+    /// Cast of the active show, for the Characters screen.
+    pub characters: Vec<CharacterEdge>,
+    pub voice_roles: Vec<VoiceRole>,
+    pub franchise: Vec<FranchiseEntry>,
+    /// Watch orders for the franchise on screen, listed above it.
+    pub watch_orders: Vec<&'static WatchOrder>,
+    /// Browsing manga: the main menu's Manga entry was picked, so searches
+    /// look for manga too.
+    pub manga: bool,
+    pub reading: Option<MangaSession>,
+    pub queue: Vec<QueueEntry>,
+    /// The current stream came from the queue, so the next entry follows it.
+    pub playing_queue: bool,
+    /// The queued episode just played was finished; play the next one once
+    /// the stream closes.
+    pub queue_continue: bool,
+    /// Keyed by media id and episode; `None` once fetched with no result.
+    pub episode_details: HashMap<(i32, i32), Option<EpisodeDetails>>,
+    pub episode_details_pending: HashSet<(i32, i32)>,
+    /// Show synopses of episodes past the viewer's progress.
+    pub show_spoilers: bool,
+    /// Sub or dub for streams started this session; starts from
+    /// `stream.translation_type` and is toggled with `t`.
+    pub translation_type: String,
+    /// Incognito: nothing watched this session reaches the history, the
+    /// local registry or AniList.
+    pub private: bool,
+    /// Play without video and leave AniList progress alone.
+    pub audio_only: bool,
+    /// Draws the Random menu's picks; seeded with `--seed` to replay them.
+    pub random: StdRng,
+    /// Last session of the show the Recap screen is about.
+    pub recap: Option<WatchSession>,
+    #[cfg(feature = "images")]
+    pub image_cache: Arc<ImageCache>,
+    #[cfg(feature = "images")]
+    pub image_prefetcher: ImagePrefetcher,
+    /// Number of list rows visible in the last frame.
+    pub list_viewport_height: usize,
+    pub pager: Option<MediaPager>,
+    pub macros: MacroRecorder,
+    pub perf: PerfStats,
+    pub count: CountPrefix,
+    pub confirm: Option<Confirm>,
+    pub show_choice: Option<ShowChoice>,
+    pub rating_prompt: Option<RatingPrompt>,
+    /// The episode just finished, to rate once its player closes.
+    pub pending_rating: Option<RatingPrompt>,
+    /// Episode ratings by media id, as kept in the registry.
+    pub episode_ratings: HashMap<i32, BTreeMap<i32, u8>>,
+    pub note_editor: Option<NoteEditor>,
+    /// Registry entries, for their notes and tags.
+    pub registry_entries: HashMap<i32, RegistryEntry>,
+    /// `ani-l doctor` results, read when the Options screen opens.
+    pub provider_health: Option<HealthManager>,
+    /// Media ids picked in the organizer.
+    pub organizer_selected: HashSet<i32>,
+    /// Episodes marked in Episode Select, to be queued together.
+    pub episodes_selected: BTreeSet<i32>,
+    /// Asking for the score to give the organizer's selection.
+    pub score_prompt: bool,
+    pub pending_work: PendingWork,
 }
 
 impl App {
@@ -78,6 +456,8 @@ impl App {
         let (action_tx, action_rx) = mpsc::unbounded_channel();
         let mut list_state = ListState::default();
         list_state.select(Some(0));
+        #[cfg(feature = "images")]
+        let image_cache = Arc::new(ImageCache::new(64));
 
         let mut app = Self {
             running: true,
@@ -89,20 +469,80 @@ impl App {
             anime_action_items: vec![],
             media_list: vec![],
             active_media: None,
+            detail_pending: None,
             config_manager,
-            history_stack: Vec::new(),
+            history_stack: VecDeque::new(),
             action_tx,
             action_rx,
             cube_angle: 0.0,
             is_loading: false,
             status_message: None,
             stream_logs: VecDeque::with_capacity(20),
+            #[cfg(feature = "images")]
             image_picker: None,
+            #[cfg(feature = "images")]
             current_cover_image: None,
+            #[cfg(feature = "images")]
+            current_cover_ascii: None,
+            #[cfg(feature = "images")]
+            ascii_covers: false,
+            #[cfg(feature = "images")]
+            current_banner_image: None,
+            #[cfg(feature = "images")]
+            page_image: None,
+            #[cfg(feature = "images")]
+            cover_area: Rect::default(),
+            #[cfg(feature = "images")]
+            page_area: Rect::default(),
             is_fetching_image: false,
             new_version: None,
             show_update_modal: false,
+            #[cfg(feature = "downloads")]
+            downloads: Vec::new(),
+            pending_retry: None,
+            backlog_finish: Vec::new(),
+            conflicts: Vec::new(),
+            characters: Vec::new(),
+            voice_roles: Vec::new(),
+            franchise: Vec::new(),
+            watch_orders: Vec::new(),
+            manga: false,
+            reading: None,
+            queue: Vec::new(),
+            playing_queue: false,
+            queue_continue: false,
+            episode_details: HashMap::new(),
+            episode_details_pending: HashSet::new(),
+            show_spoilers: false,
+            translation_type: String::new(),
+            private: false,
+            audio_only: false,
+            random: crate::random::session_rng(None),
+            recap: None,
+            #[cfg(feature = "images")]
+            image_prefetcher: ImagePrefetcher::new(image_cache.clone()),
+            #[cfg(feature = "images")]
+            image_cache,
+            list_viewport_height: 0,
+            pager: None,
+            macros: MacroRecorder::default(),
+            perf: PerfStats::default(),
+            count: CountPrefix::default(),
+            confirm: None,
+            show_choice: None,
+            rating_prompt: None,
+            pending_rating: None,
+            episode_ratings: HashMap::new(),
+            note_editor: None,
+            registry_entries: HashMap::new(),
+            provider_health: None,
+            organizer_selected: HashSet::new(),
+            episodes_selected: BTreeSet::new(),
+            score_prompt: false,
+            pending_work: PendingWork::default(),
         };
+        app.show_spoilers = app.config_manager.config.ui.episode_spoilers;
+        app.translation_type = app.config_manager.config.stream.translation_type.clone();
         app.update_localized_items();
         app
     }
@@ -114,30 +554,113 @@ impl App {
             t!("main_menu.top_scored").to_string(),
             t!("main_menu.recently_updated").to_string(),
             t!("main_menu.random").to_string(),
+            t!("main_menu.manga").to_string(),
+            #[cfg(feature = "downloads")]
+            t!("main_menu.downloads").to_string(),
+            t!("main_menu.backlog").to_string(),
+            t!("main_menu.queue").to_string(),
+            t!("main_menu.conflicts").to_string(),
+            t!("main_menu.organizer").to_string(),
             t!("main_menu.options").to_string(),
             t!("main_menu.exit").to_string(),
         ];
         self.anime_action_items = vec![
             t!("actions.stream").to_string(),
+            t!("actions.play_all").to_string(),
             t!("actions.episodes").to_string(),
             t!("actions.trailer").to_string(),
             t!("actions.reviews").to_string(),
             t!("actions.schedule").to_string(),
             t!("actions.characters").to_string(),
+            t!("actions.notes").to_string(),
             t!("actions.related").to_string(),
             t!("actions.recommendations").to_string(),
         ];
     }
 
+    /// Adds media to the end of the list, skipping duplicates and, in hide
+    /// mode, blocked entries. The selection doesn't move.
+    pub fn append_media(&mut self, media: Vec<Media>) {
+        let blocklist = &self.config_manager.config.blocklist;
+        let media: Vec<Media> = media
+            .into_iter()
+            .filter(|m| blocklist.mode != BlockMode::Hide || !blocklist.blocks(m))
+            .collect();
+        let fresh = dedup_media(&self.media_list, media);
+        self.media_list.extend(fresh.into_iter().map(Arc::new));
+    }
+
+    /// Swaps the list's first page for a fresh copy of it. Later pages stay
+    /// after it, and so do shows that have dropped off the first page; the
+    /// highlighted show stays highlighted and details already loaded for
+    /// shows still on the list are kept.
+    pub fn refresh_media(&mut self, media: Vec<Media>) {
+        let old = std::mem::take(&mut self.media_list);
+        self.append_media(media);
+        for row in self.media_list.iter_mut().filter(|m| m.slim) {
+            if let Some(loaded) = old.iter().find(|m| m.id == row.id && !m.slim) {
+                *row = loaded.clone();
+            }
+        }
+        let fresh: HashSet<i32> = self.media_list.iter().map(|m| m.id).collect();
+        self.media_list
+            .extend(old.into_iter().filter(|m| !fresh.contains(&m.id)));
+        let active = self.active_media.as_ref().map(|m| m.id);
+        match self.media_list.iter().position(|m| Some(m.id) == active) {
+            Some(i) => {
+                self.list_state.select(Some(i));
+                self.active_media = Some(self.media_list[i].clone());
+            }
+            None => {
+                let last = self.media_list.len().checked_sub(1);
+                let selected = self.list_state.selected().or(Some(0));
+                self.list_state
+                    .select(selected.zip(last).map(|(i, last)| i.min(last)));
+            }
+        }
+    }
+
+    /// Drops the cover and banner of the previously previewed media.
+    pub fn clear_cover(&mut self) {
+        #[cfg(feature = "images")]
+        {
+            self.current_cover_image = None;
+            self.current_cover_ascii = None;
+            self.current_banner_image = None;
+            self.page_image = None;
+        }
+    }
+
+    /// Probes the terminal's graphics support, once. The TUI does this at
+    /// startup, while nothing else is reading from the terminal.
+    #[cfg(feature = "images")]
     #[allow(deprecated)]
-    pub fn init_image_picker(&mut self) {
-        let picker = match Picker::from_query_stdio() {
-            Ok(p) => p,
-            Err(_) => Picker::from_fontsize((10, 20)),
-        };
+    pub fn ensure_image_picker(&mut self) {
+        if self.image_picker.is_some() {
+            return;
+        }
+        let setting = &self.config_manager.config.ui.image_protocol;
+        let picker = images::build_picker(setting);
+        self.ascii_covers = setting == "ascii"
+            || (setting == "auto"
+                && picker.protocol_type() == ProtocolType::Halfblocks
+                && !images::supports_truecolor());
         self.image_picker = Some(picker);
     }
 
+    /// `area` in pixels, for scaling images to it, once the picker knows the
+    /// terminal's cell size.
+    #[cfg(feature = "images")]
+    pub fn pixel_size(&self, area: Rect) -> Option<(u32, u32)> {
+        let (width, height) = self.image_picker.as_ref()?.font_size();
+        (area.width > 0 && area.height > 0).then(|| {
+            (
+                area.width as u32 * width as u32,
+                area.height as u32 * height as u32,
+            )
+        })
+    }
+
     pub fn on_tick(&mut self) {
         self.cube_angle += 0.02;
         if self.cube_angle > 360.0 {
@@ -187,6 +710,40 @@ impl App {
         self.list_state.select(Some(next));
     }
 
+    /// Asks first when confirmation prompts are on, otherwise sends
+    /// `action` straight away.
+    pub fn confirm_then(&mut self, message: String, action: Action) {
+        if self.config_manager.config.ui.confirm_prompts {
+            self.confirm = Some(Confirm {
+                message,
+                on_yes: action,
+            });
+        } else {
+            let _ = self.action_tx.send(action);
+        }
+    }
+
+    pub fn input_mode(&self) -> InputMode {
+        if self.show_update_modal
+            || self.confirm.is_some()
+            || self.show_choice.is_some()
+            || self.rating_prompt.is_some()
+            || self.note_editor.is_some()
+            || self.score_prompt
+        {
+            return InputMode::Dialog;
+        }
+        match self.focus {
+            Focus::SearchBar => InputMode::Search,
+            Focus::List => InputMode::List,
+        }
+    }
+
+    pub fn jump_to(&mut self, row: usize) {
+        let max = self.list_len().saturating_sub(1);
+        self.list_state.select(Some(row.min(max)));
+    }
+
     pub fn jump_backward(&mut self, amount: usize) {
         let current = self.get_selected_index();
         let next = current.saturating_sub(amount);
@@ -202,7 +759,17 @@ impl App {
                 .as_ref()
                 .and_then(|m| m.episodes)
                 .unwrap_or(100) as usize,
-            ListMode::Options => 3,
+            ListMode::Options => 4,
+            #[cfg(feature = "downloads")]
+            ListMode::Downloads => self.downloads.len(),
+            ListMode::Conflicts => self.conflicts.len(),
+            ListMode::Characters => self.characters.len(),
+            ListMode::VoiceRoles(_) => self.voice_roles.len(),
+            ListMode::Franchise => self.watch_orders.len() + self.franchise.len(),
+            ListMode::Queue => self.queue.len(),
+            ListMode::Chapters => self.reading.as_ref().map_or(0, |r| r.chapters.len()),
+            ListMode::Reader => self.reading.as_ref().map_or(0, |r| r.pages.len()),
+            ListMode::Recap => 1,
             ListMode::SubMenu(_) => 1,
             _ => self.media_list.len(),
         }
@@ -212,8 +779,29 @@ impl App {
         self.list_state.selected().unwrap_or(0)
     }
 
+    /// The organizer entries a bulk edit applies to: the selection in list
+    /// order, or the highlighted entry when nothing is selected.
+    pub fn organizer_targets(&self) -> Vec<Arc<Media>> {
+        if self.organizer_selected.is_empty() {
+            return self
+                .media_list
+                .get(self.get_selected_index())
+                .cloned()
+                .into_iter()
+                .collect();
+        }
+        self.media_list
+            .iter()
+            .filter(|m| self.organizer_selected.contains(&m.id))
+            .cloned()
+            .collect()
+    }
+
     pub fn go_to_mode(&mut self, mode: ListMode, reset_index: bool) {
-        self.history_stack.push((
+        if self.history_stack.len() >= MAX_HISTORY_DEPTH {
+            self.history_stack.pop_front();
+        }
+        self.history_stack.push_back((
             self.list_mode.clone(),
             self.get_selected_index(),
             self.active_media.clone(),
@@ -225,20 +813,52 @@ impl App {
     }
 
     pub fn go_back(&mut self) {
-        if let Some((prev_mode, prev_index, prev_media)) = self.history_stack.pop() {
+        if let Some((prev_mode, prev_index, prev_media)) = self.history_stack.pop_back() {
             self.list_mode = prev_mode;
+            self.manga &= self.list_mode != ListMode::MainMenu;
             self.list_state.select(Some(prev_index));
             self.active_media = prev_media;
-            self.current_cover_image = None;
+            self.clear_cover();
             self.stream_logs.clear();
+            self.pending_retry = None;
         } else if matches!(self.list_mode, ListMode::MainMenu) {
             self.running = false;
         } else {
             self.list_mode = ListMode::MainMenu;
+            self.manga = false;
             self.history_stack.clear();
             self.list_state.select(Some(0));
             self.active_media = None;
+            self.clear_cover();
             self.search_query.clear();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_media() {
+        let media = |id: i32, title: &str| -> Media {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "title": { "romaji": title }, "genres": []
+            }))
+            .unwrap()
+        };
+        let existing = vec![Arc::new(media(1, "Frieren"))];
+        let incoming = vec![
+            media(1, "Frieren"),
+            media(2, "FRIEREN"),
+            media(3, "Dandadan"),
+            media(3, "Dandadan"),
+            media(4, "Mushishi"),
+        ];
+        let ids: Vec<i32> = dedup_media(&existing, incoming)
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, vec![3, 4]);
+    }
+}
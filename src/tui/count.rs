@@ -0,0 +1,126 @@
+//! Vim-style count prefixes for list navigation (`5j`, `12G`, `gg`), shared
+//! by every list mode.
+
+use crate::tui::app::Action;
+use crossterm::event::KeyCode;
+
+/// Counts past this are treated as "as far as the list goes".
+const MAX_COUNT: usize = 99_999;
+
+/// What the count state machine did with a key.
+#[derive(Debug)]
+pub enum Feed {
+    /// Part of a count or a `g` prefix; wait for more.
+    Pending,
+    Navigate(Action),
+    /// Not a navigation key; handle it as usual.
+    Pass,
+}
+
+#[derive(Debug, Default)]
+pub struct CountPrefix {
+    count: Option<usize>,
+    pending_g: bool,
+}
+
+impl CountPrefix {
+    /// The count or `g` typed so far, for the status bar.
+    pub fn pending(&self) -> Option<String> {
+        match (self.count, self.pending_g) {
+            (None, false) => None,
+            (count, g) => Some(format!(
+                "{}{}",
+                count.map(|n| n.to_string()).unwrap_or_default(),
+                if g { "g" } else { "" }
+            )),
+        }
+    }
+
+    pub fn feed(&mut self, code: KeyCode) -> Feed {
+        if let KeyCode::Char(c @ '0'..='9') = code
+            && (c != '0' || self.count.is_some())
+            && !self.pending_g
+        {
+            let digit = c as usize - '0' as usize;
+            self.count = Some(
+                self.count
+                    .unwrap_or(0)
+                    .saturating_mul(10)
+                    .saturating_add(digit)
+                    .min(MAX_COUNT),
+            );
+            return Feed::Pending;
+        }
+
+        let had_prefix = self.count.is_some() || self.pending_g;
+        let pending_g = std::mem::take(&mut self.pending_g);
+        let count = self.count.take();
+        let n = count.unwrap_or(1);
+        let action = match code {
+            KeyCode::Char('g') if pending_g => Action::NavigateTo(n - 1),
+            KeyCode::Char('g') => {
+                self.count = count;
+                self.pending_g = true;
+                return Feed::Pending;
+            }
+            KeyCode::Char('G') => match count {
+                Some(n) => Action::NavigateTo(n - 1),
+                None => Action::NavigateToEnd,
+            },
+            KeyCode::Down | KeyCode::Char('j') => Action::NavigateDown(n),
+            KeyCode::Up | KeyCode::Char('k') => Action::NavigateUp(n),
+            KeyCode::PageDown | KeyCode::Char('J') => Action::NavigatePageDown(n),
+            KeyCode::PageUp | KeyCode::Char('K') => Action::NavigatePageUp(n),
+            // Esc drops a half-typed count instead of going back.
+            KeyCode::Esc if had_prefix => return Feed::Pending,
+            _ => return Feed::Pass,
+        };
+        Feed::Navigate(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(prefix: &mut CountPrefix, keys: &str) -> Option<Action> {
+        keys.chars()
+            .map(|c| prefix.feed(KeyCode::Char(c)))
+            .last()
+            .and_then(|feed| match feed {
+                Feed::Navigate(action) => Some(action),
+                _ => None,
+            })
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut prefix = CountPrefix::default();
+        assert!(matches!(
+            feed_all(&mut prefix, "5j"),
+            Some(Action::NavigateDown(5))
+        ));
+        assert!(matches!(
+            feed_all(&mut prefix, "k"),
+            Some(Action::NavigateUp(1))
+        ));
+        assert!(matches!(
+            feed_all(&mut prefix, "12G"),
+            Some(Action::NavigateTo(11))
+        ));
+        assert!(matches!(
+            feed_all(&mut prefix, "G"),
+            Some(Action::NavigateToEnd)
+        ));
+        assert!(matches!(
+            feed_all(&mut prefix, "gg"),
+            Some(Action::NavigateTo(0))
+        ));
+
+        assert!(feed_all(&mut prefix, "3").is_none());
+        assert_eq!(prefix.pending().as_deref(), Some("3"));
+        assert!(matches!(prefix.feed(KeyCode::Char('q')), Feed::Pass));
+        assert_eq!(prefix.pending(), None);
+        assert!(matches!(prefix.feed(KeyCode::Char('0')), Feed::Pass));
+    }
+}
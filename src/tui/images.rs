@@ -0,0 +1,303 @@
+use crate::net;
+use image::DynamicImage;
+use image::imageops::FilterType;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui_image::picker::{Picker, ProtocolType};
+use reqwest::Client;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+const MAX_CONCURRENT_FETCHES: usize = 4;
+
+/// Characters from darkest to brightest for ASCII covers.
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// True inside a tmux session. `$TMUX` is checked because tmux often keeps the
+/// outer terminal's `TERM` (e.g. "screen-256color").
+pub fn in_tmux() -> bool {
+    std::env::var_os("TMUX").is_some()
+}
+
+/// Builds the image picker for cover art. `setting` forces a protocol; "auto"
+/// queries the terminal. Inside tmux, graphics passthrough is switched on for
+/// the current pane first, and a failed query falls back to halfblocks rather
+/// than guessing a protocol the terminal may not understand.
+#[allow(deprecated)]
+pub fn build_picker(setting: &str) -> Picker {
+    if in_tmux() {
+        let _ = std::process::Command::new("tmux")
+            .args(["set", "-p", "allow-passthrough", "on"])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+
+    let forced = match setting {
+        "kitty" => Some(ProtocolType::Kitty),
+        "sixel" => Some(ProtocolType::Sixel),
+        "iterm2" => Some(ProtocolType::Iterm2),
+        "halfblocks" | "ascii" => return Picker::halfblocks(),
+        _ => None,
+    };
+
+    let mut picker = match Picker::from_query_stdio() {
+        Ok(p) => p,
+        Err(e) => {
+            log::debug!("Terminal graphics query failed: {}", e);
+            if in_tmux() || std::env::var_os("SSH_CONNECTION").is_some() {
+                Picker::halfblocks()
+            } else {
+                Picker::from_fontsize((10, 20))
+            }
+        }
+    };
+    if let Some(protocol) = forced {
+        picker.set_protocol_type(protocol);
+    }
+    picker
+}
+
+/// Decodes an image and, when `target` is known, shrinks it to fit that many
+/// pixels. Meant for a blocking thread: large covers take long enough to
+/// decode and scale that doing it on the UI thread makes scrolling stutter.
+pub fn decode(bytes: &[u8], target: Option<(u32, u32)>) -> Option<DynamicImage> {
+    let image = image::load_from_memory(bytes).ok()?;
+    Some(match target {
+        Some((width, height))
+            if width > 0 && height > 0 && (image.width() > width || image.height() > height) =>
+        {
+            image.resize(width, height, FilterType::Triangle)
+        }
+        _ => image,
+    })
+}
+
+/// True when the terminal advertises 24-bit colour, which halfblocks need to
+/// look like anything.
+pub fn supports_truecolor() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit")
+}
+
+/// A cover drawn as coloured characters, for terminals with neither graphics
+/// nor true colour. The art is rebuilt only when the panel size changes.
+pub struct AsciiCover {
+    image: DynamicImage,
+    rendered: Option<(Rect, Vec<Line<'static>>)>,
+}
+
+impl AsciiCover {
+    pub fn new(image: DynamicImage) -> Self {
+        Self {
+            image,
+            rendered: None,
+        }
+    }
+
+    pub fn lines(&mut self, area: Rect) -> &[Line<'static>] {
+        if self.rendered.as_ref().map(|(a, _)| *a) != Some(area) {
+            self.rendered = Some((area, ascii_art(&self.image, area.width, area.height)));
+        }
+        self.rendered
+            .as_ref()
+            .map(|(_, lines)| lines.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// Scales `image` to fit `width` x `height` cells, keeping its aspect ratio
+/// (a cell is about twice as tall as it is wide).
+fn ascii_art(image: &DynamicImage, width: u16, height: u16) -> Vec<Line<'static>> {
+    if width == 0 || height == 0 || image.width() == 0 || image.height() == 0 {
+        return Vec::new();
+    }
+    let scale = f64::min(
+        width as f64 / image.width() as f64,
+        height as f64 * 2.0 / image.height() as f64,
+    );
+    let cols = ((image.width() as f64 * scale) as u32).clamp(1, width as u32);
+    let rows = ((image.height() as f64 * scale / 2.0) as u32).clamp(1, height as u32);
+    let small = image
+        .resize_exact(cols, rows, FilterType::Triangle)
+        .to_rgb8();
+
+    small
+        .rows()
+        .map(|row| {
+            let spans: Vec<Span<'static>> = row
+                .map(|px| {
+                    let [r, g, b] = px.0;
+                    let luma = (0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64) / 255.0;
+                    let idx = ((luma * (ASCII_RAMP.len() - 1) as f64).round() as usize)
+                        .min(ASCII_RAMP.len() - 1);
+                    Span::styled(
+                        (ASCII_RAMP[idx] as char).to_string(),
+                        Style::default().fg(ansi_256(r, g, b)),
+                    )
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Nearest colour in the xterm 256-colour cube.
+fn ansi_256(r: u8, g: u8, b: u8) -> Color {
+    let level = |c: u8| (c as u16 * 5 / 255) as u8;
+    Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+}
+
+struct CacheInner {
+    entries: HashMap<String, Arc<Vec<u8>>>,
+    order: VecDeque<String>,
+}
+
+/// In-memory cache of raw cover image bytes, keyed by URL. The oldest entries
+/// are evicted once `capacity` is reached.
+pub struct ImageCache {
+    inner: Mutex<CacheInner>,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ImageCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Looks up an image to show, counting the hit or miss.
+    pub fn get(&self, url: &str) -> Option<Arc<Vec<u8>>> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let bytes = inner.entries.get(url).cloned();
+        let counter = if bytes.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        bytes
+    }
+
+    /// Like `get`, without counting towards the hit rate.
+    pub fn contains(&self, url: &str) -> bool {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.entries.contains_key(url)
+    }
+
+    /// Hits and misses from `get` so far.
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn insert(&self, url: String, bytes: Vec<u8>) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.entries.contains_key(&url) {
+            return;
+        }
+        while inner.entries.len() >= self.capacity {
+            match inner.order.pop_front() {
+                Some(oldest) => {
+                    inner.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+        inner.order.push_back(url.clone());
+        inner.entries.insert(url, Arc::new(bytes));
+    }
+}
+
+/// Downloads cover images ahead of time so scrolling shows art instantly.
+pub struct ImagePrefetcher {
+    cache: Arc<ImageCache>,
+    client: Client,
+    permits: Arc<Semaphore>,
+    tasks: Vec<JoinHandle<()>>,
+    requested: HashSet<String>,
+}
+
+impl ImagePrefetcher {
+    pub fn new(cache: Arc<ImageCache>) -> Self {
+        Self {
+            cache,
+            client: net::api_client(),
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES)),
+            tasks: Vec::new(),
+            requested: HashSet::new(),
+        }
+    }
+
+    /// Aborts every outstanding fetch. Called when the list is replaced.
+    pub fn cancel(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+        self.requested.clear();
+    }
+
+    pub fn prefetch(&mut self, urls: Vec<String>) {
+        self.tasks.retain(|t| !t.is_finished());
+
+        for url in urls {
+            if self.cache.contains(&url) || !self.requested.insert(url.clone()) {
+                continue;
+            }
+
+            let cache = self.cache.clone();
+            let client = self.client.clone();
+            let permits = self.permits.clone();
+            self.tasks.push(tokio::spawn(async move {
+                let Ok(_permit) = permits.acquire_owned().await else {
+                    return;
+                };
+                if let Ok(resp) = client.get(&url).send().await
+                    && let Ok(bytes) = resp.bytes().await
+                {
+                    cache.insert(url, bytes.to_vec());
+                }
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_art_fits_panel() {
+        let image = DynamicImage::new_rgb8(230, 325);
+        let lines = ascii_art(&image, 40, 20);
+
+        assert_eq!(lines.len(), 20);
+        assert!(lines.iter().all(|l| l.width() <= 40));
+    }
+
+    #[test]
+    fn test_decode_shrinks_to_target() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(460, 650)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let image = decode(png.get_ref(), Some((200, 200))).unwrap();
+        assert_eq!((image.width(), image.height()), (142, 200));
+        let image = decode(png.get_ref(), None).unwrap();
+        assert_eq!(image.width(), 460);
+    }
+}
@@ -0,0 +1,184 @@
+//! Keyboard macros: an input layer between the terminal and the key
+//! handlers that records key presses into F-key slots and replays them.
+//!
+//! Macros are stored in `[macros]` as key notation, e.g.
+//! `F5 = "/frieren<Enter><Enter>"`: plain characters stand for themselves
+//! and special keys are written in angle brackets.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{HashMap, VecDeque};
+
+/// What the input layer did with a key press.
+#[derive(Debug, PartialEq)]
+pub enum Intercept {
+    /// Not a macro key; handle it as usual.
+    Pass(KeyEvent),
+    /// Waiting for the F-key to record into.
+    ChooseSlot,
+    Recording(String),
+    /// Recording finished: slot and its keys in notation.
+    Recorded(String, String),
+    Cancelled,
+    Replaying(String),
+    /// An F-key with no macro bound to it.
+    Unbound(String),
+}
+
+#[derive(Default)]
+pub struct MacroRecorder {
+    choosing_slot: bool,
+    recording: Option<(String, Vec<KeyEvent>)>,
+    queue: VecDeque<KeyEvent>,
+}
+
+impl MacroRecorder {
+    /// Ctrl+R starts and stops recording.
+    fn is_toggle(key: &KeyEvent) -> bool {
+        key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL)
+    }
+
+    pub fn intercept(&mut self, key: KeyEvent, macros: &HashMap<String, String>) -> Intercept {
+        if self.choosing_slot {
+            self.choosing_slot = false;
+            return match key.code {
+                KeyCode::F(n) => {
+                    let slot = format!("F{}", n);
+                    self.recording = Some((slot.clone(), Vec::new()));
+                    Intercept::Recording(slot)
+                }
+                _ => Intercept::Cancelled,
+            };
+        }
+        if Self::is_toggle(&key) {
+            return match self.recording.take() {
+                Some((slot, keys)) => Intercept::Recorded(slot, format(&keys)),
+                None => {
+                    self.choosing_slot = true;
+                    Intercept::ChooseSlot
+                }
+            };
+        }
+        if let KeyCode::F(n) = key.code {
+            let slot = format!("F{}", n);
+            let Some(notation) = macros.get(&slot) else {
+                return Intercept::Unbound(slot);
+            };
+            // Nested replays are recorded as their keys, so the saved macro
+            // doesn't depend on the other slot staying the same.
+            let keys = parse(notation);
+            if let Some((_, recorded)) = &mut self.recording {
+                recorded.extend(keys.iter().copied());
+            }
+            self.queue.extend(keys);
+            return Intercept::Replaying(slot);
+        }
+        if let Some((_, recorded)) = &mut self.recording {
+            recorded.push(key);
+        }
+        Intercept::Pass(key)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// The next key of a running replay. Replayed keys skip `intercept`.
+    pub fn next_replayed(&mut self) -> Option<KeyEvent> {
+        self.queue.pop_front()
+    }
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("BS", KeyCode::Backspace),
+    ("Tab", KeyCode::Tab),
+    ("Space", KeyCode::Char(' ')),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("lt", KeyCode::Char('<')),
+];
+
+/// Parses key notation. Unknown `<...>` names are skipped.
+pub fn parse(notation: &str) -> Vec<KeyEvent> {
+    let mut keys = Vec::new();
+    let mut rest = notation;
+    while let Some(c) = rest.chars().next() {
+        if c == '<'
+            && let Some(end) = rest.find('>')
+        {
+            let name = &rest[1..end];
+            let code = NAMED_KEYS
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, code)| *code)
+                .or_else(|| {
+                    let n = name.strip_prefix(['F', 'f'])?.parse().ok()?;
+                    Some(KeyCode::F(n))
+                });
+            keys.extend(code.map(|code| KeyEvent::new(code, KeyModifiers::NONE)));
+            rest = &rest[end + 1..];
+        } else {
+            keys.push(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    keys
+}
+
+pub fn format(keys: &[KeyEvent]) -> String {
+    keys.iter()
+        .filter_map(|key| match key.code {
+            KeyCode::Char(' ') => Some("<Space>".to_string()),
+            KeyCode::Char('<') => Some("<lt>".to_string()),
+            KeyCode::Char(c) => Some(c.to_string()),
+            KeyCode::F(n) => Some(format!("<F{}>", n)),
+            code => NAMED_KEYS
+                .iter()
+                .find(|(_, named)| *named == code)
+                .map(|(name, _)| format!("<{}>", name)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut recorder = MacroRecorder::default();
+        let mut macros = HashMap::new();
+        let ctrl_r = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+
+        assert_eq!(recorder.intercept(ctrl_r, &macros), Intercept::ChooseSlot);
+        assert_eq!(
+            recorder.intercept(press(KeyCode::F(5)), &macros),
+            Intercept::Recording("F5".to_string())
+        );
+        for key in parse("/a b<Enter>j") {
+            assert_eq!(recorder.intercept(key, &macros), Intercept::Pass(key));
+        }
+        let Intercept::Recorded(slot, notation) = recorder.intercept(ctrl_r, &macros) else {
+            panic!("recording didn't stop");
+        };
+        assert_eq!(notation, "/a<Space>b<Enter>j");
+
+        macros.insert(slot, notation);
+        assert_eq!(
+            recorder.intercept(press(KeyCode::F(5)), &macros),
+            Intercept::Replaying("F5".to_string())
+        );
+        let replayed: Vec<KeyEvent> = std::iter::from_fn(|| recorder.next_replayed()).collect();
+        assert_eq!(replayed, parse("/a b<Enter>j"));
+        assert_eq!(format(&parse("<lt><F2><nope>x")), "<lt><F2>x");
+    }
+}
@@ -1,2 +1,7 @@
 pub mod app;
+pub mod count;
+#[cfg(feature = "images")]
+pub mod images;
+pub mod macros;
+pub mod perf;
 pub mod ui;
@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Frames kept for the averages, a couple of seconds at the usual rate.
+const WINDOW: usize = 120;
+
+/// Timings for the F12 overlay. Always collected, since it's only a few
+/// `Instant::now()` calls a frame; only drawn when `visible`.
+#[derive(Default)]
+pub struct PerfStats {
+    pub visible: bool,
+    draws: VecDeque<Duration>,
+    latencies: VecDeque<Duration>,
+    input_at: Option<Instant>,
+    pub list_cache_hits: u32,
+    pub list_cache_misses: u32,
+}
+
+/// Last, mean and worst of a window of timings.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub last: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+}
+
+impl Summary {
+    fn of(samples: &VecDeque<Duration>) -> Self {
+        let Some(&last) = samples.back() else {
+            return Self::default();
+        };
+        Self {
+            last,
+            avg: samples.iter().sum::<Duration>() / samples.len() as u32,
+            max: samples.iter().copied().max().unwrap_or_default(),
+        }
+    }
+}
+
+fn push(samples: &mut VecDeque<Duration>, sample: Duration) {
+    if samples.len() == WINDOW {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+impl PerfStats {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Call once a frame with how long `terminal.draw` took. Also closes the
+    /// latency sample for any input read since the last frame.
+    pub fn frame_drawn(&mut self, took: Duration) {
+        push(&mut self.draws, took);
+        if let Some(at) = self.input_at.take() {
+            push(&mut self.latencies, at.elapsed());
+        }
+    }
+
+    /// Call when an input event is read; the time until the next frame is on
+    /// screen is what the user feels as lag.
+    pub fn input_received(&mut self) {
+        self.input_at.get_or_insert_with(Instant::now);
+    }
+
+    pub fn draw_time(&self) -> Summary {
+        Summary::of(&self.draws)
+    }
+
+    pub fn input_latency(&self) -> Summary {
+        Summary::of(&self.latencies)
+    }
+}
+
+/// Hits as a percentage of lookups, if there were any.
+pub fn hit_rate(hits: u64, misses: u64) -> Option<f64> {
+    let total = hits + misses;
+    (total > 0).then(|| hits as f64 * 100.0 / total as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_summary() {
+        let mut stats = PerfStats::default();
+        assert_eq!(stats.draw_time(), Summary::default());
+        for ms in 1..=WINDOW as u64 + 2 {
+            stats.frame_drawn(Duration::from_millis(ms));
+        }
+        // The first two frames have fallen out of the window.
+        let draw = stats.draw_time();
+        assert_eq!(draw.last, Duration::from_millis(122));
+        assert_eq!(draw.max, Duration::from_millis(122));
+        assert_eq!(draw.avg, Duration::from_micros(62_500));
+        assert_eq!(stats.input_latency(), Summary::default());
+
+        stats.input_received();
+        stats.frame_drawn(Duration::ZERO);
+        assert_eq!(stats.latencies.len(), 1);
+        assert_eq!(hit_rate(3, 1), Some(75.0));
+        assert_eq!(hit_rate(0, 0), None);
+    }
+}
@@ -1,4 +1,13 @@
-use crate::tui::app::{App, Focus, ListMode};
+use crate::config::{BlockMode, ScoreColors};
+#[cfg(feature = "downloads")]
+use crate::download::DownloadStatus;
+use crate::franchise::FranchiseEntry;
+use crate::models::Media;
+use crate::timefmt;
+use crate::tui::app::{
+    App, Focus, InputMode, ListMode, NoteEditor, RECAP_EPISODES, RatingPrompt, ShowChoice,
+};
+use crate::tui::perf;
 use ratatui::{
     prelude::*,
     widgets::{
@@ -6,7 +15,10 @@ use ratatui::{
         canvas::{Canvas, Line as CanvasLine},
     },
 };
+#[cfg(feature = "images")]
 use ratatui_image::{Resize, StatefulImage};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 pub fn draw(f: &mut Frame, app: &mut App) {
     let main_area = f.area();
@@ -37,6 +49,30 @@ pub fn draw(f: &mut Frame, app: &mut App) {
     if matches!(app.list_mode, ListMode::StreamLogging) {
         draw_stream_logs(f, app);
     }
+
+    if let Some(choice) = &app.show_choice {
+        draw_show_choice_modal(f, choice);
+    }
+
+    if let Some(confirm) = &app.confirm {
+        draw_confirm_modal(f, &confirm.message);
+    }
+
+    if let Some(prompt) = &app.rating_prompt {
+        draw_rating_modal(f, prompt);
+    }
+
+    if let Some(editor) = &app.note_editor {
+        draw_note_modal(f, editor);
+    }
+
+    if app.score_prompt {
+        draw_score_modal(f, app.organizer_targets().len());
+    }
+
+    if app.perf.visible {
+        draw_perf_overlay(f, app);
+    }
 }
 
 fn draw_left_panel(f: &mut Frame, area: Rect, app: &mut App) {
@@ -46,7 +82,29 @@ fn draw_left_panel(f: &mut Frame, area: Rect, app: &mut App) {
     let inner = block.inner(area);
     f.render_widget(block, area);
 
-    if let Some(media) = &app.active_media {
+    if app.list_mode == ListMode::Organizer {
+        draw_organizer_selection(f, app, inner);
+        return;
+    }
+
+    if app.list_mode == ListMode::Reader {
+        if !draw_page(f, app, inner) {
+            let message = if app.is_fetching_image {
+                t!("status.loading_image").to_string()
+            } else if !cfg!(feature = "images") {
+                t!("status.images_disabled").to_string()
+            } else {
+                t!("status.no_image_found").to_string()
+            };
+            let placeholder = Paragraph::new(message)
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true });
+            f.render_widget(placeholder, inner);
+        }
+        return;
+    }
+
+    if let Some(media) = app.active_media.clone() {
         let left_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
@@ -57,13 +115,12 @@ fn draw_left_panel(f: &mut Frame, area: Rect, app: &mut App) {
             .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
             .split(left_layout[0]);
 
-        if let Some(protocol) = &mut app.current_cover_image {
-            let image = StatefulImage::new().resize(Resize::Fit(None));
-            f.render_stateful_widget(image, top_layout[0], protocol);
-        } else {
+        if !draw_cover(f, app, top_layout[0]) {
             let message = if app.is_fetching_image {
                 t!("status.loading_image").to_string()
-            } else if app.image_picker.is_none() {
+            } else if !cfg!(feature = "images") {
+                t!("status.images_disabled").to_string()
+            } else if !graphics_ready(app) && media.cover_url().is_some() {
                 t!("status.terminal_not_supported").to_string()
             } else {
                 t!("status.no_image_found").to_string()
@@ -76,7 +133,7 @@ fn draw_left_panel(f: &mut Frame, area: Rect, app: &mut App) {
             f.render_widget(placeholder, top_layout[0]);
         }
 
-        let details = vec![
+        let mut details = vec![
             Line::from(Span::styled(
                 media.preferred_title(),
                 Style::default()
@@ -89,7 +146,13 @@ fn draw_left_panel(f: &mut Frame, area: Rect, app: &mut App) {
                     t!("ui_details.score").to_string(),
                     Style::default().fg(Color::Cyan),
                 ),
-                Span::raw(format!("{}%", media.average_score.unwrap_or(0))),
+                match media.average_score {
+                    Some(score) => Span::styled(
+                        format!("{}%", score),
+                        score_style(score, &app.config_manager.config.ui.score_colors),
+                    ),
+                    None => Span::raw("0%"),
+                },
                 Span::raw(" | "),
                 Span::styled(
                     t!("ui_details.favs").to_string(),
@@ -122,6 +185,80 @@ fn draw_left_panel(f: &mut Frame, area: Rect, app: &mut App) {
             Line::from(media.genres.join(", ")),
         ];
 
+        if let Some(entry) = app.registry_entries.get(&media.id) {
+            if !entry.tags.is_empty() {
+                details.push(Line::from(Span::styled(
+                    entry
+                        .tags
+                        .iter()
+                        .map(|t| format!("#{}", t))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    Style::default().fg(Color::Magenta),
+                )));
+            }
+            if let Some(note) = &entry.note {
+                details.push(Line::from(vec![
+                    Span::styled(
+                        t!("ui_details.note").to_string(),
+                        Style::default().fg(Color::Cyan),
+                    ),
+                    Span::raw(note.clone()),
+                ]));
+            }
+        }
+
+        if let Some(ratings) = app.episode_ratings.get(&media.id) {
+            details.extend(ratings_heatmap(
+                ratings,
+                media.episodes,
+                &app.config_manager.config.ui.score_colors,
+            ));
+        }
+
+        if let Some(minutes) = media.minutes_to_finish().filter(|&m| m > 0) {
+            details.insert(
+                5,
+                Line::from(Span::styled(
+                    t!(
+                        "ui_details.left_to_finish",
+                        time = timefmt::runtime(minutes)
+                    )
+                    .to_string(),
+                    Style::default().fg(Color::Green),
+                )),
+            );
+        }
+
+        if let Some(next) = &media.next_airing_episode
+            && let Some(airs_at) = next.airing_time()
+        {
+            details.insert(
+                5,
+                Line::from(vec![
+                    Span::styled(
+                        t!("ui_details.next_airing").to_string(),
+                        Style::default().fg(Color::Cyan),
+                    ),
+                    Span::raw(
+                        t!(
+                            "ui_details.airs",
+                            ep = next.episode,
+                            when = timefmt::relative(airs_at),
+                            date = timefmt::absolute(airs_at)
+                        )
+                        .to_string(),
+                    ),
+                ]),
+            );
+        }
+
+        #[cfg(feature = "images")]
+        if let Some(banner) = &mut app.current_banner_image {
+            let image = StatefulImage::new().resize(Resize::Crop(None));
+            f.render_stateful_widget(image, top_layout[1], banner);
+        }
+
         f.render_widget(
             Paragraph::new(details).wrap(Wrap { trim: true }).block(
                 Block::default()
@@ -131,53 +268,59 @@ fn draw_left_panel(f: &mut Frame, area: Rect, app: &mut App) {
             top_layout[1],
         );
 
-        let bottom_text = vec![
-            Line::from(Span::styled(
-                t!("ui_details.description").to_string(),
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(
-                media
-                    .description
-                    .clone()
-                    .unwrap_or_default()
-                    .replace("<br>", "\n")
-                    .replace("<i>", "")
-                    .replace("</i>", ""),
-            ),
-            Line::from(""),
-            Line::from(Span::styled(
-                t!("ui_details.details").to_string(),
-                Style::default().fg(Color::Cyan),
-            )),
-            Line::from(vec![
-                Span::raw(t!("ui_details.studios").to_string()),
-                Span::raw(
+        let bottom_text = if app.list_mode == ListMode::EpisodeSelect {
+            episode_preview(app, &media)
+        } else if app.list_mode == ListMode::Recap {
+            recap_preview(app, &media)
+        } else {
+            vec![
+                Line::from(Span::styled(
+                    t!("ui_details.description").to_string(),
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                )),
+                Line::from(
                     media
-                        .studios
-                        .as_ref()
-                        .map(|s| {
-                            s.nodes
-                                .iter()
-                                .map(|n| n.name.clone())
-                                .collect::<Vec<_>>()
-                                .join(", ")
-                        })
-                        .unwrap_or("-".to_string()),
+                        .description
+                        .clone()
+                        .unwrap_or_default()
+                        .replace("<br>", "\n")
+                        .replace("<i>", "")
+                        .replace("</i>", ""),
                 ),
-            ]),
-            Line::from(vec![
-                Span::raw(t!("ui_details.aired").to_string()),
-                Span::raw(format!(
-                    "{}{}{}",
-                    media.formatted_start_date(),
-                    t!("ui_details.to"),
-                    media.formatted_end_date()
+                Line::from(""),
+                Line::from(Span::styled(
+                    t!("ui_details.details").to_string(),
+                    Style::default().fg(Color::Cyan),
                 )),
-            ]),
-        ];
+                Line::from(vec![
+                    Span::raw(t!("ui_details.studios").to_string()),
+                    Span::raw(
+                        media
+                            .studios
+                            .as_ref()
+                            .map(|s| {
+                                s.nodes
+                                    .iter()
+                                    .map(|n| n.name.clone())
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            })
+                            .unwrap_or("-".to_string()),
+                    ),
+                ]),
+                Line::from(vec![
+                    Span::raw(t!("ui_details.aired").to_string()),
+                    Span::raw(format!(
+                        "{}{}{}",
+                        media.formatted_start_date(),
+                        t!("ui_details.to"),
+                        media.formatted_end_date()
+                    )),
+                ]),
+            ]
+        };
 
         f.render_widget(
             Paragraph::new(bottom_text)
@@ -190,6 +333,174 @@ fn draw_left_panel(f: &mut Frame, area: Rect, app: &mut App) {
     }
 }
 
+/// Title, air date and synopsis of the selected episode. Synopses of episodes
+/// past the viewer's progress stay hidden until spoilers are toggled on.
+fn episode_preview(app: &App, media: &Media) -> Vec<Line<'static>> {
+    let episode = app.get_selected_index() as i32 + 1;
+    let key = (media.id, episode);
+    let details = app.episode_details.get(&key).cloned().flatten();
+
+    let mut lines = vec![Line::from(Span::styled(
+        episode_heading(app, media, episode),
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    ))];
+
+    let mut tags = Vec::new();
+    if let Some(aired) = details.as_ref().and_then(|d| d.aired) {
+        let date = aired.format("%Y-%m-%d").to_string();
+        tags.push(Span::raw(
+            t!("ui_details.episode_aired", date = date).to_string(),
+        ));
+    }
+    for (flag, label) in [
+        (
+            details.as_ref().is_some_and(|d| d.filler),
+            t!("ui_details.filler"),
+        ),
+        (
+            details.as_ref().is_some_and(|d| d.recap),
+            t!("ui_details.recap"),
+        ),
+    ] {
+        if flag {
+            tags.push(Span::styled(
+                format!(" [{}]", label),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+    }
+    lines.push(Line::from(tags));
+    lines.push(Line::from(""));
+
+    let watched = media
+        .media_list_entry
+        .as_ref()
+        .and_then(|e| e.progress)
+        .unwrap_or(0);
+    let synopsis = details.and_then(|d| d.synopsis);
+    let body = if app.episode_details_pending.contains(&key) {
+        t!("ui_details.episode_loading").to_string()
+    } else if synopsis.is_some() && episode > watched && !app.show_spoilers {
+        t!("ui_details.spoiler_hidden").to_string()
+    } else {
+        synopsis.unwrap_or_else(|| t!("ui_details.no_synopsis").to_string())
+    };
+    lines.extend(body.lines().map(|l| Line::from(l.to_string())));
+    lines
+}
+
+/// "Episode N: Title", with the title from MyAnimeList or the official
+/// streaming links when known.
+fn episode_heading(app: &App, media: &Media, episode: i32) -> String {
+    let heading = t!("ui.episode_prefix", num = episode).to_string();
+    let title = app
+        .episode_details
+        .get(&(media.id, episode))
+        .and_then(|d| d.as_ref()?.title.as_deref())
+        .or_else(|| media.streaming_episode_title(episode));
+    match title {
+        Some(title) => format!("{}: {}", heading, title),
+        None => heading,
+    }
+}
+
+/// The last few episodes watched, ending with the synopsis of the latest.
+fn recap_preview(app: &App, media: &Media) -> Vec<Line<'static>> {
+    let Some(session) = &app.recap else {
+        return Vec::new();
+    };
+    let last = session.episode;
+    let mut lines = vec![
+        Line::from(Span::styled(
+            t!("ui_details.previously_on", title = media.preferred_title()).to_string(),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(
+            t!(
+                "ui_details.last_watched",
+                ep = last,
+                when = timefmt::relative(session.watched_at)
+            )
+            .to_string(),
+        ),
+        Line::from(""),
+    ];
+    for episode in (last - RECAP_EPISODES + 1).max(1)..last {
+        lines.push(Line::from(format!(
+            "• {}",
+            episode_heading(app, media, episode)
+        )));
+    }
+    lines.push(Line::from(Span::styled(
+        format!("• {}", episode_heading(app, media, last)),
+        Style::default().add_modifier(Modifier::BOLD),
+    )));
+
+    let key = (media.id, last);
+    let body = if app.episode_details_pending.contains(&key) {
+        t!("ui_details.episode_loading").to_string()
+    } else {
+        app.episode_details
+            .get(&key)
+            .and_then(|d| d.as_ref()?.synopsis.clone())
+            .unwrap_or_else(|| t!("ui_details.no_synopsis").to_string())
+    };
+    lines.extend(body.lines().map(|l| Line::from(l.to_string())));
+    lines
+}
+
+/// Draws the loaded cover, returning false when there is nothing to show yet.
+#[cfg(feature = "images")]
+fn draw_cover(f: &mut Frame, app: &mut App, area: Rect) -> bool {
+    app.cover_area = area;
+    if let Some(protocol) = &mut app.current_cover_image {
+        let image = StatefulImage::new().resize(Resize::Fit(None));
+        f.render_stateful_widget(image, area, protocol);
+    } else if let Some(ascii) = &mut app.current_cover_ascii {
+        let art = ascii.lines(area).to_vec();
+        f.render_widget(Paragraph::new(art).alignment(Alignment::Center), area);
+    } else {
+        return false;
+    }
+    true
+}
+
+#[cfg(not(feature = "images"))]
+fn draw_cover(_f: &mut Frame, _app: &mut App, _area: Rect) -> bool {
+    false
+}
+
+/// Draws the manga page open in the reader, as large as fits.
+#[cfg(feature = "images")]
+fn draw_page(f: &mut Frame, app: &mut App, area: Rect) -> bool {
+    app.page_area = area;
+    let Some(protocol) = &mut app.page_image else {
+        return false;
+    };
+    let image = StatefulImage::new().resize(Resize::Fit(None));
+    f.render_stateful_widget(image, area, protocol);
+    true
+}
+
+#[cfg(not(feature = "images"))]
+fn draw_page(_f: &mut Frame, _app: &mut App, _area: Rect) -> bool {
+    false
+}
+
+#[cfg(feature = "images")]
+fn graphics_ready(app: &App) -> bool {
+    app.image_picker.is_some()
+}
+
+#[cfg(not(feature = "images"))]
+fn graphics_ready(_app: &App) -> bool {
+    false
+}
+
 fn draw_stream_logs(f: &mut Frame, app: &App) {
     let area = centered_rect(80, 60, f.area());
     f.render_widget(Clear, area);
@@ -218,6 +529,260 @@ fn draw_stream_logs(f: &mut Frame, app: &App) {
     f.render_widget(list, inner);
 }
 
+/// Colours an average score by band: <60, 60–74, 75–84 and 85+.
+fn score_style(score: i32, colors: &ScoreColors) -> Style {
+    let name = match score {
+        85.. => &colors.top,
+        75..=84 => &colors.high,
+        60..=74 => &colors.mid,
+        _ => &colors.low,
+    };
+    name.parse::<Color>()
+        .map(|c| Style::default().fg(c))
+        .unwrap_or_default()
+}
+
+/// The viewer's episode ratings as a strip of cells, one per episode,
+/// coloured like scores; unrated episodes are dots.
+fn ratings_heatmap(
+    ratings: &BTreeMap<i32, u8>,
+    episodes: Option<i32>,
+    colors: &ScoreColors,
+) -> Vec<Line<'static>> {
+    let Some(&last_rated) = ratings.keys().next_back() else {
+        return Vec::new();
+    };
+    let average = ratings.values().map(|&r| r as f64).sum::<f64>() / ratings.len() as f64;
+    let cells: Vec<Span> = (1..=episodes.unwrap_or(0).max(last_rated))
+        .map(|episode| match ratings.get(&episode) {
+            Some(&rating) => Span::styled("█", score_style(rating as i32 * 10, colors)),
+            None => Span::styled("·", Style::default().fg(Color::DarkGray)),
+        })
+        .collect();
+    vec![
+        Line::from("----"),
+        Line::from(Span::styled(
+            t!(
+                "ui_details.episode_ratings",
+                avg = format!("{:.1}", average),
+                count = ratings.len()
+            )
+            .to_string(),
+            Style::default().fg(Color::Cyan),
+        )),
+        Line::from(cells),
+    ]
+}
+
+/// Year/format and the first two genres as short coloured chips.
+fn media_chips(media: &Media) -> Vec<Span<'static>> {
+    const GENRE_COLORS: [Color; 6] = [
+        Color::Magenta,
+        Color::Blue,
+        Color::Green,
+        Color::Yellow,
+        Color::Cyan,
+        Color::LightRed,
+    ];
+
+    let mut spans = Vec::new();
+    let year = media.start_date.as_ref().and_then(|d| d.year);
+    let info: Vec<String> = year
+        .map(|y| y.to_string())
+        .into_iter()
+        .chain(media.format.clone())
+        .collect();
+    if !info.is_empty() {
+        spans.push(Span::styled(
+            format!(" {}", info.join(" · ")),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    for genre in media.genres.iter().take(2) {
+        // Same genre, same colour, across every list.
+        let hash = genre
+            .bytes()
+            .fold(0usize, |acc, b| acc.wrapping_add(b as usize));
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(
+            format!("[{}]", genre),
+            Style::default().fg(GENRE_COLORS[hash % GENRE_COLORS.len()]),
+        ));
+    }
+    spans
+}
+
+/// An organizer entry: its checkbox, list status, score and progress.
+fn organizer_row(media: &Media, selected: bool) -> ListItem<'static> {
+    let (mark, mark_style) = if selected {
+        ("[x] ", Style::default().fg(Color::Green))
+    } else {
+        ("[ ] ", Style::default().fg(Color::DarkGray))
+    };
+    let entry = media.media_list_entry.as_ref();
+    let mut spans = vec![
+        Span::raw("   "),
+        Span::styled(mark, mark_style),
+        Span::raw(media.preferred_title().to_string()),
+    ];
+    if let Some(status) = entry.and_then(|e| e.status.as_deref()) {
+        let color = match status {
+            "CURRENT" | "REPEATING" => Color::Cyan,
+            "COMPLETED" => Color::Green,
+            "PAUSED" => Color::Yellow,
+            "DROPPED" => Color::Red,
+            _ => Color::DarkGray,
+        };
+        spans.push(Span::styled(
+            format!(" {}", status),
+            Style::default().fg(color),
+        ));
+    }
+    if let Some(score) = entry.and_then(|e| e.score).filter(|&s| s > 0.0) {
+        spans.push(Span::styled(
+            format!(" ★{}", score),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if entry.is_some_and(|e| e.is_private()) {
+        spans.push(Span::raw(" 🔒"));
+    }
+    let progress = entry.and_then(|e| e.progress).unwrap_or(0);
+    let total = media
+        .episodes
+        .map_or_else(|| "?".to_string(), |e| e.to_string());
+    spans.push(Span::styled(
+        format!(" {}/{}", progress, total),
+        Style::default().fg(Color::DarkGray),
+    ));
+    ListItem::new(Line::from(spans))
+}
+
+/// The organizer's other pane: what is selected and what can be done
+/// with it.
+fn draw_organizer_selection(f: &mut Frame, app: &App, area: Rect) {
+    let selected: Vec<Arc<Media>> = app
+        .media_list
+        .iter()
+        .filter(|m| app.organizer_selected.contains(&m.id))
+        .cloned()
+        .collect();
+    let mut lines = vec![
+        Line::from(Span::styled(
+            t!("organizer.selected", count = selected.len()).to_string(),
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Yellow),
+        )),
+        Line::from(""),
+    ];
+    if selected.is_empty() {
+        lines.push(Line::from(Span::styled(
+            t!("organizer.nothing_selected").to_string(),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    // Leaves room for the action list below.
+    let room = (area.height as usize).saturating_sub(10);
+    lines.extend(
+        selected
+            .iter()
+            .take(room)
+            .map(|m| Line::from(format!(" • {}", m.preferred_title()))),
+    );
+    if selected.len() > room {
+        lines.push(Line::from(Span::styled(
+            t!("organizer.more", count = selected.len() - room).to_string(),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.extend(
+        [
+            t!("organizer.action_pause"),
+            t!("organizer.action_delete"),
+            t!("organizer.action_score"),
+        ]
+        .into_iter()
+        .map(|a| {
+            Line::from(Span::styled(
+                a.to_string(),
+                Style::default().fg(Color::Cyan),
+            ))
+        }),
+    );
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), area);
+}
+
+/// A franchise entry, indented under the entry it branches off, with its
+/// year, format and how far the viewer got.
+fn franchise_row(entry: &FranchiseEntry, active: bool) -> ListItem<'static> {
+    let media = &entry.media;
+    let branch = if entry.depth > 0 { "└ " } else { "" };
+    let title_style = if active {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    let mut spans = vec![
+        Span::raw(format!("   {}{}", "  ".repeat(entry.depth), branch)),
+        Span::styled(media.preferred_title().to_string(), title_style),
+    ];
+    if let Some(relation) = &entry.relation {
+        let label = match relation.as_str() {
+            "PREQUEL" => t!("relations.prequel"),
+            "SEQUEL" => t!("relations.sequel"),
+            "PARENT" => t!("relations.parent"),
+            "SIDE_STORY" => t!("relations.side_story"),
+            _ => t!("relations.spin_off"),
+        };
+        spans.push(Span::styled(
+            format!(" ({})", label),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    let year = media.start_date.as_ref().and_then(|d| d.year);
+    let info: Vec<String> = year
+        .map(|y| y.to_string())
+        .into_iter()
+        .chain(media.format.clone())
+        .collect();
+    if !info.is_empty() {
+        spans.push(Span::styled(
+            format!(" · {}", info.join(" · ")),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    let entry_status = media.media_list_entry.as_ref();
+    match entry_status.and_then(|e| e.status.as_deref()) {
+        Some("COMPLETED") => spans.push(Span::styled(" ✓", Style::default().fg(Color::Green))),
+        Some("PLANNING") | None => {}
+        Some(_) => {
+            let progress = entry_status.and_then(|e| e.progress).unwrap_or(0);
+            let total = media
+                .episodes
+                .map_or_else(|| "?".to_string(), |e| e.to_string());
+            spans.push(Span::styled(
+                format!(" {}/{}", progress, total),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+    }
+    ListItem::new(Line::from(spans))
+}
+
+/// The provider option, with how it fared at the last `ani-l doctor`.
+fn provider_option(app: &App) -> String {
+    let provider = &app.config_manager.config.general.provider;
+    let health = app.provider_health.as_ref().and_then(|h| h.fresh(provider));
+    let health = match health {
+        Some(h) if h.reachable => t!("options.health_up", ms = h.search_ms.unwrap_or(0)),
+        Some(_) => t!("options.health_down"),
+        None => t!("options.health_unknown"),
+    };
+    t!("options.provider", val = provider, health = health).to_string()
+}
+
 fn draw_list_panel(f: &mut Frame, area: Rect, app: &mut App) {
     let border_style = if app.focus == Focus::List {
         Style::default().fg(Color::Cyan)
@@ -232,6 +797,26 @@ fn draw_list_panel(f: &mut Frame, area: Rect, app: &mut App) {
         ListMode::EpisodeSelect => t!("titles.select_episode").to_string(),
         ListMode::Options => t!("titles.options").to_string(),
         ListMode::StreamLogging => t!("titles.stream_logs").to_string(),
+        #[cfg(feature = "downloads")]
+        ListMode::Downloads => t!("titles.downloads").to_string(),
+        ListMode::Backlog => t!("titles.backlog").to_string(),
+        ListMode::Conflicts => t!("titles.conflicts").to_string(),
+        ListMode::Characters => t!("titles.characters").to_string(),
+        ListMode::VoiceRoles(name) => t!("titles.voice_roles", name = name).to_string(),
+        ListMode::Franchise => t!("titles.franchise").to_string(),
+        ListMode::Chapters => t!("titles.chapters").to_string(),
+        ListMode::Reader => match &app.reading {
+            Some(r) => t!("titles.reader", chapter = r.chapters[r.chapter]).to_string(),
+            None => t!("titles.chapters").to_string(),
+        },
+        ListMode::Queue => t!("titles.queue").to_string(),
+        ListMode::Organizer => t!(
+            "titles.organizer",
+            selected = app.organizer_selected.len(),
+            total = app.media_list.len()
+        )
+        .to_string(),
+        ListMode::Recap => t!("titles.recap").to_string(),
         ListMode::AnimeList(t) => format!(" {} ", t),
         ListMode::SubMenu(t) => format!(" {} ", t),
     };
@@ -262,27 +847,222 @@ fn draw_list_panel(f: &mut Frame, area: Rect, app: &mut App) {
                 "options.language",
                 val = app.config_manager.config.general.language
             ))),
+            ListItem::new(pad(&provider_option(app))),
         ],
         ListMode::EpisodeSelect => {
             let count = app.list_len();
+            let ratings = app
+                .active_media
+                .as_ref()
+                .and_then(|m| app.episode_ratings.get(&m.id));
+            let colors = &app.config_manager.config.ui.score_colors;
             (1..=count)
-                .map(|i| ListItem::new(pad(&t!("ui.episode_prefix", num = i))))
+                .map(|i| {
+                    let episode = t!("ui.episode_prefix", num = i);
+                    let label = if app.episodes_selected.contains(&(i as i32)) {
+                        pad(&format!("{} [x]", episode))
+                    } else {
+                        pad(&episode)
+                    };
+                    match ratings.and_then(|r| r.get(&(i as i32))) {
+                        Some(&rating) => ListItem::new(Line::from(vec![
+                            Span::raw(label),
+                            Span::styled(
+                                format!(" ★{}", rating),
+                                score_style(rating as i32 * 10, colors),
+                            ),
+                        ])),
+                        None => ListItem::new(label),
+                    }
+                })
                 .collect()
         }
         ListMode::SubMenu(_) => vec![ListItem::new(pad(&t!("ui.feature_soon")))],
-        _ => app
+        ListMode::Recap => vec![ListItem::new(pad(&t!("ui.recap_continue")))],
+        ListMode::Conflicts => app
+            .conflicts
+            .iter()
+            .map(|c| {
+                ListItem::new(pad(&t!(
+                    "ui.conflict_row",
+                    title = c.title,
+                    local = c.local,
+                    remote = c.remote
+                )))
+            })
+            .collect(),
+        ListMode::Characters => app
+            .characters
+            .iter()
+            .map(|c| {
+                let mut spans = vec![Span::raw(format!("   {}", c.node.name))];
+                if c.role.as_deref() == Some("MAIN") {
+                    spans.push(Span::styled(
+                        " ★".to_string(),
+                        Style::default().fg(Color::Yellow),
+                    ));
+                }
+                if let Some(actor) = c.voice_actors.first() {
+                    spans.push(Span::styled(
+                        format!(" · {}", actor.name),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect(),
+        ListMode::VoiceRoles(_) => app
+            .voice_roles
+            .iter()
+            .map(|r| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("   {}", r.media.preferred_title())),
+                    Span::styled(
+                        format!(" · {}", r.characters),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect(),
+        ListMode::Franchise => {
+            let active = app.active_media.as_ref().map(|m| m.id);
+            let orders = app.watch_orders.iter().map(|order| {
+                ListItem::new(Line::from(Span::styled(
+                    pad(&t!(
+                        "ui.watch_order_row",
+                        name = order.name,
+                        count = order.entries.len()
+                    )),
+                    Style::default().fg(Color::Cyan),
+                )))
+            });
+            orders
+                .chain(
+                    app.franchise
+                        .iter()
+                        .map(|e| franchise_row(e, active == Some(e.media.id))),
+                )
+                .collect()
+        }
+        ListMode::Chapters => match &app.reading {
+            Some(reading) => (0..reading.chapters.len())
+                .map(|i| {
+                    let label = pad(&t!("ui.chapter_row", num = reading.chapters[i]));
+                    if reading
+                        .chapter_number(i)
+                        .is_some_and(|c| c <= reading.progress)
+                    {
+                        ListItem::new(Line::from(vec![
+                            Span::raw(label),
+                            Span::styled(" ✓", Style::default().fg(Color::Green)),
+                        ]))
+                    } else {
+                        ListItem::new(label)
+                    }
+                })
+                .collect(),
+            None => Vec::new(),
+        },
+        ListMode::Reader => (1..=app.list_len())
+            .map(|i| ListItem::new(pad(&t!("ui.page_row", num = i))))
+            .collect(),
+        ListMode::Organizer => app
             .media_list
             .iter()
-            .map(|m| {
-                let title = m.preferred_title();
-                let display_title = if title.len() > 30 {
-                    format!("{}...", &title[..27])
-                } else {
-                    title.to_string()
+            .map(|m| organizer_row(m, app.organizer_selected.contains(&m.id)))
+            .collect(),
+        ListMode::Queue => app
+            .queue
+            .iter()
+            .map(|e| {
+                ListItem::new(pad(&format!(
+                    "{} - {}",
+                    e.title,
+                    t!("ui.episode_prefix", num = e.episode)
+                )))
+            })
+            .collect(),
+        #[cfg(feature = "downloads")]
+        ListMode::Downloads => app
+            .downloads
+            .iter()
+            .map(|d| {
+                let (icon, style) = match d.status {
+                    DownloadStatus::Completed => ("✅", Style::default()),
+                    DownloadStatus::Pending => ("⏳", Style::default().fg(Color::Yellow)),
+                    DownloadStatus::Failed => ("❌", Style::default().fg(Color::Red)),
+                    DownloadStatus::Suspect => ("⚠️ ", Style::default().fg(Color::Red)),
                 };
-                ListItem::new(pad(&display_title))
+                let label = format!(
+                    "{} {} - {} · {}",
+                    icon,
+                    d.title,
+                    t!("ui.episode_prefix", num = d.episode),
+                    timefmt::relative(d.last_updated)
+                );
+                ListItem::new(pad(&label)).style(style)
             })
             .collect(),
+        _ => {
+            let chips = app.config_manager.config.ui.list_chips;
+            let colors = &app.config_manager.config.ui.score_colors;
+            let backlog = matches!(app.list_mode, ListMode::Backlog);
+            let blocklist = &app.config_manager.config.blocklist;
+            let dim_blocked = blocklist.mode == BlockMode::Dim;
+            app.media_list
+                .iter()
+                .enumerate()
+                .map(|(i, m)| {
+                    let title = m.preferred_title();
+                    // By characters: byte slicing panics inside CJK titles.
+                    let display_title = if title.chars().count() > 30 {
+                        format!("{}...", title.chars().take(27).collect::<String>())
+                    } else {
+                        title.to_string()
+                    };
+                    let mut spans = vec![Span::raw(format!("   {}", display_title))];
+                    if let Some(score) = m.average_score {
+                        spans.push(Span::styled(
+                            format!(" {}%", score),
+                            score_style(score, colors),
+                        ));
+                    }
+                    if chips {
+                        spans.extend(media_chips(m));
+                    }
+                    if let Some(entry) = app.registry_entries.get(&m.id) {
+                        for tag in &entry.tags {
+                            spans.push(Span::styled(
+                                format!(" #{}", tag),
+                                Style::default().fg(Color::Magenta),
+                            ));
+                        }
+                    }
+                    if backlog && let Some(date) = app.backlog_finish.get(i) {
+                        spans.push(Span::styled(
+                            format!(" → {}", date.format("%b %-d")),
+                            Style::default().fg(Color::Green),
+                        ));
+                    }
+                    if let Some(minutes) = m.minutes_to_finish().filter(|&m| m > 0) {
+                        spans.push(Span::styled(
+                            format!(" ≈ {}", timefmt::runtime(minutes)),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+                    let item = ListItem::new(Line::from(spans));
+                    if dim_blocked && blocklist.blocks(m) {
+                        item.style(
+                            Style::default()
+                                .fg(Color::DarkGray)
+                                .add_modifier(Modifier::DIM),
+                        )
+                    } else {
+                        item
+                    }
+                })
+                .collect()
+        }
     };
 
     let list = List::new(items)
@@ -299,6 +1079,7 @@ fn draw_list_panel(f: &mut Frame, area: Rect, app: &mut App) {
                 .add_modifier(Modifier::BOLD),
         );
 
+    app.list_viewport_height = area.height.saturating_sub(2) as usize;
     f.render_stateful_widget(list, area, &mut app.list_state);
 }
 
@@ -319,7 +1100,11 @@ fn draw_search_bar(f: &mut Frame, area: Rect, app: &App) {
             Block::default()
                 .borders(Borders::ALL)
                 .border_style(border_style)
-                .title(t!("titles.search").to_string()),
+                .title(if app.list_mode.is_library() {
+                    t!("titles.search_library").to_string()
+                } else {
+                    t!("titles.search").to_string()
+                }),
         ),
         area,
     );
@@ -332,17 +1117,72 @@ fn draw_status_bar(f: &mut Frame, area: Rect, app: &App) {
         (Color::Blue, Color::White, format!(" ℹ️  {} ", msg))
     } else {
         let help = match app.focus {
+            Focus::SearchBar if app.list_mode.is_library() => {
+                t!("ui.help_search_library").to_string()
+            }
             Focus::SearchBar => t!("ui.help_search").to_string(),
             Focus::List => match app.list_mode {
                 ListMode::MainMenu => t!("ui.help_nav_select_quit").to_string(),
                 ListMode::AnimeActions => t!("ui.help_nav_select_back").to_string(),
+                ListMode::Conflicts => t!("ui.help_conflicts").to_string(),
+                ListMode::Queue => t!("ui.help_queue").to_string(),
+                ListMode::Organizer => t!("ui.help_organizer").to_string(),
+                ListMode::Characters => t!("ui.help_characters").to_string(),
+                ListMode::Franchise => t!("ui.help_franchise").to_string(),
+                ListMode::Chapters => t!("ui.help_chapters").to_string(),
+                ListMode::Reader => t!("ui.help_reader").to_string(),
+                ListMode::EpisodeSelect => t!("ui.help_episodes").to_string(),
+                #[cfg(feature = "downloads")]
+                ListMode::Downloads => t!("ui.help_downloads").to_string(),
                 _ => t!("ui.help_full").to_string(),
             },
         };
+        let help = match app.count.pending() {
+            Some(pending) => format!("{} | {}", pending, help),
+            None => help,
+        };
         (Color::DarkGray, Color::White, format!(" {} ", help))
     };
+
+    let (mode, mode_color) = match app.input_mode() {
+        InputMode::Search => (t!("modes.search"), Color::Magenta),
+        InputMode::List => (t!("modes.list"), Color::Cyan),
+        InputMode::Dialog => (t!("modes.dialog"), Color::LightYellow),
+    };
+    let segment = Style::default()
+        .fg(Color::Black)
+        .add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::styled(format!(" {} ", mode), segment.bg(mode_color))];
+    if app.macros.is_recording() {
+        spans.push(Span::styled(
+            format!(" ● {} ", t!("modes.record")),
+            segment.bg(Color::Red),
+        ));
+    }
+    if app.private {
+        spans.push(Span::styled(
+            format!(" {} ", t!("modes.private")),
+            segment.bg(Color::LightMagenta),
+        ));
+    }
+    if app.audio_only {
+        spans.push(Span::styled(
+            format!(" ♪ {} ", t!("modes.audio")),
+            segment.bg(Color::LightGreen),
+        ));
+    }
+    if matches!(
+        app.list_mode,
+        ListMode::AnimeActions | ListMode::EpisodeSelect
+    ) {
+        spans.push(Span::styled(
+            format!(" {} ", app.translation_type.to_uppercase()),
+            segment.bg(Color::LightBlue),
+        ));
+    }
+    spans.push(Span::raw(text));
     f.render_widget(
-        Paragraph::new(text).style(Style::default().bg(bg).fg(fg)),
+        Paragraph::new(Line::from(spans)).style(Style::default().bg(bg).fg(fg)),
         area,
     );
 }
@@ -419,6 +1259,224 @@ fn draw_cube(f: &mut Frame, area: Rect, angle: f64) {
     f.render_widget(canvas, area);
 }
 
+/// F12: timings and cache figures, pinned to the top right over everything
+/// else.
+fn draw_perf_overlay(f: &mut Frame, app: &App) {
+    let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+    let timing = |label: String, s: perf::Summary| {
+        format!(
+            "{:<10}{:>6.1}{:>7.1}{:>7.1}",
+            label,
+            ms(s.last),
+            ms(s.avg),
+            ms(s.max)
+        )
+    };
+    let rate = |hits: u64, misses: u64| match perf::hit_rate(hits, misses) {
+        Some(rate) => format!("{:.0}% ({}/{})", rate, hits, hits + misses),
+        None => "-".to_string(),
+    };
+    let tasks = tokio::runtime::Handle::try_current()
+        .map(|h| h.metrics().num_alive_tasks().to_string())
+        .unwrap_or_else(|_| "-".to_string());
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("{:<10}{:>6}{:>7}{:>7}", "ms", "last", "avg", "max"),
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(timing(t!("perf.draw").to_string(), app.perf.draw_time())),
+        Line::from(timing(
+            t!("perf.latency").to_string(),
+            app.perf.input_latency(),
+        )),
+        Line::from(format!("{:<10}{}", t!("perf.tasks"), tasks)),
+    ];
+    #[cfg(feature = "images")]
+    {
+        let (hits, misses) = app.image_cache.stats();
+        lines.push(Line::from(format!(
+            "{:<10}{}",
+            t!("perf.image_cache"),
+            rate(hits, misses)
+        )));
+    }
+    lines.push(Line::from(format!(
+        "{:<10}{}",
+        t!("perf.list_cache"),
+        rate(
+            app.perf.list_cache_hits as u64,
+            app.perf.list_cache_misses as u64
+        )
+    )));
+
+    let screen = f.area();
+    let width = 34.min(screen.width);
+    let height = (lines.len() as u16 + 2).min(screen.height);
+    let area = Rect::new(screen.right() - width, screen.y, width, height);
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(t!("perf.title").to_string())
+        .style(Style::default().bg(Color::Black).fg(Color::White));
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_confirm_modal(f: &mut Frame, message: &str) {
+    let area = centered_rect(50, 20, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(t!("confirm.title").to_string())
+        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let text = vec![
+        Line::from(""),
+        Line::from(message.to_string()),
+        Line::from(""),
+        Line::from(Span::styled(
+            t!("confirm.help").to_string(),
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+    f.render_widget(
+        Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
+fn draw_rating_modal(f: &mut Frame, prompt: &RatingPrompt) {
+    let area = centered_rect(50, 20, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(t!("rating.title").to_string())
+        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let text = vec![
+        Line::from(""),
+        Line::from(t!("rating.question", title = prompt.title, ep = prompt.episode).to_string()),
+        Line::from(""),
+        Line::from(Span::styled(
+            t!("rating.help").to_string(),
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+    f.render_widget(
+        Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
+fn draw_score_modal(f: &mut Frame, count: usize) {
+    let area = centered_rect(50, 20, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(t!("organizer.score_title").to_string())
+        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let text = vec![
+        Line::from(""),
+        Line::from(t!("organizer.score_question", count = count).to_string()),
+        Line::from(""),
+        Line::from(Span::styled(
+            t!("organizer.score_help").to_string(),
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+    f.render_widget(
+        Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
+fn draw_note_modal(f: &mut Frame, editor: &NoteEditor) {
+    let area = centered_rect(60, 30, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(t!("notes.title", title = editor.media.preferred_title()).to_string())
+        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let field = |label: String, value: &str, active: bool| {
+        let cursor = if active { "▏" } else { "" };
+        let style = if active {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(Color::Cyan)
+        };
+        Line::from(vec![
+            Span::styled(label, style),
+            Span::raw(format!("{}{}", value, cursor)),
+        ])
+    };
+    let text = vec![
+        Line::from(""),
+        field(
+            t!("notes.note").to_string(),
+            &editor.note,
+            !editor.editing_tags,
+        ),
+        Line::from(""),
+        field(
+            t!("notes.tags").to_string(),
+            &editor.tags,
+            editor.editing_tags,
+        ),
+        Line::from(""),
+        Line::from(Span::styled(
+            t!("notes.help").to_string(),
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+    f.render_widget(
+        Paragraph::new(text).block(block).wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn draw_show_choice_modal(f: &mut Frame, choice: &ShowChoice) {
+    let area = centered_rect(60, 40, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(t!("titles.choose_show", query = choice.query).to_string())
+        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let mut text = vec![Line::from("")];
+    for (i, name) in choice.names.iter().enumerate() {
+        text.push(if i == choice.selected {
+            Line::from(Span::styled(
+                format!(" > {} ", name),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ))
+        } else {
+            Line::from(format!("   {} ", name))
+        });
+    }
+    text.push(Line::from(""));
+    text.push(Line::from(Span::styled(
+        t!("ui.help_choose_show").to_string(),
+        Style::default().fg(Color::Yellow),
+    )));
+    f.render_widget(
+        Paragraph::new(text).block(block).wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
 fn draw_update_modal(f: &mut Frame, app: &App) {
     if let Some(new_ver) = &app.new_version {
         let area = centered_rect(60, 20, f.area());
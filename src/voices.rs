@@ -0,0 +1,87 @@
+//! "Where have I heard this voice?": a voice actor's other roles, narrowed to
+//! the shows the viewer has completed.
+
+use crate::models::{Media, StaffMediaEdge};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone)]
+pub struct VoiceRole {
+    pub media: Media,
+    /// The characters voiced in the show, comma separated.
+    pub characters: String,
+}
+
+fn is_completed(media: &Media, completed: &HashSet<i32>) -> bool {
+    let status = media
+        .media_list_entry
+        .as_ref()
+        .and_then(|e| e.status.as_deref());
+    matches!(status, Some("COMPLETED") | Some("REPEATING")) || completed.contains(&media.id)
+}
+
+/// Roles in completed anime other than `origin`, one per show, in the order
+/// AniList listed them. A show counts as completed when the viewer's AniList
+/// entry says so or its id is in `completed` (the local registry).
+pub fn completed_roles(
+    edges: Vec<StaffMediaEdge>,
+    origin: i32,
+    completed: &HashSet<i32>,
+) -> Vec<VoiceRole> {
+    let mut roles: Vec<VoiceRole> = Vec::new();
+    for edge in edges {
+        let media = edge.node;
+        if media.is_manga() || media.id == origin || !is_completed(&media, completed) {
+            continue;
+        }
+        let names: Vec<String> = edge.characters.iter().map(|c| c.name.to_string()).collect();
+        match roles.iter_mut().find(|r| r.media.id == media.id) {
+            Some(role) => {
+                for name in names {
+                    if !role.characters.split(", ").any(|n| n == name) {
+                        role.characters.push_str(&format!(", {}", name));
+                    }
+                }
+            }
+            None => roles.push(VoiceRole {
+                media,
+                characters: names.join(", "),
+            }),
+        }
+    }
+    roles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completed_roles() {
+        let edges: Vec<StaffMediaEdge> = serde_json::from_value(serde_json::json!([
+            { "characters": [{ "name": { "full": "Frieren" } }],
+              "node": { "type": "ANIME", "id": 1, "title": {}, "genres": [] } },
+            { "characters": [{ "name": { "full": "Yor" } }],
+              "node": { "type": "ANIME", "id": 2, "title": {}, "genres": [],
+                        "mediaListEntry": { "status": "COMPLETED" } } },
+            { "characters": [{ "name": { "full": "Yor (child)" } }],
+              "node": { "type": "ANIME", "id": 2, "title": {}, "genres": [],
+                        "mediaListEntry": { "status": "COMPLETED" } } },
+            { "characters": [{ "name": { "full": "Mai" } }],
+              "node": { "type": "ANIME", "id": 3, "title": {}, "genres": [],
+                        "mediaListEntry": { "status": "CURRENT" } } },
+            { "characters": [{ "name": { "full": "Saber" } }],
+              "node": { "type": "ANIME", "id": 4, "title": {}, "genres": [] } },
+            { "characters": [{ "name": { "full": "Saber" } }],
+              "node": { "type": "MANGA", "id": 5, "title": {}, "genres": [],
+                        "mediaListEntry": { "status": "COMPLETED" } } }
+        ]))
+        .unwrap();
+
+        let roles = completed_roles(edges, 1, &HashSet::from([1, 4]));
+        let found: Vec<(i32, &str)> = roles
+            .iter()
+            .map(|r| (r.media.id, r.characters.as_str()))
+            .collect();
+        assert_eq!(found, vec![(2, "Yor, Yor (child)"), (4, "Saber")]);
+    }
+}
@@ -0,0 +1,64 @@
+//! Community watch orders for franchises where release order is a poor
+//! guide (Monogatari, Fate), bundled in `watch_orders.json` and offered on
+//! the franchise screen.
+
+use serde::Deserialize;
+
+/// The titles in the file are for whoever edits it; the screen shows
+/// AniList's.
+const WATCH_ORDERS: &str = include_str!("watch_orders.json");
+
+#[derive(Debug, Deserialize)]
+pub struct WatchOrder {
+    pub name: String,
+    pub entries: Vec<WatchOrderEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WatchOrderEntry {
+    /// MyAnimeList id, which is what community watch orders are written
+    /// against; AniList has it for every show as `idMal`.
+    pub mal_id: i32,
+}
+
+impl WatchOrder {
+    pub fn mal_ids(&self) -> Vec<i32> {
+        self.entries.iter().map(|e| e.mal_id).collect()
+    }
+}
+
+lazy_static! {
+    static ref PRESETS: Vec<WatchOrder> =
+        serde_json::from_str(WATCH_ORDERS).expect("bundled watch orders are valid");
+}
+
+/// Watch orders covering any of the franchise's shows, by MyAnimeList id.
+pub fn for_franchise(mal_ids: &[i32]) -> Vec<&'static WatchOrder> {
+    PRESETS
+        .iter()
+        .filter(|order| order.entries.iter().any(|e| mal_ids.contains(&e.mal_id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_franchise() {
+        // Nisemonogatari
+        let orders = for_franchise(&[11597]);
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].mal_ids()[0], 5081);
+        // Fate/stay night: Unlimited Blade Works (AniList 19603)
+        assert_eq!(for_franchise(&[22297]).len(), 1);
+        assert!(for_franchise(&[1]).is_empty());
+
+        for order in PRESETS.iter() {
+            let mut ids = order.mal_ids();
+            ids.sort();
+            ids.dedup();
+            assert_eq!(ids.len(), order.entries.len(), "{}", order.name);
+        }
+    }
+}